@@ -0,0 +1,24 @@
+use std::env::current_dir;
+use std::fs::create_dir_all;
+
+use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
+
+use ics101_router::msg::{
+    BestPoolResponse, DeploymentsResponse, ExecuteMsg, InstantiateMsg, QueryMsg, SwapResponse,
+};
+
+// Clients are generated from these files, `contract::tests::test_schema_in_sync` fails
+// when the messages change without rerunning `cargo run --example schema`.
+fn main() {
+    let mut out_dir = current_dir().unwrap();
+    out_dir.push("schema");
+    create_dir_all(&out_dir).unwrap();
+    remove_schemas(&out_dir).unwrap();
+
+    export_schema(&schema_for!(InstantiateMsg), &out_dir);
+    export_schema(&schema_for!(ExecuteMsg), &out_dir);
+    export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(DeploymentsResponse), &out_dir);
+    export_schema(&schema_for!(BestPoolResponse), &out_dir);
+    export_schema(&schema_for!(SwapResponse), &out_dir);
+}
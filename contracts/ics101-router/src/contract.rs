@@ -0,0 +1,466 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    to_binary, Addr, BankMsg, Binary, Coin, Deps, DepsMut, Env, MessageInfo, Order, Reply,
+    Response, StdError, StdResult, SubMsg, SubMsgResult, Uint128, WasmMsg,
+};
+use cw2::set_contract_version;
+
+use ibcswap_packets::{MsgSwapRequest, SwapMsgType};
+use ics101::market::{InterchainLiquidityPool, PoolStatus};
+use ics101::msg::{
+    ExecuteMsg as Ics101ExecuteMsg, InterchainListResponse, QueryMsg as Ics101QueryMsg,
+};
+
+use crate::error::ContractError;
+use crate::msg::{
+    BestPoolResponse, DeploymentsResponse, ExecuteMsg, InstantiateMsg, QueryMsg, SwapResponse,
+};
+use crate::state::{Config, Deployment, PendingSwap, CONFIG, DEPLOYMENTS, PENDING_SWAP};
+
+// Version info, for migration info
+const CONTRACT_NAME: &str = "ics101-router";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub const SWAP_REPLY_ID: u64 = 1;
+// Page size used to list the pools of a deployment, the ics101 maximum
+const POOL_PAGE_LIMIT: u32 = 30;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    _msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    CONFIG.save(deps.storage, &Config { owner: info.sender.clone() })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "instantiate")
+        .add_attribute("owner", info.sender))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::RegisterDeployment {
+            contract,
+            channel_id,
+        } => register_deployment(deps, info, contract, channel_id),
+        ExecuteMsg::RemoveDeployment { contract } => remove_deployment(deps, info, contract),
+        ExecuteMsg::Swap {
+            denom_out,
+            minimum_receive,
+            recipient,
+            slippage,
+        } => swap(deps, env, info, denom_out, minimum_receive, recipient, slippage),
+    }
+}
+
+fn assert_owner(deps: Deps, info: &MessageInfo) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized);
+    }
+    Ok(())
+}
+
+fn register_deployment(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: String,
+    channel_id: String,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info)?;
+
+    let contract = deps.api.addr_validate(&contract)?;
+    let deployment = Deployment {
+        contract: contract.clone(),
+        channel_id: channel_id.clone(),
+    };
+    DEPLOYMENTS.save(deps.storage, &contract, &deployment)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "register_deployment")
+        .add_attribute("contract", contract)
+        .add_attribute("channel_id", channel_id))
+}
+
+fn remove_deployment(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: String,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info)?;
+
+    let contract = deps.api.addr_validate(&contract)?;
+    if !DEPLOYMENTS.has(deps.storage, &contract) {
+        return Err(ContractError::UnknownDeployment {
+            contract: contract.to_string(),
+        });
+    }
+    DEPLOYMENTS.remove(deps.storage, &contract);
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_deployment")
+        .add_attribute("contract", contract))
+}
+
+/// Forwards the sent coin to the deployment quoting the best output. The
+/// sender stays the sender of the ics101 swap, so failed packets refund it
+/// directly, and the reply refunds it if the swap can't be sent at all.
+fn swap(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    denom_out: String,
+    minimum_receive: Option<Uint128>,
+    recipient: String,
+    slippage: u64,
+) -> Result<Response, ContractError> {
+    let token_in = match info.funds.as_slice() {
+        [coin] => coin.clone(),
+        _ => return Err(ContractError::InvalidFunds),
+    };
+
+    let best = best_pool(deps.as_ref(), &token_in, &denom_out)?;
+    if let Some(minimum) = minimum_receive {
+        if best.token_out.amount < minimum {
+            return Err(ContractError::MinimumReceive {
+                amount: best.token_out.amount.to_string(),
+                minimum: minimum.to_string(),
+            });
+        }
+    }
+
+    let swap_msg = Ics101ExecuteMsg::Swap(MsgSwapRequest {
+        swap_type: SwapMsgType::LEFT,
+        sender: info.sender.to_string(),
+        pool_id: best.pool_id.clone(),
+        token_in: token_in.clone(),
+        token_out: best.token_out.clone(),
+        slippage,
+        recipient,
+        timeout_height: 0,
+        timeout_timestamp: 0,
+        route: None,
+        memo: None,
+        refund_to: None,
+        forward: None,
+    });
+
+    PENDING_SWAP.save(
+        deps.storage,
+        &PendingSwap {
+            sender: info.sender,
+            contract: best.contract.clone(),
+            pool_id: best.pool_id.clone(),
+            token_in: token_in.clone(),
+            expected_out: best.token_out,
+        },
+    )?;
+
+    let sub_msg = SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: best.contract.to_string(),
+            msg: to_binary(&swap_msg)?,
+            funds: vec![token_in],
+        },
+        SWAP_REPLY_ID,
+    );
+
+    Ok(Response::new()
+        .add_submessage(sub_msg)
+        .add_attribute("action", "route_swap")
+        .add_attribute("contract", best.contract)
+        .add_attribute("pool_id", best.pool_id))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        SWAP_REPLY_ID => {
+            let pending = PENDING_SWAP.load(deps.storage)?;
+            PENDING_SWAP.remove(deps.storage);
+
+            match msg.result {
+                SubMsgResult::Ok(_) => {
+                    let res = SwapResponse {
+                        contract: pending.contract.clone(),
+                        pool_id: pending.pool_id.clone(),
+                        token_in: pending.token_in,
+                        expected_out: pending.expected_out,
+                    };
+                    Ok(Response::new()
+                        .set_data(to_binary(&res)?)
+                        .add_attribute("action", "swap")
+                        .add_attribute("contract", pending.contract)
+                        .add_attribute("pool_id", pending.pool_id))
+                }
+                // the funds never left the router, hand them back
+                SubMsgResult::Err(err) => Ok(Response::new()
+                    .add_message(BankMsg::Send {
+                        to_address: pending.sender.to_string(),
+                        amount: vec![pending.token_in],
+                    })
+                    .add_attribute("action", "swap_refund")
+                    .add_attribute("error", err)),
+            }
+        }
+        id => Err(ContractError::UnknownReplyId { id }),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Deployments {} => to_binary(&query_deployments(deps)?),
+        QueryMsg::BestPool {
+            token_in,
+            denom_out,
+        } => to_binary(
+            &best_pool(deps, &token_in, &denom_out)
+                .map_err(|err| StdError::generic_err(err.to_string()))?,
+        ),
+    }
+}
+
+fn query_deployments(deps: Deps) -> StdResult<DeploymentsResponse> {
+    let deployments = DEPLOYMENTS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, deployment)| deployment))
+        .collect::<StdResult<Vec<Deployment>>>()?;
+
+    Ok(DeploymentsResponse { deployments })
+}
+
+/// Quotes the swap in every active pool of the pair on every deployment and
+/// returns the one with the highest output.
+fn best_pool(
+    deps: Deps,
+    token_in: &Coin,
+    denom_out: &str,
+) -> Result<BestPoolResponse, ContractError> {
+    let mut best: Option<BestPoolResponse> = None;
+
+    for item in DEPLOYMENTS.range(deps.storage, None, None, Order::Ascending) {
+        let (_, deployment) = item?;
+        for pool in deployment_pools(deps, &deployment.contract)? {
+            let has_pair = pool.find_asset_by_denom(&token_in.denom).is_ok()
+                && pool.find_asset_by_denom(denom_out).is_ok();
+            if pool.status != PoolStatus::Active || !has_pair {
+                continue;
+            }
+
+            // pools that can't quote the swap are skipped
+            let quote: StdResult<Coin> = deps.querier.query_wasm_smart(
+                &deployment.contract,
+                &Ics101QueryMsg::LeftSwap {
+                    pool_id: pool.id.clone(),
+                    token_in: token_in.clone(),
+                    token_out: Coin {
+                        denom: denom_out.to_string(),
+                        amount: Uint128::zero(),
+                    },
+                },
+            );
+            let token_out = match quote {
+                Ok(token_out) => token_out,
+                Err(_) => continue,
+            };
+            if best
+                .as_ref()
+                .is_some_and(|best| best.token_out.amount >= token_out.amount)
+            {
+                continue;
+            }
+
+            best = Some(BestPoolResponse {
+                contract: deployment.contract.clone(),
+                channel_id: deployment.channel_id.clone(),
+                pool_id: pool.id,
+                token_out,
+            });
+        }
+    }
+
+    best.ok_or_else(|| ContractError::NoPool {
+        denom_in: token_in.denom.clone(),
+        denom_out: denom_out.to_string(),
+    })
+}
+
+fn deployment_pools(deps: Deps, contract: &Addr) -> StdResult<Vec<InterchainLiquidityPool>> {
+    let mut pools = vec![];
+    let mut start_after = None;
+    loop {
+        let page: InterchainListResponse = deps.querier.query_wasm_smart(
+            contract,
+            &Ics101QueryMsg::InterchainPoolList {
+                start_after,
+                limit: Some(POOL_PAGE_LIMIT),
+            },
+        )?;
+        let len = page.pools.len();
+        start_after = page.pools.last().map(|pool| pool.id.clone());
+        pools.extend(page.pools);
+        if len < POOL_PAGE_LIMIT as usize {
+            return Ok(pools);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{
+        coin, from_binary, from_slice, ContractResult, CosmosMsg, SystemResult, WasmQuery,
+    };
+    use ics101::market::{FeeApplication, PoolAsset, PoolSide, PoolType};
+
+    fn pool(id: &str, status: PoolStatus) -> InterchainLiquidityPool {
+        let asset = |side, denom| PoolAsset {
+            side,
+            balance: coin(1000, denom),
+            weight: 50,
+            decimal: 6,
+        };
+        InterchainLiquidityPool {
+            id: id.to_string(),
+            source_creator: "maker".to_string(),
+            destination_creator: "taker".to_string(),
+            assets: vec![asset(PoolSide::SOURCE, "uatom"), asset(PoolSide::DESTINATION, "uosmo")],
+            supply: coin(1000, id),
+            status,
+            counter_party_port: "port".to_string(),
+            counter_party_channel: "channel-0".to_string(),
+            swap_fee: 30,
+            source_chain_id: "chain-a".to_string(),
+            destination_chain_id: "chain-b".to_string(),
+            pool_price: 0,
+            packet_timeout_seconds: None,
+            fee_application: FeeApplication::Source,
+            pool_type: PoolType::Weighted,
+            right_swap_fee: None,
+        }
+    }
+
+    #[test]
+    fn test_swap_routes_to_best_pool() {
+        let mut deps = mock_dependencies();
+        // each deployment quotes its own output, the inactive pool is never quoted
+        deps.querier.update_wasm(|query| {
+            let (contract, msg) = match query {
+                WasmQuery::Smart { contract_addr, msg } => (contract_addr, msg),
+                _ => panic!("unexpected query"),
+            };
+            let res = match from_slice(msg).unwrap() {
+                Ics101QueryMsg::InterchainPoolList { .. } => {
+                    let pools = match contract.as_str() {
+                        "ics101a" => vec![pool("pool1", PoolStatus::Active)],
+                        _ => vec![
+                            pool("pool2", PoolStatus::Active),
+                            pool("pool3", PoolStatus::Initialized),
+                        ],
+                    };
+                    to_binary(&InterchainListResponse { pools })
+                }
+                Ics101QueryMsg::LeftSwap { pool_id, .. } => match pool_id.as_str() {
+                    "pool1" => to_binary(&coin(90, "uosmo")),
+                    "pool2" => to_binary(&coin(95, "uosmo")),
+                    _ => panic!("inactive pool quoted"),
+                },
+                _ => panic!("unexpected query"),
+            };
+            SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+        });
+
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), InstantiateMsg {})
+            .unwrap();
+        for contract in ["ics101a", "ics101b"] {
+            let msg = ExecuteMsg::RegisterDeployment {
+                contract: contract.to_string(),
+                channel_id: "channel-0".to_string(),
+            };
+            let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg.clone())
+                .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized);
+            execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        }
+
+        let swap = |minimum_receive| ExecuteMsg::Swap {
+            denom_out: "uosmo".to_string(),
+            minimum_receive: Some(Uint128::new(minimum_receive)),
+            recipient: "recipient".to_string(),
+            slippage: 100,
+        };
+        let info = mock_info("user", &[coin(100, "uatom")]);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), swap(96)).unwrap_err();
+        assert!(matches!(err, ContractError::MinimumReceive { .. }));
+
+        let res = execute(deps.as_mut(), mock_env(), info, swap(95)).unwrap();
+        assert_eq!(res.messages[0].id, SWAP_REPLY_ID);
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, funds }) => {
+                assert_eq!(contract_addr, "ics101b");
+                assert_eq!(funds, &vec![coin(100, "uatom")]);
+                match from_binary(msg).unwrap() {
+                    Ics101ExecuteMsg::Swap(req) => {
+                        assert_eq!(req.pool_id, "pool2");
+                        assert_eq!(req.sender, "user");
+                    }
+                    _ => panic!("expected swap"),
+                }
+            }
+            _ => panic!("expected wasm execute"),
+        }
+
+        // a swap that fails to send is refunded
+        let reply_msg = Reply {
+            id: SWAP_REPLY_ID,
+            result: SubMsgResult::Err("channel closed".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "user".to_string(),
+                amount: vec![coin(100, "uatom")],
+            })
+        );
+    }
+
+    #[test]
+    fn test_schema_in_sync() {
+        use cosmwasm_schema::schema_for;
+        use serde_json::Value;
+
+        let checked_in = |json: &str| serde_json::from_str::<Value>(json).unwrap();
+        let stale = "schema out of date, run `cargo run --example schema`";
+        assert_eq!(
+            serde_json::to_value(schema_for!(InstantiateMsg)).unwrap(),
+            checked_in(include_str!("../schema/instantiate_msg.json")),
+            "{}",
+            stale
+        );
+        assert_eq!(
+            serde_json::to_value(schema_for!(ExecuteMsg)).unwrap(),
+            checked_in(include_str!("../schema/execute_msg.json")),
+            "{}",
+            stale
+        );
+        assert_eq!(
+            serde_json::to_value(schema_for!(QueryMsg)).unwrap(),
+            checked_in(include_str!("../schema/query_msg.json")),
+            "{}",
+            stale
+        );
+    }
+}
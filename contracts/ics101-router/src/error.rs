@@ -0,0 +1,26 @@
+use cosmwasm_std::StdError;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Deployment {contract} is not registered")]
+    UnknownDeployment { contract: String },
+
+    #[error("Send exactly one coin to swap")]
+    InvalidFunds,
+
+    #[error("No active pool for {denom_in} -> {denom_out}")]
+    NoPool { denom_in: String, denom_out: String },
+
+    #[error("Best output {amount} is below the minimum receive {minimum}")]
+    MinimumReceive { amount: String, minimum: String },
+
+    #[error("Got a submessage reply with unknown id: {id}")]
+    UnknownReplyId { id: u64 },
+}
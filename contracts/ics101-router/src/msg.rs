@@ -0,0 +1,55 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Coin, Uint128};
+
+use crate::state::Deployment;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum ExecuteMsg {
+    /// Adds an ics101 instance to the registry, owner only
+    RegisterDeployment { contract: String, channel_id: String },
+    /// Removes an ics101 instance from the registry, owner only
+    RemoveDeployment { contract: String },
+    /// Swaps the sent coin in the pool with the best output across all deployments
+    Swap {
+        denom_out: String,
+        minimum_receive: Option<Uint128>,
+        /// Receiver of the output on the counterparty chain
+        recipient: String,
+        slippage: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum QueryMsg {
+    /// All registered deployments. Return type is DeploymentsResponse.
+    Deployments {},
+    /// Pool with the best output for a swap. Return type is BestPoolResponse.
+    BestPool { token_in: Coin, denom_out: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DeploymentsResponse {
+    pub deployments: Vec<Deployment>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BestPoolResponse {
+    pub contract: Addr,
+    pub channel_id: String,
+    pub pool_id: String,
+    pub token_out: Coin,
+}
+
+/// Data set on the response of a forwarded swap.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapResponse {
+    pub contract: Addr,
+    pub pool_id: String,
+    pub token_in: Coin,
+    pub expected_out: Coin,
+}
@@ -0,0 +1,36 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Coin};
+use cw_storage_plus::{Item, Map};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Config {
+    // Address allowed to change the registry
+    pub owner: Addr,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+/// An ics101 instance the router can swap through.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Deployment {
+    pub contract: Addr,
+    /// Channel the instance talks to its counterparty over
+    pub channel_id: String,
+}
+
+// Map from ics101 contract address to its deployment
+pub const DEPLOYMENTS: Map<&Addr, Deployment> = Map::new("deployments");
+
+/// Swap forwarded to an ics101 instance, read back by the reply handler.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingSwap {
+    pub sender: Addr,
+    pub contract: Addr,
+    pub pool_id: String,
+    pub token_in: Coin,
+    pub expected_out: Coin,
+}
+
+pub const PENDING_SWAP: Item<PendingSwap> = Item::new("pending_swap");
@@ -3,12 +3,19 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-// use ics101::msg::DetailsResponse;
-use ics101::msg::ExecuteMsg;
-use ics101::msg::InstantiateMsg;
-// use ics101::msg::ListResponse;
-use ics101::msg::QueryMsg;
+use ics101::msg::{
+    ChannelCapabilitiesResponse, ChannelsResponse, Cw20HookMsg, DecodedPacketResponse,
+    DepositReceiptsResponse, ExecuteMsg, FirmQuoteResponse, InstantiateMsg, InterchainListResponse,
+    InterchainPoolResponse, LpCheckpointsResponse, LpTokenResponse, MigrateMsg, OrderListResponse,
+    PacketJournalResponse, PendingWithdrawalsResponse, PoolListResponse, PoolSupplyResponse,
+    ProtocolFeesResponse, QueryConfigResponse, QueryMsg, RateResponse, ReceivedPacketsResponse,
+    ReconcileResponse, RequiredCounterDepositResponse, RolesResponse, SharesForDepositResponse,
+    SimulateResponse, StorageVersionResponse, SudoMsg, TakePoolRequiredFundsResponse,
+    VerifyPoolResponse, WithdrawalQueueResponse,
+};
 
+// Clients are generated from these files, `contract::tests::test_schema_in_sync` fails
+// when the messages change without rerunning `cargo run --example schema`.
 fn main() {
     let mut out_dir = current_dir().unwrap();
     out_dir.push("schema");
@@ -18,6 +25,35 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
-    // export_schema(&schema_for!(ListResponse), &out_dir);
-    // export_schema(&schema_for!(DetailsResponse), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
+    export_schema(&schema_for!(SudoMsg), &out_dir);
+    export_schema(&schema_for!(Cw20HookMsg), &out_dir);
+
+    export_schema(&schema_for!(QueryConfigResponse), &out_dir);
+    export_schema(&schema_for!(RolesResponse), &out_dir);
+    export_schema(&schema_for!(StorageVersionResponse), &out_dir);
+    export_schema(&schema_for!(InterchainPoolResponse), &out_dir);
+    export_schema(&schema_for!(InterchainListResponse), &out_dir);
+    export_schema(&schema_for!(PoolListResponse), &out_dir);
+    export_schema(&schema_for!(OrderListResponse), &out_dir);
+    export_schema(&schema_for!(TakePoolRequiredFundsResponse), &out_dir);
+    export_schema(&schema_for!(RequiredCounterDepositResponse), &out_dir);
+    export_schema(&schema_for!(VerifyPoolResponse), &out_dir);
+    export_schema(&schema_for!(LpTokenResponse), &out_dir);
+    export_schema(&schema_for!(PoolSupplyResponse), &out_dir);
+    export_schema(&schema_for!(RateResponse), &out_dir);
+    export_schema(&schema_for!(FirmQuoteResponse), &out_dir);
+    export_schema(&schema_for!(SimulateResponse), &out_dir);
+    export_schema(&schema_for!(SharesForDepositResponse), &out_dir);
+    export_schema(&schema_for!(DecodedPacketResponse), &out_dir);
+    export_schema(&schema_for!(DepositReceiptsResponse), &out_dir);
+    export_schema(&schema_for!(ReconcileResponse), &out_dir);
+    export_schema(&schema_for!(PendingWithdrawalsResponse), &out_dir);
+    export_schema(&schema_for!(ProtocolFeesResponse), &out_dir);
+    export_schema(&schema_for!(ReceivedPacketsResponse), &out_dir);
+    export_schema(&schema_for!(PacketJournalResponse), &out_dir);
+    export_schema(&schema_for!(LpCheckpointsResponse), &out_dir);
+    export_schema(&schema_for!(WithdrawalQueueResponse), &out_dir);
+    export_schema(&schema_for!(ChannelCapabilitiesResponse), &out_dir);
+    export_schema(&schema_for!(ChannelsResponse), &out_dir);
 }
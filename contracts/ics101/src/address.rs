@@ -0,0 +1,95 @@
+use bech32::{FromBase32, Variant};
+use cosmwasm_std::{Addr, Api, Storage};
+
+use crate::{state::COUNTERPARTY_ADDRESS_PREFIX, ContractError};
+
+/// Byte lengths of account (20) and contract (32) addresses on cosmos chains.
+const ADDRESS_LENGTHS: [usize; 2] = [20, 32];
+
+/// Validates an address of this chain, e.g. the receiver of unlocked tokens.
+pub fn validate_local_address(api: &dyn Api, address: &str) -> Result<Addr, ContractError> {
+    api.addr_validate(address)
+        .map_err(|err| ContractError::InvalidAddress {
+            address: address.to_string(),
+            reason: err.to_string(),
+        })
+}
+
+/// Validates an address living on the chain at the other end of `channel_id`.
+/// The address has to be valid bech32 of a known length and, when a prefix is
+/// registered for the channel, use that prefix.
+pub fn validate_counterparty_address(
+    storage: &dyn Storage,
+    channel_id: &str,
+    address: &str,
+) -> Result<String, ContractError> {
+    let invalid = |reason: &str| ContractError::InvalidAddress {
+        address: address.to_string(),
+        reason: reason.to_string(),
+    };
+
+    let (prefix, data, variant) =
+        bech32::decode(address).map_err(|err| invalid(&err.to_string()))?;
+    if variant != Variant::Bech32 {
+        return Err(invalid("expected bech32 encoding"));
+    }
+
+    let bytes = Vec::<u8>::from_base32(&data).map_err(|err| invalid(&err.to_string()))?;
+    if !ADDRESS_LENGTHS.contains(&bytes.len()) {
+        return Err(invalid(&format!("invalid length {}", bytes.len())));
+    }
+
+    if let Some(expected) = COUNTERPARTY_ADDRESS_PREFIX.may_load(storage, channel_id)? {
+        if prefix != expected {
+            return Err(invalid(&format!(
+                "expected prefix {} for channel {}",
+                expected, channel_id
+            )));
+        }
+    }
+
+    Ok(address.to_string())
+}
+
+/// Checks that a prefix can be used as the human readable part of bech32 addresses.
+pub fn validate_prefix(prefix: &str) -> Result<(), ContractError> {
+    let valid = !prefix.is_empty()
+        && prefix.len() <= 83
+        && prefix
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit());
+    if !valid {
+        return Err(ContractError::InvalidAddressPrefix {
+            prefix: prefix.to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::mock_dependencies;
+
+    const COSMOS_ADDR: &str = "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu";
+
+    #[test]
+    fn test_validate_counterparty_address() {
+        let mut deps = mock_dependencies();
+
+        // without a registered prefix only the format is checked
+        assert!(validate_counterparty_address(&deps.storage, "channel-0", COSMOS_ADDR).is_ok());
+        assert!(validate_counterparty_address(&deps.storage, "channel-0", "receiver").is_err());
+
+        COUNTERPARTY_ADDRESS_PREFIX
+            .save(deps.as_mut().storage, "channel-0", &"osmo".to_string())
+            .unwrap();
+        let err =
+            validate_counterparty_address(&deps.storage, "channel-0", COSMOS_ADDR).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAddress { .. }));
+        assert!(validate_counterparty_address(&deps.storage, "channel-1", COSMOS_ADDR).is_ok());
+
+        assert!(validate_prefix("osmo").is_ok());
+        assert!(validate_prefix("Osmo").is_err());
+    }
+}
@@ -55,6 +55,26 @@ pub fn calculate_pow(
     Ok(result)
 }
 
+// Computes base^(exp) for bases above 2 as well. The base is divided by sqrt(2) until it
+// is in range, sqrt(2)^(divisions * exp) multiplies the result back.
+pub fn calculate_pow_unbounded(base: Decimal, exp: Decimal) -> StdResult<Decimal> {
+    let sqrt_two = Decimal::from_ratio(2u128, 1u128).sqrt();
+    let mut base = base;
+    let mut divisions = 0u128;
+    while base > sqrt_two {
+        base = base
+            .checked_div(sqrt_two)
+            .map_err(|e| StdError::generic_err(e.to_string()))?;
+        divisions += 1;
+    }
+    let factor = calculate_pow(
+        sqrt_two,
+        exp.checked_mul(Decimal::from_ratio(divisions, 1u128))?,
+        None,
+    )?;
+    Ok(calculate_pow(base, exp, None)?.checked_mul(factor)?)
+}
+
 // Contract: 0 < base <= 2
 // 0 <= exp < 1.
 pub fn pow_approx(base: Decimal, exp: Decimal, precision: Decimal) -> StdResult<Decimal> {
@@ -174,4 +194,21 @@ mod tests {
             "0.839898055"
         );
     }
+
+    #[test]
+    fn check_pow_unbounded() {
+        // 1000000^0.5
+        let res = calculate_pow_unbounded(
+            Decimal::from_str("1000000").unwrap(),
+            Decimal::from_str("0.5").unwrap(),
+        );
+        assert_eq!(&res.unwrap().to_string()[0..9], "999.99999");
+
+        // 40^0.2
+        let res = calculate_pow_unbounded(
+            Decimal::from_str("40").unwrap(),
+            Decimal::from_str("0.2").unwrap(),
+        );
+        assert_eq!(&res.unwrap().to_string()[0..7], "2.09127");
+    }
 }
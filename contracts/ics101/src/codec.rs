@@ -0,0 +1,97 @@
+//! Wire format of the ics101 packets and of the messages and state changes they carry.
+//! Every path building or reading packet data goes through these helpers, so a change of
+//! the format is made here.
+
+use cosmwasm_std::{from_slice, to_binary, Binary, StdResult, Storage};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{state::COMPACT_CHANNELS, types::InterchainSwapPacketData};
+
+/// Encodes a packet for `channel_id` in the encoding negotiated for the channel.
+pub(crate) fn encode_packet(
+    storage: &dyn Storage,
+    channel_id: &str,
+    packet: &InterchainSwapPacketData,
+) -> StdResult<Binary> {
+    if COMPACT_CHANNELS.has(storage, channel_id) {
+        Ok(Binary::from(packet.to_compact()))
+    } else {
+        to_binary(packet)
+    }
+}
+
+/// Decodes packet data sent in either the JSON or the compact encoding.
+pub(crate) fn decode_packet(data: &[u8]) -> StdResult<InterchainSwapPacketData> {
+    InterchainSwapPacketData::from_wire(data)
+}
+
+/// Encodes the message or state change a packet carries.
+pub(crate) fn encode_payload<T: Serialize>(payload: &T) -> StdResult<Binary> {
+    to_binary(payload)
+}
+
+/// Decodes the message or state change a packet carries.
+pub(crate) fn decode_payload<T: DeserializeOwned>(data: &[u8]) -> StdResult<T> {
+    from_slice(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{coin, testing::MockStorage};
+
+    use crate::{
+        msg::{MsgSwapRequest, SwapMsgType},
+        types::{InterchainMessageType, StateChange},
+    };
+
+    #[test]
+    fn test_round_trip_in_both_encodings() {
+        let msg = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: "pool1".to_string(),
+            token_in: coin(100, "uatom"),
+            token_out: coin(90, "uosmo"),
+            slippage: 100,
+            recipient: "recipient".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            forward: None,
+            memo: None,
+            refund_to: None,
+        };
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![coin(90, "uosmo")]),
+            pool_tokens: None,
+            pool_id: Some("pool1".to_string()),
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: encode_payload(&msg).unwrap(),
+            state_change: Some(encode_payload(&state_change).unwrap()),
+            memo: None,
+        };
+
+        let mut storage = MockStorage::new();
+        COMPACT_CHANNELS.save(&mut storage, "channel-1", &true).unwrap();
+        for channel_id in ["channel-0", "channel-1"] {
+            let data = encode_packet(&storage, channel_id, &packet).unwrap();
+            let decoded = decode_packet(&data).unwrap();
+            assert_eq!(decoded, packet);
+            assert_eq!(decode_payload::<MsgSwapRequest>(&decoded.data).unwrap(), msg);
+            let decoded_change: StateChange =
+                decode_payload(decoded.state_change.as_ref().unwrap()).unwrap();
+            assert_eq!(decoded_change, state_change);
+        }
+        assert_ne!(
+            encode_packet(&storage, "channel-0", &packet).unwrap(),
+            encode_packet(&storage, "channel-1", &packet).unwrap()
+        );
+    }
+}
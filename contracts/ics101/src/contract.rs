@@ -1,49 +1,88 @@
-use std::ops::{Div, Mul};
+use std::collections::BTreeMap;
 use std::vec;
 
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    from_binary, to_binary, Binary, Coin, Deps, DepsMut, Env, IbcMsg, IbcTimeout, MessageInfo,
-    Order, Reply, ReplyOn, Response, StdError, StdResult, SubMsg, SubMsgResult, Uint128, WasmMsg,
+    from_binary, to_binary, Addr, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env, IbcMsg,
+    IbcTimeout, IbcTimeoutBlock, MessageInfo, Order, Reply, Response, StdError, StdResult, Storage,
+    SubMsg, SubMsgResult, Timestamp, Uint128, WasmMsg,
 };
 use protobuf::Message;
 
 use cw2::set_contract_version;
-use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, MinterResponse};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, Logo, TokenInfoResponse};
 use cw_storage_plus::Bound;
 
+use crate::address::{validate_counterparty_address, validate_local_address, validate_prefix};
+use crate::codec::{decode_packet, encode_packet, encode_payload};
 use crate::error::ContractError;
+use crate::forward::{on_forward_transfer_reply, on_lifecycle_complete};
 use crate::ibc::{ACK_FAILURE_ID, RECEIVE_ID};
-use crate::interchainswap_handler::ack_fail;
-use crate::market::{InterchainLiquidityPool, InterchainMarketMaker, PoolSide, PoolStatus, LP_TOKEN_PRECISION};
+use crate::icq::{icq_channel_for_connection, pool_query_packet};
+use crate::interchainswap_handler::{
+    accrue_fee, ack_fail, add_escrow, escrowed_funds, on_take_multi_deposit_mint_reply,
+    packet_pool_id, recount_escrowed_funds, release_escrow, save_order,
+};
+use crate::market::{
+    unlocked_shares, InterchainLiquidityPool, InterchainMarketMaker, MarketFeeUpdateProposal,
+    PoolSide, PoolStatus, FEE_PRECISION, LP_TOKEN_PRECISION, MINIMUM_LIQUIDITY,
+};
 use crate::msg::{
-    Cw20HookMsg, ExecuteMsg, InstantiateMsg, InterchainListResponse, InterchainPoolResponse,
-    MigrateMsg, MsgCancelMultiAssetDepositRequest, MsgCancelPoolRequest,
-    MsgMakeMultiAssetDepositRequest, MsgMakePoolRequest, MsgMultiAssetWithdrawRequest,
-    MsgRemovePool, MsgSingleAssetDepositRequest, MsgSwapRequest, MsgTakeMultiAssetDepositRequest,
-    MsgTakePoolRequest, OrderListResponse, PoolListResponse, QueryConfigResponse, QueryMsg,
-    SwapMsgType, TokenInstantiateMsg,
+    ChannelCapabilitiesResponse, ChannelResponse, ChannelsResponse, Cw20HookMsg,
+    DecodedPacketResponse, DepositAsset, DepositReceiptsResponse, ExecuteMsg, FirmQuoteResponse,
+    InstantiateMsg, InterchainListResponse, InterchainPoolResponse, LPAllocation,
+    LpCheckpointsResponse, LpTokenMarketing, LpTokenResponse, MigrateMsg,
+    MsgCancelMultiAssetDepositRequest, MsgCancelPoolRequest, MsgDecommissionPoolRequest,
+    MsgMakeMultiAssetDepositByRatioRequest, MsgMakeMultiAssetDepositRequest, MsgMakePoolRequest,
+    MsgMatchMultiAssetDepositRequest, MsgMultiAssetWithdrawRequest, MsgRemovePool,
+    MsgSetPoolManagerRequest, MsgSetPoolStatusRequest, MsgSingleAssetDepositRequest, MsgSwapRequest,
+    MsgTakeMultiAssetDepositRequest, MsgTakePoolRequest, OrderListResponse, PacketJournalResponse,
+    PendingWithdrawalsResponse, PoolListResponse, PoolSupplyResponse, ProtocolFeesResponse,
+    QueryConfigResponse, QueryMsg, RateOutput, RateResponse, ReceivedPacketsResponse,
+    ReconcileCursor, ReconcileResponse, ReconcileStage, RequiredCounterDepositResponse,
+    RolesResponse, SharesForDepositResponse, SimulateResponse, StorageVersionResponse, SudoMsg,
+    SwapMsgType, TakePermit, TakePoolRequiredFundsResponse, VerifyPoolResponse,
+    WithdrawalQueueResponse,
 };
 use crate::response::MsgInstantiateContractResponse;
+use crate::simulate::SimulationStorage;
+use crate::tokenfactory;
+use crate::undo;
 use crate::state::{
-    Config, ACTIVE_ORDERS, CONFIG, LOG_VOLUME, MULTI_ASSET_DEPOSIT_ORDERS, POOLS, POOL_TOKENS_LIST,
-    TEMP,
+    AssetMetadata, Config, DepositReceipt, DepositStatus, DustThresholds, JournalEntry,
+    LpCheckpoint, LpTokenKind, LpTokenStatus, PendingWithdrawal, QueuedWithdrawal, ReceivedPacket,
+    Role, ACTIVE_ORDERS, ASSET_METADATA, BOOTSTRAP_DEPOSITS, BOOTSTRAP_TARGETS, CHANNEL_INFO,
+    CLAIM_ADDRESSES, CLOSED_CHANNELS, COLLECTED_FEES, CONFIG, COUNTERPARTY_ADDRESS_PREFIX,
+    COUNTERPARTY_CAPABILITIES, COUNTERPARTY_CHAIN_IDS, COUNTERPARTY_POOLS, COUNTERPARTY_PORTS,
+    CURRENT_STORAGE_VERSION, DEFAULT_MAX_INITIALIZED_POOLS, DEFAULT_MAX_SWAP_FEE_BPS,
+    DEFAULT_MAX_TIMEOUT_SECONDS, DEFAULT_MIN_SWAP_FEE_BPS, DEFAULT_MIN_TIMEOUT_SECONDS,
+    DEFAULT_QUOTE_VALIDITY_BLOCKS, DEFAULT_TIMEOUT_SECONDS, DEFAULT_TREASURY_TIMELOCK_SECONDS,
+    DEPOSIT_RECEIPTS, DEPOSIT_RECEIPT_COUNT, DRAIN_DEADLINES, DRIFT_TOLERANCE, DUST_THRESHOLDS,
+    INITIALIZED_POOLS, IN_FLIGHT_PACKETS, LEGACY_CONFIG, LEGACY_PACKET_JOURNAL, LEGACY_SWAP_ESCROW,
+    LOG_VOLUME, LP_CHECKPOINTS, LP_TOKEN_MARKETING, LP_TOKEN_STATUS, MULTI_ASSET_DEPOSIT_ORDERS,
+    PACKET_JOURNAL, PACKET_JOURNAL_COUNT, PENDING_DEPOSIT_RECEIPTS, PENDING_JOURNAL_ENTRIES,
+    PENDING_WITHDRAWALS, PENDING_WITHDRAWAL_COUNT, POOLS, POOL_MANAGERS, POOL_TOKENS_LIST,
+    POOL_WHITELISTS, PROCESSING, PROTOCOL_FEES, RECEIVED_PACKETS, ROLES, STORAGE_VERSION,
+    SWAP_CLAIMS, SWAP_ESCROW, TEMP, TREASURY, UNDO_EXPIRY, UNDO_LOG, VERSIONED_MAPS, VETOED_PACKETS,
+    WITHDRAWAL_QUEUE,
 };
 use crate::types::{
     InterchainMessageType, InterchainSwapPacketData, MultiAssetDepositOrder, OrderStatus,
     StateChange
 };
 use crate::utils::{
-    get_coins_from_deposits, get_order_id, get_pool_id_with_tokens, INSTANTIATE_TOKEN_REPLY_ID,
+    assert_allowed, assert_swap_slippage, bind_counterparty_chain_id, coins_to_string,
+    get_coins_from_deposits, get_order_id, get_pool_id_with_tokens, instantiate_lp_token_once,
+    is_lifecycle_status, local_capabilities, packet_data_hash, revision_number, send_tokens_coin,
+    validate_asset_decimals, FORWARD_TRANSFER_REPLY_ID, INSTANTIATE_TOKEN_REPLY_ID,
+    MAXIMUM_SLIPPAGE, TAKE_MULTI_DEPOSIT_MINT_REPLY_ID,
 };
 
 
 // Version info, for migration info
 const CONTRACT_NAME: &str = "ics101-interchainswap";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
-const DEFAULT_TIMEOUT_TIMESTAMP_OFFSET: u64 = 600;
-const MAXIMUM_SLIPPAGE: u64 = 10000;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -51,26 +90,114 @@ pub fn instantiate(
     _env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
     let config = Config {
         counter: 0,
         token_code_id: msg.token_code_id,
         admin: info.sender.to_string(),
+        pending_admin: None,
         router: msg.router,
+        min_timeout_seconds: DEFAULT_MIN_TIMEOUT_SECONDS,
+        max_timeout_seconds: DEFAULT_MAX_TIMEOUT_SECONDS,
+        default_timeout_seconds: msg.default_timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS),
+        min_swap_fee_bps: DEFAULT_MIN_SWAP_FEE_BPS,
+        max_swap_fee_bps: DEFAULT_MAX_SWAP_FEE_BPS,
+        disabled_messages: vec![],
+        debug_attributes: false,
+        quote_validity_blocks: DEFAULT_QUOTE_VALIDITY_BLOCKS,
+        treasury_timelock_seconds: DEFAULT_TREASURY_TIMELOCK_SECONDS,
+        protocol_fee_share_bps: 0,
+        protocol_fee_collector: None,
+        max_initialized_pools: DEFAULT_MAX_INITIALIZED_POOLS,
+        lp_token_kind: msg.lp_token_kind.unwrap_or_default(),
     };
+    assert_timeout_in_bounds(&config, config.default_timeout_seconds)?;
 
     CONFIG.save(deps.storage, &config)?;
+    STORAGE_VERSION.save(deps.storage, &CURRENT_STORAGE_VERSION)?;
     Ok(Response::default())
 }
 
+/// Timeout of a packet of `pool`, the timeout the message sets or else the pool override or
+/// the contract default. A message timestamp is in nanoseconds and has to lie within the
+/// timeout bounds from now, a message height is one of the counterparty chain.
+fn packet_timeout(
+    storage: &dyn Storage,
+    env: &Env,
+    pool: &InterchainLiquidityPool,
+    timeout_height: u64,
+    timeout_timestamp: u64,
+) -> Result<IbcTimeout, ContractError> {
+    let config = CONFIG.load(storage)?;
+    let block = match timeout_height {
+        0 => None,
+        height => Some(IbcTimeoutBlock {
+            revision: revision_number(counterparty_chain_id(env, pool)),
+            height,
+        }),
+    };
+    let timestamp = match timeout_timestamp {
+        0 => None,
+        nanos => {
+            let timestamp = Timestamp::from_nanos(nanos);
+            let seconds = timestamp.seconds().saturating_sub(env.block.time.seconds());
+            assert_timeout_in_bounds(&config, seconds)?;
+            Some(timestamp)
+        }
+    };
+
+    Ok(match (block, timestamp) {
+        (Some(block), Some(timestamp)) => IbcTimeout::with_both(block, timestamp),
+        (Some(block), None) => IbcTimeout::with_block(block),
+        (None, Some(timestamp)) => IbcTimeout::with_timestamp(timestamp),
+        (None, None) => {
+            let seconds = pool.packet_timeout_seconds.unwrap_or(config.default_timeout_seconds);
+            IbcTimeout::with_timestamp(env.block.time.plus_seconds(seconds))
+        }
+    })
+}
+
+// the chain of a pool this contract is not on
+fn counterparty_chain_id<'a>(env: &Env, pool: &'a InterchainLiquidityPool) -> &'a str {
+    if pool.source_chain_id == env.block.chain_id {
+        &pool.destination_chain_id
+    } else {
+        &pool.source_chain_id
+    }
+}
+
+fn assert_timeout_in_bounds(config: &Config, timeout: u64) -> Result<(), ContractError> {
+    if !config.timeout_in_bounds(timeout) {
+        return Err(ContractError::InvalidTimeout {
+            timeout,
+            min: config.min_timeout_seconds,
+            max: config.max_timeout_seconds,
+        });
+    }
+    Ok(())
+}
+
+/// The entry point to the contract for messages of the chain's modules.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::IbcLifecycleComplete(msg) => on_lifecycle_complete(deps, env, msg),
+    }
+}
+
 /// The entry point to the contract for processing replies from submessages.
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
     match msg.id {
         INSTANTIATE_TOKEN_REPLY_ID => {
-            let data = msg.result.clone().unwrap().data.unwrap();
+            let data = msg
+                .result
+                .into_result()
+                .map_err(StdError::generic_err)?
+                .data
+                .ok_or_else(|| StdError::generic_err("Missing instantiate reply data"))?;
             let res: MsgInstantiateContractResponse = Message::parse_from_bytes(data.as_slice())
                 .map_err(|_| {
                     StdError::parse_err("MsgInstantiateContractResponse", "failed to parse data")
@@ -97,11 +224,23 @@ pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractE
             //     }
             // }
 
-            let pool_id = TEMP.load(deps.storage).unwrap();
+            let pool_id = TEMP.load(deps.storage)?;
             TEMP.remove(deps.storage);
+            if LP_TOKEN_STATUS.may_load(deps.storage, &pool_id)?
+                != Some(LpTokenStatus::Instantiating)
+            {
+                return Err(StdError::generic_err(format!(
+                    "No LP token is being instantiated for {}",
+                    pool_id
+                ))
+                .into());
+            }
             POOL_TOKENS_LIST.save(deps.storage, &pool_id, &lp_token.to_string())?;
+            LP_TOKEN_STATUS.save(deps.storage, &pool_id, &LpTokenStatus::Ready)?;
             Ok(Response::new().add_attribute("liquidity_token_addr", lp_token))
         }
+        TAKE_MULTI_DEPOSIT_MINT_REPLY_ID => on_take_multi_deposit_mint_reply(deps, msg.result),
+        FORWARD_TRANSFER_REPLY_ID => on_forward_transfer_reply(deps, msg.result),
         RECEIVE_ID => match msg.result {
             SubMsgResult::Ok(_) => Ok(Response::new()),
             SubMsgResult::Err(err) => Ok(Response::new().set_data(ack_fail(err))),
@@ -116,6 +255,111 @@ pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractE
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    let releasing = matches!(msg, ExecuteMsg::ReleaseGuard {});
+    if !releasing && PROCESSING.may_load(deps.storage)?.unwrap_or_default() {
+        return Err(ContractError::Reentrancy);
+    }
+    let config = CONFIG.load(deps.storage)?;
+    if let Some(message_type) = packet_types(&msg)
+        .into_iter()
+        .find(|message_type| !config.is_enabled(message_type))
+    {
+        return Err(ContractError::MessageDisabled { message_type });
+    }
+    for (pool_id, message_type) in pool_packets(&msg) {
+        // a missing pool is reported by the handler
+        if let Some(pool) = POOLS.may_load(deps.storage, pool_id)? {
+            assert_allowed(&pool.status, &message_type)?;
+        }
+        assert_whitelisted(deps.storage, pool_id, &info.sender, &message_type)?;
+    }
+
+    let res = dispatch(deps.branch(), env.clone(), info, msg)?;
+    track_sent_packets(deps.storage, &env, &res)?;
+    guard_contract_calls(deps.storage, &env, res)
+}
+
+/// Rejects calls back into the contract while the contract calls of `res` run, e.g. a
+/// cw20 reentering during a transfer. The last message of the response releases it.
+fn guard_contract_calls(
+    storage: &mut dyn Storage,
+    env: &Env,
+    res: Response,
+) -> Result<Response, ContractError> {
+    let calls_contracts = res
+        .messages
+        .iter()
+        .any(|sub_msg| matches!(sub_msg.msg, CosmosMsg::Wasm(WasmMsg::Execute { .. })));
+    if !calls_contracts {
+        return Ok(res);
+    }
+    PROCESSING.save(storage, &true)?;
+    Ok(res.add_message(WasmMsg::Execute {
+        contract_addr: env.contract.address.to_string(),
+        msg: to_binary(&ExecuteMsg::ReleaseGuard {})?,
+        funds: vec![],
+    }))
+}
+
+fn release_guard(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    if info.sender != env.contract.address {
+        return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
+    }
+    PROCESSING.remove(deps.storage);
+    Ok(Response::default().add_attribute("action", "release_guard"))
+}
+
+/// Counts the packets a response sends as in flight for their pools and journals them.
+fn track_sent_packets(
+    storage: &mut dyn Storage,
+    env: &Env,
+    res: &Response,
+) -> Result<(), ContractError> {
+    for sub_msg in &res.messages {
+        if let CosmosMsg::Ibc(IbcMsg::SendPacket {
+            channel_id, data, ..
+        }) = &sub_msg.msg
+        {
+            // interchain queries carry no swap packet data
+            let packet_data = match decode_packet(data) {
+                Ok(packet_data) => packet_data,
+                Err(_) => continue,
+            };
+            if let Some(pool_id) = packet_pool_id(&packet_data)? {
+                IN_FLIGHT_PACKETS.update(storage, &pool_id, |count| -> StdResult<_> {
+                    Ok(count.unwrap_or_default() + 1)
+                })?;
+
+                let id = PACKET_JOURNAL_COUNT.may_load(storage, &pool_id)?.unwrap_or_default() + 1;
+                PACKET_JOURNAL_COUNT.save(storage, &pool_id, &id)?;
+                let entry = JournalEntry {
+                    id,
+                    sequence: None,
+                    channel_id: channel_id.clone(),
+                    tokens: packet_data.decode_message()?.tokens(),
+                    packet_type: packet_data.r#type,
+                    sent_at: Some(env.block.time.seconds()),
+                    result: None,
+                    completed_at: None,
+                };
+                PACKET_JOURNAL.save(storage, (&pool_id, id), &entry)?;
+                let hash = packet_data_hash(data);
+                let mut pending =
+                    PENDING_JOURNAL_ENTRIES.may_load(storage, &hash)?.unwrap_or_default();
+                pending.push(id);
+                PENDING_JOURNAL_ENTRIES.save(storage, &hash, &pending)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn dispatch(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
@@ -125,19 +369,141 @@ pub fn execute(
         ExecuteMsg::MakePool(msg) => make_pool(deps, env, info, msg),
         ExecuteMsg::TakePool(msg) => take_pool(deps, env, info, msg),
         ExecuteMsg::CancelPool(msg) => cancel_pool(deps, env, info, msg),
+        ExecuteMsg::SetPoolManager(msg) => set_pool_manager(deps, env, info, msg),
+        ExecuteMsg::SetPoolStatus(msg) => set_pool_status(deps, env, info, msg),
+        ExecuteMsg::UpdatePoolFee(msg) => update_pool_fee(deps, env, info, msg),
+        ExecuteMsg::DecommissionPool(msg) => decommission_pool(deps, env, info, msg),
         ExecuteMsg::SingleAssetDeposit(msg) => single_asset_deposit(deps, env, info, msg),
         ExecuteMsg::MakeMultiAssetDeposit(msg) => make_multi_asset_deposit(deps, env, info, msg),
+        ExecuteMsg::MakeMultiAssetDepositByRatio(msg) => {
+            make_multi_asset_deposit_by_ratio(deps, env, info, msg)
+        }
         ExecuteMsg::CancelMultiAssetDeposit(msg) => {
             cancel_multi_asset_deposit(deps, env, info, msg)
         }
         ExecuteMsg::TakeMultiAssetDeposit(msg) => take_multi_asset_deposit(deps, env, info, msg),
+        ExecuteMsg::MatchOrders(msg) => match_orders(deps, env, msg),
         ExecuteMsg::MultiAssetWithdraw(msg) => multi_asset_withdraw(deps, env, info, msg),
         ExecuteMsg::Swap(msg) => swap(deps, env, info, msg),
+        ExecuteMsg::BatchSwap { legs } => batch_swap(deps, env, info, legs),
+        ExecuteMsg::FirmSwap {
+            swap,
+            quoted_out,
+            quote_height,
+        } => firm_swap(deps, env, info, swap, quoted_out, quote_height),
+        ExecuteMsg::SetQuoteValidity { blocks } => set_quote_validity(deps, env, info, blocks),
+        ExecuteMsg::WithdrawTreasury { denom, amount, to } => {
+            withdraw_treasury(deps, env, info, denom, amount, to)
+        }
+        ExecuteMsg::ExecuteTreasuryWithdrawal { id } => {
+            execute_treasury_withdrawal(deps, env, info, id)
+        }
+        ExecuteMsg::CancelTreasuryWithdrawal { id } => {
+            cancel_treasury_withdrawal(deps, env, info, id)
+        }
+        ExecuteMsg::SetTreasuryTimelock { seconds } => {
+            set_treasury_timelock(deps, env, info, seconds)
+        }
+        ExecuteMsg::SetProtocolFee { share_bps, collector } => {
+            set_protocol_fee(deps, env, info, share_bps, collector)
+        }
+        ExecuteMsg::CollectProtocolFees { pool_id } => {
+            collect_protocol_fees(deps, env, info, pool_id)
+        }
         ExecuteMsg::RemovePool(msg) => remove_pool(deps, env, info, msg),
         ExecuteMsg::SetLogAddress { pool_id, address } => {
             set_log_address(deps, env, info, pool_id, address)
         } //ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
-        ExecuteMsg::SetRouter { address } => set_router_address(deps, env, info, address)
+        ExecuteMsg::SetRouter { address } => set_router_address(deps, env, info, address),
+        ExecuteMsg::UpdateConfig {
+            token_code_id,
+            router,
+            default_timeout_seconds,
+        } => update_config(deps, env, info, token_code_id, router, default_timeout_seconds),
+        ExecuteMsg::TransferOwnership { address } => {
+            transfer_ownership(deps, env, info, address)
+        }
+        ExecuteMsg::AcceptOwnership {} => accept_ownership(deps, env, info),
+        ExecuteMsg::SetAddressPrefix { channel_id, prefix } => {
+            set_address_prefix(deps, env, info, channel_id, prefix)
+        }
+        ExecuteMsg::SetCounterpartyPort {
+            connection_id,
+            port_id,
+        } => set_counterparty_port(deps, env, info, connection_id, port_id),
+        ExecuteMsg::SetClaimAddress {
+            channel_id,
+            counterparty,
+            address,
+        } => set_claim_address(deps, env, info, channel_id, counterparty, address),
+        ExecuteMsg::ClaimSwapOutput {
+            channel_id,
+            counterparty,
+        } => claim_swap_output(deps, env, info, channel_id, counterparty),
+        ExecuteMsg::ProcessWithdrawalQueue { denom, limit } => {
+            process_withdrawal_queue(deps, env, info, denom, limit)
+        }
+        ExecuteMsg::SyncCounterpartyPool { pool_id } => {
+            sync_counterparty_pool(deps, env, info, pool_id)
+        }
+        ExecuteMsg::SetDriftTolerance { tolerance } => {
+            set_drift_tolerance(deps, env, info, tolerance)
+        }
+        ExecuteMsg::SetTimeoutBounds {
+            min_seconds,
+            max_seconds,
+        } => set_timeout_bounds(deps, env, info, min_seconds, max_seconds),
+        ExecuteMsg::SetSwapFeeBounds { min_bps, max_bps } => {
+            set_swap_fee_bounds(deps, env, info, min_bps, max_bps)
+        }
+        ExecuteMsg::SetInitializedPoolLimit { limit } => {
+            set_initialized_pool_limit(deps, env, info, limit)
+        }
+        ExecuteMsg::SingleSidedDeposit {
+            pool_id,
+            token_in,
+            min_shares,
+        } => single_sided_deposit(deps, env, info, pool_id, token_in, min_shares),
+        ExecuteMsg::ZapOut {
+            pool_id,
+            lp_amount,
+            denom_out,
+            min_out,
+        } => zap_out(deps, env, info, pool_id, lp_amount, denom_out, min_out),
+        ExecuteMsg::ReleaseGuard {} => release_guard(deps, env, info),
+        ExecuteMsg::MigrateStorage { limit } => migrate_storage(deps, env, info, limit),
+        ExecuteMsg::SetDebugAttributes { enabled } => {
+            set_debug_attributes(deps, env, info, enabled)
+        }
+        ExecuteMsg::SetMessageEnabled {
+            message_type,
+            enabled,
+        } => set_message_enabled(deps, env, info, message_type, enabled),
+        ExecuteMsg::SetPacketVeto {
+            channel_id,
+            sequence,
+            vetoed,
+        } => set_packet_veto(deps, env, info, channel_id, sequence, vetoed),
+        ExecuteMsg::SetDustThresholds {
+            pool_id,
+            min_swap,
+            min_deposit,
+        } => set_dust_thresholds(deps, env, info, pool_id, min_swap, min_deposit),
+        ExecuteMsg::GrantRole { address, role } => grant_role(deps, env, info, address, role),
+        ExecuteMsg::RevokeRole { address, role } => revoke_role(deps, env, info, address, role),
+        ExecuteMsg::RevertPacket {
+            channel_id,
+            sequence,
+        } => revert_packet(deps, env, info, channel_id, sequence),
+        ExecuteMsg::SetAssetMetadata { denom, metadata } => {
+            set_asset_metadata(deps, env, info, denom, metadata)
+        }
+        ExecuteMsg::SetPoolWhitelist { pool_id, addresses } => {
+            set_pool_whitelist(deps, env, info, pool_id, addresses)
+        }
+        ExecuteMsg::SetLpTokenMarketing { pool_id, marketing } => {
+            set_lp_token_marketing(deps, env, info, pool_id, marketing)
+        }
     }
 }
 
@@ -154,7 +520,12 @@ fn remove_pool(
         )));
     }
 
+    if let Some(pool) = POOLS.may_load(deps.storage, &msg.pool_id)? {
+        INITIALIZED_POOLS.remove(deps.storage, (&pool.source_creator, &pool.id));
+    }
     POOL_TOKENS_LIST.remove(deps.storage, &msg.pool_id);
+    LP_TOKEN_STATUS.remove(deps.storage, &msg.pool_id);
+    LP_TOKEN_MARKETING.remove(deps.storage, &msg.pool_id);
     POOLS.remove(deps.storage, &msg.pool_id);
 
     Ok(Response::default())
@@ -198,502 +569,756 @@ fn set_router_address(
     Ok(Response::default())
 }
 
-/// Receives a message of type [`Cw20ReceiveMsg`] and processes it depending on the received template.
-///
-/// * **cw20_msg** is the CW20 message that has to be processed.
-pub fn receive_cw20(
+fn update_config(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
-    cw20_msg: Cw20ReceiveMsg,
+    token_code_id: Option<u64>,
+    router: Option<String>,
+    default_timeout_seconds: Option<u64>,
 ) -> Result<Response, ContractError> {
-    match from_binary(&cw20_msg.msg) {
-        Ok(Cw20HookMsg::WithdrawLiquidity {
-            pool_id,
-            receiver,
-            counterparty_receiver,
-            timeout_height,
-            timeout_timestamp,
-        }) => {
-            // TODO: add sender check
-            let msg: MsgMultiAssetWithdrawRequest = MsgMultiAssetWithdrawRequest {
-                pool_id: pool_id.clone(),
-                receiver,
-                counterparty_receiver,
-                pool_token: Coin {
-                    denom: pool_id,
-                    amount: cw20_msg.amount,
-                },
-                timeout_height,
-                timeout_timestamp,
-                memo: None
-            };
-            multi_asset_withdraw(deps, env, info, msg)
-        }
-        Err(err) => Err(err.into()),
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
+    }
+
+    let mut res = Response::default().add_attribute("action", "update_config");
+    if let Some(token_code_id) = token_code_id {
+        config.token_code_id = token_code_id;
+        res = res.add_attribute("token_code_id", token_code_id.to_string());
+    }
+    if let Some(router) = router {
+        res = res.add_attribute("router", router.clone());
+        config.router = router;
     }
+    if let Some(seconds) = default_timeout_seconds {
+        assert_timeout_in_bounds(&config, seconds)?;
+        config.default_timeout_seconds = seconds;
+        res = res.add_attribute("default_timeout_seconds", seconds.to_string());
+    }
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(res)
 }
 
-fn make_pool(
+/// Offers ownership of the contract to `address`, which becomes admin once it accepts.
+/// A later offer replaces a pending one.
+fn transfer_ownership(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
-    msg: MsgMakePoolRequest,
+    address: String,
 ) -> Result<Response, ContractError> {
-    // validate message
-    let _source_port = msg.source_port.clone();
-    let source_channel = msg.source_channel.clone();
-
-    if let Err(err) = msg.validate_basic() {
-        return Err(ContractError::Std(StdError::generic_err(format!(
-            "Failed to validate message: {}",
-            err
-        ))));
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
     }
+    let pending_admin = deps.api.addr_validate(&address)?;
+    config.pending_admin = Some(pending_admin.to_string());
+    CONFIG.save(deps.storage, &config)?;
 
-    let mut tokens: [Coin; 2] = Default::default();
-    tokens[0] = msg.liquidity[0].balance.clone();
-    tokens[1] = msg.liquidity[1].balance.clone();
-
-    let pool_id = get_pool_id_with_tokens(
-        &tokens,
-        msg.source_chain_id.clone(),
-        msg.destination_chain_id.clone(),
-    );
+    Ok(Response::default()
+        .add_attribute("action", "transfer_ownership")
+        .add_attribute("pending_admin", pending_admin))
+}
 
-    TEMP.save(deps.storage, &pool_id)?;
-    // load pool throw error if not found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &pool_id)?;
-    if let Some(_pool) = interchain_pool_temp {
-        return Err(ContractError::Std(StdError::generic_err(
-            "Pool already exists".to_string(),
-        )));
+fn accept_ownership(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.pending_admin.as_deref() != Some(info.sender.as_str()) {
+        return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
     }
+    let previous_admin = std::mem::replace(&mut config.admin, info.sender.to_string());
+    config.pending_admin = None;
+    CONFIG.save(deps.storage, &config)?;
 
-    // check if given tokens are received here
-    let mut ok = false;
-    // First token in this chain only first token needs to be verified
-    for asset in info.funds {
-        if (asset.denom == tokens[0].denom && asset.amount == tokens[0].amount)
-            || (asset.denom == tokens[1].denom && asset.amount == tokens[1].amount)
-        {
-            ok = true;
+    Ok(Response::default()
+        .add_attribute("action", "accept_ownership")
+        .add_attribute("previous_admin", previous_admin)
+        .add_attribute("admin", info.sender))
+}
+
+/// Packet types an execute message sends.
+fn packet_types(msg: &ExecuteMsg) -> Vec<InterchainMessageType> {
+    let swap_type = |msg: &MsgSwapRequest| match msg.swap_type {
+        SwapMsgType::LEFT => InterchainMessageType::LeftSwap,
+        SwapMsgType::RIGHT => InterchainMessageType::RightSwap,
+    };
+    match msg {
+        ExecuteMsg::MakePool(_) => vec![InterchainMessageType::MakePool],
+        ExecuteMsg::TakePool(_) => vec![InterchainMessageType::TakePool],
+        ExecuteMsg::CancelPool(_) => vec![InterchainMessageType::CancelPool],
+        ExecuteMsg::SetPoolManager(_) => vec![InterchainMessageType::SetPoolManager],
+        ExecuteMsg::SetPoolStatus(_) => vec![InterchainMessageType::SetPoolStatus],
+        ExecuteMsg::UpdatePoolFee(_) => vec![InterchainMessageType::UpdateFee],
+        ExecuteMsg::DecommissionPool(_) => vec![InterchainMessageType::DecommissionPool],
+        ExecuteMsg::SingleAssetDeposit(_) | ExecuteMsg::SingleSidedDeposit { .. } => {
+            vec![InterchainMessageType::SingleAssetDeposit]
         }
+        ExecuteMsg::MakeMultiAssetDeposit(_) | ExecuteMsg::MakeMultiAssetDepositByRatio(_) => {
+            vec![InterchainMessageType::MakeMultiDeposit]
+        }
+        ExecuteMsg::CancelMultiAssetDeposit(_) => vec![InterchainMessageType::CancelMultiDeposit],
+        ExecuteMsg::TakeMultiAssetDeposit(_) => vec![InterchainMessageType::TakeMultiDeposit],
+        ExecuteMsg::MatchOrders(_) => vec![InterchainMessageType::MatchMultiDeposit],
+        ExecuteMsg::MultiAssetWithdraw(_) | ExecuteMsg::ZapOut { .. } => {
+            vec![InterchainMessageType::MultiWithdraw]
+        }
+        ExecuteMsg::Swap(msg) | ExecuteMsg::FirmSwap { swap: msg, .. } => vec![swap_type(msg)],
+        ExecuteMsg::BatchSwap { legs } => legs.iter().map(swap_type).collect(),
+        _ => vec![],
     }
-    if !ok {
-        return Err(ContractError::Std(StdError::generic_err(
-            "Funds mismatch: Funds mismatched to with message and sent values: Make Pool"
-                .to_string(),
-        )));
-    }
+}
 
-    let supply: Coin = Coin {
-        amount: Uint128::from(0u64),
-        denom: pool_id.clone(),
-    };
-    let interchain_pool: InterchainLiquidityPool = InterchainLiquidityPool {
-        id: pool_id.clone(),
-        source_creator: msg.creator.clone(),
-        destination_creator: msg.counterparty_creator.clone(),
-        assets: msg.liquidity.clone(),
-        supply,
-        status: PoolStatus::Initialized,
-        counter_party_port: msg.source_port.clone(),
-        counter_party_channel: msg.source_channel.clone(),
-        swap_fee: msg.swap_fee,
-        source_chain_id: msg.source_chain_id.clone(),
-        destination_chain_id: msg.destination_chain_id.clone(),
-        pool_price: 0,
+/// Pools an execute message acts on, with the packet type it sends them.
+fn pool_packets(msg: &ExecuteMsg) -> Vec<(&str, InterchainMessageType)> {
+    let pool_ids: Vec<&str> = match msg {
+        ExecuteMsg::TakePool(msg) => vec![&msg.pool_id],
+        ExecuteMsg::CancelPool(msg) => vec![&msg.pool_id],
+        ExecuteMsg::SetPoolManager(msg) => vec![&msg.pool_id],
+        ExecuteMsg::SetPoolStatus(msg) => vec![&msg.pool_id],
+        ExecuteMsg::UpdatePoolFee(msg) => vec![&msg.pool_id],
+        ExecuteMsg::DecommissionPool(msg) => vec![&msg.pool_id],
+        ExecuteMsg::SingleAssetDeposit(msg) => vec![&msg.pool_id],
+        ExecuteMsg::SingleSidedDeposit { pool_id, .. } => vec![pool_id],
+        ExecuteMsg::MakeMultiAssetDeposit(msg) => vec![&msg.pool_id],
+        ExecuteMsg::MakeMultiAssetDepositByRatio(msg) => vec![&msg.pool_id],
+        ExecuteMsg::CancelMultiAssetDeposit(msg) => vec![&msg.pool_id],
+        ExecuteMsg::TakeMultiAssetDeposit(msg) => vec![&msg.pool_id],
+        ExecuteMsg::MatchOrders(msg) => vec![&msg.pool_id],
+        ExecuteMsg::MultiAssetWithdraw(msg) => vec![&msg.pool_id],
+        ExecuteMsg::ZapOut { pool_id, .. } => vec![pool_id],
+        ExecuteMsg::Swap(msg) | ExecuteMsg::FirmSwap { swap: msg, .. } => vec![&msg.pool_id],
+        ExecuteMsg::BatchSwap { legs } => legs.iter().map(|leg| leg.pool_id.as_str()).collect(),
+        _ => vec![],
     };
-    POOLS.save(deps.storage, &pool_id, &interchain_pool)?;
+    pool_ids.into_iter().zip(packet_types(msg)).collect()
+}
 
-    // Instantiate token
-    let config = CONFIG.load(deps.storage)?;
-    let sub_msg: Vec<SubMsg>;
-    if let Some(_lp_token) = POOL_TOKENS_LIST.may_load(deps.storage, &pool_id)? {
-        return Err(ContractError::Std(StdError::generic_err(
-            "Pool token already exist: Make Pool".to_string(),
-        )));
-        //sub_msg = vec![];
-    } else {
-        // Create the LP token contract
-        sub_msg = vec![SubMsg {
-            msg: WasmMsg::Instantiate {
-                code_id: config.token_code_id,
-                msg: to_binary(&TokenInstantiateMsg {
-                    name: "sideLP".to_string(),
-                    symbol: "sideLP".to_string(),
-                    decimals: LP_TOKEN_PRECISION,
-                    initial_balances: vec![],
-                    marketing: None,
-                    mint: Some(MinterResponse {
-                        minter: env.contract.address.to_string(),
-                        cap: None,
-                    }),
-                })?,
-                funds: vec![],
-                admin: None,
-                label: String::from("Sidechain LP token"),
-            }
-            .into(),
-            id: INSTANTIATE_TOKEN_REPLY_ID,
-            gas_limit: None,
-            reply_on: ReplyOn::Success,
-        }];
+fn set_debug_attributes(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
     }
+    config.debug_attributes = enabled;
+    CONFIG.save(deps.storage, &config)?;
 
-    let state_change_data = to_binary(&StateChange {
-        in_tokens: None,
-        out_tokens: None,
-        pool_tokens: None,
-        pool_id: Some(pool_id.clone()),
-        multi_deposit_order_id: None,
-        source_chain_id: None,
-        shares: None,
-    })?;
-
-    let pool_data = to_binary(&msg)?;
-    // Assuming `msg.memo` is an Option<String> containing the base64-encoded memo
-   // Decode the base64 memo using the standard engine
-    let ibc_packet_data = InterchainSwapPacketData {
-        r#type: InterchainMessageType::MakePool,
-        data: pool_data,
-        state_change: Some(state_change_data),
-        memo: msg.memo
-    };
+    Ok(Response::default()
+        .add_attribute("action", "set_debug_attributes")
+        .add_attribute("enabled", enabled.to_string()))
+}
 
-    
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: source_channel,
-        data: to_binary(&ibc_packet_data)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
+/// Moves entries of the namespaces being migrated, `limit` in total so a large map is
+/// moved over several transactions.
+fn migrate_storage(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    limit: u32,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
+    }
 
-    let res = Response::default()
-        .add_attribute("pool_id", pool_id.clone())
-        .add_attribute("action", "make_pool")
-        .add_attribute("ics101-lp-instantiate", pool_id)
-        .add_submessages(sub_msg)
-        .add_message(ibc_msg);
+    let mut remaining = limit as usize;
+    let mut res = Response::default().add_attribute("action", "migrate_storage");
+    for map in VERSIONED_MAPS {
+        if remaining == 0 {
+            break;
+        }
+        let moved = map.migrate(deps.storage, remaining)?;
+        remaining -= moved;
+        if moved > 0 {
+            res = res.add_attribute(map.namespace(), moved.to_string());
+        }
+    }
     Ok(res)
 }
 
-fn take_pool(
+fn set_message_enabled(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
-    msg: MsgTakePoolRequest,
+    message_type: InterchainMessageType,
+    enabled: bool,
 ) -> Result<Response, ContractError> {
-    // load pool throw error if not found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
-    let interchain_pool;
-    if let Some(pool) = interchain_pool_temp {
-        interchain_pool = pool
-    } else {
+    assert_role(deps.as_ref(), &info, Role::Pauser)?;
+    let mut config = CONFIG.load(deps.storage)?;
+
+    config.disabled_messages.retain(|disabled| *disabled != message_type);
+    if !enabled {
+        config.disabled_messages.push(message_type.clone());
+    }
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "set_message_enabled")
+        .add_attribute("message_type", format!("{:?}", message_type))
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+fn set_packet_veto(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    channel_id: String,
+    sequence: u64,
+    vetoed: bool,
+) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info, Role::Pauser)?;
+
+    if RECEIVED_PACKETS.has(deps.storage, (&channel_id, sequence)) {
         return Err(ContractError::Std(StdError::generic_err(format!(
-            "Pool doesn't exist {}",
-            msg.pool_id
+            "Packet {} on {} was already received",
+            sequence, channel_id
         ))));
     }
-
-    let config = CONFIG.load(deps.storage)?;
-    // Send cw20 instantiate message
-    let sub_msg: Vec<SubMsg>;
-    if let Some(_lp_token) = POOL_TOKENS_LIST.may_load(deps.storage, &msg.pool_id)? {
-        // do nothing
-        sub_msg = vec![];
+    if vetoed {
+        VETOED_PACKETS.save(deps.storage, (&channel_id, sequence), &true)?;
     } else {
-        // Create the LP token contract
-        sub_msg = vec![SubMsg {
-            msg: WasmMsg::Instantiate {
-                code_id: config.token_code_id,
-                msg: to_binary(&TokenInstantiateMsg {
-                    name: "sideLP".to_string(),
-                    symbol: "sideLP".to_string(),
-                    decimals: LP_TOKEN_PRECISION,
-                    initial_balances: vec![],
-                    marketing: None,
-                    mint: Some(MinterResponse {
-                        minter: env.contract.address.to_string(),
-                        cap: None,
-                    }),
-                })?,
-                funds: vec![],
-                admin: None,
-                label: String::from("Sidechain LP token"),
-            }
-            .into(),
-            id: INSTANTIATE_TOKEN_REPLY_ID,
-            gas_limit: None,
-            reply_on: ReplyOn::Success,
-        }];
+        VETOED_PACKETS.remove(deps.storage, (&channel_id, sequence));
     }
 
-    TEMP.save(deps.storage, &msg.pool_id)?;
+    Ok(Response::default()
+        .add_attribute("action", "set_packet_veto")
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("sequence", sequence.to_string())
+        .add_attribute("vetoed", vetoed.to_string()))
+}
 
-    if interchain_pool.status != PoolStatus::Initialized {
-        return Err(ContractError::InvalidStatus);
+/// Passes for the admin and for addresses granted `role`.
+fn assert_role(deps: Deps, info: &MessageInfo, role: Role) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.admin == info.sender {
+        return Ok(());
+    }
+    let roles = ROLES.may_load(deps.storage, info.sender.as_str())?.unwrap_or_default();
+    if !roles.contains(&role) {
+        return Err(ContractError::MissingRole {
+            role: role.as_str().to_string(),
+        });
     }
+    Ok(())
+}
 
-    // order can only be taken by creator
-    if interchain_pool.destination_creator != info.sender {
-        return Err(ContractError::InvalidSender);
+fn set_asset_metadata(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    denom: String,
+    metadata: Option<AssetMetadata>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "not allowed".to_string(),
+        )));
     }
 
-    // check balance and funds sent handle error
-    let token = interchain_pool
-        .find_asset_by_side(PoolSide::SOURCE)
-        .map_err(|err| StdError::generic_err(format!("Failed to find asset: {}", err)))?;
-    // check if given tokens are received here
-    let mut ok = false;
-    for asset in info.funds {
-        if asset.denom == token.balance.denom && asset.amount == token.balance.amount {
-            ok = true;
-        }
+    match &metadata {
+        Some(metadata) => ASSET_METADATA.save(deps.storage, &denom, metadata)?,
+        None => ASSET_METADATA.remove(deps.storage, &denom),
     }
-    if !ok {
+
+    Ok(Response::default()
+        .add_attribute("action", "set_asset_metadata")
+        .add_attribute("denom", denom)
+        .add_attribute("registered", metadata.is_some().to_string()))
+}
+
+fn grant_role(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String,
+    role: Role,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
         return Err(ContractError::Std(StdError::generic_err(
-            "Funds mismatch: Funds mismatched to with message and sent values: Take Pool"
-                .to_string(),
+            "not allowed".to_string(),
         )));
     }
 
-    let mut tokens: [Coin; 2] = Default::default();
-    tokens[0] = interchain_pool.assets[0].balance.clone();
-    tokens[1] = interchain_pool.assets[1].balance.clone();
+    let address = validate_local_address(deps.api, &address)?;
+    let mut roles = ROLES.may_load(deps.storage, address.as_str())?.unwrap_or_default();
+    if !roles.contains(&role) {
+        roles.push(role);
+        ROLES.save(deps.storage, address.as_str(), &roles)?;
+    }
 
-    // find number of tokens to be minted
-    // Create the interchain market maker (amm).
-    let amm = InterchainMarketMaker {
-        pool_id: msg.pool_id.clone(),
-        pool: interchain_pool.clone(),
-        fee_rate: interchain_pool.swap_fee,
-    };
+    Ok(Response::default()
+        .add_attribute("action", "grant_role")
+        .add_attribute("address", address)
+        .add_attribute("role", role.as_str()))
+}
 
-    let pool_tokens = amm
-        .deposit_multi_asset(&tokens)
-        .map_err(|err| StdError::generic_err(format!("Failed to deposit multi asset: {}", err)))?;
-    let mut new_shares = Uint128::from(0u128);
-    for pool in pool_tokens {
-        new_shares += pool.amount;
+fn revoke_role(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String,
+    role: Role,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "not allowed".to_string(),
+        )));
     }
 
-    let state_change_data = to_binary(&StateChange {
-        in_tokens: None,
-        out_tokens: None,
-        pool_tokens: None,
-        pool_id: None,
-        multi_deposit_order_id: None,
-        source_chain_id: None,
-        shares: Some(new_shares),
-    })?;
+    let mut roles = ROLES.may_load(deps.storage, &address)?.unwrap_or_default();
+    roles.retain(|r| *r != role);
+    if roles.is_empty() {
+        ROLES.remove(deps.storage, &address);
+    } else {
+        ROLES.save(deps.storage, &address, &roles)?;
+    }
 
-    let pool_data = to_binary(&msg).unwrap();
-    let ibc_packet_data = InterchainSwapPacketData {
-        r#type: InterchainMessageType::TakePool,
-        data: pool_data,
-        state_change: Some(state_change_data),
-        memo: msg.memo,
-    };
+    Ok(Response::default()
+        .add_attribute("action", "revoke_role")
+        .add_attribute("address", address)
+        .add_attribute("role", role.as_str()))
+}
 
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: interchain_pool.counter_party_channel,
-        data: to_binary(&ibc_packet_data)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
+fn set_address_prefix(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    channel_id: String,
+    prefix: String,
+) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info, Role::ChannelManager)?;
 
-    let res = Response::default()
-        .add_submessages(sub_msg)
-        .add_message(ibc_msg)
-        .add_attribute("pool_id", msg.pool_id)
-        .add_attribute("action", "take_pool");
-    Ok(res)
+    validate_prefix(&prefix)?;
+    COUNTERPARTY_ADDRESS_PREFIX.save(deps.storage, &channel_id, &prefix)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "set_address_prefix")
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("prefix", prefix))
 }
 
-fn cancel_pool(
+fn set_counterparty_port(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
-    msg: MsgCancelPoolRequest,
+    connection_id: String,
+    port_id: String,
 ) -> Result<Response, ContractError> {
-    // load pool throw error if not found
-    let config = CONFIG.load(deps.storage)?;
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
-    let interchain_pool;
-    if let Some(pool) = interchain_pool_temp {
-        interchain_pool = pool
-    } else {
+    assert_role(deps.as_ref(), &info, Role::ChannelManager)?;
+
+    COUNTERPARTY_PORTS.save(deps.storage, &connection_id, &port_id)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "set_counterparty_port")
+        .add_attribute("connection_id", connection_id)
+        .add_attribute("port_id", port_id))
+}
+
+fn set_claim_address(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    channel_id: String,
+    counterparty: String,
+    address: String,
+) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info, Role::ChannelManager)?;
+
+    let address = validate_local_address(deps.api, &address)?;
+    CLAIM_ADDRESSES.save(deps.storage, (&channel_id, &counterparty), &address)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "set_claim_address")
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("counterparty", counterparty)
+        .add_attribute("address", address))
+}
+
+/// Pays the swap outputs held for a counterparty sender to its claim address.
+fn claim_swap_output(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    channel_id: String,
+    counterparty: String,
+) -> Result<Response, ContractError> {
+    let key = (channel_id.as_str(), counterparty.as_str());
+    let address = CLAIM_ADDRESSES.may_load(deps.storage, key)?.ok_or_else(|| {
+        ContractError::Std(StdError::generic_err(format!(
+            "No claim address set for {}",
+            counterparty
+        )))
+    })?;
+    let claims = SWAP_CLAIMS.may_load(deps.storage, key)?.unwrap_or_default();
+    if claims.is_empty() {
         return Err(ContractError::Std(StdError::generic_err(format!(
-            "Pool doesn't exist {}",
-            msg.pool_id
+            "Nothing to claim for {}",
+            counterparty
         ))));
     }
+    SWAP_CLAIMS.remove(deps.storage, key);
+    for coin in claims.iter() {
+        release_escrow(deps.storage, coin)?;
+    }
 
-    if interchain_pool.status != PoolStatus::Initialized {
-        return Err(ContractError::InvalidStatus);
+    let mut sub_messages = vec![];
+    for coin in claims.iter() {
+        sub_messages.extend(send_tokens_coin(&address, coin.clone())?);
     }
 
-    // order can only be cancelled by creator or admin
-    if !((interchain_pool.source_creator == info.sender) || (info.sender == config.admin)) {
-        return Err(ContractError::InvalidSender);
+    Ok(Response::default()
+        .add_submessages(sub_messages)
+        .add_attribute("action", "claim_swap_output")
+        .add_attribute("counterparty", counterparty)
+        .add_attribute("receiver", address)
+        .add_attribute("amount", coins_to_string(&claims)))
+}
+
+fn process_withdrawal_queue(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    denom: String,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let queued = WITHDRAWAL_QUEUE
+        .prefix(&denom)
+        .range(deps.storage, None, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut balance = deps
+        .querier
+        .query_balance(&env.contract.address, &denom)?
+        .amount;
+    let escrow = escrowed_funds(deps.storage, &denom)?;
+
+    let mut sub_messages = vec![];
+    let mut paid = 0u64;
+    for (id, withdrawal) in queued {
+        // later withdrawals wait for the earlier ones
+        if balance < withdrawal.token.amount + escrow {
+            break;
+        }
+        balance -= withdrawal.token.amount;
+        WITHDRAWAL_QUEUE.remove(deps.storage, (&denom, id));
+        sub_messages.extend(send_tokens_coin(
+            &Addr::unchecked(withdrawal.receiver),
+            withdrawal.token,
+        )?);
+        paid += 1;
     }
 
-    let pool_data = to_binary(&msg).unwrap();
-    let ibc_packet_data = InterchainSwapPacketData {
-        r#type: InterchainMessageType::CancelPool,
-        data: pool_data,
-        state_change: None,
-        memo: msg.memo,
-    };
+    Ok(Response::default()
+        .add_submessages(sub_messages)
+        .add_attribute("action", "process_withdrawal_queue")
+        .add_attribute("denom", denom)
+        .add_attribute("paid", paid.to_string()))
+}
+
+/// Sends an interchain query for the counterparty copy of a pool. Anyone may
+/// trigger it, e.g. a keeper that syncs pools periodically.
+fn sync_counterparty_pool(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    pool_id: String,
+) -> Result<Response, ContractError> {
+    let pool = POOLS.may_load(deps.storage, &pool_id)?.ok_or_else(|| {
+        ContractError::Std(StdError::generic_err(format!(
+            "Pool doesn't exist {}",
+            pool_id
+        )))
+    })?;
+
+    let channel = CHANNEL_INFO.load(deps.storage, &pool.counter_party_channel)?;
+    let contract = channel
+        .counterparty_endpoint
+        .port_id
+        .strip_prefix("wasm.")
+        .ok_or_else(|| {
+            StdError::generic_err(format!(
+                "Counterparty port {} is not a contract port",
+                channel.counterparty_endpoint.port_id
+            ))
+        })?;
+    let icq_channel = icq_channel_for_connection(deps.storage, &channel.connection_id)?
+        .ok_or_else(|| {
+            StdError::generic_err(format!(
+                "No interchain query channel on connection {}",
+                channel.connection_id
+            ))
+        })?;
 
     let ibc_msg = IbcMsg::SendPacket {
-        channel_id: interchain_pool.counter_party_channel,
-        data: to_binary(&ibc_packet_data)?,
+        channel_id: icq_channel,
+        data: to_binary(&pool_query_packet(contract, &pool_id)?)?,
         timeout: IbcTimeout::from(
             env.block
                 .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
+                .plus_seconds(CONFIG.load(deps.storage)?.default_timeout_seconds),
         ),
     };
 
-    let res = Response::default()
+    Ok(Response::default()
         .add_message(ibc_msg)
-        .add_attribute("pool_id", msg.pool_id)
-        .add_attribute("action", "take_pool");
-    Ok(res)
+        .add_attribute("action", "sync_counterparty_pool")
+        .add_attribute("pool_id", pool_id))
 }
 
-pub fn single_asset_deposit(
+fn set_drift_tolerance(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
-    msg: MsgSingleAssetDepositRequest,
+    tolerance: u32,
 ) -> Result<Response, ContractError> {
-    if let Err(err) = msg.validate_basic() {
-        return Err(ContractError::Std(StdError::generic_err(format!(
-            "Failed to validate message: {}",
-            err
-        ))));
+    let config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "not allowed".to_string(),
+        )));
     }
 
-    // check if given tokens are received here
-    let mut ok = false;
-    for asset in info.funds {
-        if asset.denom == msg.token.denom && asset.amount == msg.token.amount {
-            ok = true;
-        }
-    }
-    if !ok {
+    DRIFT_TOLERANCE.save(deps.storage, &tolerance)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "set_drift_tolerance")
+        .add_attribute("tolerance", tolerance.to_string()))
+}
+
+fn set_timeout_bounds(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    min_seconds: u64,
+    max_seconds: u64,
+) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info, Role::ChannelManager)?;
+    let mut config = CONFIG.load(deps.storage)?;
+
+    if min_seconds == 0 || min_seconds > max_seconds {
         return Err(ContractError::Std(StdError::generic_err(
-            "Funds mismatch: Funds mismatched to with message and sent values: Take Pool"
-                .to_string(),
+            "Invalid timeout bounds".to_string(),
         )));
     }
 
-    let pool_id = msg.pool_id.clone();
-    let pool = POOLS.load(deps.storage, &pool_id)?;
+    config.min_timeout_seconds = min_seconds;
+    config.max_timeout_seconds = max_seconds;
+    assert_timeout_in_bounds(&config, config.default_timeout_seconds)?;
+    CONFIG.save(deps.storage, &config)?;
 
-    // If the pool is empty, then return a `Failure` response
-    if pool.supply.amount.is_zero() {
+    Ok(Response::default()
+        .add_attribute("action", "set_timeout_bounds")
+        .add_attribute("min_seconds", min_seconds.to_string())
+        .add_attribute("max_seconds", max_seconds.to_string()))
+}
+
+fn set_swap_fee_bounds(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    min_bps: u32,
+    max_bps: u32,
+) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info, Role::FeeSetter)?;
+    let mut config = CONFIG.load(deps.storage)?;
+
+    if min_bps > max_bps || max_bps >= u32::from(FEE_PRECISION) {
         return Err(ContractError::Std(StdError::generic_err(
-            "Single asset cannot be provided to empty pool".to_string(),
+            "Invalid swap fee bounds".to_string(),
         )));
     }
 
-    if pool.status != PoolStatus::Active {
-        return Err(ContractError::NotReadyForSwap);
-    }
+    config.min_swap_fee_bps = min_bps;
+    config.max_swap_fee_bps = max_bps;
+    CONFIG.save(deps.storage, &config)?;
 
-    // Create the interchain market maker (amm).
-    let amm = InterchainMarketMaker {
-        pool_id,
-        pool: pool.clone(),
-        fee_rate: pool.swap_fee,
-    };
+    Ok(Response::default()
+        .add_attribute("action", "set_swap_fee_bounds")
+        .add_attribute("min_bps", min_bps.to_string())
+        .add_attribute("max_bps", max_bps.to_string()))
+}
 
-    // Deposit single asset to the AMM.
-    let pool_token = amm
-        .deposit_single_asset(&msg.token)
-        .map_err(|err| StdError::generic_err(format!("Failed to deposit single asset: {}", err)))?;
+fn set_initialized_pool_limit(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    limit: u32,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
+    }
+    config.max_initialized_pools = limit;
+    CONFIG.save(deps.storage, &config)?;
 
-    let msg_data = to_binary(&msg).unwrap();
-    let state_change_data = to_binary(&StateChange {
-        in_tokens: None,
-        out_tokens: None,
-        pool_tokens: Some(vec![pool_token.clone()]),
-        pool_id: None,
-        multi_deposit_order_id: None,
-        source_chain_id: None,
-        shares: Some(pool_token.amount),
-    })?;
-    // Construct the IBC swap packet.
-    let packet_data = InterchainSwapPacketData {
-        r#type: InterchainMessageType::SingleAssetDeposit,
-        data: msg_data, // Use proper serialization for the `data` field.
-        state_change: Some(state_change_data),
-        memo: msg.memo,
-    };
+    Ok(Response::default()
+        .add_attribute("action", "set_initialized_pool_limit")
+        .add_attribute("limit", limit.to_string()))
+}
 
-    // Send the IBC swap packet.
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: pool.counter_party_channel,
-        data: to_binary(&packet_data)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
+/// Reverts the state changes of a received packet, e.g. after an accounting
+/// bug. Changes of packets received later are kept, see [`undo::revert`].
+fn revert_packet(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    channel_id: String,
+    sequence: u64,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "not allowed".to_string(),
+        )));
+    }
 
-    let res = Response::default()
-        .add_message(ibc_msg)
-        .add_attribute("pool_id", msg.pool_id)
-        .add_attribute("action", "single_asset_deposit");
-    Ok(res)
+    let record = UNDO_LOG
+        .may_load(deps.storage, (&channel_id, sequence))?
+        .ok_or_else(|| {
+            StdError::generic_err(format!("No undo record for {} {}", channel_id, sequence))
+        })?;
+    undo::revert(deps.storage, &record)?;
+    UNDO_LOG.remove(deps.storage, (&channel_id, sequence));
+    UNDO_EXPIRY.remove(deps.storage, (record.received_at, &channel_id, sequence));
+
+    Ok(Response::default()
+        .add_attribute("action", "revert_packet")
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("sequence", sequence.to_string()))
 }
 
-fn make_multi_asset_deposit(
+/// Receives a message of type [`Cw20ReceiveMsg`] and processes it depending on the received template.
+///
+/// * **cw20_msg** is the CW20 message that has to be processed.
+pub fn receive_cw20(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg: MsgMakeMultiAssetDepositRequest,
+    cw20_msg: Cw20ReceiveMsg,
 ) -> Result<Response, ContractError> {
-    // load pool throw error if not found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
-    let interchain_pool;
-    if let Some(pool) = interchain_pool_temp {
-        interchain_pool = pool
-    } else {
+    match from_binary(&cw20_msg.msg) {
+        Ok(Cw20HookMsg::WithdrawLiquidity {
+            pool_id,
+            receiver,
+            counterparty_receiver,
+            timeout_height,
+            timeout_timestamp,
+        }) => {
+            // TODO: add sender check
+            let msg: MsgMultiAssetWithdrawRequest = MsgMultiAssetWithdrawRequest {
+                pool_id: pool_id.clone(),
+                receiver,
+                counterparty_receiver,
+                pool_token: Coin {
+                    denom: pool_id,
+                    amount: cw20_msg.amount,
+                },
+                timeout_height,
+                timeout_timestamp,
+                memo: None
+            };
+            multi_asset_withdraw(deps, env, info, msg)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn make_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    mut msg: MsgMakePoolRequest,
+) -> Result<Response, ContractError> {
+    // validate message
+    let _source_port = msg.source_port.clone();
+    let source_channel = msg.source_channel.clone();
+
+    if let Err(err) = msg.validate_basic() {
         return Err(ContractError::Std(StdError::generic_err(format!(
-            "Pool doesn't exist {}",
-            msg.pool_id
+            "Failed to validate message: {}",
+            err
         ))));
     }
-    // TODO: deposit balance or any balance can't be zero
-    // Add checks in every function
 
-    let mut tokens: [Coin; 2] = Default::default();
-    tokens[0] = msg.deposits[0].balance.clone();
-    tokens[1] = msg.deposits[1].balance.clone();
+    validate_local_address(deps.api, &msg.creator)?;
+    // the initialized pools are capped per creator, who has to be the sender
+    if msg.creator != info.sender {
+        return Err(ContractError::InvalidSender);
+    }
+    if let Some(refund_to) = &msg.refund_to {
+        validate_local_address(deps.api, refund_to)?;
+    }
+    validate_counterparty_address(deps.storage, &source_channel, &msg.counterparty_creator)?;
+    validate_asset_decimals(deps.storage, &msg.liquidity)?;
 
-    // check if given tokens are received here
-    let mut ok = false;
-    // First token in this chain only first token needs to be verified
-    for asset in info.funds {
-        if asset.denom == tokens[0].denom && asset.amount == tokens[0].amount
-            || (asset.denom == tokens[1].denom && asset.amount == tokens[1].amount)
-        {
-            ok = true;
+    // the chain ids may be left empty, the pool id is derived from the ids filled in here
+    if msg.source_chain_id.is_empty() {
+        msg.source_chain_id = env.block.chain_id.clone();
+    }
+    msg.destination_chain_id =
+        bind_counterparty_chain_id(deps.storage, &source_channel, &msg.destination_chain_id)?;
+
+    let config = CONFIG.load(deps.storage)?;
+    if let Some(timeout) = msg.packet_timeout_seconds {
+        assert_timeout_in_bounds(&config, timeout)?;
+    }
+    for fee in [Some(msg.swap_fee), msg.right_swap_fee].into_iter().flatten() {
+        if !config.swap_fee_in_bounds(fee) {
+            return Err(ContractError::InvalidSwapFee {
+                fee,
+                min: config.min_swap_fee_bps,
+                max: config.max_swap_fee_bps,
+            });
         }
     }
+
+    let tokens: Vec<Coin> = msg.liquidity.iter().map(|asset| asset.balance.clone()).collect();
+
+    let pool_id = get_pool_id_with_tokens(
+        &tokens,
+        msg.source_chain_id.clone(),
+        msg.destination_chain_id.clone(),
+    );
+
+    // load pool throw error if not found
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &pool_id)?;
+    if let Some(_pool) = interchain_pool_temp {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Pool already exists".to_string(),
+        )));
+    }
+
+    // each pool waiting to be taken holds an LP token and storage entries
+    let initialized = INITIALIZED_POOLS
+        .prefix(&msg.creator)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(config.max_initialized_pools as usize)
+        .count();
+    if initialized >= config.max_initialized_pools as usize {
+        return Err(ContractError::TooManyInitializedPools {
+            creator: msg.creator,
+            limit: config.max_initialized_pools,
+        });
+    }
+
+    // check if given tokens are received here, only the assets of this chain are sent
+    let ok = msg
+        .liquidity
+        .iter()
+        .filter(|asset| asset.side == PoolSide::SOURCE)
+        .all(|asset| info.funds.contains(&asset.balance));
     if !ok {
         return Err(ContractError::Std(StdError::generic_err(
             "Funds mismatch: Funds mismatched to with message and sent values: Make Pool"
@@ -701,100 +1326,107 @@ fn make_multi_asset_deposit(
         )));
     }
 
-    // Check the pool status
-    if interchain_pool.status != PoolStatus::Active {
-        return Err(ContractError::NotReadyForSwap);
-    }
-
-    // Create the interchain market maker
-    let amm = InterchainMarketMaker {
-        pool_id: interchain_pool.clone().id,
-        pool: interchain_pool.clone(),
-        fee_rate: interchain_pool.swap_fee,
+    let supply: Coin = Coin {
+        amount: Uint128::from(0u64),
+        denom: pool_id.clone(),
     };
-
-    // Deposit the assets into the interchain market maker
-    let pool_tokens = amm.deposit_multi_asset(&[
-        msg.deposits[0].balance.clone(),
-        msg.deposits[1].balance.clone(),
-    ])?;
-
-    let mut config = CONFIG.load(deps.storage)?;
-
-    let mut multi_asset_order = MultiAssetDepositOrder {
-        id: "".to_string(),
-        chain_id: msg.chain_id.clone(),
-        pool_id: msg.pool_id.clone(),
-        source_maker: msg.deposits[0].sender.clone(),
-        destination_taker: msg.deposits[1].sender.clone(),
-        deposits: get_coins_from_deposits(msg.deposits.clone()),
-        //pool_tokens: pool_tokens,
-        status: OrderStatus::Pending,
-        created_at: env.block.height,
+    let interchain_pool: InterchainLiquidityPool = InterchainLiquidityPool {
+        id: pool_id.clone(),
+        source_creator: msg.creator.clone(),
+        destination_creator: msg.counterparty_creator.clone(),
+        assets: msg.liquidity.clone(),
+        supply,
+        status: PoolStatus::Initialized,
+        counter_party_port: msg.source_port.clone(),
+        counter_party_channel: msg.source_channel.clone(),
+        swap_fee: msg.swap_fee,
+        source_chain_id: msg.source_chain_id.clone(),
+        destination_chain_id: msg.destination_chain_id.clone(),
+        pool_price: 0,
+        packet_timeout_seconds: msg.packet_timeout_seconds,
+        fee_application: msg.fee_application,
+        pool_type: msg.pool_type,
+        right_swap_fee: msg.right_swap_fee,
     };
+    POOLS.save(deps.storage, &pool_id, &interchain_pool)?;
+    INITIALIZED_POOLS.save(deps.storage, (&msg.creator, &pool_id), &true)?;
 
-    // load orders
-    // check for order, if exist throw error.
-
-    let ac_key = msg.deposits[0].sender.clone()
-        + "-"
-        + &msg.pool_id.clone()
-        + "-"
-        + &msg.deposits[1].sender.clone();
-    // let multi_asset_order_temp = ACTIVE_ORDERS.may_load(deps.storage, ac_key.clone())?;
-
-    // if let Some(_order) = multi_asset_order_temp {
-    //     return Err(ContractError::ErrPreviousOrderNotCompleted);
-    // }
-    config.counter += 1;
-    multi_asset_order.id = get_order_id(msg.deposits[0].sender.clone(), config.counter);
-    //}
-
-    // save order in source chain
-    let key = msg.pool_id.clone() + "-" + &multi_asset_order.id;
-    MULTI_ASSET_DEPOSIT_ORDERS.save(deps.storage, key, &multi_asset_order)?;
-    ACTIVE_ORDERS.save(deps.storage, ac_key, &multi_asset_order)?;
-    CONFIG.save(deps.storage, &config)?;
-
-    // Construct the IBC packet
-    let state_change_data = to_binary(&StateChange {
+    // Instantiate token
+    if POOL_TOKENS_LIST.has(deps.storage, &pool_id) || LP_TOKEN_STATUS.has(deps.storage, &pool_id)
+    {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Pool token already exist: Make Pool".to_string(),
+        )));
+    }
+    if let Some(marketing) = &msg.lp_token_marketing {
+        LP_TOKEN_MARKETING.save(deps.storage, &pool_id, marketing)?;
+    }
+    let sub_msg = instantiate_lp_token_once(
+        deps.storage,
+        &pool_id,
+        &config,
+        env.contract.address.to_string(),
+    )?;
+
+    let state_change_data = encode_payload(&StateChange {
         in_tokens: None,
         out_tokens: None,
-        pool_tokens: Some(pool_tokens),
-        pool_id: None,
-        multi_deposit_order_id: Some(multi_asset_order.id),
+        pool_tokens: None,
+        pool_id: Some(pool_id.clone()),
+        multi_deposit_order_id: None,
         source_chain_id: None,
         shares: None,
     })?;
-    let packet_data = InterchainSwapPacketData {
-        r#type: InterchainMessageType::MakeMultiDeposit,
-        data: to_binary(&msg)?,
+
+    let pool_data = encode_payload(&msg)?;
+    // Assuming `msg.memo` is an Option<String> containing the base64-encoded memo
+   // Decode the base64 memo using the standard engine
+    let ibc_packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::MakePool,
+        data: pool_data,
         state_change: Some(state_change_data),
         memo: msg.memo
     };
 
+    
+    let data = encode_packet(deps.storage, &source_channel, &ibc_packet_data)?;
+
+    
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
     let ibc_msg = IbcMsg::SendPacket {
-        channel_id: interchain_pool.counter_party_channel,
-        data: to_binary(&packet_data)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
+        channel_id: source_channel,
+        data,
+        timeout,
     };
 
     let res = Response::default()
-        .add_message(ibc_msg)
-        .add_attribute("pool_id", msg.pool_id)
-        .add_attribute("action", "make_multi_asset_deposit");
+        .add_attribute("pool_id", pool_id.clone())
+        .add_attribute("action", "make_pool")
+        .add_attribute("ics101-lp-instantiate", pool_id)
+        .add_submessages(sub_msg)
+        .add_message(ibc_msg);
     Ok(res)
 }
 
-fn cancel_multi_asset_deposit(
+/// Coins the taker of a pool attaches on this chain, the balances of the assets of this chain.
+fn take_pool_funds(pool: &InterchainLiquidityPool) -> Vec<Coin> {
+    pool.assets_by_side(PoolSide::SOURCE)
+        .into_iter()
+        .map(|asset| asset.balance)
+        .collect()
+}
+
+fn take_pool(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg: MsgCancelMultiAssetDepositRequest,
+    msg: MsgTakePoolRequest,
 ) -> Result<Response, ContractError> {
     // load pool throw error if not found
     let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
@@ -807,105 +1439,40 @@ fn cancel_multi_asset_deposit(
             msg.pool_id
         ))));
     }
-    // get order
-    // load orders
-    let key = msg.pool_id.clone() + "-" + &msg.order_id;
-    let multi_asset_order_temp = MULTI_ASSET_DEPOSIT_ORDERS.may_load(deps.storage, key)?;
-    let multi_asset_order;
-    if let Some(order) = multi_asset_order_temp {
-        multi_asset_order = order;
-    } else {
-        return Err(ContractError::ErrOrderNotFound);
-    }
 
-    if multi_asset_order.source_maker != info.sender {
+    let config = CONFIG.load(deps.storage)?;
+    // a token left by an earlier attempt, or still being instantiated, is reused
+    let sub_msg = instantiate_lp_token_once(
+        deps.storage,
+        &msg.pool_id,
+        &config,
+        env.contract.address.to_string(),
+    )?;
+
+    // order can only be taken by creator
+    if interchain_pool.destination_creator != info.sender {
         return Err(ContractError::InvalidSender);
     }
-
-    if multi_asset_order.status != OrderStatus::Pending {
-        return Err(ContractError::ErrOrderAlreadyCompleted);
+    if let Some(refund_to) = &msg.refund_to {
+        validate_local_address(deps.api, refund_to)?;
+    }
+    validate_counterparty_address(
+        deps.storage,
+        &interchain_pool.counter_party_channel,
+        &msg.counter_creator,
+    )?;
+
+    // check if given tokens are received here, the taker funds the assets of this chain
+    let ok = take_pool_funds(&interchain_pool).iter().all(|token| info.funds.contains(token));
+    if !ok {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Funds mismatch: Funds mismatched to with message and sent values: Take Pool"
+                .to_string(),
+        )));
     }
 
-    let packet_data = InterchainSwapPacketData {
-        r#type: InterchainMessageType::CancelMultiDeposit,
-        data: to_binary(&msg)?,
-        state_change: None,
-        memo: msg.memo,
-    };
-
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: interchain_pool.counter_party_channel,
-        data: to_binary(&packet_data)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
-
-    let res = Response::default()
-        .add_message(ibc_msg)
-        .add_attribute("pool_id", msg.pool_id)
-        .add_attribute("action", "cancel_multi_asset_deposit");
-    Ok(res)
-}
-
-fn take_multi_asset_deposit(
-    deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
-    msg: MsgTakeMultiAssetDepositRequest,
-) -> Result<Response, ContractError> {
-    // load pool throw error if not found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
-    let interchain_pool;
-    if let Some(pool) = interchain_pool_temp {
-        interchain_pool = pool
-    } else {
-        return Err(ContractError::Std(StdError::generic_err(format!(
-            "Pool doesn't exist {}",
-            msg.pool_id
-        ))));
-    }
-    // get order
-    // load orders
-    let key = msg.pool_id.clone() + "-" + &msg.order_id;
-    let multi_asset_order_temp = MULTI_ASSET_DEPOSIT_ORDERS.may_load(deps.storage, key)?;
-    let multi_asset_order;
-    if let Some(order) = multi_asset_order_temp {
-        multi_asset_order = order;
-    } else {
-        return Err(ContractError::ErrOrderNotFound);
-    }
-
-    if multi_asset_order.destination_taker != info.sender {
-        return Err(ContractError::ErrFailedMultiAssetDeposit);
-    }
-
-    if multi_asset_order.status == OrderStatus::Complete {
-        return Err(ContractError::ErrOrderAlreadyCompleted);
-    }
-
-    let token = interchain_pool
-        .find_asset_by_side(PoolSide::SOURCE)
-        .map_err(|err| StdError::generic_err(format!("Failed to find asset: {}", err)))?;
-    // check if given tokens are received here
-    let mut ok = false;
-    // First token in this chain only first token needs to be verified
-    for asset in info.funds {
-        if asset.denom == token.balance.denom
-            && multi_asset_order.deposits[1].amount == asset.amount
-            && asset.denom == multi_asset_order.deposits[1].denom
-        {
-            ok = true;
-        }
-    }
-    if !ok {
-        return Err(ContractError::Std(StdError::generic_err(
-            "Funds mismatch: Funds mismatched to with message and sent values: Take Multi Asset"
-                .to_string(),
-        )));
-    }
+    let tokens: Vec<Coin> =
+        interchain_pool.assets.iter().map(|asset| asset.balance.clone()).collect();
 
     // find number of tokens to be minted
     // Create the interchain market maker (amm).
@@ -915,555 +1482,1236 @@ fn take_multi_asset_deposit(
         fee_rate: interchain_pool.swap_fee,
     };
 
-    let pool_tokens = amm.deposit_multi_asset(&multi_asset_order.deposits)?;
+    let pool_tokens = amm
+        .deposit_multi_asset(&tokens)
+        .map_err(|err| StdError::generic_err(format!("Failed to deposit multi asset: {}", err)))?;
     let mut new_shares = Uint128::from(0u128);
-    for pool in pool_tokens.clone() {
+    for pool in pool_tokens {
         new_shares += pool.amount;
     }
+    unlocked_shares(new_shares)?;
 
-    // Construct the IBC packet
-    let state_change_data = to_binary(&StateChange {
+    let state_change_data = encode_payload(&StateChange {
         in_tokens: None,
         out_tokens: None,
-        pool_tokens: Some(pool_tokens),
+        pool_tokens: None,
         pool_id: None,
         multi_deposit_order_id: None,
         source_chain_id: None,
         shares: Some(new_shares),
     })?;
-    let packet_data = InterchainSwapPacketData {
-        r#type: InterchainMessageType::TakeMultiDeposit,
-        data: to_binary(&msg)?,
+
+    let pool_data = encode_payload(&msg)?;
+    let ibc_packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::TakePool,
+        data: pool_data,
         state_change: Some(state_change_data),
-        memo: msg.memo
+        memo: msg.memo,
     };
 
+    let data = encode_packet(
+        deps.storage,
+        &interchain_pool.counter_party_channel,
+        &ibc_packet_data,
+    )?;
+
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
     let ibc_msg = IbcMsg::SendPacket {
         channel_id: interchain_pool.counter_party_channel,
-        data: to_binary(&packet_data)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
+        data,
+        timeout,
     };
 
     let res = Response::default()
+        .add_submessages(sub_msg)
         .add_message(ibc_msg)
         .add_attribute("pool_id", msg.pool_id)
-        .add_attribute("action", "take_multi_asset_deposit");
+        .add_attribute("action", "take_pool");
     Ok(res)
 }
 
-// Pass pool id asset i.e cw20
-fn multi_asset_withdraw(
+fn set_dust_thresholds(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
-    msg: MsgMultiAssetWithdrawRequest,
+    pool_id: String,
+    min_swap: Uint128,
+    min_deposit: Uint128,
 ) -> Result<Response, ContractError> {
-    // Get liquidity pool
-    // load pool throw error if not found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
-    let interchain_pool;
-    if let Some(pool) = interchain_pool_temp {
-        interchain_pool = pool
-    } else {
-        return Err(ContractError::Std(StdError::generic_err(format!(
-            "Pool doesn't exist {}",
-            msg.pool_id
-        ))));
+    let config = CONFIG.load(deps.storage)?;
+    let interchain_pool = POOLS.load(deps.storage, &pool_id)?;
+    if pool_manager(deps.storage, &interchain_pool)? != info.sender && config.admin != info.sender
+    {
+        return Err(ContractError::InvalidSender);
     }
 
-    let sub_messages: Vec<SubMsg>;
-    if let Some(lp_token) = POOL_TOKENS_LIST.may_load(deps.storage, &msg.pool_id)? {
-        // Transfer tokens from user account to contract
-        let msg = Cw20ExecuteMsg::TransferFrom {
-            owner: info.sender.to_string(),
-            recipient: env.contract.address.to_string(),
-            amount: msg.pool_token.amount,
-        };
-        let exec = WasmMsg::Execute {
-            contract_addr: lp_token,
-            msg: to_binary(&msg)?,
-            funds: vec![],
-        };
-        sub_messages = vec![SubMsg::new(exec)];
-    } else {
-        // throw error token not found, initialization is done in make_pool and
-        // take_pool
-        return Err(ContractError::Std(StdError::generic_err(
-            "LP Token is not initialized".to_string(),
-        )));
+    let thresholds = DustThresholds {
+        min_swap,
+        min_deposit,
+    };
+    DUST_THRESHOLDS.save(deps.storage, &pool_id, &thresholds)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "set_dust_thresholds")
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("min_swap", min_swap.to_string())
+        .add_attribute("min_deposit", min_deposit.to_string()))
+}
+
+/// Rejects amounts of the local asset below what the pool accepts.
+fn assert_not_dust(
+    storage: &dyn Storage,
+    pool_id: &str,
+    amount: Uint128,
+    min: fn(&DustThresholds) -> Uint128,
+) -> Result<(), ContractError> {
+    let thresholds = DUST_THRESHOLDS.may_load(storage, pool_id)?.unwrap_or_default();
+    let min = min(&thresholds);
+    if amount < min {
+        return Err(ContractError::BelowMinimum { amount, min });
     }
+    Ok(())
+}
 
-    // Create the interchain market maker
-    let amm = InterchainMarketMaker {
-        pool_id: interchain_pool.clone().id,
-        pool: interchain_pool.clone(),
-        fee_rate: interchain_pool.swap_fee,
+/// Lets `taker` take an order meant for someone else when the maker signed a permit for it.
+fn verify_take_permit(
+    deps: Deps,
+    env: &Env,
+    order: &MultiAssetDepositOrder,
+    msg: &MsgTakeMultiAssetDepositRequest,
+    taker: &Addr,
+) -> Result<(), ContractError> {
+    let (pubkey, signature) = match (&order.permit_pubkey, &msg.permit) {
+        (Some(pubkey), Some(signature)) => (pubkey, signature),
+        _ => return Err(ContractError::ErrFailedMultiAssetDeposit),
     };
+    let permit = TakePermit {
+        chain_id: env.block.chain_id.clone(),
+        pool_id: order.pool_id.clone(),
+        order_id: order.id.clone(),
+        taker: taker.to_string(),
+    };
+    let valid = deps
+        .api
+        .secp256k1_verify(&permit.hash()?, signature, pubkey)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    if !valid {
+        return Err(ContractError::InvalidPermit);
+    }
+    Ok(())
+}
 
-    let refund_assets = amm
-        .multi_asset_withdraw(msg.pool_token.clone())
-        .map_err(|err| StdError::generic_err(format!("Failed to withdraw multi asset: {}", err)))?;
+/// Address in control of a pool, its creator unless management was delegated.
+fn pool_manager(storage: &dyn Storage, pool: &InterchainLiquidityPool) -> StdResult<String> {
+    Ok(POOL_MANAGERS
+        .may_load(storage, &pool.id)?
+        .unwrap_or_else(|| pool.source_creator.clone()))
+}
 
-    let source_denom = interchain_pool
-        .find_asset_by_side(PoolSide::SOURCE)
-        .map_err(|err| StdError::generic_err(format!("Failed to find asset: {}", err)))?;
+/// The pool with its collected fees added to the balances. Deposits are priced on it, so they
+/// buy into the fees accrued before them rather than take a share of them for free.
+fn pool_with_fees(
+    storage: &dyn Storage,
+    pool: &InterchainLiquidityPool,
+) -> StdResult<InterchainLiquidityPool> {
+    let mut pool = pool.clone();
+    for fee in COLLECTED_FEES.may_load(storage, &pool.id)?.unwrap_or_default() {
+        pool.add_asset(fee)?;
+    }
+    Ok(pool)
+}
 
-    let destination_denom = interchain_pool
-        .find_asset_by_side(PoolSide::DESTINATION)
-        .map_err(|err| StdError::generic_err(format!("Failed to find asset: {}", err)))?;
+/// Passes for the manager of `pool`, the admin and addresses granted `role`.
+fn assert_pool_role(
+    deps: Deps,
+    info: &MessageInfo,
+    pool: &InterchainLiquidityPool,
+    role: Role,
+) -> Result<(), ContractError> {
+    if pool_manager(deps.storage, pool)? == info.sender {
+        return Ok(());
+    }
+    assert_role(deps, info, role)
+}
 
-    let mut source_out = Coin {
-        denom: "mock".to_string(),
-        amount: Uint128::zero(),
-    };
-    let mut destination_out = Coin {
-        denom: "mock".to_string(),
-        amount: Uint128::zero(),
-    };
+/// Rejects swaps and deposits of senders missing from the whitelist of a pool that has one.
+fn assert_whitelisted(
+    storage: &dyn Storage,
+    pool_id: &str,
+    sender: &Addr,
+    message_type: &InterchainMessageType,
+) -> Result<(), ContractError> {
+    use InterchainMessageType::*;
+    if !matches!(
+        message_type,
+        SingleAssetDeposit
+            | MakeMultiDeposit
+            | TakeMultiDeposit
+            | MatchMultiDeposit
+            | LeftSwap
+            | RightSwap
+    ) {
+        return Ok(());
+    }
+    match POOL_WHITELISTS.may_load(storage, pool_id)? {
+        Some(whitelist) if !whitelist.iter().any(|address| address == sender) => {
+            Err(ContractError::InvalidSender)
+        }
+        _ => Ok(()),
+    }
+}
 
-    for asset in refund_assets {
-        if &asset.denom == &source_denom.balance.denom {
-            source_out = asset.clone();
+fn set_pool_whitelist(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    pool_id: String,
+    addresses: Option<Vec<String>>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let interchain_pool = POOLS.load(deps.storage, &pool_id)?;
+    if pool_manager(deps.storage, &interchain_pool)? != info.sender && config.admin != info.sender
+    {
+        return Err(ContractError::InvalidSender);
+    }
+
+    let count = match addresses {
+        Some(addresses) => {
+            for address in &addresses {
+                validate_local_address(deps.api, address)?;
+            }
+            POOL_WHITELISTS.save(deps.storage, &pool_id, &addresses)?;
+            addresses.len()
+        }
+        None => {
+            POOL_WHITELISTS.remove(deps.storage, &pool_id);
+            0
         }
-        if &asset.denom == &destination_denom.balance.denom {
-            destination_out = asset;
+    };
+
+    Ok(Response::default()
+        .add_attribute("action", "set_pool_whitelist")
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("addresses", count.to_string()))
+}
+
+/// Sets the marketing of the LP token of a pool. A cw20 token that exists already is updated,
+/// which needs the contract to be its marketing admin, as it is of tokens it instantiates.
+fn set_lp_token_marketing(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    pool_id: String,
+    marketing: LpTokenMarketing,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let interchain_pool = POOLS.load(deps.storage, &pool_id)?;
+    if pool_manager(deps.storage, &interchain_pool)? != info.sender && config.admin != info.sender
+    {
+        return Err(ContractError::InvalidSender);
+    }
+    LP_TOKEN_MARKETING.save(deps.storage, &pool_id, &marketing)?;
+
+    let mut res = Response::default();
+    let lp_token = POOL_TOKENS_LIST.may_load(deps.storage, &pool_id)?;
+    if let Some(lp_token) = lp_token.filter(|token| tokenfactory::split_denom(token).is_none()) {
+        res = res.add_message(WasmMsg::Execute {
+            contract_addr: lp_token.clone(),
+            msg: to_binary(&Cw20ExecuteMsg::UpdateMarketing {
+                project: Some(marketing.project.unwrap_or_default()),
+                description: Some(marketing.description.unwrap_or_default()),
+                marketing: None,
+            })?,
+            funds: vec![],
+        });
+        if let Some(logo_url) = marketing.logo_url {
+            res = res.add_message(WasmMsg::Execute {
+                contract_addr: lp_token,
+                msg: to_binary(&Cw20ExecuteMsg::UploadLogo(Logo::Url(logo_url)))?,
+                funds: vec![],
+            });
         }
     }
 
-    let state_change_data = to_binary(&StateChange {
-        in_tokens: Some(vec![msg.pool_token.clone()]),
-        out_tokens: Some(vec![source_out, destination_out]),
-        pool_tokens: Some(vec![msg.pool_token.clone()]),
-        pool_id: None,
-        multi_deposit_order_id: None,
-        source_chain_id: None,
-        shares: None,
-    })?;
+    Ok(res
+        .add_attribute("action", "set_lp_token_marketing")
+        .add_attribute("pool_id", pool_id))
+}
 
-    let packet = InterchainSwapPacketData {
-        r#type: InterchainMessageType::MultiWithdraw,
-        data: to_binary(&msg)?,
-        state_change: Some(state_change_data),
-        memo: msg.memo,
-    };
+fn set_pool_manager(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgSetPoolManagerRequest,
+) -> Result<Response, ContractError> {
+    let interchain_pool = POOLS.load(deps.storage, &msg.pool_id)?;
+    if pool_manager(deps.storage, &interchain_pool)? != info.sender {
+        return Err(ContractError::InvalidSender);
+    }
+    if let Some(manager) = &msg.manager {
+        validate_local_address(deps.api, manager)?;
+    }
 
+    // the manager changes on this chain once the counterparty acknowledges it
+    let ibc_packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::SetPoolManager,
+        data: encode_payload(&msg)?,
+        state_change: None,
+        memo: msg.memo.clone(),
+    };
+    let data = encode_packet(
+        deps.storage,
+        &interchain_pool.counter_party_channel,
+        &ibc_packet_data,
+    )?;
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
     let ibc_msg = IbcMsg::SendPacket {
         channel_id: interchain_pool.counter_party_channel,
-        data: to_binary(&packet)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
+        data,
+        timeout,
     };
 
-    let res = Response::default()
-        .add_submessages(sub_messages)
+    Ok(Response::default()
         .add_message(ibc_msg)
         .add_attribute("pool_id", msg.pool_id)
-        .add_attribute("action", "multi_asset_withdraw");
-    Ok(res)
+        .add_attribute("manager", msg.manager.unwrap_or_default())
+        .add_attribute("action", "set_pool_manager"))
 }
 
-fn swap(
+fn set_pool_status(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg: MsgSwapRequest,
+    msg: MsgSetPoolStatusRequest,
 ) -> Result<Response, ContractError> {
-    // Get liquidity pool
-    // load pool throw error if not found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
-    let interchain_pool;
-    if let Some(pool) = interchain_pool_temp {
-        interchain_pool = pool
-    } else {
-        return Err(ContractError::Std(StdError::generic_err(format!(
-            "Pool doesn't exist {}",
-            msg.pool_id
-        ))));
-    }
-
-    // Check the pool status
-    if interchain_pool.status != PoolStatus::Active {
-        return Err(ContractError::NotReadyForSwap);
-    }
-
-    // check if given tokens are received here
-    let mut ok = false;
-    // First token in this chain only first token needs to be verified
-    for asset in info.funds {
-        if asset.denom == msg.token_in.denom && asset.amount == msg.token_in.amount {
-            ok = true;
-        }
+    let interchain_pool = POOLS.load(deps.storage, &msg.pool_id)?;
+    assert_pool_role(deps.as_ref(), &info, &interchain_pool, Role::Pauser)?;
+    if !is_lifecycle_status(&msg.status) {
+        return Err(ContractError::InvalidStatus);
     }
-    if !ok {
-        return Err(ContractError::Std(StdError::generic_err(
-            "Funds mismatch: Funds mismatched to with message and sent values: Swap".to_string(),
-        )));
+    if msg.drain_deadline.is_some() && msg.status != PoolStatus::Draining {
+        return Err(ContractError::InvalidStatus);
     }
 
-    // Create the interchain market maker
-    let amm = InterchainMarketMaker {
-        pool_id: interchain_pool.clone().id,
-        pool: interchain_pool.clone(),
-        fee_rate: interchain_pool.swap_fee,
+    // the status changes on this chain once the counterparty acknowledges it
+    let ibc_packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::SetPoolStatus,
+        data: encode_payload(&msg)?,
+        state_change: None,
+        memo: msg.memo.clone(),
+    };
+    let data = encode_packet(
+        deps.storage,
+        &interchain_pool.counter_party_channel,
+        &ibc_packet_data,
+    )?;
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = IbcMsg::SendPacket {
+        channel_id: interchain_pool.counter_party_channel,
+        data,
+        timeout,
     };
 
-    // Construct the IBC data packet
-    let swap_data = to_binary(&msg)?;
-    let token_out: Coin;
-    let msg_type: InterchainMessageType;
+    Ok(Response::default()
+        .add_message(ibc_msg)
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("status", format!("{:?}", msg.status))
+        .add_attribute("action", "set_pool_status"))
+}
 
-    match msg.swap_type {
-        SwapMsgType::LEFT => {
-            msg_type = InterchainMessageType::LeftSwap;
-            token_out = amm.compute_swap(msg.token_in.clone(), &msg.token_out.denom)?;
-        }
-        SwapMsgType::RIGHT => {
-            msg_type = InterchainMessageType::RightSwap;
-            token_out = amm.compute_offer_amount(msg.token_in.clone(), msg.token_out.clone())?;
+fn update_pool_fee(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MarketFeeUpdateProposal,
+) -> Result<Response, ContractError> {
+    let interchain_pool = POOLS.load(deps.storage, &msg.pool_id)?;
+    assert_pool_role(deps.as_ref(), &info, &interchain_pool, Role::FeeSetter)?;
+    let config = CONFIG.load(deps.storage)?;
+    for fee in [Some(msg.fee_rate), msg.right_fee_rate].into_iter().flatten() {
+        if !config.swap_fee_in_bounds(fee) {
+            return Err(ContractError::InvalidSwapFee {
+                fee,
+                min: config.min_swap_fee_bps,
+                max: config.max_swap_fee_bps,
+            });
         }
     }
 
-    // Slippage checking
-    let factor = MAXIMUM_SLIPPAGE - msg.slippage;
-    let expected = msg
-        .token_out
-        .amount
-        .mul(Uint128::from(factor))
-        .div(Uint128::from(MAXIMUM_SLIPPAGE));
-    if token_out.amount.lt(&expected) {
-        return Err(ContractError::FailedOnSwapReceived {
-            err: format!(
-                "slippage check failed! expected: {}, output: {:?}, factor: {}",
-                expected, token_out, factor
-            ),
-        });
+    // the fee changes on this chain once the counterparty acknowledges it
+    let ibc_packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::UpdateFee,
+        data: encode_payload(&msg)?,
+        state_change: None,
+        memo: None,
+    };
+    let data = encode_packet(
+        deps.storage,
+        &interchain_pool.counter_party_channel,
+        &ibc_packet_data,
+    )?;
+    let timeout = packet_timeout(deps.storage, &env, &interchain_pool, 0, 0)?;
+    let ibc_msg = IbcMsg::SendPacket {
+        channel_id: interchain_pool.counter_party_channel,
+        data,
+        timeout,
+    };
+
+    Ok(Response::default()
+        .add_message(ibc_msg)
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("title", msg.title)
+        .add_attribute("fee_rate", msg.fee_rate.to_string())
+        .add_attribute("action", "update_pool_fee"))
+}
+
+fn decommission_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgDecommissionPoolRequest,
+) -> Result<Response, ContractError> {
+    let interchain_pool = POOLS.load(deps.storage, &msg.pool_id)?;
+    assert_pool_role(deps.as_ref(), &info, &interchain_pool, Role::Pauser)?;
+
+    // the locked minimum liquidity is never withdrawn
+    let withdrawn = interchain_pool.supply.amount <= Uint128::new(MINIMUM_LIQUIDITY);
+    let expired = match DRAIN_DEADLINES.may_load(deps.storage, &msg.pool_id)? {
+        Some(deadline) => env.block.time.seconds() >= deadline,
+        None => false,
+    };
+    if !withdrawn && !expired {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Pool still has liquidity and its drain deadline hasn't passed".to_string(),
+        )));
     }
 
-    let state_change_data = to_binary(&StateChange {
-        in_tokens: None,
-        out_tokens: Some(vec![token_out]),
-        pool_tokens: None,
-        pool_id: None,
-        multi_deposit_order_id: None,
-        source_chain_id: None,
-        shares: None,
-    })?;
+    // the pool is deleted on this chain once the counterparty acknowledges it
+    let ibc_packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::DecommissionPool,
+        data: encode_payload(&msg)?,
+        state_change: None,
+        memo: msg.memo.clone(),
+    };
+    let data = encode_packet(
+        deps.storage,
+        &interchain_pool.counter_party_channel,
+        &ibc_packet_data,
+    )?;
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = IbcMsg::SendPacket {
+        channel_id: interchain_pool.counter_party_channel,
+        data,
+        timeout,
+    };
 
-    let packet = InterchainSwapPacketData {
-        r#type: msg_type,
-        data: swap_data,
-        state_change: Some(state_change_data),
+    Ok(Response::default()
+        .add_message(ibc_msg)
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("action", "decommission_pool"))
+}
+
+fn cancel_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgCancelPoolRequest,
+) -> Result<Response, ContractError> {
+    // load pool throw error if not found
+    let config = CONFIG.load(deps.storage)?;
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
+    let interchain_pool;
+    if let Some(pool) = interchain_pool_temp {
+        interchain_pool = pool
+    } else {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Pool doesn't exist {}",
+            msg.pool_id
+        ))));
+    }
+
+    // order can only be cancelled by creator or admin
+    if !((pool_manager(deps.storage, &interchain_pool)? == info.sender)
+        || (info.sender == config.admin))
+    {
+        return Err(ContractError::InvalidSender);
+    }
+
+    let pool_data = encode_payload(&msg)?;
+    let ibc_packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::CancelPool,
+        data: pool_data,
+        state_change: None,
         memo: msg.memo,
     };
 
+    let data = encode_packet(
+        deps.storage,
+        &interchain_pool.counter_party_channel,
+        &ibc_packet_data,
+    )?;
+
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
     let ibc_msg = IbcMsg::SendPacket {
         channel_id: interchain_pool.counter_party_channel,
-        data: to_binary(&packet)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
+        data,
+        timeout,
     };
 
     let res = Response::default()
         .add_message(ibc_msg)
         .add_attribute("pool_id", msg.pool_id)
-        .add_attribute("action", "swap");
+        .add_attribute("action", "take_pool");
     Ok(res)
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Config {} => to_binary(&query_config(deps)?),
-        QueryMsg::InterchainPool { pool_id } => to_binary(&query_interchain_pool(deps, pool_id)?),
-        QueryMsg::InterchainPoolList { start_after, limit } => {
-            to_binary(&query_interchain_pool_list(deps, start_after, limit)?)
+pub fn single_asset_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgSingleAssetDepositRequest,
+) -> Result<Response, ContractError> {
+    if let Err(err) = msg.validate_basic() {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Failed to validate message: {}",
+            err
+        ))));
+    }
+
+    // check if given tokens are received here
+    let mut ok = false;
+    for asset in info.funds {
+        if asset.denom == msg.token.denom && asset.amount == msg.token.amount {
+            ok = true;
         }
-        QueryMsg::Order { pool_id, order_id } => to_binary(&query_order(deps, pool_id, order_id)?),
-        QueryMsg::OrderList { start_after, limit } => {
-            to_binary(&query_orders(deps, start_after, limit)?)
+    }
+    if !ok {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Funds mismatch: Funds mismatched to with message and sent values: Take Pool"
+                .to_string(),
+        )));
+    }
+
+    let pool_id = msg.pool_id.clone();
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+    assert_not_dust(deps.storage, &pool_id, msg.token.amount, |t| t.min_deposit)?;
+
+    validate_local_address(deps.api, &msg.sender)?;
+    if let Some(recipient) = &msg.recipient {
+        validate_local_address(deps.api, recipient)?;
+    }
+    if let Some(refund_to) = &msg.refund_to {
+        validate_local_address(deps.api, refund_to)?;
+    }
+    // the taker only receives shares when some are minted on the counterparty
+    if msg.lp_allocation != LPAllocation::TakerChain {
+        validate_counterparty_address(deps.storage, &pool.counter_party_channel, &msg.lp_taker)?;
+    }
+
+    // If the pool is empty, then return a `Failure` response
+    if pool.supply.amount.is_zero() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Single asset cannot be provided to empty pool".to_string(),
+        )));
+    }
+
+    // Create the interchain market maker (amm).
+    let amm = InterchainMarketMaker {
+        pool_id,
+        pool: pool_with_fees(deps.storage, &pool)?,
+        fee_rate: pool.swap_fee,
+    };
+
+    // Deposit single asset to the AMM.
+    let bootstrap = pool.status == PoolStatus::Bootstrap;
+    let pool_token = if bootstrap {
+        // only an unfunded asset of this chain can be deposited into a bootstrap pool
+        let unfunded = pool.assets_by_side(PoolSide::SOURCE).iter().any(|asset| {
+            asset.balance.denom == msg.token.denom && asset.balance.amount.is_zero()
+        });
+        if !unfunded {
+            return Err(ContractError::OnlyBootstrapDeposit);
         }
-        QueryMsg::PoolAddressByToken { pool_id } => to_binary(&query_pool_address(deps, pool_id)?),
-        QueryMsg::PoolTokenList { start_after, limit } => {
-            to_binary(&query_pool_list(deps, start_after, limit)?)
+        // each deposit is priced on the pool before the others, so one at a time
+        if BOOTSTRAP_DEPOSITS.has(deps.storage, &msg.pool_id) {
+            return Err(ContractError::BootstrapDepositInFlight {
+                pool_id: msg.pool_id,
+            });
         }
-        QueryMsg::LeftSwap {
-            pool_id,
-            token_in,
-            token_out,
-        } => to_binary(&query_left_swap(deps, pool_id, token_in, token_out)?),
-        QueryMsg::RightSwap {
-            pool_id,
-            token_in,
-            token_out,
-        } => to_binary(&query_right_swap(deps, pool_id, token_in, token_out)?),
-        QueryMsg::QueryActiveOrders {
-            source_maker,
-            destination_taker,
-            pool_id,
-        } => to_binary(&query_active_orders(
-            deps,
-            pool_id,
-            source_maker,
-            destination_taker,
-        )?),
-        QueryMsg::Rate { pool_id, amount } => to_binary(&query_rate(deps, pool_id, amount)?),
+        let target = BOOTSTRAP_TARGETS
+            .may_load(deps.storage, &msg.pool_id)?
+            .unwrap_or_default()
+            .into_iter()
+            .find(|target| target.denom == msg.token.denom)
+            .ok_or_else(|| {
+                StdError::generic_err(format!("No bootstrap target for {}", msg.token.denom))
+            })?;
+        amm.deposit_bootstrap_asset(&msg.token, &target)
+    } else {
+        amm.deposit_single_asset(&msg.token)
     }
-}
+    .map_err(|err| StdError::generic_err(format!("Failed to deposit single asset: {}", err)))?;
 
-/// Settings for pagination
-const MAX_LIMIT: u32 = 30;
-const DEFAULT_LIMIT: u32 = 10;
+    let msg_data = encode_payload(&msg)?;
+    let state_change_data = encode_payload(&StateChange {
+        in_tokens: None,
+        out_tokens: None,
+        pool_tokens: Some(vec![pool_token.clone()]),
+        pool_id: None,
+        multi_deposit_order_id: None,
+        source_chain_id: None,
+        shares: Some(pool_token.amount),
+    })?;
+    // Construct the IBC swap packet.
+    let packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::SingleAssetDeposit,
+        data: msg_data, // Use proper serialization for the `data` field.
+        state_change: Some(state_change_data),
+        memo: msg.memo,
+    };
+    let data = encode_packet(deps.storage, &pool.counter_party_channel, &packet_data)?;
+
+    // Keep a receipt until the packet is acknowledged or times out
+    let receipt_id = DEPOSIT_RECEIPT_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    DEPOSIT_RECEIPT_COUNT.save(deps.storage, &receipt_id)?;
+    let receipt = DepositReceipt {
+        id: receipt_id,
+        sender: msg.sender.clone(),
+        pool_id: msg.pool_id.clone(),
+        token: msg.token,
+        status: DepositStatus::Pending,
+        created_at: env.block.time.seconds(),
+    };
+    DEPOSIT_RECEIPTS.save(deps.storage, (&msg.sender, receipt_id), &receipt)?;
+    add_escrow(deps.storage, &receipt.token)?;
+    let hash = packet_data_hash(&data);
+    let mut pending = PENDING_DEPOSIT_RECEIPTS
+        .may_load(deps.storage, &hash)?
+        .unwrap_or_default();
+    pending.push(receipt_id);
+    PENDING_DEPOSIT_RECEIPTS.save(deps.storage, &hash, &pending)?;
+    if bootstrap {
+        BOOTSTRAP_DEPOSITS.save(deps.storage, &msg.pool_id, &hash)?;
+    }
 
-fn query_config(deps: Deps) -> StdResult<QueryConfigResponse> {
-    let config = CONFIG.load(deps.storage)?;
+    // Send the IBC swap packet.
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = IbcMsg::SendPacket {
+        channel_id: pool.counter_party_channel,
+        data,
+        timeout,
+    };
 
-    Ok(QueryConfigResponse {
-        counter: config.counter,
-        token_code_id: config.token_code_id,
-    })
+    let res = Response::default()
+        .add_message(ibc_msg)
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("receipt_id", receipt_id.to_string())
+        .add_attribute("action", "single_asset_deposit");
+    Ok(res)
 }
 
-#[entry_point]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    let ver = cw2::get_contract_version(deps.storage)?;
-    // ensure we are migrating from an allowed contract
-    if ver.contract != CONTRACT_NAME {
-        return Err(StdError::generic_err("Can only upgrade from same type").into());
-    }
-    // note: better to do proper semver compare, but string compare *usually* works
-    if ver.version.as_str() >= CONTRACT_VERSION {
-        return Err(StdError::generic_err("Cannot upgrade from a newer version").into());
+fn single_sided_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pool_id: String,
+    token_in: Coin,
+    min_shares: Uint128,
+) -> Result<Response, ContractError> {
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+    let amm = InterchainMarketMaker {
+        pool_id: pool_id.clone(),
+        pool: pool_with_fees(deps.storage, &pool)?,
+        fee_rate: pool.swap_fee,
+    };
+    let shares = amm
+        .deposit_single_asset(&token_in)
+        .map_err(|err| StdError::generic_err(format!("Failed to deposit single asset: {}", err)))?;
+    if shares.amount < min_shares {
+        return Err(ContractError::InvalidSlippage);
     }
 
-    // set the new version
-    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-
-    Ok(Response::default())
+    // all shares are minted to the sender once the counterparty acknowledges the deposit
+    let msg = MsgSingleAssetDepositRequest {
+        pool_id,
+        sender: info.sender.to_string(),
+        token: token_in,
+        lp_allocation: LPAllocation::TakerChain,
+        lp_taker: String::new(),
+        timeout_height: 0,
+        timeout_timestamp: 0,
+        memo: None,
+        recipient: None,
+        refund_to: None,
+    };
+    let res = single_asset_deposit(deps, env, info, msg)?;
+    Ok(res.add_attribute("shares", shares.amount.to_string()))
 }
 
-fn query_interchain_pool(deps: Deps, pool_id: String) -> StdResult<InterchainPoolResponse> {
-    // load pool throw error if found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &pool_id)?;
+fn make_multi_asset_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgMakeMultiAssetDepositRequest,
+) -> Result<Response, ContractError> {
+    // load pool throw error if not found
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
     let interchain_pool;
     if let Some(pool) = interchain_pool_temp {
-        interchain_pool = pool;
+        interchain_pool = pool
     } else {
-        return Err(StdError::generic_err("Pool not found".to_string()));
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Pool doesn't exist {}",
+            msg.pool_id
+        ))));
     }
+    // TODO: deposit balance or any balance can't be zero
+    // Add checks in every function
+    let amount = match msg.deposits.first() {
+        Some(deposit) => deposit.balance.amount,
+        None => return Err(ContractError::ErrFailedMultiAssetDeposit),
+    };
+    assert_not_dust(deps.storage, &msg.pool_id, amount, |t| t.min_deposit)?;
 
-    Ok(InterchainPoolResponse {
-        id: interchain_pool.id,
-        source_creator: interchain_pool.source_creator,
-        destination_creator: interchain_pool.destination_creator,
-        assets: interchain_pool.assets,
-        swap_fee: interchain_pool.swap_fee,
-        supply: interchain_pool.supply,
-        status: interchain_pool.status,
-        counter_party_channel: interchain_pool.counter_party_channel,
-        counter_party_port: interchain_pool.counter_party_port,
-        source_chain_id: interchain_pool.source_chain_id,
-        destination_chain_id: interchain_pool.destination_chain_id,
-    })
-}
-
-fn query_interchain_pool_list(
-    deps: Deps,
-    start_after: Option<String>,
-    limit: Option<u32>,
-) -> StdResult<InterchainListResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = start_after.map(|denom| Bound::ExclusiveRaw(denom.into_bytes()));
-    let list = POOLS
-        .range(deps.storage, start, None, Order::Ascending)
-        .take(limit)
-        .map(
-            |item: Result<(String, InterchainLiquidityPool), cosmwasm_std::StdError>| {
-                item.unwrap().1
-            },
-        )
-        .collect::<Vec<InterchainLiquidityPool>>();
+    validate_local_address(deps.api, &msg.deposits[0].sender)?;
+    if let Some(recipient) = &msg.recipient {
+        validate_local_address(deps.api, recipient)?;
+    }
+    if let Some(refund_to) = &msg.refund_to {
+        validate_local_address(deps.api, refund_to)?;
+    }
+    let tokens = get_coins_from_deposits(msg.deposits.clone());
+    if interchain_pool.maker_side(&tokens)? != PoolSide::SOURCE {
+        return Err(ContractError::Std(StdError::generic_err(
+            "The maker deposits must be assets of this chain".to_string(),
+        )));
+    }
+    let taker = msg.deposits[interchain_pool.assets_by_side(PoolSide::SOURCE).len()].clone();
+    validate_counterparty_address(
+        deps.storage,
+        &interchain_pool.counter_party_channel,
+        &taker.sender,
+    )?;
 
-    Ok(InterchainListResponse { pools: list })
-}
+    // check if given tokens are received here
+    // only the deposits of this chain are sent with the message
+    for token in tokens.iter() {
+        if interchain_pool.side_of(&token.denom)? == PoolSide::SOURCE
+            && !info.funds.contains(token)
+        {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Funds mismatch: Funds mismatched to with message and sent values: Make Pool"
+                    .to_string(),
+            )));
+        }
+    }
 
-fn query_order(deps: Deps, pool_id: String, order_id: String) -> StdResult<MultiAssetDepositOrder> {
-    let key = pool_id + "-" + &order_id;
-    let multi_asset_order_temp = MULTI_ASSET_DEPOSIT_ORDERS.may_load(deps.storage, key)?;
-    let multi_asset_order;
-    if let Some(order) = multi_asset_order_temp {
-        multi_asset_order = order;
-    } else {
-        return Err(StdError::generic_err("Order not found".to_string()));
+    // Create the interchain market maker
+    let amm = InterchainMarketMaker {
+        pool_id: interchain_pool.clone().id,
+        pool: pool_with_fees(deps.storage, &interchain_pool)?,
+        fee_rate: interchain_pool.swap_fee,
     };
 
-    Ok(multi_asset_order)
-}
+    // Deposit the assets into the interchain market maker
+    let pool_tokens = amm.deposit_multi_asset(&tokens)?;
 
-fn query_orders(
-    deps: Deps,
-    start_after: Option<String>,
-    limit: Option<u32>,
-) -> StdResult<OrderListResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = start_after.map(|denom| Bound::ExclusiveRaw(denom.into_bytes()));
-    let list = MULTI_ASSET_DEPOSIT_ORDERS
-        .range(deps.storage, start, None, Order::Ascending)
-        .take(limit)
-        .map(
-            |item: Result<(String, MultiAssetDepositOrder), cosmwasm_std::StdError>| {
-                item.unwrap().1
-            },
-        )
-        .collect::<Vec<MultiAssetDepositOrder>>();
+    let mut config = CONFIG.load(deps.storage)?;
 
-    Ok(OrderListResponse { orders: list })
-}
+    let mut multi_asset_order = MultiAssetDepositOrder {
+        id: "".to_string(),
+        chain_id: msg.chain_id.clone(),
+        pool_id: msg.pool_id.clone(),
+        source_maker: msg.deposits[0].sender.clone(),
+        destination_taker: taker.sender.clone(),
+        deposits: tokens,
+        //pool_tokens: pool_tokens,
+        status: OrderStatus::Pending,
+        created_at: env.block.height,
+        recipient: msg.recipient.clone(),
+        permit_pubkey: msg.permit_pubkey.clone(),
+    };
 
-fn query_pool_address(deps: Deps, pool_id: String) -> StdResult<String> {
-    let res;
-    if let Some(lp_token) = POOL_TOKENS_LIST.may_load(deps.storage, &pool_id)? {
-        res = lp_token
-    } else {
-        // throw error token not found, initialization is done in make_pool and
-        // take_pool
-        return Err(StdError::generic_err(
-            "LP Token is not initialized".to_string(),
-        ));
+    // load orders
+    // check for order, if exist throw error.
+
+    let ac_key = msg.deposits[0].sender.clone()
+        + "-"
+        + &msg.pool_id.clone()
+        + "-"
+        + &taker.sender.clone();
+    // let multi_asset_order_temp = ACTIVE_ORDERS.may_load(deps.storage, ac_key.clone())?;
+
+    // if let Some(_order) = multi_asset_order_temp {
+    //     return Err(ContractError::ErrPreviousOrderNotCompleted);
+    // }
+    let count = config.increment_counter()?;
+    multi_asset_order.id = get_order_id(&env, &msg.pool_id, &msg.deposits[0].sender, count);
+    //}
+
+    // save order in source chain
+    let key = msg.pool_id.clone() + "-" + &multi_asset_order.id;
+    if MULTI_ASSET_DEPOSIT_ORDERS.has(deps.storage, key.clone()) {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Order already exists {}",
+            multi_asset_order.id
+        ))));
     }
+    save_order(deps.storage, key, &multi_asset_order)?;
+    ACTIVE_ORDERS.save(deps.storage, ac_key, &multi_asset_order)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    // Construct the IBC packet
+    let state_change_data = encode_payload(&StateChange {
+        in_tokens: None,
+        out_tokens: None,
+        pool_tokens: Some(pool_tokens),
+        pool_id: None,
+        multi_deposit_order_id: Some(multi_asset_order.id),
+        source_chain_id: None,
+        shares: None,
+    })?;
+    let packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::MakeMultiDeposit,
+        data: encode_payload(&msg)?,
+        state_change: Some(state_change_data),
+        memo: msg.memo
+    };
+
+    let data = encode_packet(deps.storage, &interchain_pool.counter_party_channel, &packet_data)?;
+
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = IbcMsg::SendPacket {
+        channel_id: interchain_pool.counter_party_channel,
+        data,
+        timeout,
+    };
 
+    let res = Response::default()
+        .add_message(ibc_msg)
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("action", "make_multi_asset_deposit");
     Ok(res)
 }
 
-fn query_pool_list(
-    deps: Deps,
-    start_after: Option<String>,
-    limit: Option<u32>,
-) -> StdResult<PoolListResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = start_after.map(|denom| Bound::ExclusiveRaw(denom.into_bytes()));
-    let list = POOL_TOKENS_LIST
-        .range(deps.storage, start, None, Order::Ascending)
-        .take(limit)
-        .map(|item: Result<(String, String), cosmwasm_std::StdError>| item.unwrap().1)
-        .collect::<Vec<String>>();
+/// Makes a multi asset deposit of the amounts worth `total_value` at the current pool ratio.
+/// The funds sent may exceed the local amount by the tolerance, the rest is refunded.
+fn make_multi_asset_deposit_by_ratio(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgMakeMultiAssetDepositByRatioRequest,
+) -> Result<Response, ContractError> {
+    if msg.tolerance > MAXIMUM_SLIPPAGE {
+        return Err(ContractError::InvalidSlippage);
+    }
+    let pool = POOLS.load(deps.storage, &msg.pool_id)?;
+    let mut local = vec![];
+    let mut remote = vec![];
+    let mut refunds = vec![];
+    for needed in pool.balanced_amounts(&msg.total_value)? {
+        if pool.side_of(&needed.denom)? != PoolSide::SOURCE {
+            remote.push(needed);
+            continue;
+        }
+        let sent = Coin {
+            amount: info
+                .funds
+                .iter()
+                .find(|coin| coin.denom == needed.denom)
+                .map(|coin| coin.amount)
+                .unwrap_or_default(),
+            denom: needed.denom.clone(),
+        };
+        let least = sent.amount.multiply_ratio(MAXIMUM_SLIPPAGE - msg.tolerance, MAXIMUM_SLIPPAGE);
+        if needed.amount.is_zero() || needed.amount > sent.amount || needed.amount < least {
+            return Err(ContractError::DepositRatioMoved { needed, sent });
+        }
+        if sent.amount > needed.amount {
+            refunds.push(Coin {
+                denom: needed.denom.clone(),
+                amount: sent.amount - needed.amount,
+            });
+        }
+        local.push(needed);
+    }
 
-    Ok(PoolListResponse { pools: list })
+    let refund_to = msg.refund_to.clone().unwrap_or_else(|| info.sender.to_string());
+    let refund_to = validate_local_address(deps.api, &refund_to)?;
+    let deposits = local
+        .iter()
+        .map(|balance| DepositAsset {
+            sender: info.sender.to_string(),
+            balance: balance.clone(),
+        })
+        .chain(remote.iter().map(|balance| DepositAsset {
+            sender: msg.counterparty_sender.clone(),
+            balance: balance.clone(),
+        }))
+        .collect();
+    let deposit = MsgMakeMultiAssetDepositRequest {
+        pool_id: msg.pool_id,
+        deposits,
+        chain_id: msg.chain_id,
+        timeout_height: msg.timeout_height,
+        timeout_timestamp: msg.timeout_timestamp,
+        memo: msg.memo,
+        recipient: msg.recipient,
+        permit_pubkey: msg.permit_pubkey,
+        refund_to: msg.refund_to,
+    };
+    let deposit_info = MessageInfo {
+        sender: info.sender,
+        funds: local.clone(),
+    };
+    let mut res = make_multi_asset_deposit(deps, env, deposit_info, deposit)?;
+    if !refunds.is_empty() {
+        for refund in refunds.iter() {
+            res = res.add_submessages(send_tokens_coin(&refund_to, refund.clone())?);
+        }
+        res = res.add_attribute("refund", coins_to_string(&refunds));
+    }
+    Ok(res
+        .add_attribute("local_deposit", coins_to_string(&local))
+        .add_attribute("remote_deposit", coins_to_string(&remote)))
 }
 
-fn query_left_swap(
-    deps: Deps,
-    pool_id: String,
-    token_in: Coin,
-    token_out: Coin,
-) -> StdResult<Coin> {
-    // Get liquidity pool
+fn cancel_multi_asset_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgCancelMultiAssetDepositRequest,
+) -> Result<Response, ContractError> {
     // load pool throw error if not found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &pool_id)?;
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
     let interchain_pool;
     if let Some(pool) = interchain_pool_temp {
         interchain_pool = pool
     } else {
-        return Err(StdError::generic_err(format!(
+        return Err(ContractError::Std(StdError::generic_err(format!(
             "Pool doesn't exist {}",
-            pool_id
-        )));
+            msg.pool_id
+        ))));
+    }
+    // get order
+    // load orders
+    let key = msg.pool_id.clone() + "-" + &msg.order_id;
+    let multi_asset_order_temp = MULTI_ASSET_DEPOSIT_ORDERS.may_load(deps.storage, key)?;
+    let multi_asset_order;
+    if let Some(order) = multi_asset_order_temp {
+        multi_asset_order = order;
+    } else {
+        return Err(ContractError::ErrOrderNotFound);
     }
 
-    // Check the pool status
-    if interchain_pool.status != PoolStatus::Active {
-        return Err(StdError::generic_err(
-            "Pool not ready for swap!".to_string(),
-        ));
+    if multi_asset_order.source_maker != info.sender {
+        return Err(ContractError::InvalidSender);
     }
 
-    // Create the interchain market maker
-    let amm = InterchainMarketMaker {
-        pool_id: interchain_pool.clone().id,
-        pool: interchain_pool.clone(),
-        fee_rate: interchain_pool.swap_fee,
+    if multi_asset_order.status != OrderStatus::Pending {
+        return Err(ContractError::ErrOrderAlreadyCompleted);
+    }
+
+    let packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::CancelMultiDeposit,
+        data: encode_payload(&msg)?,
+        state_change: None,
+        memo: msg.memo,
     };
-    let result = amm.compute_swap(token_in, &token_out.denom)?;
-    Ok(result)
+
+    let data = encode_packet(deps.storage, &interchain_pool.counter_party_channel, &packet_data)?;
+
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = IbcMsg::SendPacket {
+        channel_id: interchain_pool.counter_party_channel,
+        data,
+        timeout,
+    };
+
+    let res = Response::default()
+        .add_message(ibc_msg)
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("action", "cancel_multi_asset_deposit");
+    Ok(res)
 }
 
-fn query_right_swap(
-    deps: Deps,
-    pool_id: String,
-    token_in: Coin,
-    token_out: Coin,
-) -> StdResult<Coin> {
-    // Get liquidity pool
+/// Coins the taker of a pending order attaches on this chain. The order holds the maker's
+/// deposits first and the taker's after them, which have to be the assets of this chain.
+fn required_counter_deposit(
+    pool: &InterchainLiquidityPool,
+    order: &MultiAssetDepositOrder,
+) -> Result<Vec<Coin>, ContractError> {
+    if order.status == OrderStatus::Complete {
+        return Err(ContractError::ErrOrderAlreadyCompleted);
+    }
+    if pool.maker_side(&order.deposits).ok() != Some(PoolSide::DESTINATION) {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Order {} isn't taken with the assets of this chain",
+            order.id
+        ))));
+    }
+    let maker_assets = pool.assets_by_side(PoolSide::DESTINATION).len();
+    Ok(order.deposits[maker_assets..].to_vec())
+}
+
+fn take_multi_asset_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgTakeMultiAssetDepositRequest,
+) -> Result<Response, ContractError> {
     // load pool throw error if not found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &pool_id)?;
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
     let interchain_pool;
     if let Some(pool) = interchain_pool_temp {
         interchain_pool = pool
     } else {
-        return Err(StdError::generic_err(format!(
+        return Err(ContractError::Std(StdError::generic_err(format!(
             "Pool doesn't exist {}",
-            pool_id
-        )));
+            msg.pool_id
+        ))));
+    }
+    // get order
+    // load orders
+    let key = msg.pool_id.clone() + "-" + &msg.order_id;
+    let multi_asset_order_temp = MULTI_ASSET_DEPOSIT_ORDERS.may_load(deps.storage, key)?;
+    let multi_asset_order;
+    if let Some(order) = multi_asset_order_temp {
+        multi_asset_order = order;
+    } else {
+        return Err(ContractError::ErrOrderNotFound);
     }
 
-    // Check the pool status
-    if interchain_pool.status != PoolStatus::Active {
-        return Err(StdError::generic_err(
-            "Pool not ready for swap!".to_string(),
-        ));
+    if multi_asset_order.destination_taker != info.sender {
+        verify_take_permit(deps.as_ref(), &env, &multi_asset_order, &msg, &info.sender)?;
     }
 
-    // Create the interchain market maker
+    let required = required_counter_deposit(&interchain_pool, &multi_asset_order)?;
+    for token in required.iter() {
+        assert_not_dust(deps.storage, &msg.pool_id, token.amount, |t| t.min_deposit)?;
+    }
+
+    if let Some(recipient) = &msg.recipient {
+        validate_local_address(deps.api, recipient)?;
+    }
+    if let Some(refund_to) = &msg.refund_to {
+        validate_local_address(deps.api, refund_to)?;
+    }
+
+    // check if given tokens are received here
+    if !required.iter().all(|token| info.funds.contains(token)) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Funds mismatch: Funds mismatched to with message and sent values: Take Multi Asset"
+                .to_string(),
+        )));
+    }
+
+    // find number of tokens to be minted
+    // Create the interchain market maker (amm).
     let amm = InterchainMarketMaker {
-        pool_id: interchain_pool.clone().id,
-        pool: interchain_pool.clone(),
+        pool_id: msg.pool_id.clone(),
+        pool: pool_with_fees(deps.storage, &interchain_pool)?,
         fee_rate: interchain_pool.swap_fee,
     };
-    let result = amm.compute_offer_amount(token_in, token_out)?;
-    Ok(result)
+
+    let pool_tokens = amm.deposit_multi_asset(&multi_asset_order.deposits)?;
+    let mut new_shares = Uint128::from(0u128);
+    for pool in pool_tokens.clone() {
+        new_shares += pool.amount;
+    }
+
+    // Construct the IBC packet
+    let state_change_data = encode_payload(&StateChange {
+        in_tokens: None,
+        out_tokens: None,
+        pool_tokens: Some(pool_tokens),
+        pool_id: None,
+        multi_deposit_order_id: None,
+        source_chain_id: None,
+        shares: Some(new_shares),
+    })?;
+    let packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::TakeMultiDeposit,
+        data: encode_payload(&msg)?,
+        state_change: Some(state_change_data),
+        memo: msg.memo
+    };
+
+    let data = encode_packet(deps.storage, &interchain_pool.counter_party_channel, &packet_data)?;
+
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = IbcMsg::SendPacket {
+        channel_id: interchain_pool.counter_party_channel,
+        data,
+        timeout,
+    };
+
+    let res = Response::default()
+        .add_message(ibc_msg)
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("action", "take_multi_asset_deposit");
+    Ok(res)
 }
 
-fn query_active_orders(
-    deps: Deps,
-    pool_id: String,
-    source_maker: String,
-    destination_taker: String,
-) -> StdResult<MultiAssetDepositOrder> {
-    let key = source_maker + "-" + &pool_id + "-" + &destination_taker;
-    let multi_asset_order_temp = ACTIVE_ORDERS.may_load(deps.storage, key)?;
-    let multi_asset_order;
-    if let Some(order) = multi_asset_order_temp {
-        multi_asset_order = order;
-    } else {
-        return Err(StdError::generic_err("No active order".to_string()));
+/// Sends the maker deposits of a pending order made here and of its mirror made on the
+/// counterparty chain as one multi asset deposit. The counterparty settles both orders on
+/// receipt and this chain on the acknowledgement.
+fn match_orders(
+    deps: DepsMut,
+    env: Env,
+    msg: MsgMatchMultiAssetDepositRequest,
+) -> Result<Response, ContractError> {
+    let interchain_pool = POOLS.load(deps.storage, &msg.pool_id)?;
+    let mut orders = vec![];
+    for order_id in [&msg.order_id, &msg.counter_order_id] {
+        let key = msg.pool_id.clone() + "-" + order_id;
+        let order = MULTI_ASSET_DEPOSIT_ORDERS
+            .may_load(deps.storage, key)?
+            .ok_or(ContractError::ErrOrderNotFound)?;
+        if order.status != OrderStatus::Pending {
+            return Err(ContractError::ErrOrderAlreadyCompleted);
+        }
+        orders.push(order);
+    }
+    let (order, counter) = (&orders[0], &orders[1]);
+    if !order.mirrors(counter, &interchain_pool)
+        || interchain_pool.maker_side(&order.deposits)? != PoolSide::SOURCE
+    {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Order {} doesn't mirror order {}",
+            order.id, counter.id
+        ))));
+    }
+
+    let amm = InterchainMarketMaker {
+        pool_id: msg.pool_id.clone(),
+        pool: pool_with_fees(deps.storage, &interchain_pool)?,
+        fee_rate: interchain_pool.swap_fee,
     };
+    let pool_tokens = amm.deposit_multi_asset(&order.deposits)?;
+    let new_shares = pool_tokens.iter().map(|token| token.amount).sum::<Uint128>();
 
-    Ok(multi_asset_order)
+    let state_change_data = encode_payload(&StateChange {
+        in_tokens: None,
+        out_tokens: None,
+        pool_tokens: Some(pool_tokens),
+        pool_id: None,
+        multi_deposit_order_id: None,
+        source_chain_id: None,
+        shares: Some(new_shares),
+    })?;
+    let packet_data = InterchainSwapPacketData {
+        r#type: InterchainMessageType::MatchMultiDeposit,
+        data: encode_payload(&msg)?,
+        state_change: Some(state_change_data),
+        memo: msg.memo.clone(),
+    };
+
+    let data = encode_packet(deps.storage, &interchain_pool.counter_party_channel, &packet_data)?;
+
+    let timeout = packet_timeout(
+        deps.storage,
+        &env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = IbcMsg::SendPacket {
+        channel_id: interchain_pool.counter_party_channel,
+        data,
+        timeout,
+    };
+
+    let res = Response::default()
+        .add_message(ibc_msg)
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("action", "match_orders")
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("counter_order_id", msg.counter_order_id);
+    Ok(res)
 }
 
-fn query_rate(deps: Deps, pool_id: String, amount: Uint128) -> StdResult<Vec<Coin>> {
+// Pass pool id asset i.e cw20
+fn multi_asset_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgMultiAssetWithdrawRequest,
+) -> Result<Response, ContractError> {
     // Get liquidity pool
     // load pool throw error if not found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &pool_id)?;
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
     let interchain_pool;
     if let Some(pool) = interchain_pool_temp {
         interchain_pool = pool
     } else {
-        return Err(StdError::generic_err(format!(
+        return Err(ContractError::Std(StdError::generic_err(format!(
             "Pool doesn't exist {}",
-            pool_id
-        )));
+            msg.pool_id
+        ))));
     }
 
+    validate_local_address(deps.api, &msg.receiver)?;
+    validate_counterparty_address(
+        deps.storage,
+        &interchain_pool.counter_party_channel,
+        &msg.counterparty_receiver,
+    )?;
+
     // Create the interchain market maker
     let amm = InterchainMarketMaker {
         pool_id: interchain_pool.clone().id,
@@ -1471,25 +2719,7108 @@ fn query_rate(deps: Deps, pool_id: String, amount: Uint128) -> StdResult<Vec<Coi
         fee_rate: interchain_pool.swap_fee,
     };
 
-    amm.multi_asset_withdraw(Coin {
-        amount,
-        denom: pool_id,
-    })
+    let refund_assets = amm
+        .multi_asset_withdraw(msg.pool_token.clone())
+        .map_err(|err| StdError::generic_err(format!("Failed to withdraw multi asset: {}", err)))?;
+
+    withdraw_packet(
+        deps.as_ref(),
+        &env,
+        &info,
+        &interchain_pool,
+        msg,
+        refund_assets,
+    )
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+fn zap_out(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pool_id: String,
+    lp_amount: Uint128,
+    denom_out: String,
+    min_out: Uint128,
+) -> Result<Response, ContractError> {
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+    if pool.side_of(&denom_out)? != PoolSide::SOURCE {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Zap out can only land in an asset of this chain, not {}",
+            denom_out
+        ))));
+    }
 
-    #[test]
-    fn test_instantiate() {
-        let mut deps = mock_dependencies();
+    let pool_token = Coin {
+        denom: pool_id.clone(),
+        amount: lp_amount,
+    };
+    let amm = InterchainMarketMaker {
+        pool_id: pool_id.clone(),
+        pool: pool.clone(),
+        fee_rate: pool.swap_fee,
+    };
+    let refund_assets = amm
+        .multi_asset_withdraw(pool_token.clone())
+        .map_err(|err| StdError::generic_err(format!("Failed to withdraw multi asset: {}", err)))?;
 
-        // Instantiate an empty contract
-        let instantiate_msg = InstantiateMsg { token_code_id: 1, router: "".to_string() };
-        let info = mock_info("anyone", &[]);
-        let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
-        assert_eq!(0, res.messages.len());
+    // swap the counterparty share against the pool as it is after the withdrawal
+    let mut withdrawn = pool.clone();
+    for asset in &refund_assets {
+        withdrawn.subtract_asset(asset.clone())?;
+    }
+    withdrawn.subtract_supply(pool_token.clone())?;
+    let amm = InterchainMarketMaker {
+        pool_id: pool_id.clone(),
+        pool: withdrawn,
+        fee_rate: pool.swap_fee,
+    };
+    let mut amount_out = Uint128::zero();
+    for asset in refund_assets {
+        if asset.denom == denom_out {
+            amount_out += asset.amount;
+        } else if !asset.amount.is_zero() {
+            amount_out += amm.compute_swap(asset, &denom_out)?.amount;
+        }
+    }
+    if amount_out < min_out {
+        return Err(ContractError::InvalidSlippage);
+    }
+
+    // Only the asset landed in leaves the pool, the other shares stay in it as the input of
+    // the swap, so the counterparty has nothing to unlock.
+    let msg = MsgMultiAssetWithdrawRequest {
+        pool_id,
+        receiver: info.sender.to_string(),
+        counterparty_receiver: String::new(),
+        pool_token,
+        timeout_height: 0,
+        timeout_timestamp: 0,
+        memo: None,
+    };
+    let out = Coin {
+        denom: denom_out,
+        amount: amount_out,
+    };
+    let res = withdraw_packet(deps.as_ref(), &env, &info, &pool, msg, vec![out])?;
+    Ok(res
+        .add_attribute("zap", "out")
+        .add_attribute("amount_out", amount_out.to_string()))
+}
+
+/// Pulls the LP tokens of a withdrawal into the contract and sends the packet releasing
+/// `out_tokens` from the pool.
+fn withdraw_packet(
+    deps: Deps,
+    env: &Env,
+    info: &MessageInfo,
+    interchain_pool: &InterchainLiquidityPool,
+    msg: MsgMultiAssetWithdrawRequest,
+    out_tokens: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    let mut withdrawn = interchain_pool.clone();
+    for token in &out_tokens {
+        withdrawn.subtract_asset(token.clone())?;
+    }
+    withdrawn.subtract_supply(msg.pool_token.clone())?;
+    interchain_pool.assert_share_price_floor(&withdrawn)?;
+
+    let sub_messages: Vec<SubMsg>;
+    if let Some(lp_token) = POOL_TOKENS_LIST.may_load(deps.storage, &msg.pool_id)? {
+        if tokenfactory::split_denom(&lp_token).is_some() {
+            // native LP shares are sent along with the message
+            let shares = Coin {
+                denom: lp_token,
+                amount: msg.pool_token.amount,
+            };
+            if !info.funds.contains(&shares) {
+                return Err(ContractError::Std(StdError::generic_err(format!(
+                    "Funds mismatch: {} has to be sent to withdraw",
+                    shares
+                ))));
+            }
+            sub_messages = vec![];
+        } else {
+            // Transfer tokens from user account to contract
+            let msg = Cw20ExecuteMsg::TransferFrom {
+                owner: info.sender.to_string(),
+                recipient: env.contract.address.to_string(),
+                amount: msg.pool_token.amount,
+            };
+            let exec = WasmMsg::Execute {
+                contract_addr: lp_token,
+                msg: to_binary(&msg)?,
+                funds: vec![],
+            };
+            sub_messages = vec![SubMsg::new(exec)];
+        }
+    } else {
+        // throw error token not found, initialization is done in make_pool and
+        // take_pool
+        return Err(ContractError::Std(StdError::generic_err(
+            "LP Token is not initialized".to_string(),
+        )));
+    }
+
+    let state_change_data = encode_payload(&StateChange {
+        in_tokens: Some(vec![msg.pool_token.clone()]),
+        out_tokens: Some(out_tokens),
+        pool_tokens: Some(vec![msg.pool_token.clone()]),
+        pool_id: None,
+        multi_deposit_order_id: None,
+        source_chain_id: None,
+        shares: None,
+    })?;
+
+    let packet = InterchainSwapPacketData {
+        r#type: InterchainMessageType::MultiWithdraw,
+        data: encode_payload(&msg)?,
+        state_change: Some(state_change_data),
+        memo: msg.memo,
+    };
+
+    let data = encode_packet(deps.storage, &interchain_pool.counter_party_channel, &packet)?;
+
+    let timeout = packet_timeout(
+        deps.storage,
+        env,
+        interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = IbcMsg::SendPacket {
+        channel_id: interchain_pool.counter_party_channel.clone(),
+        data,
+        timeout,
+    };
+
+    let res = Response::default()
+        .add_submessages(sub_messages)
+        .add_message(ibc_msg)
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("action", "multi_asset_withdraw");
+    Ok(res)
+}
+
+fn swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgSwapRequest,
+) -> Result<Response, ContractError> {
+    // check if given tokens are received here
+    let mut ok = false;
+    // First token in this chain only first token needs to be verified
+    for asset in info.funds {
+        if asset.denom == msg.token_in.denom && asset.amount == msg.token_in.amount {
+            ok = true;
+        }
+    }
+    if !ok {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Funds mismatch: Funds mismatched to with message and sent values: Swap".to_string(),
+        )));
+    }
+
+    let pool_id = msg.pool_id.clone();
+    escrow_swap_input(deps.storage, &msg)?;
+    let ibc_msg = swap_packet(deps.as_ref(), &env, msg)?;
+
+    let res = Response::default()
+        .add_message(ibc_msg)
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("action", "swap");
+    Ok(res)
+}
+
+/// Splits an order across several pools. The funds have to cover all legs
+/// together and one packet is sent per leg.
+/// Swaps like Swap, as long as the pool still gives the quote made at `quote_height`.
+fn firm_swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MsgSwapRequest,
+    quoted_out: Coin,
+    quote_height: u64,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let valid_until = quote_height.saturating_add(config.quote_validity_blocks);
+    if quote_height > env.block.height || env.block.height > valid_until {
+        return Err(ContractError::QuoteExpired {
+            quote_height,
+            valid_until,
+        });
+    }
+
+    let pool = POOLS.load(deps.storage, &msg.pool_id)?;
+    let current = swap_quote(&pool, &msg.swap_type, msg.token_in.clone(), msg.token_out.clone())?;
+    if current != quoted_out {
+        return Err(ContractError::QuoteChanged {
+            quoted: quoted_out,
+            current,
+        });
+    }
+
+    swap(deps, env, info, msg)
+}
+
+fn set_quote_validity(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    blocks: u64,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
+    }
+    config.quote_validity_blocks = blocks;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "set_quote_validity")
+        .add_attribute("blocks", blocks.to_string()))
+}
+
+/// Queues a withdrawal of a local denom. The treasury is drawn first, then the protocol
+/// share of the fees of the pools trading the denom from this chain, the LP fees are left.
+fn withdraw_treasury(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    amount: Uint128,
+    to: String,
+) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info, Role::Treasurer)?;
+    let to = validate_local_address(deps.api, &to)?;
+    if amount.is_zero() {
+        return Err(ContractError::InvalidAmount);
+    }
+
+    let held = TREASURY.may_load(deps.storage, &denom)?.unwrap_or_default();
+    let from_treasury = held.min(amount);
+    let mut remaining = amount - from_treasury;
+
+    let mut drawn_fees = vec![];
+    for item in POOLS.range(deps.storage, None, None, Order::Ascending) {
+        if remaining.is_zero() {
+            break;
+        }
+        let (pool_id, pool) = item?;
+        match pool.find_asset_by_denom(&denom) {
+            Ok(asset) if asset.side == PoolSide::SOURCE => {}
+            _ => continue,
+        }
+        let mut fees = PROTOCOL_FEES.may_load(deps.storage, &pool_id)?.unwrap_or_default();
+        let mut pool_drawn = Uint128::zero();
+        for fee in fees.iter_mut().filter(|fee| fee.denom == denom) {
+            let drawn = fee.amount.min(remaining);
+            fee.amount -= drawn;
+            remaining -= drawn;
+            pool_drawn += drawn;
+        }
+        fees.retain(|fee| !fee.amount.is_zero());
+        drawn_fees.push((pool_id, fees, pool_drawn));
+    }
+
+    if !remaining.is_zero() {
+        return Err(ContractError::InsufficientTreasury {
+            denom,
+            available: amount - remaining,
+            requested: amount,
+        });
+    }
+
+    if held == from_treasury {
+        TREASURY.remove(deps.storage, &denom);
+    } else {
+        TREASURY.save(deps.storage, &denom, &(held - from_treasury))?;
+    }
+    let mut protocol_fees = vec![];
+    for (pool_id, fees, drawn) in drawn_fees {
+        if fees.is_empty() {
+            PROTOCOL_FEES.remove(deps.storage, &pool_id);
+        } else {
+            PROTOCOL_FEES.save(deps.storage, &pool_id, &fees)?;
+        }
+        if !drawn.is_zero() {
+            protocol_fees.push((pool_id, drawn));
+        }
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let id = PENDING_WITHDRAWAL_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    PENDING_WITHDRAWAL_COUNT.save(deps.storage, &id)?;
+    let withdrawal = PendingWithdrawal {
+        id,
+        denom,
+        amount,
+        to: to.to_string(),
+        executable_at: env.block.time.seconds() + config.treasury_timelock_seconds,
+        protocol_fees,
+    };
+    PENDING_WITHDRAWALS.save(deps.storage, id, &withdrawal)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "withdraw_treasury")
+        .add_attribute("id", id.to_string())
+        .add_attribute("amount", format!("{}{}", withdrawal.amount, withdrawal.denom))
+        .add_attribute("to", withdrawal.to)
+        .add_attribute("executable_at", withdrawal.executable_at.to_string()))
+}
+
+fn execute_treasury_withdrawal(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info, Role::Treasurer)?;
+    let withdrawal = PENDING_WITHDRAWALS.load(deps.storage, id)?;
+    if env.block.time.seconds() < withdrawal.executable_at {
+        return Err(ContractError::WithdrawalTimelocked {
+            id,
+            executable_at: withdrawal.executable_at,
+        });
+    }
+    PENDING_WITHDRAWALS.remove(deps.storage, id);
+
+    let sub_messages = send_tokens_coin(
+        &Addr::unchecked(&withdrawal.to),
+        Coin {
+            denom: withdrawal.denom.clone(),
+            amount: withdrawal.amount,
+        },
+    )?;
+
+    Ok(Response::default()
+        .add_submessages(sub_messages)
+        .add_attribute("action", "execute_treasury_withdrawal")
+        .add_attribute("id", id.to_string())
+        .add_attribute("amount", format!("{}{}", withdrawal.amount, withdrawal.denom))
+        .add_attribute("to", withdrawal.to))
+}
+
+/// Drops a queued withdrawal, its funds go back to the protocol fees of the pools they were
+/// drawn from and the rest to the treasury.
+fn cancel_treasury_withdrawal(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info, Role::Treasurer)?;
+    let withdrawal = PENDING_WITHDRAWALS.load(deps.storage, id)?;
+    PENDING_WITHDRAWALS.remove(deps.storage, id);
+    let mut to_treasury = withdrawal.amount;
+    for (pool_id, drawn) in withdrawal.protocol_fees {
+        // the fees of a pool decommissioned since belong to the treasury
+        if !POOLS.has(deps.storage, &pool_id) {
+            continue;
+        }
+        let fee = Coin {
+            denom: withdrawal.denom.clone(),
+            amount: drawn,
+        };
+        accrue_fee(deps.storage, &PROTOCOL_FEES, &pool_id, fee)?;
+        to_treasury = to_treasury.saturating_sub(drawn);
+    }
+    if !to_treasury.is_zero() {
+        TREASURY.update(deps.storage, &withdrawal.denom, |held| -> StdResult<_> {
+            Ok(held.unwrap_or_default() + to_treasury)
+        })?;
+    }
+
+    Ok(Response::default()
+        .add_attribute("action", "cancel_treasury_withdrawal")
+        .add_attribute("id", id.to_string()))
+}
+
+fn set_treasury_timelock(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    seconds: u64,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
+    }
+    config.treasury_timelock_seconds = seconds;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "set_treasury_timelock")
+        .add_attribute("seconds", seconds.to_string()))
+}
+
+fn set_protocol_fee(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    share_bps: u32,
+    collector: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
+    }
+    if share_bps > u32::from(FEE_PRECISION) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Invalid protocol fee share".to_string(),
+        )));
+    }
+    let collector = collector
+        .map(|collector| validate_local_address(deps.api, &collector))
+        .transpose()?;
+
+    config.protocol_fee_share_bps = share_bps;
+    config.protocol_fee_collector = collector.map(|collector| collector.to_string());
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "set_protocol_fee")
+        .add_attribute("share_bps", share_bps.to_string())
+        .add_attribute("collector", config.protocol_fee_collector.unwrap_or_default()))
+}
+
+/// Sends the protocol fees a pool accrued in its local denoms to the collector. The fees
+/// in counterparty denoms stay, they are collected on the counterparty chain.
+fn collect_protocol_fees(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    pool_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let collector = match config.protocol_fee_collector {
+        Some(collector) if collector == info.sender => info.sender,
+        _ => {
+            return Err(ContractError::Std(StdError::generic_err("not allowed".to_string())));
+        }
+    };
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+
+    let fees = PROTOCOL_FEES.may_load(deps.storage, &pool_id)?.unwrap_or_default();
+    let (collected, remote): (Vec<Coin>, Vec<Coin>) = fees
+        .into_iter()
+        .partition(|fee| matches!(pool.side_of(&fee.denom), Ok(PoolSide::SOURCE)));
+    if collected.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "No protocol fees to collect".to_string(),
+        )));
+    }
+    if remote.is_empty() {
+        PROTOCOL_FEES.remove(deps.storage, &pool_id);
+    } else {
+        PROTOCOL_FEES.save(deps.storage, &pool_id, &remote)?;
+    }
+
+    let mut sub_messages = vec![];
+    for fee in collected.iter() {
+        sub_messages.append(&mut send_tokens_coin(&collector, fee.clone())?);
+    }
+
+    Ok(Response::default()
+        .add_submessages(sub_messages)
+        .add_attribute("action", "collect_protocol_fees")
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("fees", coins_to_string(&collected))
+        .add_attribute("to", collector))
+}
+
+/// Output of a left swap, or the offer a right swap needs, on the current pool state.
+fn swap_quote(
+    pool: &InterchainLiquidityPool,
+    swap_type: &SwapMsgType,
+    token_in: Coin,
+    token_out: Coin,
+) -> StdResult<Coin> {
+    let amm = InterchainMarketMaker::new(pool, pool.sender_fee_rate(swap_type));
+    match swap_type {
+        SwapMsgType::LEFT => amm.compute_swap(token_in, &token_out.denom),
+        SwapMsgType::RIGHT => amm.compute_offer_amount(token_in, token_out),
+    }
+}
+
+fn batch_swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    legs: Vec<MsgSwapRequest>,
+) -> Result<Response, ContractError> {
+    if legs.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Batch swap needs at least one leg".to_string(),
+        )));
+    }
+
+    let mut total_in: Vec<Coin> = vec![];
+    for (i, leg) in legs.iter().enumerate() {
+        if legs[..i].iter().any(|prev| prev.pool_id == leg.pool_id) {
+            return Err(ContractError::Std(StdError::generic_err(format!(
+                "Duplicate pool in batch swap {}",
+                leg.pool_id
+            ))));
+        }
+        match total_in.iter_mut().find(|coin| coin.denom == leg.token_in.denom) {
+            Some(coin) => {
+                coin.amount = coin.amount.checked_add(leg.token_in.amount).map_err(StdError::from)?
+            }
+            None => total_in.push(leg.token_in.clone()),
+        }
+    }
+
+    // every denom has to be sent exactly once for the sum of its legs
+    let ok = total_in.len() == info.funds.len()
+        && total_in.iter().all(|coin| info.funds.contains(coin));
+    if !ok {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Funds mismatch: Funds mismatched to with message and sent values: BatchSwap"
+                .to_string(),
+        )));
+    }
+
+    let mut res = Response::default()
+        .add_attribute("action", "batch_swap")
+        .add_attribute("legs", legs.len().to_string());
+    for leg in legs {
+        let pool_id = leg.pool_id.clone();
+        escrow_swap_input(deps.storage, &leg)?;
+        res = res
+            .add_message(swap_packet(deps.as_ref(), &env, leg)?)
+            .add_attribute("pool_id", pool_id);
+    }
+    Ok(res)
+}
+
+/// Books the swap input as held until the packet is acknowledged or times out.
+fn escrow_swap_input(storage: &mut dyn Storage, msg: &MsgSwapRequest) -> StdResult<()> {
+    let key = (msg.pool_id.as_str(), msg.token_in.denom.as_str());
+    SWAP_ESCROW.update(storage, key, |held| -> StdResult<_> {
+        Ok(held.unwrap_or_default().checked_add(msg.token_in.amount)?)
+    })?;
+    add_escrow(storage, &msg.token_in)
+}
+
+/// Quotes a swap against the pool and builds the packet carrying it.
+fn swap_packet(deps: Deps, env: &Env, msg: MsgSwapRequest) -> Result<IbcMsg, ContractError> {
+    // Get liquidity pool
+    // load pool throw error if not found
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
+    let interchain_pool;
+    if let Some(pool) = interchain_pool_temp {
+        interchain_pool = pool
+    } else {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Pool doesn't exist {}",
+            msg.pool_id
+        ))));
+    }
+
+    assert_not_dust(deps.storage, &msg.pool_id, msg.token_in.amount, |t| t.min_swap)?;
+
+    // a pool may hold several assets per chain, swaps cross from an asset of this chain
+    // to one of the counterparty
+    if interchain_pool.side_of(&msg.token_in.denom)? != PoolSide::SOURCE
+        || interchain_pool.side_of(&msg.token_out.denom)? != PoolSide::DESTINATION
+    {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Swaps go from an asset of this chain to one of the counterparty, not {} to {}",
+            msg.token_in.denom, msg.token_out.denom
+        ))));
+    }
+
+    validate_local_address(deps.api, &msg.sender)?;
+    if let Some(refund_to) = &msg.refund_to {
+        validate_local_address(deps.api, refund_to)?;
+    }
+    match &msg.forward {
+        // the output leaves the counterparty chain, a recipient there is not needed
+        Some(forward) => {
+            if msg.route.is_some() {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "A swap output cannot be both routed and forwarded".to_string(),
+                )));
+            }
+            if forward.channel.is_empty() || forward.receiver.is_empty() {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Forward channel and receiver are required".to_string(),
+                )));
+            }
+        }
+        None => {
+            validate_counterparty_address(
+                deps.storage,
+                &interchain_pool.counter_party_channel,
+                &msg.recipient,
+            )?;
+        }
+    }
+
+    // Construct the IBC data packet
+    let swap_data = encode_payload(&msg)?;
+    let msg_type = match msg.swap_type {
+        SwapMsgType::LEFT => InterchainMessageType::LeftSwap,
+        SwapMsgType::RIGHT => InterchainMessageType::RightSwap,
+    };
+    let token_out = swap_quote(
+        &interchain_pool,
+        &msg.swap_type,
+        msg.token_in.clone(),
+        msg.token_out.clone(),
+    )?;
+
+    // Slippage checking
+    assert_swap_slippage(&msg, &token_out)?;
+
+    let state_change_data = encode_payload(&StateChange {
+        in_tokens: None,
+        out_tokens: Some(vec![token_out]),
+        pool_tokens: None,
+        pool_id: None,
+        multi_deposit_order_id: None,
+        source_chain_id: None,
+        shares: None,
+    })?;
+
+    let packet = InterchainSwapPacketData {
+        r#type: msg_type,
+        data: swap_data,
+        state_change: Some(state_change_data),
+        memo: msg.memo,
+    };
+
+    let data = encode_packet(deps.storage, &interchain_pool.counter_party_channel, &packet)?;
+
+    let timeout = packet_timeout(
+        deps.storage,
+        env,
+        &interchain_pool,
+        msg.timeout_height,
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = IbcMsg::SendPacket {
+        channel_id: interchain_pool.counter_party_channel,
+        data,
+        timeout,
+    };
+
+    Ok(ibc_msg)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::StorageVersion {} => to_binary(&StorageVersionResponse {
+            version: STORAGE_VERSION.may_load(deps.storage)?.unwrap_or_default(),
+            pending: VERSIONED_MAPS
+                .iter()
+                .filter(|map| map.pending(deps.storage))
+                .map(|map| map.namespace().to_string())
+                .collect(),
+        }),
+        QueryMsg::Roles { address } => to_binary(&RolesResponse {
+            roles: ROLES.may_load(deps.storage, &address)?.unwrap_or_default(),
+        }),
+        QueryMsg::AssetMetadata { denom } => {
+            to_binary(&ASSET_METADATA.may_load(deps.storage, &denom)?)
+        }
+        QueryMsg::DustThresholds { pool_id } => {
+            to_binary(&DUST_THRESHOLDS.may_load(deps.storage, &pool_id)?.unwrap_or_default())
+        }
+        QueryMsg::CollectedFees { pool_id } => {
+            to_binary(&COLLECTED_FEES.may_load(deps.storage, &pool_id)?.unwrap_or_default())
+        }
+        QueryMsg::ProtocolFees { pool_id } => to_binary(&query_protocol_fees(deps, pool_id)?),
+        QueryMsg::Treasury {} => to_binary(&query_treasury(deps)?),
+        QueryMsg::PendingWithdrawals { start_after, limit } => {
+            to_binary(&query_pending_withdrawals(deps, start_after, limit)?)
+        }
+        QueryMsg::WithdrawalQueue {
+            denom,
+            start_after,
+            limit,
+        } => to_binary(&query_withdrawal_queue(deps, denom, start_after, limit)?),
+        QueryMsg::InterchainPool { pool_id } => to_binary(&query_interchain_pool(deps, pool_id)?),
+        QueryMsg::InterchainPoolList { start_after, limit } => {
+            to_binary(&query_interchain_pool_list(deps, start_after, limit)?)
+        }
+        QueryMsg::Order { pool_id, order_id } => to_binary(&query_order(deps, pool_id, order_id)?),
+        QueryMsg::TakePoolRequiredFunds { pool_id } => {
+            to_binary(&query_take_pool_required_funds(deps, pool_id)?)
+        }
+        QueryMsg::RequiredCounterDeposit { pool_id, order_id } => {
+            to_binary(&query_required_counter_deposit(deps, pool_id, order_id)?)
+        }
+        QueryMsg::OrderList { start_after, limit } => {
+            to_binary(&query_orders(deps, start_after, limit)?)
+        }
+        QueryMsg::PoolAddressByToken { pool_id } => to_binary(&query_pool_address(deps, pool_id)?),
+        QueryMsg::VerifyPool { pool_id } => to_binary(&query_verify_pool(deps, env, pool_id)?),
+        QueryMsg::LpToken { pool_id } => to_binary(&query_lp_token(deps, pool_id)?),
+        QueryMsg::PoolTokenList { start_after, limit } => {
+            to_binary(&query_pool_list(deps, start_after, limit)?)
+        }
+        QueryMsg::LeftSwap {
+            pool_id,
+            token_in,
+            token_out,
+        } => to_binary(&query_left_swap(deps, pool_id, token_in, token_out)?),
+        QueryMsg::RightSwap {
+            pool_id,
+            token_in,
+            token_out,
+        } => to_binary(&query_right_swap(deps, pool_id, token_in, token_out)?),
+        QueryMsg::EstimateLeftSwap {
+            pool_id,
+            denom_in,
+            amount_in,
+            denom_out,
+        } => to_binary(&query_left_swap(
+            deps,
+            pool_id,
+            Coin::new(amount_in.u128(), denom_in),
+            Coin::new(0, denom_out),
+        )?),
+        QueryMsg::EstimateRightSwap {
+            pool_id,
+            denom_in,
+            denom_out,
+            amount_out,
+        } => to_binary(&query_right_swap(
+            deps,
+            pool_id,
+            Coin::new(0, denom_in),
+            Coin::new(amount_out.u128(), denom_out),
+        )?),
+        QueryMsg::SharesForDeposit { pool_id, deposits } => {
+            to_binary(&query_shares_for_deposit(deps, pool_id, deposits)?)
+        }
+        QueryMsg::AssetsForShares { pool_id, shares } => {
+            to_binary(&query_assets_for_shares(deps, pool_id, shares)?)
+        }
+        QueryMsg::FirmQuote {
+            pool_id,
+            swap_type,
+            token_in,
+            token_out,
+        } => to_binary(&query_firm_quote(deps, env, pool_id, swap_type, token_in, token_out)?),
+        QueryMsg::QueryActiveOrders {
+            source_maker,
+            destination_taker,
+            pool_id,
+            start_after,
+            limit,
+        } => to_binary(&query_active_orders(
+            deps,
+            source_maker,
+            pool_id,
+            destination_taker,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::Rate { pool_id, amount } => to_binary(&query_rate(deps, pool_id, amount)?),
+        QueryMsg::RateDetail { pool_id, amount } => {
+            to_binary(&query_rate_detail(deps, pool_id, amount)?)
+        }
+        QueryMsg::AddressPrefix { channel_id } => {
+            to_binary(&COUNTERPARTY_ADDRESS_PREFIX.may_load(deps.storage, &channel_id)?)
+        }
+        QueryMsg::PacketVetoed {
+            channel_id,
+            sequence,
+        } => to_binary(&VETOED_PACKETS.has(deps.storage, (&channel_id, sequence))),
+        QueryMsg::CounterpartyPort { connection_id } => {
+            to_binary(&COUNTERPARTY_PORTS.may_load(deps.storage, &connection_id)?)
+        }
+        QueryMsg::Channels {} => to_binary(&query_channels(deps)?),
+        QueryMsg::ChannelCapabilities { channel_id } => {
+            to_binary(&query_channel_capabilities(deps, channel_id)?)
+        }
+        QueryMsg::SwapClaims {
+            channel_id,
+            counterparty,
+        } => to_binary(
+            &SWAP_CLAIMS
+                .may_load(deps.storage, (&channel_id, &counterparty))?
+                .unwrap_or_default(),
+        ),
+        QueryMsg::CounterpartyPool { pool_id } => {
+            to_binary(&COUNTERPARTY_POOLS.may_load(deps.storage, &pool_id)?)
+        }
+        QueryMsg::DecodePacket { data } => to_binary(&query_decode_packet(data)?),
+        QueryMsg::PoolSupply { pool_id } => to_binary(&query_pool_supply(deps, pool_id)?),
+        QueryMsg::DepositReceipts {
+            sender,
+            start_after,
+            limit,
+        } => to_binary(&query_deposit_receipts(deps, sender, start_after, limit)?),
+        QueryMsg::Reconcile {
+            pool_id,
+            denom,
+            start_after,
+            limit,
+        } => to_binary(&query_reconcile(deps, env, pool_id, denom, start_after, limit)?),
+        QueryMsg::PacketJournal {
+            pool_id,
+            start_after,
+            limit,
+        } => to_binary(&query_packet_journal(deps, pool_id, start_after, limit)?),
+        QueryMsg::LpCheckpoints {
+            pool_id,
+            start_after,
+            limit,
+        } => to_binary(&query_lp_checkpoints(deps, pool_id, start_after, limit)?),
+        QueryMsg::ReceivedPackets {
+            channel_id,
+            start_after,
+            limit,
+        } => to_binary(&query_received_packets(deps, channel_id, start_after, limit)?),
+        QueryMsg::PacketUndo {
+            channel_id,
+            sequence,
+        } => to_binary(&UNDO_LOG.load(deps.storage, (&channel_id, sequence))?),
+        QueryMsg::Simulate { msg, sender, funds } => {
+            to_binary(&query_simulate(deps, env, *msg, sender, funds)?)
+        }
+    }
+}
+
+/// Settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+fn query_config(deps: Deps) -> StdResult<QueryConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+
+    Ok(QueryConfigResponse {
+        counter: config.counter,
+        token_code_id: config.token_code_id,
+        debug_attributes: config.debug_attributes,
+        admin: config.admin,
+        pending_admin: config.pending_admin,
+        lp_token_kind: config.lp_token_kind,
+    })
+}
+
+fn query_protocol_fees(deps: Deps, pool_id: String) -> StdResult<ProtocolFeesResponse> {
+    let config = CONFIG.load(deps.storage)?;
+
+    Ok(ProtocolFeesResponse {
+        share_bps: config.protocol_fee_share_bps,
+        collector: config.protocol_fee_collector,
+        fees: PROTOCOL_FEES.may_load(deps.storage, &pool_id)?.unwrap_or_default(),
+    })
+}
+
+fn query_decode_packet(data: Binary) -> StdResult<DecodedPacketResponse> {
+    let packet = decode_packet(&data)?;
+
+    Ok(DecodedPacketResponse {
+        message: packet.decode_message()?,
+        state_change: packet.decode_state_change()?,
+        r#type: packet.r#type,
+        memo: packet.memo,
+    })
+}
+
+/// Runs an execute message against a scratch copy of the state and reports the
+/// packet it would send. Nothing is persisted.
+fn query_simulate(
+    deps: Deps,
+    env: Env,
+    msg: ExecuteMsg,
+    sender: String,
+    funds: Vec<Coin>,
+) -> StdResult<SimulateResponse> {
+    let mut storage = SimulationStorage::new(deps.storage);
+    let deps_mut = DepsMut {
+        storage: &mut storage,
+        api: deps.api,
+        querier: deps.querier,
+    };
+    let info = MessageInfo {
+        sender: deps.api.addr_validate(&sender)?,
+        funds,
+    };
+
+    match execute(deps_mut, env, info, msg) {
+        Ok(res) => {
+            let packet = res
+                .messages
+                .iter()
+                .find_map(|sub_msg| match &sub_msg.msg {
+                    CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => Some(decode_packet(data)),
+                    _ => None,
+                })
+                .transpose()?;
+            Ok(SimulateResponse {
+                packet,
+                error: None,
+            })
+        }
+        Err(err) => Ok(SimulateResponse {
+            packet: None,
+            error: Some(err.to_string()),
+        }),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let ver = cw2::get_contract_version(deps.storage)?;
+    // ensure we are migrating from an allowed contract
+    if ver.contract != CONTRACT_NAME {
+        return Err(StdError::generic_err("Can only upgrade from same type").into());
+    }
+    // note: better to do proper semver compare, but string compare *usually* works
+    if ver.version.as_str() >= CONTRACT_VERSION {
+        return Err(StdError::generic_err("Cannot upgrade from a newer version").into());
+    }
+
+    // the order counter used to be a u64, which is stored as a json number
+    if let Ok(legacy) = LEGACY_CONFIG.load(deps.storage) {
+        let config = Config {
+            counter: legacy.counter.into(),
+            token_code_id: legacy.token_code_id,
+            admin: legacy.admin,
+            pending_admin: None,
+            router: legacy.router,
+            min_timeout_seconds: DEFAULT_MIN_TIMEOUT_SECONDS,
+            max_timeout_seconds: DEFAULT_MAX_TIMEOUT_SECONDS,
+            default_timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            min_swap_fee_bps: DEFAULT_MIN_SWAP_FEE_BPS,
+            max_swap_fee_bps: DEFAULT_MAX_SWAP_FEE_BPS,
+            disabled_messages: vec![],
+            debug_attributes: false,
+            quote_validity_blocks: DEFAULT_QUOTE_VALIDITY_BLOCKS,
+            treasury_timelock_seconds: DEFAULT_TREASURY_TIMELOCK_SECONDS,
+            protocol_fee_share_bps: 0,
+            protocol_fee_collector: None,
+            max_initialized_pools: DEFAULT_MAX_INITIALIZED_POOLS,
+            lp_token_kind: LpTokenKind::Cw20,
+        };
+        CONFIG.save(deps.storage, &config)?;
+    }
+
+    // bind the connections of channels opened before ports were bound, so a new channel
+    // cannot bind them first
+    let channels = CHANNEL_INFO
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (_, channel) in channels {
+        if !COUNTERPARTY_PORTS.has(deps.storage, &channel.connection_id) {
+            COUNTERPARTY_PORTS.save(
+                deps.storage,
+                &channel.connection_id,
+                &channel.counterparty_endpoint.port_id,
+            )?;
+        }
+    }
+
+    // the journal used to be keyed by sequence, entries now get an id when the packet is sent
+    let entries = LEGACY_PACKET_JOURNAL
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for ((pool_id, sequence), mut entry) in entries {
+        LEGACY_PACKET_JOURNAL.remove(deps.storage, (&pool_id, sequence));
+        let id = PACKET_JOURNAL_COUNT.may_load(deps.storage, &pool_id)?.unwrap_or_default() + 1;
+        PACKET_JOURNAL_COUNT.save(deps.storage, &pool_id, &id)?;
+        entry.id = id;
+        PACKET_JOURNAL.save(deps.storage, (&pool_id, id), &entry)?;
+    }
+
+    // swap escrow is kept per denom since pools may hold several assets on this chain
+    let escrows = LEGACY_SWAP_ESCROW
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (pool_id, held) in escrows {
+        LEGACY_SWAP_ESCROW.remove(deps.storage, &pool_id);
+        if let Some(pool) = POOLS.may_load(deps.storage, &pool_id)? {
+            let denom = pool.find_asset_by_side(PoolSide::SOURCE)?.balance.denom;
+            SWAP_ESCROW.save(deps.storage, (&pool_id, &denom), &held)?;
+        }
+    }
+
+    // escrow is kept as a total per denom instead of being summed up on every payout
+    recount_escrowed_funds(deps.storage)?;
+
+    STORAGE_VERSION.save(deps.storage, &CURRENT_STORAGE_VERSION)?;
+
+    // set the new version
+    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::default())
+}
+
+fn query_interchain_pool(deps: Deps, pool_id: String) -> StdResult<InterchainPoolResponse> {
+    // load pool throw error if found
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &pool_id)?;
+    let interchain_pool;
+    if let Some(pool) = interchain_pool_temp {
+        interchain_pool = pool;
+    } else {
+        return Err(StdError::generic_err("Pool not found".to_string()));
+    }
+    let config = CONFIG.load(deps.storage)?;
+
+    Ok(InterchainPoolResponse {
+        lp_token: POOL_TOKENS_LIST.may_load(deps.storage, &pool_id)?,
+        spot_price: interchain_pool.spot_price(),
+        min_swap_fee_bps: config.min_swap_fee_bps,
+        max_swap_fee_bps: config.max_swap_fee_bps,
+        pending_packets: IN_FLIGHT_PACKETS.may_load(deps.storage, &pool_id)?.unwrap_or_default(),
+        id: interchain_pool.id,
+        source_creator: interchain_pool.source_creator,
+        destination_creator: interchain_pool.destination_creator,
+        assets: interchain_pool.assets,
+        swap_fee: interchain_pool.swap_fee,
+        right_swap_fee: interchain_pool.right_swap_fee,
+        supply: interchain_pool.supply,
+        status: interchain_pool.status,
+        counter_party_channel: interchain_pool.counter_party_channel,
+        counter_party_port: interchain_pool.counter_party_port,
+        source_chain_id: interchain_pool.source_chain_id,
+        destination_chain_id: interchain_pool.destination_chain_id,
+        packet_timeout_seconds: interchain_pool.packet_timeout_seconds,
+        manager: POOL_MANAGERS.may_load(deps.storage, &pool_id)?,
+    })
+}
+
+fn query_interchain_pool_list(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<InterchainListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|denom| Bound::ExclusiveRaw(denom.into_bytes()));
+    let list = POOLS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(
+            |item: Result<(String, InterchainLiquidityPool), cosmwasm_std::StdError>| {
+                item.unwrap().1
+            },
+        )
+        .collect::<Vec<InterchainLiquidityPool>>();
+
+    Ok(InterchainListResponse { pools: list })
+}
+
+fn query_take_pool_required_funds(
+    deps: Deps,
+    pool_id: String,
+) -> StdResult<TakePoolRequiredFundsResponse> {
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+    Ok(TakePoolRequiredFundsResponse {
+        funds: take_pool_funds(&pool),
+        taker: pool.destination_creator,
+    })
+}
+
+fn query_required_counter_deposit(
+    deps: Deps,
+    pool_id: String,
+    order_id: String,
+) -> StdResult<RequiredCounterDepositResponse> {
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+    let order = query_order(deps, pool_id, order_id)?;
+    let funds = required_counter_deposit(&pool, &order)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    Ok(RequiredCounterDepositResponse {
+        funds,
+        taker: order.destination_taker,
+    })
+}
+
+fn query_order(deps: Deps, pool_id: String, order_id: String) -> StdResult<MultiAssetDepositOrder> {
+    let key = pool_id + "-" + &order_id;
+    let multi_asset_order_temp = MULTI_ASSET_DEPOSIT_ORDERS.may_load(deps.storage, key)?;
+    let multi_asset_order;
+    if let Some(order) = multi_asset_order_temp {
+        multi_asset_order = order;
+    } else {
+        return Err(StdError::generic_err("Order not found".to_string()));
+    };
+
+    Ok(multi_asset_order)
+}
+
+fn query_orders(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<OrderListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|denom| Bound::ExclusiveRaw(denom.into_bytes()));
+    let list = MULTI_ASSET_DEPOSIT_ORDERS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(
+            |item: Result<(String, MultiAssetDepositOrder), cosmwasm_std::StdError>| {
+                item.unwrap().1
+            },
+        )
+        .collect::<Vec<MultiAssetDepositOrder>>();
+
+    Ok(OrderListResponse { orders: list })
+}
+
+fn query_pool_address(deps: Deps, pool_id: String) -> StdResult<String> {
+    let res;
+    if let Some(lp_token) = POOL_TOKENS_LIST.may_load(deps.storage, &pool_id)? {
+        res = lp_token
+    } else {
+        // throw error token not found, initialization is done in make_pool and
+        // take_pool
+        return Err(StdError::generic_err(
+            "LP Token is not initialized".to_string(),
+        ));
+    }
+
+    Ok(res)
+}
+
+fn query_deposit_receipts(
+    deps: Deps,
+    sender: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<DepositReceiptsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let receipts = DEPOSIT_RECEIPTS
+        .prefix(&sender)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, receipt)| receipt))
+        .collect::<StdResult<Vec<DepositReceipt>>>()?;
+
+    Ok(DepositReceiptsResponse { receipts })
+}
+
+fn query_verify_pool(deps: Deps, env: Env, pool_id: String) -> StdResult<VerifyPoolResponse> {
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+    let mut violations = vec![];
+
+    if pool.assets.iter().map(|asset| asset.weight).sum::<u32>() != 100 {
+        violations.push("weights don't add up to 100".to_string());
+    }
+    if pool.status == PoolStatus::Active && !pool.is_funded() {
+        violations.push("active pool with an empty asset".to_string());
+    }
+    let invariant = pool.invariant()?;
+    let supply = Decimal::from_atomics(pool.supply.amount, LP_TOKEN_PRECISION.into())
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    // an empty pool backs no shares
+    let invariant_per_share = invariant.checked_div(supply).unwrap_or_default();
+
+    let supply = query_pool_supply(deps, pool_id.clone())?;
+    if !supply.consistent {
+        violations.push(format!(
+            "LP token supply {} above pool supply {}",
+            supply.lp_token_supply, supply.supply.amount
+        ));
+    }
+    let mut reconcile = vec![];
+    for asset in pool.assets_by_side(PoolSide::SOURCE) {
+        let denom_reconcile =
+            reconcile_denom(deps, &env, asset.balance.denom, None, Some(MAX_LIMIT))?;
+        if !denom_reconcile.shortfall.is_zero() {
+            violations.push(format!(
+                "balance short of {} {} for recorded and escrowed funds",
+                denom_reconcile.shortfall, denom_reconcile.denom
+            ));
+        }
+        reconcile.push(denom_reconcile);
+    }
+
+    Ok(VerifyPoolResponse {
+        pool_id,
+        invariant,
+        invariant_per_share,
+        supply,
+        reconcile,
+        violations,
+    })
+}
+
+fn query_lp_token(deps: Deps, pool_id: String) -> StdResult<LpTokenResponse> {
+    let address = POOL_TOKENS_LIST.may_load(deps.storage, &pool_id)?;
+    let mut status = LP_TOKEN_STATUS.may_load(deps.storage, &pool_id)?;
+    // tokens instantiated before their status was tracked
+    if status.is_none() && address.is_some() {
+        status = Some(LpTokenStatus::Ready);
+    }
+    Ok(LpTokenResponse { status, address })
+}
+
+fn query_treasury(deps: Deps) -> StdResult<Vec<Coin>> {
+    TREASURY
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect()
+}
+
+fn query_pending_withdrawals(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PendingWithdrawalsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let withdrawals = PENDING_WITHDRAWALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, withdrawal)| withdrawal))
+        .collect::<StdResult<Vec<PendingWithdrawal>>>()?;
+
+    Ok(PendingWithdrawalsResponse { withdrawals })
+}
+
+fn query_channels(deps: Deps) -> StdResult<ChannelsResponse> {
+    let mut pool_counts: BTreeMap<String, u64> = BTreeMap::new();
+    for item in POOLS.range(deps.storage, None, None, Order::Ascending) {
+        let (_, pool) = item?;
+        *pool_counts.entry(pool.counter_party_channel).or_default() += 1;
+    }
+
+    let channels = CHANNEL_INFO
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (_, channel) = item?;
+            Ok(ChannelResponse {
+                counterparty_chain_id: COUNTERPARTY_CHAIN_IDS.may_load(deps.storage, &channel.id)?,
+                open: !CLOSED_CHANNELS.has(deps.storage, &channel.id),
+                pool_count: pool_counts.get(&channel.id).copied().unwrap_or_default(),
+                id: channel.id,
+                counterparty_endpoint: channel.counterparty_endpoint,
+                connection_id: channel.connection_id,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(ChannelsResponse { channels })
+}
+
+fn query_channel_capabilities(
+    deps: Deps,
+    channel_id: String,
+) -> StdResult<ChannelCapabilitiesResponse> {
+    let local = local_capabilities();
+    let counterparty = COUNTERPARTY_CAPABILITIES.may_load(deps.storage, &channel_id)?;
+
+    Ok(ChannelCapabilitiesResponse {
+        negotiated: counterparty.as_ref().map(|counterparty| local.intersect(counterparty)),
+        local,
+        counterparty,
+    })
+}
+
+fn query_withdrawal_queue(
+    deps: Deps,
+    denom: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<WithdrawalQueueResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let withdrawals = WITHDRAWAL_QUEUE
+        .prefix(&denom)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, withdrawal)| withdrawal))
+        .collect::<StdResult<Vec<QueuedWithdrawal>>>()?;
+
+    Ok(WithdrawalQueueResponse { withdrawals })
+}
+
+fn query_reconcile(
+    deps: Deps,
+    env: Env,
+    pool_id: String,
+    denom: Option<String>,
+    start_after: Option<ReconcileCursor>,
+    limit: Option<u32>,
+) -> StdResult<ReconcileResponse> {
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+    let local = pool.assets_by_side(PoolSide::SOURCE);
+    let denom = match denom {
+        Some(denom) if local.iter().any(|asset| asset.balance.denom == denom) => denom,
+        Some(denom) => {
+            return Err(StdError::generic_err(format!(
+                "{} is not an asset of pool {} on this chain",
+                denom, pool_id
+            )))
+        }
+        None => pool.find_asset_by_side(PoolSide::SOURCE)?.balance.denom,
+    };
+    reconcile_denom(deps, &env, denom, start_after, limit)
+}
+
+/// Counts up to `limit` pools and withdrawals holding `denom`, continuing from `start_after`.
+fn reconcile_denom(
+    deps: Deps,
+    env: &Env,
+    denom: String,
+    start_after: Option<ReconcileCursor>,
+    limit: Option<u32>,
+) -> StdResult<ReconcileResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let mut cursor = match start_after {
+        Some(cursor) => cursor,
+        None => ReconcileCursor {
+            stage: ReconcileStage::Pools { start_after: None },
+            recorded: TREASURY.may_load(deps.storage, &denom)?.unwrap_or_default(),
+            escrow: escrowed_funds(deps.storage, &denom)?,
+        },
+    };
+
+    let mut counted = 0;
+    let done = loop {
+        let left = limit - counted;
+        match &cursor.stage {
+            ReconcileStage::Pools { start_after } => {
+                let start = start_after.as_deref().map(Bound::exclusive);
+                let pools = POOLS
+                    .range(deps.storage, start, None, Order::Ascending)
+                    .take(left)
+                    .collect::<StdResult<Vec<_>>>()?;
+                counted += pools.len();
+                let last = pools.last().map(|(pool_id, _)| pool_id.clone());
+                // pools trading the same denom share the bank balance
+                for (_, pool) in pools {
+                    let asset = match pool.find_asset_by_denom(&denom) {
+                        Ok(asset) if asset.side == PoolSide::SOURCE => asset,
+                        _ => continue,
+                    };
+                    cursor.recorded += asset.balance.amount;
+                    let mut fees =
+                        COLLECTED_FEES.may_load(deps.storage, &pool.id)?.unwrap_or_default();
+                    let protocol_fees = PROTOCOL_FEES.may_load(deps.storage, &pool.id)?;
+                    fees.extend(protocol_fees.unwrap_or_default());
+                    cursor.recorded += fees
+                        .iter()
+                        .filter(|fee| fee.denom == denom)
+                        .map(|fee| fee.amount)
+                        .sum::<Uint128>();
+                }
+                cursor.stage = match counted < limit {
+                    true => ReconcileStage::PendingWithdrawals { start_after: None },
+                    false => ReconcileStage::Pools { start_after: last.or(start_after.clone()) },
+                };
+            }
+            ReconcileStage::PendingWithdrawals { start_after } => {
+                let start = start_after.map(Bound::exclusive);
+                let withdrawals = PENDING_WITHDRAWALS
+                    .range(deps.storage, start, None, Order::Ascending)
+                    .take(left)
+                    .collect::<StdResult<Vec<_>>>()?;
+                counted += withdrawals.len();
+                let last = withdrawals.last().map(|(id, _)| *id);
+                for (_, withdrawal) in withdrawals {
+                    if withdrawal.denom == denom {
+                        cursor.recorded += withdrawal.amount;
+                    }
+                }
+                cursor.stage = match counted < limit {
+                    true => ReconcileStage::QueuedWithdrawals { start_after: None },
+                    false => ReconcileStage::PendingWithdrawals {
+                        start_after: last.or(*start_after),
+                    },
+                };
+            }
+            // withdrawals waiting for liquidity already left the pool
+            ReconcileStage::QueuedWithdrawals { start_after } => {
+                let start = start_after.map(Bound::exclusive);
+                let withdrawals = WITHDRAWAL_QUEUE
+                    .prefix(&denom)
+                    .range(deps.storage, start, None, Order::Ascending)
+                    .take(left)
+                    .collect::<StdResult<Vec<_>>>()?;
+                counted += withdrawals.len();
+                let last = withdrawals.last().map(|(id, _)| *id);
+                for (_, withdrawal) in withdrawals {
+                    cursor.escrow += withdrawal.token.amount;
+                }
+                if counted < limit {
+                    break true;
+                }
+                cursor.stage = ReconcileStage::QueuedWithdrawals {
+                    start_after: last.or(*start_after),
+                };
+            }
+        }
+        if counted >= limit {
+            break false;
+        }
+    };
+
+    let balance = deps
+        .querier
+        .query_balance(&env.contract.address, &denom)?
+        .amount;
+    let expected = cursor.recorded + cursor.escrow;
+    let (surplus, shortfall) = match done {
+        true => (balance.saturating_sub(expected), expected.saturating_sub(balance)),
+        false => (Uint128::zero(), Uint128::zero()),
+    };
+    Ok(ReconcileResponse {
+        denom,
+        recorded: cursor.recorded,
+        escrow: cursor.escrow,
+        balance,
+        surplus,
+        shortfall,
+        next: (!done).then_some(cursor),
+    })
+}
+
+fn query_packet_journal(
+    deps: Deps,
+    pool_id: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PacketJournalResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let entries = PACKET_JOURNAL
+        .prefix(&pool_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, entry)| entry))
+        .collect::<StdResult<Vec<JournalEntry>>>()?;
+
+    Ok(PacketJournalResponse { entries })
+}
+
+fn query_lp_checkpoints(
+    deps: Deps,
+    pool_id: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<LpCheckpointsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let checkpoints = LP_CHECKPOINTS
+        .prefix(&pool_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, checkpoint)| checkpoint))
+        .collect::<StdResult<Vec<LpCheckpoint>>>()?;
+
+    Ok(LpCheckpointsResponse { checkpoints })
+}
+
+fn query_received_packets(
+    deps: Deps,
+    channel_id: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ReceivedPacketsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let packets = RECEIVED_PACKETS
+        .prefix(&channel_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, packet)| packet))
+        .collect::<StdResult<Vec<ReceivedPacket>>>()?;
+
+    Ok(ReceivedPacketsResponse { packets })
+}
+
+fn query_pool_supply(deps: Deps, pool_id: String) -> StdResult<PoolSupplyResponse> {
+    let interchain_pool = POOLS
+        .may_load(deps.storage, &pool_id)?
+        .ok_or_else(|| StdError::generic_err("Pool not found".to_string()))?;
+
+    let lp_token = POOL_TOKENS_LIST.may_load(deps.storage, &pool_id)?;
+    let lp_token_supply = match &lp_token {
+        Some(denom) if tokenfactory::split_denom(denom).is_some() => {
+            deps.querier.query_supply(denom)?.amount
+        }
+        Some(lp_token) => {
+            let info: TokenInfoResponse = deps
+                .querier
+                .query_wasm_smart(lp_token, &Cw20QueryMsg::TokenInfo {})?;
+            info.total_supply
+        }
+        None => Uint128::zero(),
+    };
+
+    Ok(PoolSupplyResponse {
+        consistent: lp_token_supply <= interchain_pool.supply.amount,
+        supply: interchain_pool.supply,
+        lp_token,
+        lp_token_supply,
+    })
+}
+
+fn query_pool_list(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<PoolListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|denom| Bound::ExclusiveRaw(denom.into_bytes()));
+    let list = POOL_TOKENS_LIST
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item: Result<(String, String), cosmwasm_std::StdError>| item.unwrap().1)
+        .collect::<Vec<String>>();
+
+    Ok(PoolListResponse { pools: list })
+}
+
+fn query_shares_for_deposit(
+    deps: Deps,
+    pool_id: String,
+    deposits: Vec<Coin>,
+) -> StdResult<SharesForDepositResponse> {
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+    let amm = InterchainMarketMaker::new(&pool_with_fees(deps.storage, &pool)?, pool.swap_fee);
+    let pool_tokens = amm.deposit_multi_asset(&deposits)?;
+    let shares = pool_tokens.iter().map(|token| token.amount).sum();
+
+    Ok(SharesForDepositResponse { pool_tokens, shares })
+}
+
+fn query_assets_for_shares(deps: Deps, pool_id: String, shares: Uint128) -> StdResult<Vec<Coin>> {
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+    if shares > pool.supply.amount {
+        return Err(StdError::generic_err("Shares exceed the pool supply"));
+    }
+    let amm = InterchainMarketMaker::new(&pool, pool.swap_fee);
+    amm.multi_asset_withdraw(Coin { denom: pool_id, amount: shares })
+}
+
+fn query_left_swap(
+    deps: Deps,
+    pool_id: String,
+    token_in: Coin,
+    token_out: Coin,
+) -> StdResult<Coin> {
+    // Get liquidity pool
+    // load pool throw error if not found
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &pool_id)?;
+    let interchain_pool;
+    if let Some(pool) = interchain_pool_temp {
+        interchain_pool = pool
+    } else {
+        return Err(StdError::generic_err(format!(
+            "Pool doesn't exist {}",
+            pool_id
+        )));
+    }
+
+    // Check the pool status
+    if interchain_pool.status != PoolStatus::Active {
+        return Err(StdError::generic_err(
+            "Pool not ready for swap!".to_string(),
+        ));
+    }
+
+    // Create the interchain market maker
+    let amm = InterchainMarketMaker {
+        pool_id: interchain_pool.clone().id,
+        pool: interchain_pool.clone(),
+        fee_rate: interchain_pool.swap_fee,
+    };
+    let result = amm.compute_swap(token_in, &token_out.denom)?;
+    Ok(result)
+}
+
+fn query_firm_quote(
+    deps: Deps,
+    env: Env,
+    pool_id: String,
+    swap_type: SwapMsgType,
+    token_in: Coin,
+    token_out: Coin,
+) -> StdResult<FirmQuoteResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let pool = POOLS.load(deps.storage, &pool_id)?;
+    if pool.status != PoolStatus::Active {
+        return Err(StdError::generic_err("Pool not ready for swap!".to_string()));
+    }
+
+    Ok(FirmQuoteResponse {
+        quoted_out: swap_quote(&pool, &swap_type, token_in, token_out)?,
+        quote_height: env.block.height,
+        quote_time: env.block.time,
+        valid_until_height: env.block.height.saturating_add(config.quote_validity_blocks),
+    })
+}
+
+fn query_right_swap(
+    deps: Deps,
+    pool_id: String,
+    token_in: Coin,
+    token_out: Coin,
+) -> StdResult<Coin> {
+    // Get liquidity pool
+    // load pool throw error if not found
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &pool_id)?;
+    let interchain_pool;
+    if let Some(pool) = interchain_pool_temp {
+        interchain_pool = pool
+    } else {
+        return Err(StdError::generic_err(format!(
+            "Pool doesn't exist {}",
+            pool_id
+        )));
+    }
+
+    // Check the pool status
+    if interchain_pool.status != PoolStatus::Active {
+        return Err(StdError::generic_err(
+            "Pool not ready for swap!".to_string(),
+        ));
+    }
+
+    // Create the interchain market maker
+    let amm = InterchainMarketMaker {
+        pool_id: interchain_pool.clone().id,
+        pool: interchain_pool.clone(),
+        fee_rate: interchain_pool.swap_fee_for(&SwapMsgType::RIGHT),
+    };
+    let result = amm.compute_offer_amount(token_in, token_out)?;
+    Ok(result)
+}
+
+fn query_active_orders(
+    deps: Deps,
+    source_maker: String,
+    pool_id: Option<String>,
+    destination_taker: Option<String>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<OrderListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    // keys are `maker-pool-taker`, so all orders of a maker (and pool) share a prefix
+    let prefix = match &pool_id {
+        Some(pool_id) => format!("{}-{}-", source_maker, pool_id),
+        None => format!("{}-", source_maker),
+    };
+    // '.' directly follows '-', closing the prefix range
+    let end = format!("{}.", &prefix[..prefix.len() - 1]);
+    let start = match start_after {
+        Some(start_after) => {
+            Bound::ExclusiveRaw(format!("{}-{}", source_maker, start_after).into_bytes())
+        }
+        None => Bound::InclusiveRaw(prefix.into_bytes()),
+    };
+
+    let orders = ACTIVE_ORDERS
+        .range(
+            deps.storage,
+            Some(start),
+            Some(Bound::ExclusiveRaw(end.into_bytes())),
+            Order::Ascending,
+        )
+        .filter(|item| match (item, &destination_taker) {
+            (Ok((_, order)), Some(taker)) => &order.destination_taker == taker,
+            _ => true,
+        })
+        .take(limit)
+        .map(|item| item.map(|(_, order)| order))
+        .collect::<StdResult<Vec<MultiAssetDepositOrder>>>()?;
+
+    Ok(OrderListResponse { orders })
+}
+
+fn query_rate(deps: Deps, pool_id: String, amount: Uint128) -> StdResult<Vec<Coin>> {
+    // Get liquidity pool
+    // load pool throw error if not found
+    let interchain_pool_temp = POOLS.may_load(deps.storage, &pool_id)?;
+    let interchain_pool;
+    if let Some(pool) = interchain_pool_temp {
+        interchain_pool = pool
+    } else {
+        return Err(StdError::generic_err(format!(
+            "Pool doesn't exist {}",
+            pool_id
+        )));
+    }
+
+    // Create the interchain market maker
+    let amm = InterchainMarketMaker {
+        pool_id: interchain_pool.clone().id,
+        pool: interchain_pool.clone(),
+        fee_rate: interchain_pool.swap_fee,
+    };
+
+    amm.multi_asset_withdraw(Coin {
+        amount,
+        denom: pool_id,
+    })
+}
+
+fn query_rate_detail(deps: Deps, pool_id: String, amount: Uint128) -> StdResult<RateResponse> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("LP amount must be positive"));
+    }
+
+    let outputs = query_rate(deps, pool_id.clone(), amount)?
+        .into_iter()
+        .map(|coin| {
+            let share_price = Decimal::checked_from_ratio(coin.amount, amount)
+                .map_err(|err| StdError::generic_err(err.to_string()))?;
+            Ok(RateOutput {
+                denom: coin.denom,
+                amount: coin.amount,
+                share_price,
+            })
+        })
+        .collect::<StdResult<Vec<RateOutput>>>()?;
+
+    Ok(RateResponse {
+        pool_id,
+        lp_amount: amount,
+        outputs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::{FeeApplication, PoolAsset, PoolType};
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{coin, OwnedDeps};
+
+    // contract instantiated by "admin" with the default config
+    fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            token_code_id: 1,
+            router: "".to_string(),
+            default_timeout_seconds: None,
+            lp_token_kind: None,
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), instantiate_msg).unwrap();
+        deps
+    }
+
+    // pool of 1000uatom on this chain and 1000uosmo on the counterparty, with 1000 shares
+    fn test_pool(id: &str, status: PoolStatus) -> InterchainLiquidityPool {
+        InterchainLiquidityPool {
+            id: id.to_string(),
+            source_creator: "maker".to_string(),
+            destination_creator: "taker".to_string(),
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            supply: coin(1000, id),
+            status,
+            counter_party_port: "port".to_string(),
+            counter_party_channel: "channel-0".to_string(),
+            swap_fee: 30,
+            source_chain_id: "chain-a".to_string(),
+            destination_chain_id: "chain-b".to_string(),
+            pool_price: 0,
+            packet_timeout_seconds: None,
+            fee_application: FeeApplication::Source,
+            pool_type: PoolType::Weighted,
+            right_swap_fee: None,
+        }
+    }
+
+    // request making a pool of 1000 `denom` on this chain and 1000uosmo on the counterparty
+    fn make_pool_request(denom: &str) -> MsgMakePoolRequest {
+        MsgMakePoolRequest {
+            source_port: "port".to_string(),
+            source_channel: "channel-0".to_string(),
+            source_chain_id: "chain-a".to_string(),
+            destination_chain_id: "chain-b".to_string(),
+            counterparty_channel: "channel-1".to_string(),
+            creator: "creator".to_string(),
+            counterparty_creator: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            liquidity: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, denom),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            swap_fee: 30,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            packet_timeout_seconds: None,
+            refund_to: None,
+            fee_application: FeeApplication::Source,
+            pool_type: PoolType::Weighted,
+            right_swap_fee: None,
+            lp_token_marketing: None,
+            bootstrap_targets: None,
+        }
+    }
+
+    // makes the pool as "creator", paying for the first asset of its liquidity
+    fn execute_make_pool(
+        deps: DepsMut,
+        msg: MsgMakePoolRequest,
+    ) -> Result<Response, ContractError> {
+        let info = mock_info("creator", &[msg.liquidity[0].balance.clone()]);
+        execute(deps, mock_env(), info, ExecuteMsg::MakePool(msg))
+    }
+
+    #[test]
+    fn test_instantiate() {
+        let mut deps = mock_dependencies();
+
+        // Instantiate an empty contract
+        let instantiate_msg = InstantiateMsg {
+            token_code_id: 1,
+            router: "".to_string(),
+            default_timeout_seconds: None,
+            lp_token_kind: None,
+        };
+        let info = mock_info("anyone", &[]);
+        let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+        assert_eq!(0, res.messages.len());
+    }
+
+    #[test]
+    fn test_bootstrap_pool_single_deposit() {
+        use crate::interchainswap_handler::on_packet_failure;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        // pool made on the counterparty with only its asset funded
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            source_creator: "taker".to_string(),
+            destination_creator: "maker".to_string(),
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(0, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            supply: coin(500, pool_id.clone()),
+            ..test_pool(&pool_id, PoolStatus::Bootstrap)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        // the maker prices its 1000uosmo at 2000uatom
+        let targets = vec![coin(2000, "uatom")];
+        BOOTSTRAP_TARGETS.save(deps.as_mut().storage, &pool_id, &targets).unwrap();
+
+        let deposit = |token: Coin| MsgSingleAssetDepositRequest {
+            pool_id: pool_id.clone(),
+            sender: "lp_sender".to_string(),
+            token,
+            lp_allocation: LPAllocation::TakerChain,
+            lp_taker: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            recipient: None,
+            refund_to: None,
+        };
+
+        // the funded side can't be deposited while bootstrapping
+        let funded = coin(10, "uosmo");
+        let err = single_asset_deposit(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("lp", std::slice::from_ref(&funded)),
+            deposit(funded),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::OnlyBootstrapDeposit);
+
+        // a dust deposit can't open the pool and take the shares of the whole side
+        let dust = coin(10, "uatom");
+        let err = single_asset_deposit(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("lp", std::slice::from_ref(&dust)),
+            deposit(dust),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("below the bootstrap target 2000uatom"));
+
+        // the unfunded side gets shares by weight and its target and sends a packet
+        let unfunded = coin(2000, "uatom");
+        let res = single_asset_deposit(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("lp", std::slice::from_ref(&unfunded)),
+            deposit(unfunded.clone()),
+        )
+        .unwrap();
+        assert_eq!(1, res.messages.len());
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            _ => panic!("expected ibc packet"),
+        };
+        let packet_data: InterchainSwapPacketData = from_binary(&data).unwrap();
+        let state_change: StateChange =
+            from_binary(packet_data.state_change.as_ref().unwrap()).unwrap();
+        assert_eq!(state_change.shares, Some(Uint128::new(500)));
+
+        // a second deposit waits for the result of the first
+        let err = single_asset_deposit(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("lp", std::slice::from_ref(&unfunded)),
+            deposit(unfunded.clone()),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::BootstrapDepositInFlight { pool_id: pool_id.clone() });
+
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        on_packet_failure(deps.as_mut(), packet, "timeout".to_string()).unwrap();
+        single_asset_deposit(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("lp", std::slice::from_ref(&unfunded)),
+            deposit(unfunded),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_deposit_receipt_follows_packet() {
+        use crate::interchainswap_handler::on_packet_failure;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let token = coin(100, "uatom");
+        let msg = MsgSingleAssetDepositRequest {
+            pool_id: pool_id.clone(),
+            sender: "lp_sender".to_string(),
+            token: token.clone(),
+            lp_allocation: LPAllocation::MakerChain,
+            lp_taker: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            recipient: None,
+            refund_to: None,
+        };
+        let res =
+            single_asset_deposit(deps.as_mut(), mock_env(), mock_info("lp", &[token]), msg)
+                .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            _ => panic!("expected ibc packet"),
+        };
+
+        let receipts = |deps: Deps| -> Vec<DepositReceipt> {
+            query_deposit_receipts(deps, "lp_sender".to_string(), None, None)
+                .unwrap()
+                .receipts
+        };
+        let pending = receipts(deps.as_ref());
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, 1);
+        assert_eq!(pending[0].status, DepositStatus::Pending);
+
+        let packet_data: InterchainSwapPacketData = from_binary(&data).unwrap();
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        assert_eq!(packet.data, data);
+        on_packet_failure(deps.as_mut(), packet, "timeout".to_string()).unwrap();
+        assert_eq!(receipts(deps.as_ref())[0].status, DepositStatus::Refunded);
+        assert!(PENDING_DEPOSIT_RECEIPTS.is_empty(&deps.storage));
+    }
+
+    #[test]
+    fn test_simulate_does_not_change_state() {
+        let deps = setup();
+
+        let simulate = |sender: &str| QueryMsg::Simulate {
+            msg: Box::new(ExecuteMsg::SetRouter { address: "router".to_string() }),
+            sender: sender.to_string(),
+            funds: vec![],
+        };
+
+        let res: SimulateResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), simulate("anyone")).unwrap()).unwrap();
+        assert_eq!(res.error, Some("Generic error: not allowed".to_string()));
+
+        let res: SimulateResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), simulate("admin")).unwrap()).unwrap();
+        assert_eq!(res, SimulateResponse { packet: None, error: None });
+        assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().router, "");
+    }
+
+    #[test]
+    fn test_batch_swap_checks_funds_and_pools() {
+        let mut deps = mock_dependencies();
+        let leg = |pool_id: &str, amount: u128| MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "trader".to_string(),
+            pool_id: pool_id.to_string(),
+            token_in: coin(amount, "uatom"),
+            token_out: coin(1, "uosmo"),
+            slippage: 100,
+            recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+
+        let err = batch_swap(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("trader", &[coin(20, "uatom")]),
+            vec![leg("pool1", 10), leg("pool1", 10)],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Duplicate pool"));
+
+        // funds have to match the sum of the legs
+        let err = batch_swap(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("trader", &[coin(10, "uatom")]),
+            vec![leg("pool1", 10), leg("pool2", 10)],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Funds mismatch"));
+    }
+
+    #[test]
+    fn test_batch_swap_settles_each_leg() {
+        use crate::codec::decode_payload;
+        use crate::ibc::{ibc_packet_ack, ibc_packet_timeout};
+        use crate::interchainswap_handler::{ack_fail, ack_success, escrowed_funds};
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use cosmwasm_std::{
+            Addr, BankMsg, CosmosMsg, IbcAcknowledgement, IbcPacketAckMsg, IbcPacketTimeoutMsg,
+        };
+
+        let mut deps = setup();
+        let pool = |id: &str| InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1_000_000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1_000_000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            ..test_pool(id, PoolStatus::Active)
+        };
+        for id in ["pool1", "pool2", "pool3"] {
+            POOLS.save(deps.as_mut().storage, id, &pool(id)).unwrap();
+        }
+        let leg = |pool_id: &str, amount: u128| MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "trader".to_string(),
+            pool_id: pool_id.to_string(),
+            token_in: coin(amount, "uatom"),
+            token_out: coin(1, "uosmo"),
+            slippage: 100,
+            recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+
+        let msg = ExecuteMsg::BatchSwap {
+            legs: vec![leg("pool1", 1000), leg("pool2", 2000), leg("pool3", 3000)],
+        };
+        let res =
+            execute(deps.as_mut(), mock_env(), mock_info("trader", &[coin(6000, "uatom")]), msg)
+                .unwrap();
+
+        // one packet per leg, each quoted against its own pool
+        let mut packets = vec![];
+        for (sub, (pool_id, amount)) in
+            res.messages.iter().zip([("pool1", 1000), ("pool2", 2000), ("pool3", 3000)])
+        {
+            let data = match &sub.msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket { channel_id, data, .. }) => {
+                    assert_eq!(channel_id, "channel-0");
+                    data.clone()
+                }
+                msg => panic!("unexpected message {:?}", msg),
+            };
+            let packet_data = decode_packet(&data).unwrap();
+            let swap: MsgSwapRequest = decode_payload(&packet_data.data).unwrap();
+            assert_eq!(swap.pool_id, pool_id);
+            assert_eq!(swap.token_in, coin(amount, "uatom"));
+            let held = SWAP_ESCROW.load(deps.as_ref().storage, (pool_id, "uatom")).unwrap();
+            assert_eq!(held, Uint128::new(amount));
+
+            let mut packet = mock_ibc_packet_recv("channel-0", &()).unwrap().packet;
+            packet.data = data;
+            packets.push(packet);
+        }
+        assert_eq!(res.messages.len(), 3);
+        assert_eq!(escrowed_funds(deps.as_ref().storage, "uatom").unwrap(), Uint128::new(6000));
+
+        // the first leg settles into its pool
+        let ack = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success()),
+            packets[0].clone(),
+            Addr::unchecked("relayer"),
+        );
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), ack).unwrap();
+        assert!(res.messages.is_empty());
+        let state_change: StateChange =
+            decode_payload(&decode_packet(&packets[0].data).unwrap().state_change.unwrap())
+                .unwrap();
+        let out = state_change.out_tokens.unwrap()[0].amount;
+        let settled = POOLS.load(deps.as_ref().storage, "pool1").unwrap();
+        assert_eq!(settled.find_asset_by_denom("uatom").unwrap().balance.amount.u128(), 1_000_997);
+        assert_eq!(
+            settled.find_asset_by_denom("uosmo").unwrap().balance.amount,
+            Uint128::new(1_000_000) - out
+        );
+
+        // the second fails on the counterparty and the third times out, both are refunded
+        // without touching their pools
+        let ack = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_fail("slippage".to_string())),
+            packets[1].clone(),
+            Addr::unchecked("relayer"),
+        );
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), ack).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "trader".to_string(),
+                amount: vec![coin(2000, "uatom")],
+            })
+        );
+        let timeout = IbcPacketTimeoutMsg::new(packets[2].clone(), Addr::unchecked("relayer"));
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), timeout).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "trader".to_string(),
+                amount: vec![coin(3000, "uatom")],
+            })
+        );
+        for id in ["pool2", "pool3"] {
+            assert_eq!(POOLS.load(deps.as_ref().storage, id).unwrap(), pool(id));
+        }
+
+        for id in ["pool1", "pool2", "pool3"] {
+            let held = SWAP_ESCROW.may_load(deps.as_ref().storage, (id, "uatom")).unwrap();
+            assert_eq!(held.unwrap_or_default(), Uint128::zero());
+        }
+        assert_eq!(escrowed_funds(deps.as_ref().storage, "uatom").unwrap(), Uint128::zero());
+    }
+
+    #[test]
+    fn test_make_pool_timeout_bounds() {
+        let mut deps = setup();
+        let request = |timeout: u64| MsgMakePoolRequest {
+            packet_timeout_seconds: Some(timeout),
+            ..make_pool_request("uatom")
+        };
+
+        for timeout in [59, 86401] {
+            let err = execute_make_pool(deps.as_mut(), request(timeout)).unwrap_err();
+            assert_eq!(err, ContractError::InvalidTimeout { timeout, min: 60, max: 86400 });
+        }
+        assert!(POOLS.is_empty(&deps.storage));
+
+        execute_make_pool(deps.as_mut(), request(1800)).unwrap();
+        let (_, pool) =
+            POOLS.range(&deps.storage, None, None, Order::Ascending).next().unwrap().unwrap();
+        assert_eq!(pool.packet_timeout_seconds, Some(1800));
+    }
+
+    #[test]
+    fn test_migrate_widens_counter() {
+        use cosmwasm_std::Storage;
+
+        let mut deps = mock_dependencies();
+        cw2::set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+        deps.storage.set(
+            b"config",
+            br#"{"counter":7,"token_code_id":1,"admin":"admin","router":"router"}"#,
+        );
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        let mut config = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(config.counter, 7);
+        assert_eq!(config.increment_counter().unwrap(), 8);
+
+        config.counter = u128::MAX;
+        assert!(config.increment_counter().is_err());
+    }
+
+    #[test]
+    fn test_order_ids_are_scoped() {
+        let env = mock_env();
+        let id = get_order_id(&env, "pool1", "maker", 1);
+        assert!(id.starts_with("multi_deposit_order"));
+        assert_eq!(id, get_order_id(&env, "pool1", "maker", 1));
+
+        // the same maker and counter elsewhere get another id
+        let mut other_chain = mock_env();
+        other_chain.block.chain_id = "chain-b".to_string();
+        let mut other_contract = mock_env();
+        other_contract.contract.address = Addr::unchecked("contract2");
+        let others = [
+            get_order_id(&other_chain, "pool1", "maker", 1),
+            get_order_id(&other_contract, "pool1", "maker", 1),
+            get_order_id(&env, "pool2", "maker", 1),
+        ];
+        for other in others {
+            assert_ne!(other, id);
+        }
+    }
+
+    #[test]
+    fn test_query_pool_supply() {
+        use cosmwasm_std::{ContractResult, SystemResult, WasmQuery};
+
+        let mut deps = setup();
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        POOL_TOKENS_LIST.save(deps.as_mut().storage, &pool_id, &"lp_token".to_string()).unwrap();
+
+        let token_info = |total_supply: u128| {
+            move |_: &WasmQuery| {
+                let info = TokenInfoResponse {
+                    name: "lp".to_string(),
+                    symbol: "LP".to_string(),
+                    decimals: 6,
+                    total_supply: Uint128::new(total_supply),
+                };
+                SystemResult::Ok(ContractResult::Ok(to_binary(&info).unwrap()))
+            }
+        };
+        let supply = |deps: Deps| -> PoolSupplyResponse {
+            let msg = QueryMsg::PoolSupply { pool_id: pool_id.clone() };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+
+        deps.querier.update_wasm(token_info(1000));
+        let res = supply(deps.as_ref());
+        assert_eq!(res.supply, coin(1000, &pool_id));
+        assert_eq!(res.lp_token, Some("lp_token".to_string()));
+        assert_eq!(res.lp_token_supply, Uint128::new(1000));
+        assert!(res.consistent);
+
+        // more shares in circulation than the pool accounts for
+        deps.querier.update_wasm(token_info(1001));
+        let res = supply(deps.as_ref());
+        assert_eq!(res.lp_token_supply, Uint128::new(1001));
+        assert!(!res.consistent);
+    }
+
+    #[test]
+    fn test_query_active_orders() {
+        let mut deps = mock_dependencies();
+        let order = |pool_id: &str, taker: &str| MultiAssetDepositOrder {
+            id: format!("{}{}", pool_id, taker),
+            pool_id: pool_id.to_string(),
+            chain_id: "chain-a".to_string(),
+            source_maker: "maker".to_string(),
+            destination_taker: taker.to_string(),
+            deposits: vec![],
+            status: OrderStatus::Pending,
+            created_at: 0,
+            recipient: Some("vault".to_string()),
+            permit_pubkey: None,
+        };
+        for (maker, pool_id, taker) in [
+            ("maker", "pool1", "taker1"),
+            ("maker", "pool1", "taker2"),
+            ("maker", "pool2", "taker1"),
+            ("makerb", "pool1", "taker1"),
+        ] {
+            let key = format!("{}-{}-{}", maker, pool_id, taker);
+            ACTIVE_ORDERS
+                .save(deps.as_mut().storage, key, &order(pool_id, taker))
+                .unwrap();
+        }
+
+        let query = |pool_id: Option<&str>, taker: Option<&str>, start_after: Option<&str>| {
+            query_active_orders(
+                deps.as_ref(),
+                "maker".to_string(),
+                pool_id.map(String::from),
+                taker.map(String::from),
+                start_after.map(String::from),
+                None,
+            )
+            .unwrap()
+            .orders
+            .len()
+        };
+        assert_eq!(query(None, None, None), 3);
+        assert_eq!(query(Some("pool1"), None, None), 2);
+        assert_eq!(query(None, Some("taker1"), None), 2);
+        assert_eq!(query(Some("pool1"), Some("taker2"), None), 1);
+        assert_eq!(query(None, None, Some("pool1-taker2")), 1);
+    }
+
+    #[test]
+    fn test_query_rate_detail() {
+        let mut deps = setup();
+        let pool_id = "pool1".to_string();
+        let mut pool = test_pool(&pool_id, PoolStatus::Active);
+        pool.assets[1].balance = coin(2000, "uosmo");
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let rate = |amount: u128| {
+            let msg = QueryMsg::RateDetail { pool_id: pool_id.clone(), amount: amount.into() };
+            query(deps.as_ref(), mock_env(), msg)
+        };
+        rate(0).unwrap_err();
+
+        // a tenth of the shares is worth a tenth of each asset
+        let res: RateResponse = from_binary(&rate(100).unwrap()).unwrap();
+        assert_eq!(res.pool_id, pool_id);
+        assert_eq!(res.lp_amount, Uint128::new(100));
+        assert_eq!(
+            res.outputs,
+            vec![
+                RateOutput {
+                    denom: "uatom".to_string(),
+                    amount: Uint128::new(100),
+                    share_price: Decimal::one(),
+                },
+                RateOutput {
+                    denom: "uosmo".to_string(),
+                    amount: Uint128::new(200),
+                    share_price: Decimal::from_ratio(2u128, 1u128),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_packet_is_rejected() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: "pool1".to_string(),
+            token_in: coin(10, "uatom"),
+            token_out: coin(10, "uosmo"),
+            slippage: 100,
+            recipient: "recipient".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: to_binary(&swap).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+
+        let err = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::MalformedPacket {
+                field: "StateChange".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_take_multi_deposit_mint_failure_rolls_back() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use cosmwasm_std::{ReplyOn, SubMsgResponse};
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        let order = MultiAssetDepositOrder {
+            id: "order1".to_string(),
+            pool_id: pool_id.clone(),
+            chain_id: "chain-a".to_string(),
+            source_maker: "maker".to_string(),
+            destination_taker: "taker".to_string(),
+            deposits: vec![coin(10, "uatom"), coin(10, "uosmo")],
+            status: OrderStatus::Pending,
+            created_at: 0,
+            recipient: Some("vault".to_string()),
+            permit_pubkey: None,
+        };
+        let order_key = format!("{}-{}", pool_id, order.id);
+        let active_key = format!("maker-{}-taker", pool_id);
+        let storage = deps.as_mut().storage;
+        POOLS.save(storage, &pool_id, &pool).unwrap();
+        POOL_TOKENS_LIST.save(storage, &pool_id, &"lp_token".to_string()).unwrap();
+        MULTI_ASSET_DEPOSIT_ORDERS.save(storage, order_key.clone(), &order).unwrap();
+        ACTIVE_ORDERS.save(storage, active_key.clone(), &order).unwrap();
+
+        let take = MsgTakeMultiAssetDepositRequest {
+            sender: "taker".to_string(),
+            pool_id: pool_id.clone(),
+            order_id: order.id.clone(),
+            lp_allocation: LPAllocation::MakerChain,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            recipient: None,
+            permit: None,
+            refund_to: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::TakeMultiDeposit,
+            data: to_binary(&take).unwrap(),
+            state_change: Some(
+                to_binary(&StateChange {
+                    in_tokens: None,
+                    out_tokens: None,
+                    pool_tokens: Some(vec![coin(20, pool_id.clone())]),
+                    pool_id: None,
+                    multi_deposit_order_id: None,
+                    source_chain_id: None,
+                    shares: Some(Uint128::new(20)),
+                })
+                .unwrap(),
+            ),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let res = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+        assert_eq!(res.messages[0].id, TAKE_MULTI_DEPOSIT_MINT_REPLY_ID);
+        assert_eq!(res.messages[0].reply_on, ReplyOn::Always);
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => match from_binary(msg).unwrap() {
+                Cw20ExecuteMsg::Mint { recipient, .. } => assert_eq!(recipient, "vault"),
+                _ => panic!("expected mint"),
+            },
+            _ => panic!("expected wasm execute"),
+        }
+        assert!(!ACTIVE_ORDERS.has(&deps.storage, active_key.clone()));
+
+        // a successful mint keeps the deposit
+        let reply_msg = |result| Reply { id: TAKE_MULTI_DEPOSIT_MINT_REPLY_ID, result };
+        let ok = SubMsgResult::Ok(SubMsgResponse { events: vec![], data: None });
+        reply(deps.as_mut(), mock_env(), reply_msg(ok)).unwrap();
+        let supply = POOLS.load(&deps.storage, &pool_id).unwrap().supply;
+        assert_eq!(supply, coin(1020, pool_id.clone()));
+
+        // a failed mint restores the order and pool and fails the ack
+        MULTI_ASSET_DEPOSIT_ORDERS.save(deps.as_mut().storage, order_key.clone(), &order).unwrap();
+        ACTIVE_ORDERS.save(deps.as_mut().storage, active_key.clone(), &order).unwrap();
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+        let err = SubMsgResult::Err("mint failed".to_string());
+        let res = reply(deps.as_mut(), mock_env(), reply_msg(err)).unwrap();
+        let failure = ibcswap_packets::FailureAck {
+            error: "mint failed".to_string(),
+            message_type: InterchainMessageType::TakeMultiDeposit,
+            pool_id: Some(pool_id.clone()),
+            order_id: Some(order.id.clone()),
+        };
+        assert_eq!(res.data, Some(ack_fail(failure.encode().unwrap())));
+        assert_eq!(POOLS.load(&deps.storage, &pool_id).unwrap(), pool);
+        assert_eq!(MULTI_ASSET_DEPOSIT_ORDERS.load(&deps.storage, order_key).unwrap(), order);
+        assert_eq!(ACTIVE_ORDERS.load(&deps.storage, active_key).unwrap(), order);
+    }
+
+    #[test]
+    fn test_make_pool_swap_fee_bounds() {
+        let mut deps = setup();
+        let bounds = ExecuteMsg::SetSwapFeeBounds { min_bps: 10, max_bps: 100 };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), bounds).unwrap();
+        let request = |swap_fee: u32, right_swap_fee: Option<u32>| MsgMakePoolRequest {
+            swap_fee,
+            right_swap_fee,
+            ..make_pool_request("uatom")
+        };
+
+        for fee in [9, 101] {
+            let err = execute_make_pool(deps.as_mut(), request(fee, None)).unwrap_err();
+            assert_eq!(err, ContractError::InvalidSwapFee { fee, min: 10, max: 100 });
+        }
+        // the fee of right swaps is bounded as well
+        let err = execute_make_pool(deps.as_mut(), request(30, Some(101))).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSwapFee { fee: 101, min: 10, max: 100 });
+        assert!(POOLS.is_empty(&deps.storage));
+
+        // the bounds are inclusive
+        execute_make_pool(deps.as_mut(), request(100, Some(10))).unwrap();
+        assert!(!POOLS.is_empty(&deps.storage));
+    }
+
+    #[test]
+    fn test_single_sided_deposit() {
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let token_in = coin(100, "uatom");
+        let deposit = |min_shares: u128| ExecuteMsg::SingleSidedDeposit {
+            pool_id: pool_id.clone(),
+            token_in: token_in.clone(),
+            min_shares: Uint128::new(min_shares),
+        };
+        let info = mock_info("depositor", std::slice::from_ref(&token_in));
+
+        let err =
+            execute(deps.as_mut(), mock_env(), info.clone(), deposit(1_000_000)).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSlippage);
+
+        // still accepted under its old name
+        let msg: ExecuteMsg = serde_json::from_value(serde_json::json!({
+            "ZapIn": { "pool_id": pool_id, "token_in": token_in, "min_shares": "1" }
+        }))
+        .unwrap();
+        assert_eq!(msg, deposit(1));
+
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => {
+                let packet: InterchainSwapPacketData = from_binary(data).unwrap();
+                let msg: MsgSingleAssetDepositRequest = from_binary(&packet.data).unwrap();
+                assert_eq!(msg.sender, "depositor");
+                assert_eq!(msg.lp_allocation, LPAllocation::TakerChain);
+                assert_eq!(msg.token, token_in);
+            }
+            _ => panic!("expected ibc packet"),
+        }
+    }
+
+    #[test]
+    fn test_zap_out() {
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        POOL_TOKENS_LIST.save(deps.as_mut().storage, &pool_id, &"lp_token".to_string()).unwrap();
+
+        let zap = |denom_out: &str, min_out: u128| ExecuteMsg::ZapOut {
+            pool_id: pool_id.clone(),
+            lp_amount: Uint128::new(100),
+            denom_out: denom_out.to_string(),
+            min_out: Uint128::new(min_out),
+        };
+        let info = mock_info("zapper", &[]);
+
+        // the counterparty asset can't be received on this chain
+        execute(deps.as_mut(), mock_env(), info.clone(), zap("uosmo", 0)).unwrap_err();
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), zap("uatom", 1000)).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSlippage);
+
+        let res = execute(deps.as_mut(), mock_env(), info, zap("uatom", 150)).unwrap();
+        match &res.messages[1].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => {
+                let packet: InterchainSwapPacketData = from_binary(data).unwrap();
+                let state_change = packet.decode_valid_state_change().unwrap();
+                let out = state_change.out_tokens.unwrap();
+                assert_eq!(out.len(), 1);
+                assert_eq!(out[0].denom, "uatom");
+                // more than the plain share of 100 but less than without price impact
+                assert!(out[0].amount > Uint128::new(150) && out[0].amount < Uint128::new(200));
+            }
+            _ => panic!("expected ibc packet"),
+        }
+    }
+
+    #[test]
+    fn test_deposit_buys_into_collected_fees() {
+        use crate::interchainswap_handler::on_received_multi_withdraw;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+        let pool_id = "pool1".to_string();
+        let mut pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let fees = vec![coin(100, "uatom"), coin(100, "uosmo")];
+        COLLECTED_FEES.save(deps.as_mut().storage, &pool_id, &fees).unwrap();
+
+        // the deposit is priced on the balances and the fees collected before it
+        let deposits = vec![coin(100, "uatom"), coin(100, "uosmo")];
+        let msg = QueryMsg::SharesForDeposit {
+            pool_id: pool_id.clone(),
+            deposits: deposits.clone(),
+        };
+        let res: SharesForDepositResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(res.shares < Uint128::new(100));
+        for deposit in deposits {
+            pool.add_asset(deposit).unwrap();
+        }
+        pool.add_supply(coin(res.shares.u128(), pool_id.clone())).unwrap();
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        // withdrawing right away returns no more than was deposited
+        let msg = QueryMsg::AssetsForShares {
+            pool_id: pool_id.clone(),
+            shares: res.shares,
+        };
+        let assets: Vec<Coin> =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        let withdraw = MsgMultiAssetWithdrawRequest {
+            pool_id: pool_id.clone(),
+            receiver: "depositor".to_string(),
+            counterparty_receiver: "depositor".to_string(),
+            pool_token: coin(res.shares.u128(), pool_id.clone()),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(assets.clone()),
+            pool_tokens: Some(vec![coin(res.shares.u128(), pool_id.clone())]),
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &withdraw).unwrap().packet;
+        on_received_multi_withdraw(deps.as_mut(), mock_env(), &packet, withdraw, state_change)
+            .unwrap();
+        let left = COLLECTED_FEES.load(&deps.storage, &pool_id).unwrap();
+        for ((asset, fee), left) in assets.iter().zip(fees).zip(left) {
+            assert!(asset.amount + fee.amount - left.amount <= Uint128::new(100));
+        }
+    }
+
+    #[test]
+    fn test_zap_out_fee_share() {
+        use crate::interchainswap_handler::{on_packet_success, on_received_multi_withdraw};
+        use cosmwasm_std::testing::{mock_ibc_packet_recv, MOCK_CONTRACT_ADDR};
+        use cosmwasm_std::BankMsg;
+
+        let pool_id = "pool1".to_string();
+        let fees = vec![coin(10, "uatom"), coin(10, "uosmo")];
+        let zap_out = MsgMultiAssetWithdrawRequest {
+            pool_id: pool_id.clone(),
+            receiver: "zapper".to_string(),
+            counterparty_receiver: String::new(),
+            pool_token: coin(100, pool_id.clone()),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![coin(180, "uosmo")]),
+            pool_tokens: Some(vec![coin(100, pool_id.clone())]),
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let collected = |deps: &OwnedDeps<_, _, _>| {
+            COLLECTED_FEES.load(deps.as_ref().storage, &pool_id).unwrap()
+        };
+
+        // the counterparty of the zapper pays nothing and keeps the fees in its own denom
+        let mut deps = setup();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        COLLECTED_FEES.save(deps.as_mut().storage, &pool_id, &fees).unwrap();
+        let packet = mock_ibc_packet_recv("channel-0", &zap_out).unwrap().packet;
+        let res = on_received_multi_withdraw(
+            deps.as_mut(),
+            mock_env(),
+            &packet,
+            zap_out.clone(),
+            state_change.clone(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(collected(&deps), vec![coin(10, "uatom"), coin(9, "uosmo")]);
+
+        // the zapper's chain pays the fee share in its own denom and books the same change
+        let mut deps = setup();
+        let pool = InterchainLiquidityPool {
+            assets: pool
+                .assets
+                .iter()
+                .map(|asset| PoolAsset {
+                    side: match asset.side {
+                        PoolSide::SOURCE => PoolSide::DESTINATION,
+                        PoolSide::DESTINATION => PoolSide::SOURCE,
+                    },
+                    ..asset.clone()
+                })
+                .collect(),
+            ..pool
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        POOL_TOKENS_LIST.save(deps.as_mut().storage, &pool_id, &"lp_token".to_string()).unwrap();
+        COLLECTED_FEES.save(deps.as_mut().storage, &pool_id, &fees).unwrap();
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![coin(1010, "uosmo")]);
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::MultiWithdraw,
+            data: to_binary(&zap_out).unwrap(),
+            state_change: Some(to_binary(&state_change).unwrap()),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let res = on_packet_success(deps.as_mut(), mock_env(), packet, None).unwrap();
+        let paid: Vec<_> = res
+            .messages
+            .iter()
+            .filter_map(|sub| match &sub.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                    Some((to_address.clone(), amount.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            paid,
+            vec![
+                ("zapper".to_string(), vec![coin(180, "uosmo")]),
+                ("zapper".to_string(), vec![coin(1, "uosmo")]),
+            ]
+        );
+        assert_eq!(collected(&deps), vec![coin(10, "uatom"), coin(9, "uosmo")]);
+    }
+
+    #[test]
+    fn test_set_pool_manager() {
+        use crate::interchainswap_handler::{do_ibc_packet_receive, on_packet_success};
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            source_creator: "creator".to_string(),
+            ..test_pool(&pool_id, PoolStatus::Initialized)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let set_manager = MsgSetPoolManagerRequest {
+            pool_id: pool_id.clone(),
+            manager: Some("dao".to_string()),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let msg = ExecuteMsg::SetPoolManager(set_manager.clone());
+        let err =
+            execute(deps.as_mut(), mock_env(), mock_info("dao", &[]), msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+
+        // the manager only changes once the counterparty applied it too
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        assert!(!POOL_MANAGERS.has(&deps.storage, &pool_id));
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::SetPoolManager,
+            data: to_binary(&set_manager).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        on_packet_success(deps.as_mut(), mock_env(), packet.clone(), None).unwrap();
+        let res = query_interchain_pool(deps.as_ref(), pool_id.clone()).unwrap();
+        assert_eq!(res.manager, Some("dao".to_string()));
+
+        // the creator handed over control, the manager can cancel the pool
+        let cancel = MsgCancelPoolRequest {
+            pool_id: pool_id.clone(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let msg = ExecuteMsg::CancelPool(cancel);
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("dao", &[]), msg).unwrap();
+
+        // the counterparty keeps the same manager
+        let mut counterparty = mock_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            token_code_id: 1,
+            router: "".to_string(),
+            default_timeout_seconds: None,
+            lp_token_kind: None,
+        };
+        let info = mock_info("admin", &[]);
+        instantiate(counterparty.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+        POOLS.save(counterparty.as_mut().storage, &pool_id, &pool).unwrap();
+        do_ibc_packet_receive(counterparty.as_mut(), mock_env(), &packet).unwrap();
+        assert_eq!(
+            POOL_MANAGERS.load(&counterparty.storage, &pool_id).unwrap(),
+            "dao".to_string()
+        );
+    }
+
+    #[test]
+    fn test_roles() {
+        let mut deps = setup();
+
+        let grant = ExecuteMsg::GrantRole {
+            address: "fees".to_string(),
+            role: Role::FeeSetter,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("fees", &[]), grant.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), grant).unwrap();
+        let roles = |deps: Deps| -> RolesResponse {
+            let msg = QueryMsg::Roles {
+                address: "fees".to_string(),
+            };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(roles(deps.as_ref()).roles, vec![Role::FeeSetter]);
+
+        // a role only opens the entries it covers
+        let fee_bounds = ExecuteMsg::SetSwapFeeBounds { min_bps: 10, max_bps: 100 };
+        let prefix = ExecuteMsg::SetAddressPrefix {
+            channel_id: "channel-0".to_string(),
+            prefix: "osmo".to_string(),
+        };
+        let fees = mock_info("fees", &[]);
+        execute(deps.as_mut(), mock_env(), fees.clone(), fee_bounds.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), fees.clone(), prefix.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::MissingRole {
+                role: "channel_manager".to_string()
+            }
+        );
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), prefix).unwrap();
+
+        let revoke = ExecuteMsg::RevokeRole {
+            address: "fees".to_string(),
+            role: Role::FeeSetter,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), revoke).unwrap();
+        assert!(roles(deps.as_ref()).roles.is_empty());
+        execute(deps.as_mut(), mock_env(), fees, fee_bounds).unwrap_err();
+    }
+
+    #[test]
+    fn test_disabled_message_type() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+        let grant = ExecuteMsg::GrantRole {
+            address: "pauser".to_string(),
+            role: Role::Pauser,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), grant).unwrap();
+
+        let set_enabled = |enabled: bool| ExecuteMsg::SetMessageEnabled {
+            message_type: InterchainMessageType::RightSwap,
+            enabled,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("pauser", &[]), set_enabled(false)).unwrap();
+
+        let swap = |swap_type: SwapMsgType| MsgSwapRequest {
+            swap_type,
+            sender: "sender".to_string(),
+            pool_id: "pool1".to_string(),
+            token_in: coin(10, "uatom"),
+            token_out: coin(10, "uosmo"),
+            slippage: 100,
+            recipient: "recipient".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let disabled = ContractError::MessageDisabled {
+            message_type: InterchainMessageType::RightSwap,
+        };
+        let info = mock_info("sender", &[coin(10, "uatom")]);
+
+        // right swaps are refused on both paths, left swaps get past the switch
+        let msg = ExecuteMsg::Swap(swap(SwapMsgType::RIGHT));
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(err, disabled);
+        let msg = ExecuteMsg::Swap(swap(SwapMsgType::LEFT));
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_ne!(err, disabled);
+
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::RightSwap,
+            data: to_binary(&swap(SwapMsgType::RIGHT)).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let err = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap_err();
+        assert_eq!(err, disabled);
+
+        execute(deps.as_mut(), mock_env(), mock_info("pauser", &[]), set_enabled(true)).unwrap();
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert!(config.is_enabled(&InterchainMessageType::RightSwap));
+    }
+
+    #[test]
+    fn test_reconcile() {
+        use crate::interchainswap_handler::record_packet;
+        use crate::state::PacketResult;
+        use cosmwasm_std::testing::{mock_ibc_packet_recv, MOCK_CONTRACT_ADDR};
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![coin(1010, "uatom")]);
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: pool_id.clone(),
+            token_in: coin(10, "uatom"),
+            token_out: coin(1, "uosmo"),
+            slippage: 100,
+            recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let info = mock_info("sender", &[coin(10, "uatom")]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Swap(swap)).unwrap();
+
+        let reconcile = |deps: Deps| -> ReconcileResponse {
+            let msg = QueryMsg::Reconcile {
+                pool_id: pool_id.clone(),
+                denom: None,
+                start_after: None,
+                limit: None,
+            };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        let report = reconcile(deps.as_ref());
+        assert_eq!(report.denom, "uatom");
+        assert_eq!(report.recorded, Uint128::new(1000));
+        assert_eq!(report.escrow, Uint128::new(10));
+        assert_eq!(report.surplus, Uint128::zero());
+        assert_eq!(report.shortfall, Uint128::zero());
+
+        // once acknowledged the input is no longer escrow, here it was never added to the pool
+        let data: InterchainSwapPacketData = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => from_binary(data).unwrap(),
+            _ => panic!("expected ibc packet"),
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &data).unwrap().packet;
+        record_packet(deps.as_mut().storage, &mock_env(), &packet, PacketResult::Success).unwrap();
+        let report = reconcile(deps.as_ref());
+        assert_eq!(report.escrow, Uint128::zero());
+        assert_eq!(report.surplus, Uint128::new(10));
+        assert_eq!(report.next, None);
+
+        // a report counted a pool at a time adds up to the same
+        let other = test_pool("pool2", PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, "pool2", &other).unwrap();
+        let page = |deps: Deps, start_after: Option<ReconcileCursor>| -> ReconcileResponse {
+            let msg = QueryMsg::Reconcile {
+                pool_id: pool_id.clone(),
+                denom: None,
+                start_after,
+                limit: Some(1),
+            };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        let mut report = page(deps.as_ref(), None);
+        let mut pages = 1;
+        while let Some(next) = report.next.clone() {
+            assert_eq!(report.shortfall, Uint128::zero());
+            report = page(deps.as_ref(), Some(next));
+            pages += 1;
+        }
+        assert_eq!(pages, 3);
+        assert_eq!(report.recorded, Uint128::new(2000));
+        assert_eq!(report.shortfall, Uint128::new(990));
+    }
+
+    #[test]
+    fn test_multi_asset_deposit_without_deposits() {
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let msg = ExecuteMsg::MakeMultiAssetDeposit(MsgMakeMultiAssetDepositRequest {
+            pool_id,
+            deposits: vec![],
+            chain_id: "chain-a".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            recipient: None,
+            permit_pubkey: None,
+            refund_to: None,
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::ErrFailedMultiAssetDeposit);
+    }
+
+    #[test]
+    fn test_packet_journal() {
+        use crate::interchainswap_handler::record_packet;
+        use crate::state::{PacketResult, LEGACY_PACKET_JOURNAL};
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: pool_id.clone(),
+            token_in: coin(10, "uatom"),
+            token_out: coin(1, "uosmo"),
+            slippage: 100,
+            recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let info = mock_info("sender", &[coin(10, "uatom")]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Swap(swap)).unwrap();
+
+        let journal = |deps: Deps| -> Vec<JournalEntry> {
+            let msg = QueryMsg::PacketJournal {
+                pool_id: pool_id.clone(),
+                start_after: None,
+                limit: None,
+            };
+            let res: PacketJournalResponse =
+                from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            res.entries
+        };
+
+        // the packet is journaled as soon as it is sent
+        let sent = JournalEntry {
+            id: 1,
+            sequence: None,
+            channel_id: "channel-0".to_string(),
+            packet_type: InterchainMessageType::LeftSwap,
+            tokens: vec![coin(10, "uatom")],
+            sent_at: Some(mock_env().block.time.seconds()),
+            result: None,
+            completed_at: None,
+        };
+        assert_eq!(journal(deps.as_ref()), vec![sent.clone()]);
+
+        let data: InterchainSwapPacketData = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => from_binary(data).unwrap(),
+            _ => panic!("expected ibc packet"),
+        };
+        let mut packet = mock_ibc_packet_recv("channel-0", &data).unwrap().packet;
+        packet.sequence = 7;
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(30);
+        record_packet(deps.as_mut().storage, &env, &packet, PacketResult::Timeout).unwrap();
+        let completed = JournalEntry {
+            sequence: Some(7),
+            result: Some(PacketResult::Timeout),
+            completed_at: Some(env.block.time.seconds()),
+            ..sent
+        };
+        assert_eq!(journal(deps.as_ref()), vec![completed.clone()]);
+
+        // entries of the journal by sequence get ids after the existing ones on migration
+        let legacy = JournalEntry {
+            id: 0,
+            sequence: Some(3),
+            sent_at: None,
+            result: Some(PacketResult::Success),
+            ..completed.clone()
+        };
+        LEGACY_PACKET_JOURNAL.save(deps.as_mut().storage, (&pool_id, 3), &legacy).unwrap();
+        cw2::set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(journal(deps.as_ref()), vec![completed, JournalEntry { id: 2, ..legacy }]);
+    }
+
+    #[test]
+    fn test_withdraw_share_price_floor() {
+        use crate::interchainswap_handler::on_received_multi_withdraw;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let msg = MsgMultiAssetWithdrawRequest {
+            pool_id: pool_id.clone(),
+            receiver: "receiver".to_string(),
+            counterparty_receiver: "receiver".to_string(),
+            pool_token: coin(100, pool_id.clone()),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let state_change = |atom_out: u128| StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![coin(atom_out, "uatom"), coin(100, "uosmo")]),
+            pool_tokens: Some(vec![coin(100, pool_id.clone())]),
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &msg).unwrap().packet;
+
+        // paying out more than the burnt share is worth dilutes the remaining LPs
+        let err = on_received_multi_withdraw(
+            deps.as_mut(),
+            mock_env(),
+            &packet,
+            msg.clone(),
+            state_change(150),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("share price"));
+        assert_eq!(POOLS.load(&deps.storage, &pool_id).unwrap(), pool);
+
+        on_received_multi_withdraw(deps.as_mut(), mock_env(), &packet, msg, state_change(100))
+            .unwrap();
+        let withdrawn = POOLS.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(withdrawn.supply, coin(900, pool_id));
+    }
+
+    #[test]
+    fn test_minimum_liquidity_lock() {
+        use crate::interchainswap_handler::on_received_take_pool;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = mock_dependencies();
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            supply: coin(0, pool_id.clone()),
+            ..test_pool(&pool_id, PoolStatus::Initialized)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        POOL_TOKENS_LIST.save(deps.as_mut().storage, &pool_id, &"lp_token".to_string()).unwrap();
+
+        let msg = MsgTakePoolRequest {
+            counter_creator: "maker".to_string(),
+            creator: "taker".to_string(),
+            pool_id: pool_id.clone(),
+            lp_allocation: LPAllocation::MakerChain,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            refund_to: None,
+        };
+        let state_change = |shares: u128| StateChange {
+            in_tokens: None,
+            out_tokens: None,
+            pool_tokens: None,
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: Some(Uint128::new(shares)),
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &msg).unwrap().packet;
+
+        // liquidity that doesn't even cover the locked minimum is refused
+        on_received_take_pool(
+            deps.as_mut(),
+            mock_env(),
+            &packet,
+            msg.clone(),
+            state_change(MINIMUM_LIQUIDITY),
+        )
+        .unwrap_err();
+
+        let res =
+            on_received_take_pool(deps.as_mut(), mock_env(), &packet, msg, state_change(2000))
+                .unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                let mint: Cw20ExecuteMsg = from_binary(msg).unwrap();
+                let amount = Uint128::new(2000 - MINIMUM_LIQUIDITY);
+                assert_eq!(mint, Cw20ExecuteMsg::Mint { recipient: "maker".to_string(), amount });
+            }
+            _ => panic!("expected mint"),
+        }
+        let pool = POOLS.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(pool.supply, coin(2000, pool_id));
+    }
+
+    #[test]
+    fn test_dust_thresholds() {
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let set = ExecuteMsg::SetDustThresholds {
+            pool_id: pool_id.clone(),
+            min_swap: Uint128::new(10),
+            min_deposit: Uint128::new(50),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), set.clone());
+        assert_eq!(err.unwrap_err(), ContractError::InvalidSender);
+        execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), set).unwrap();
+
+        let query_msg = QueryMsg::DustThresholds { pool_id: pool_id.clone() };
+        let thresholds: DustThresholds =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(thresholds.min_deposit, Uint128::new(50));
+
+        let swap = |amount: u128| {
+            ExecuteMsg::Swap(MsgSwapRequest {
+                swap_type: SwapMsgType::LEFT,
+                sender: "sender".to_string(),
+                pool_id: pool_id.clone(),
+                token_in: coin(amount, "uatom"),
+                token_out: coin(1, "uosmo"),
+                slippage: 100,
+                recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                route: None,
+                memo: None,
+                refund_to: None,
+                forward: None,
+            })
+        };
+        let info = mock_info("sender", &[coin(9, "uatom")]);
+        let err = execute(deps.as_mut(), mock_env(), info, swap(9)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::BelowMinimum {
+                amount: Uint128::new(9),
+                min: Uint128::new(10)
+            }
+        );
+        let info = mock_info("sender", &[coin(10, "uatom")]);
+        execute(deps.as_mut(), mock_env(), info, swap(10)).unwrap();
+    }
+
+    #[test]
+    fn test_received_packet_journal() {
+        use crate::ibc::ibc_packet_receive;
+        use crate::state::PacketResult;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: "missing".to_string(),
+            token_in: coin(10, "uatom"),
+            token_out: coin(10, "uosmo"),
+            slippage: 100,
+            recipient: "recipient".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: to_binary(&swap).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let mut msg = mock_ibc_packet_recv("channel-0", &packet_data).unwrap();
+        msg.packet.sequence = 1;
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::Unspecified,
+            data: Binary::default(),
+            state_change: None,
+            memo: None,
+        };
+        let mut msg = mock_ibc_packet_recv("channel-0", &packet_data).unwrap();
+        msg.packet.sequence = 2;
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let query_msg = QueryMsg::ReceivedPackets {
+            channel_id: "channel-0".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res: ReceivedPacketsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(res.packets.len(), 2);
+
+        let refused = &res.packets[0];
+        assert_eq!(refused.sequence, 1);
+        assert_eq!(refused.packet_type, Some(InterchainMessageType::LeftSwap));
+        assert_eq!(refused.pool_id, Some("missing".to_string()));
+        assert!(matches!(refused.result, PacketResult::Failure { .. }));
+        assert!(refused.error_code.is_some());
+
+        let accepted = &res.packets[1];
+        assert_eq!(accepted.result, PacketResult::Success);
+        assert_eq!(accepted.error_code, None);
+    }
+
+    #[test]
+    fn test_interchain_pool_response() {
+        use crate::interchainswap_handler::record_packet;
+        use crate::state::PacketResult;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(2000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        POOL_TOKENS_LIST.save(deps.as_mut().storage, &pool_id, &"lp_token".to_string()).unwrap();
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: pool_id.clone(),
+            token_in: coin(10, "uatom"),
+            token_out: coin(1, "uosmo"),
+            slippage: 100,
+            recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let info = mock_info("sender", &[coin(10, "uatom")]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Swap(swap)).unwrap();
+
+        let pool_response = |deps: Deps| -> InterchainPoolResponse {
+            let msg = QueryMsg::InterchainPool { pool_id: pool_id.clone() };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        let response = pool_response(deps.as_ref());
+        assert_eq!(response.lp_token, Some("lp_token".to_string()));
+        assert_eq!(response.spot_price, Some(Decimal::from_ratio(2u128, 1u128)));
+        assert_eq!(response.max_swap_fee_bps, DEFAULT_MAX_SWAP_FEE_BPS);
+        assert_eq!(response.pending_packets, 1);
+
+        let data: InterchainSwapPacketData = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => from_binary(data).unwrap(),
+            _ => panic!("expected ibc packet"),
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &data).unwrap().packet;
+        record_packet(deps.as_mut().storage, &mock_env(), &packet, PacketResult::Timeout).unwrap();
+        assert_eq!(pool_response(deps.as_ref()).pending_packets, 0);
+    }
+
+    #[test]
+    fn test_asset_metadata_registry() {
+        let mut deps = setup();
+
+        let metadata = AssetMetadata {
+            symbol: "ATOM".to_string(),
+            decimals: 6,
+            logo: None,
+        };
+        let register = ExecuteMsg::SetAssetMetadata {
+            denom: "uatom".to_string(),
+            metadata: Some(metadata.clone()),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), register.clone())
+            .unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), register).unwrap();
+
+        let query_msg = QueryMsg::AssetMetadata { denom: "uatom".to_string() };
+        let registered: Option<AssetMetadata> =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(registered, Some(metadata));
+
+        let make_pool = |decimal: u32| {
+            ExecuteMsg::MakePool(MsgMakePoolRequest {
+                liquidity: vec![
+                    PoolAsset {
+                        side: PoolSide::SOURCE,
+                        balance: coin(1000, "uatom"),
+                        weight: 50,
+                        decimal,
+                    },
+                    PoolAsset {
+                        side: PoolSide::DESTINATION,
+                        balance: coin(1000, "uosmo"),
+                        weight: 50,
+                        decimal: 6,
+                    },
+                ],
+                ..make_pool_request("uatom")
+            })
+        };
+        let info = mock_info("creator", &[coin(1000, "uatom")]);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), make_pool(18)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::DecimalMismatch {
+                denom: "uatom".to_string(),
+                claimed: 18,
+                registered: 6,
+            }
+        );
+        execute(deps.as_mut(), mock_env(), info, make_pool(6)).unwrap();
+    }
+
+    #[test]
+    fn test_take_order_with_permit() {
+        let mut deps = setup();
+
+        // taker chain, the maker's asset is on the other side
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        let order = MultiAssetDepositOrder {
+            id: "order1".to_string(),
+            pool_id: pool_id.clone(),
+            chain_id: "chain-a".to_string(),
+            source_maker: "maker".to_string(),
+            destination_taker: "taker".to_string(),
+            deposits: vec![coin(10, "uatom"), coin(10, "uosmo")],
+            status: OrderStatus::Pending,
+            created_at: 0,
+            recipient: None,
+            permit_pubkey: Some(
+                Binary::from_base64("ArtQ4tiaTtcGY9CAZZ/grUubw+BsF6InQzlmy1nO7gIN").unwrap(),
+            ),
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let order_key = format!("{}-{}", pool_id, order.id);
+        MULTI_ASSET_DEPOSIT_ORDERS.save(deps.as_mut().storage, order_key, &order).unwrap();
+
+        // signatures over the permits of "other" and "intruder"
+        let signed_for_other = "3sx0u3GxuA4C7vy/MMzLvTFNzA7D+fhtlDrBYtSez5dmHHTYYkEaZPqob4ZF9Bv08u\
+                                zNSWll/pAvDx3lN7wrQw==";
+        let signed_for_intruder = "AY+cEWPtORQRyXu7EQgsy32JoVcmY/pgtEiKoyizOv4Cw96WYpfqcQ2DW3hQyr\
+                                   OF6l5ngyN8JRmIEHYdJ3PpZQ==";
+        let take = |permit: Option<&str>| {
+            ExecuteMsg::TakeMultiAssetDeposit(MsgTakeMultiAssetDepositRequest {
+                sender: "other".to_string(),
+                pool_id: pool_id.clone(),
+                order_id: "order1".to_string(),
+                lp_allocation: LPAllocation::MakerChain,
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                memo: None,
+                recipient: None,
+                permit: permit.map(|permit| Binary::from_base64(permit).unwrap()),
+                refund_to: None,
+            })
+        };
+        let info = mock_info("other", &[coin(10, "uosmo")]);
+
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), take(None)).unwrap_err();
+        assert_eq!(err, ContractError::ErrFailedMultiAssetDeposit);
+        let msg = take(Some(signed_for_intruder));
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidPermit);
+        execute(deps.as_mut(), mock_env(), info, take(Some(signed_for_other))).unwrap();
+    }
+
+    #[test]
+    fn test_refund_to() {
+        use crate::interchainswap_handler::on_packet_failure;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use cosmwasm_std::BankMsg;
+
+        let mut deps = setup();
+
+        // a router swapping on behalf of its user
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "router".to_string(),
+            pool_id: "pool1".to_string(),
+            token_in: coin(10, "uatom"),
+            token_out: coin(10, "uosmo"),
+            slippage: 100,
+            recipient: "recipient".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: Some("user".to_string()),
+            forward: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: to_binary(&swap).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let res = on_packet_failure(deps.as_mut(), packet, "timeout".to_string()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "user".to_string(),
+                amount: vec![coin(10, "uatom")],
+            })
+        );
+    }
+
+    #[test]
+    fn test_swap_fee_accrual() {
+        use crate::interchainswap_handler::on_packet_success;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: pool_id.clone(),
+            token_in: coin(1000, "uatom"),
+            token_out: coin(400, "uosmo"),
+            slippage: 100,
+            recipient: "recipient".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![coin(400, "uosmo")]),
+            pool_tokens: None,
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: to_binary(&swap).unwrap(),
+            state_change: Some(to_binary(&state_change).unwrap()),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        on_packet_success(deps.as_mut(), mock_env(), packet, None).unwrap();
+
+        // 30 bps of the input stay out of the pool balance
+        let pool = POOLS.load(deps.as_ref().storage, &pool_id).unwrap();
+        assert_eq!(pool.find_asset_by_denom("uatom").unwrap().balance, coin(1997, "uatom"));
+        assert_eq!(pool.find_asset_by_denom("uosmo").unwrap().balance, coin(600, "uosmo"));
+        let msg = QueryMsg::CollectedFees { pool_id };
+        let fees: Vec<Coin> = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(fees, vec![coin(3, "uatom")]);
+    }
+
+    #[test]
+    fn test_set_pool_status() {
+        use crate::interchainswap_handler::{do_ibc_packet_receive, on_packet_success};
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let set_status = |status: PoolStatus| MsgSetPoolStatusRequest {
+            pool_id: pool_id.clone(),
+            status,
+            drain_deadline: None,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let msg = ExecuteMsg::SetPoolStatus(set_status(PoolStatus::Frozen));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nobody", &[]), msg.clone())
+            .unwrap_err();
+        assert_eq!(err, ContractError::MissingRole { role: "pauser".to_string() });
+        // the pool manager and pausers set the status too
+        execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), msg.clone()).unwrap();
+        let grant = ExecuteMsg::GrantRole {
+            address: "pauser".to_string(),
+            role: Role::Pauser,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), grant).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("pauser", &[]), msg).unwrap();
+        let msg = ExecuteMsg::SetPoolStatus(set_status(PoolStatus::Bootstrap));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidStatus);
+
+        // the pool freezes once the counterparty acknowledges it
+        let msg = ExecuteMsg::SetPoolStatus(set_status(PoolStatus::Frozen));
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::SetPoolStatus,
+            data: to_binary(&set_status(PoolStatus::Frozen)).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        on_packet_success(deps.as_mut(), mock_env(), packet, None).unwrap();
+        let frozen = ContractError::PoolNotOpen { status: PoolStatus::Frozen };
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: pool_id.clone(),
+            token_in: coin(10, "uatom"),
+            token_out: coin(1, "uosmo"),
+            slippage: 100,
+            recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let info = mock_info("sender", &[coin(10, "uatom")]);
+        let msg = ExecuteMsg::Swap(swap.clone());
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(err, frozen);
+        let deposit = MsgSingleAssetDepositRequest {
+            pool_id: pool_id.clone(),
+            sender: "sender".to_string(),
+            token: coin(10, "uatom"),
+            lp_allocation: LPAllocation::MakerChain,
+            lp_taker: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            recipient: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::SingleAssetDeposit(deposit);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, frozen);
+
+        // swaps sent before the freeze are rejected by the counterparty
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![coin(9, "uosmo")]),
+            pool_tokens: None,
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: to_binary(&swap).unwrap(),
+            state_change: Some(to_binary(&state_change).unwrap()),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let err = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap_err();
+        assert_eq!(err, frozen);
+
+        // the counterparty drains the frozen pool
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::SetPoolStatus,
+            data: to_binary(&set_status(PoolStatus::Draining)).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+        let pool = POOLS.load(deps.as_ref().storage, &pool_id).unwrap();
+        assert_eq!(pool.status, PoolStatus::Draining);
+    }
+
+    #[test]
+    fn test_decommission_pool() {
+        use crate::interchainswap_handler::{on_packet_success, on_received_multi_withdraw};
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            supply: coin(5000, pool_id.clone()),
+            ..test_pool(&pool_id, PoolStatus::Draining)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let fees = vec![coin(3, "uatom"), coin(2, "uosmo")];
+        COLLECTED_FEES.save(deps.as_mut().storage, &pool_id, &fees).unwrap();
+        let protocol_fees = vec![coin(1, "uatom"), coin(1, "uosmo")];
+        PROTOCOL_FEES.save(deps.as_mut().storage, &pool_id, &protocol_fees).unwrap();
+        let now = mock_env().block.time.seconds();
+        DRAIN_DEADLINES.save(deps.as_mut().storage, &pool_id, &(now + 1)).unwrap();
+
+        let decommission = MsgDecommissionPoolRequest {
+            pool_id: pool_id.clone(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let msg = ExecuteMsg::DecommissionPool(decommission.clone());
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nobody", &[]), msg.clone())
+            .unwrap_err();
+        assert_eq!(err, ContractError::MissingRole { role: "pauser".to_string() });
+        // LP is still outstanding before the deadline
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg.clone()).unwrap_err();
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(1);
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg.clone()).unwrap();
+        // the pool manager and pausers decommission it too
+        execute(deps.as_mut(), env.clone(), mock_info("maker", &[]), msg.clone()).unwrap();
+        let grant = ExecuteMsg::GrantRole {
+            address: "pauser".to_string(),
+            role: Role::Pauser,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), grant).unwrap();
+        execute(deps.as_mut(), env, mock_info("pauser", &[]), msg).unwrap();
+
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::DecommissionPool,
+            data: to_binary(&decommission).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        on_packet_success(deps.as_mut(), mock_env(), packet, None).unwrap();
+        let treasury = |deps: Deps| -> Vec<Coin> {
+            from_binary(&query(deps, mock_env(), QueryMsg::Treasury {}).unwrap()).unwrap()
+        };
+
+        // LP is left, the pool is retired and only the local protocol fees go to the treasury
+        let retired = POOLS.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(retired.status, PoolStatus::Retired);
+        assert_eq!(COLLECTED_FEES.load(&deps.storage, &pool_id).unwrap(), fees);
+        assert!(!PROTOCOL_FEES.has(&deps.storage, &pool_id));
+        assert!(!DRAIN_DEADLINES.has(&deps.storage, &pool_id));
+        assert_eq!(treasury(deps.as_ref()), vec![coin(1, "uatom")]);
+
+        // the LP holders withdraw their share, the pool is deleted once they did
+        let withdraw = MsgMultiAssetWithdrawRequest {
+            pool_id: pool_id.clone(),
+            receiver: "holder".to_string(),
+            counterparty_receiver: "holder".to_string(),
+            pool_token: coin(4000, pool_id.clone()),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![coin(800, "uatom"), coin(800, "uosmo")]),
+            pool_tokens: Some(vec![coin(4000, pool_id.clone())]),
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &withdraw).unwrap().packet;
+        on_received_multi_withdraw(deps.as_mut(), mock_env(), &packet, withdraw, state_change)
+            .unwrap();
+        assert!(!POOLS.has(&deps.storage, &pool_id));
+        assert!(!COLLECTED_FEES.has(&deps.storage, &pool_id));
+
+        // the residual of the locked minimum is the local asset and its fees left
+        assert_eq!(treasury(deps.as_ref()), vec![coin(202, "uatom")]);
+    }
+
+    #[test]
+    fn test_lp_token_lifecycle() {
+        use cosmwasm_std::SubMsgResponse;
+
+        let mut deps = setup();
+
+        // taker chain, waiting for the taker's asset
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            supply: coin(0, pool_id.clone()),
+            ..test_pool(&pool_id, PoolStatus::Initialized)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let lp_token = |deps: Deps| -> LpTokenResponse {
+            let msg = QueryMsg::LpToken { pool_id: pool_id.clone() };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(lp_token(deps.as_ref()), LpTokenResponse { status: None, address: None });
+
+        let take = ExecuteMsg::TakePool(MsgTakePoolRequest {
+            counter_creator: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            creator: "taker".to_string(),
+            pool_id: pool_id.clone(),
+            lp_allocation: LPAllocation::TakerChain,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            refund_to: None,
+        });
+        let info = mock_info("taker", &[coin(1000, "uosmo")]);
+
+        // the first attempt instantiates the token
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), take.clone()).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(res.messages[0].id, INSTANTIATE_TOKEN_REPLY_ID);
+        let status = lp_token(deps.as_ref()).status;
+        assert_eq!(status, Some(LpTokenStatus::Instantiating));
+
+        // a retry before the reply arrives doesn't instantiate a second one
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), take.clone()).unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        let mut data = MsgInstantiateContractResponse::new();
+        data.set_contract_address("lp_token".to_string());
+        let instantiated = Reply {
+            id: INSTANTIATE_TOKEN_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: Some(Binary::from(data.write_to_bytes().unwrap())),
+            }),
+        };
+        reply(deps.as_mut(), mock_env(), instantiated.clone()).unwrap();
+        assert_eq!(
+            lp_token(deps.as_ref()),
+            LpTokenResponse {
+                status: Some(LpTokenStatus::Ready),
+                address: Some("lp_token".to_string()),
+            }
+        );
+
+        // once ready the token is reused and stray replies are rejected
+        let res = execute(deps.as_mut(), mock_env(), info, take).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        TEMP.save(deps.as_mut().storage, &pool_id).unwrap();
+        reply(deps.as_mut(), mock_env(), instantiated).unwrap_err();
+    }
+
+    #[test]
+    fn test_compact_channel() {
+        use crate::ibc::{ibc_channel_connect, ibc_channel_open};
+        use crate::interchainswap_handler::on_packet_success;
+        use crate::utils::{ICS101_COMPACT_VERSION, ICS101_VERSION};
+        use cosmwasm_std::testing::{
+            mock_ibc_channel, mock_ibc_channel_connect_ack, mock_ibc_packet_recv,
+        };
+        use cosmwasm_std::{IbcChannelOpenMsg, IbcOrder};
+
+        let mut deps = setup();
+
+        // both ends have to propose the same encoding
+        let channel = mock_ibc_channel("channel-0", IbcOrder::Unordered, ICS101_COMPACT_VERSION);
+        let msg = IbcChannelOpenMsg::new_try(channel.clone(), ICS101_VERSION);
+        ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap_err();
+        let msg = IbcChannelOpenMsg::new_try(channel, ICS101_COMPACT_VERSION);
+        ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap();
+        let msg =
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, ICS101_COMPACT_VERSION);
+        ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let set_status = MsgSetPoolStatusRequest {
+            pool_id: pool_id.clone(),
+            status: PoolStatus::Frozen,
+            drain_deadline: None,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let msg = ExecuteMsg::SetPoolStatus(set_status.clone());
+        let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            _ => panic!("expected ibc packet"),
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::SetPoolStatus,
+            data: to_binary(&set_status).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        assert_eq!(data.as_slice(), packet_data.to_compact().as_slice());
+
+        // the compact packet is decoded when acknowledged
+        let mut packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        packet.data = data;
+        on_packet_success(deps.as_mut(), mock_env(), packet, None).unwrap();
+        assert_eq!(POOLS.load(deps.as_ref().storage, &pool_id).unwrap().status, PoolStatus::Frozen);
+    }
+
+    #[test]
+    fn test_debug_attributes() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: pool_id.clone(),
+            token_in: coin(100, "uosmo"),
+            token_out: coin(90, "uatom"),
+            slippage: 100,
+            recipient: "recipient".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![coin(90, "uatom")]),
+            pool_tokens: None,
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: to_binary(&swap).unwrap(),
+            state_change: Some(to_binary(&state_change).unwrap()),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let debug_keys = |attributes: &[cosmwasm_std::Attribute]| -> Vec<String> {
+            attributes
+                .iter()
+                .filter(|attr| attr.key.starts_with("debug_"))
+                .map(|attr| attr.key.clone())
+                .collect()
+        };
+
+        let res = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+        assert!(debug_keys(&res.attributes).is_empty());
+
+        let msg = ExecuteMsg::SetDebugAttributes { enabled: true };
+        execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), msg.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        let msg = QueryMsg::Config {};
+        let config: QueryConfigResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(config.debug_attributes);
+
+        let res = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+        assert_eq!(
+            debug_keys(&res.attributes),
+            vec![
+                "debug_token_out",
+                "debug_fee_charged",
+                "debug_output",
+                "debug_before_uatom",
+                "debug_before_uosmo",
+                "debug_before_supply",
+                "debug_after_uatom",
+                "debug_after_uosmo",
+                "debug_after_supply",
+            ]
+        );
+        let after_uatom = res.attributes.iter().find(|attr| attr.key == "debug_after_uatom");
+        assert_eq!(after_uatom.unwrap().value, "820");
+    }
+
+    #[test]
+    fn test_reentrancy_guard() {
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        POOL_TOKENS_LIST.save(deps.as_mut().storage, &pool_id, &"lp_token".to_string()).unwrap();
+
+        let zap = ExecuteMsg::ZapOut {
+            pool_id: pool_id.clone(),
+            lp_amount: Uint128::new(100),
+            denom_out: "uatom".to_string(),
+            min_out: Uint128::zero(),
+        };
+        let info = mock_info("zapper", &[]);
+
+        // the LP token is called, the guard is released after it
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), zap.clone()).unwrap();
+        let release = WasmMsg::Execute {
+            contract_addr: mock_env().contract.address.to_string(),
+            msg: to_binary(&ExecuteMsg::ReleaseGuard {}).unwrap(),
+            funds: vec![],
+        };
+        assert_eq!(res.messages.last().unwrap().msg, CosmosMsg::Wasm(release));
+
+        // the LP token calling back in the meantime is rejected
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lp_token", &[]), zap.clone())
+            .unwrap_err();
+        assert_eq!(err, ContractError::Reentrancy);
+        let release = ExecuteMsg::ReleaseGuard {};
+        execute(deps.as_mut(), mock_env(), mock_info("lp_token", &[]), release.clone())
+            .unwrap_err();
+
+        let contract = mock_env().contract.address;
+        execute(deps.as_mut(), mock_env(), mock_info(contract.as_str(), &[]), release).unwrap();
+        execute(deps.as_mut(), mock_env(), info, zap).unwrap();
+    }
+
+    #[test]
+    fn test_firm_quote() {
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let msg = QueryMsg::FirmQuote {
+            pool_id: pool_id.clone(),
+            swap_type: SwapMsgType::LEFT,
+            token_in: coin(10, "uatom"),
+            token_out: coin(1, "uosmo"),
+        };
+        let quote: FirmQuoteResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(quote.quote_height, mock_env().block.height);
+        assert_eq!(quote.valid_until_height, mock_env().block.height + 10);
+
+        let firm_swap = |quoted_out: Coin| ExecuteMsg::FirmSwap {
+            swap: MsgSwapRequest {
+                swap_type: SwapMsgType::LEFT,
+                sender: "sender".to_string(),
+                pool_id: pool_id.clone(),
+                token_in: coin(10, "uatom"),
+                token_out: coin(1, "uosmo"),
+                slippage: 100,
+                recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                route: None,
+                memo: None,
+                refund_to: None,
+                forward: None,
+            },
+            quoted_out,
+            quote_height: quote.quote_height,
+        };
+        let info = mock_info("sender", &[coin(10, "uatom")]);
+
+        // the quote expires after the validity window
+        let mut env = mock_env();
+        env.block.height = quote.valid_until_height + 1;
+        let msg = firm_swap(quote.quoted_out.clone());
+        let err = execute(deps.as_mut(), env, info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::QuoteExpired {
+                quote_height: quote.quote_height,
+                valid_until: quote.valid_until_height,
+            }
+        );
+
+        // a pool that moved no longer gives the quoted amount
+        let mut moved = pool.clone();
+        moved.add_asset(coin(1000, "uatom")).unwrap();
+        POOLS.save(deps.as_mut().storage, &pool_id, &moved).unwrap();
+        let msg = firm_swap(quote.quoted_out.clone());
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::QuoteChanged { .. }));
+
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let mut env = mock_env();
+        env.block.height = quote.valid_until_height;
+        execute(deps.as_mut(), env, info, firm_swap(quote.quoted_out)).unwrap();
+    }
+
+    #[test]
+    fn test_verify_pool() {
+        use cosmwasm_std::testing::mock_dependencies_with_balance;
+
+        let mut deps = mock_dependencies_with_balance(&[coin(4_000_000, "uatom")]);
+        let instantiate_msg = InstantiateMsg {
+            token_code_id: 1,
+            router: "".to_string(),
+            default_timeout_seconds: None,
+            lp_token_kind: None,
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), instantiate_msg).unwrap();
+
+        let pool_id = "pool1".to_string();
+        let mut pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(4_000_000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1_000_000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            supply: coin(1_000_000, pool_id.clone()),
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let verify = |deps: Deps| -> VerifyPoolResponse {
+            let msg = QueryMsg::VerifyPool { pool_id: pool_id.clone() };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        let report = verify(deps.as_ref());
+        assert!(report.violations.is_empty(), "{:?}", report.violations);
+        // sqrt(4 * 1) whole tokens for one whole share
+        assert_eq!(report.invariant.to_string()[0..6], *"1.9999");
+        assert_eq!(report.invariant_per_share.to_string()[0..6], *"1.9999");
+
+        // more recorded than the contract holds, and broken weights
+        pool.assets[0].balance = coin(5_000_000, "uatom");
+        pool.assets[1].weight = 60;
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let report = verify(deps.as_ref());
+        assert_eq!(report.reconcile[0].shortfall, Uint128::new(1_000_000));
+        assert_eq!(
+            report.violations,
+            vec![
+                "weights don't add up to 100".to_string(),
+                "balance short of 1000000 uatom for recorded and escrowed funds".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_treasury_withdrawal() {
+        use cosmwasm_std::BankMsg;
+
+        let mut deps = setup();
+        let grant = ExecuteMsg::GrantRole {
+            address: "treasurer".to_string(),
+            role: Role::Treasurer,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), grant).unwrap();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            supply: coin(5000, pool_id.clone()),
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let fees = vec![coin(30, "uatom"), coin(2, "uosmo")];
+        PROTOCOL_FEES.save(deps.as_mut().storage, &pool_id, &fees).unwrap();
+        // the fees of the LPs are never drawn
+        let lp_fees = vec![coin(500, "uatom")];
+        COLLECTED_FEES.save(deps.as_mut().storage, &pool_id, &lp_fees).unwrap();
+        TREASURY.save(deps.as_mut().storage, "uatom", &Uint128::new(100)).unwrap();
+
+        let withdraw = |amount: u128| ExecuteMsg::WithdrawTreasury {
+            denom: "uatom".to_string(),
+            amount: Uint128::new(amount),
+            to: "receiver".to_string(),
+        };
+        let treasurer = mock_info("treasurer", &[]);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), withdraw(10))
+            .unwrap_err();
+        assert_eq!(err, ContractError::MissingRole { role: "treasurer".to_string() });
+        let err =
+            execute(deps.as_mut(), mock_env(), treasurer.clone(), withdraw(131)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientTreasury {
+                denom: "uatom".to_string(),
+                available: Uint128::new(130),
+                requested: Uint128::new(131),
+            }
+        );
+
+        // drains the treasury before the protocol fees
+        execute(deps.as_mut(), mock_env(), treasurer.clone(), withdraw(120)).unwrap();
+        assert!(!TREASURY.has(&deps.storage, "uatom"));
+        let fees = PROTOCOL_FEES.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(fees, vec![coin(10, "uatom"), coin(2, "uosmo")]);
+
+        let msg = QueryMsg::PendingWithdrawals { start_after: None, limit: None };
+        let pending: PendingWithdrawalsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        let executable_at = mock_env().block.time.seconds() + DEFAULT_TREASURY_TIMELOCK_SECONDS;
+        assert_eq!(
+            pending.withdrawals,
+            vec![PendingWithdrawal {
+                id: 1,
+                denom: "uatom".to_string(),
+                amount: Uint128::new(120),
+                to: "receiver".to_string(),
+                executable_at,
+                protocol_fees: vec![(pool_id.clone(), Uint128::new(20))],
+            }]
+        );
+
+        let msg = ExecuteMsg::ExecuteTreasuryWithdrawal { id: 1 };
+        let err = execute(deps.as_mut(), mock_env(), treasurer.clone(), msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::WithdrawalTimelocked { id: 1, executable_at });
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(DEFAULT_TREASURY_TIMELOCK_SECONDS);
+        let res = execute(deps.as_mut(), env, treasurer.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "receiver".to_string(),
+                amount: vec![coin(120, "uatom")],
+            })
+        );
+        assert!(!PENDING_WITHDRAWALS.has(&deps.storage, 1));
+
+        // a cancelled withdrawal returns to the treasury and the protocol fees it came from
+        TREASURY.save(deps.as_mut().storage, "uatom", &Uint128::new(4)).unwrap();
+        execute(deps.as_mut(), mock_env(), treasurer.clone(), withdraw(10)).unwrap();
+        let fees = PROTOCOL_FEES.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(fees, vec![coin(4, "uatom"), coin(2, "uosmo")]);
+        let msg = ExecuteMsg::CancelTreasuryWithdrawal { id: 2 };
+        execute(deps.as_mut(), mock_env(), treasurer, msg).unwrap();
+        let msg = QueryMsg::Treasury {};
+        let treasury: Vec<Coin> =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(treasury, vec![coin(4, "uatom")]);
+        let fees = PROTOCOL_FEES.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(fees, vec![coin(10, "uatom"), coin(2, "uosmo")]);
+        assert_eq!(COLLECTED_FEES.load(&deps.storage, &pool_id).unwrap(), lp_fees);
+    }
+
+    #[test]
+    fn test_fee_application() {
+        use crate::interchainswap_handler::{do_ibc_packet_receive, on_packet_success};
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use ibcswap_packets::InterchainSwapPacketAcknowledgement;
+
+        let mut receiver = mock_dependencies();
+        let mut sender = mock_dependencies();
+        let pool_id = "pool1".to_string();
+        let mut pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1_000_000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1_000_000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            fee_application: FeeApplication::Split,
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        let left = SwapMsgType::LEFT;
+        assert_eq!((pool.sender_fee_rate(&left), pool.receiver_fee_rate(&left)), (15, 15));
+        pool.fee_application = FeeApplication::Destination;
+        assert_eq!((pool.sender_fee_rate(&left), pool.receiver_fee_rate(&left)), (0, 30));
+        for deps in [&mut receiver, &mut sender] {
+            let instantiate_msg = InstantiateMsg {
+                token_code_id: 1,
+                router: "".to_string(),
+                default_timeout_seconds: None,
+                lp_token_kind: None,
+            };
+            instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), instantiate_msg)
+                .unwrap();
+            POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        }
+
+        let packet = |min_out: u128| {
+            let swap = MsgSwapRequest {
+                swap_type: SwapMsgType::LEFT,
+                sender: "sender".to_string(),
+                pool_id: pool_id.clone(),
+                token_in: coin(100_000, "uosmo"),
+                token_out: coin(min_out, "uatom"),
+                slippage: 100,
+                recipient: "recipient".to_string(),
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                route: None,
+                memo: None,
+                refund_to: None,
+                forward: None,
+            };
+            // quoted by a sender with outdated balances
+            let state_change = StateChange {
+                in_tokens: None,
+                out_tokens: Some(vec![coin(95_000, "uatom")]),
+                pool_tokens: None,
+                pool_id: None,
+                multi_deposit_order_id: None,
+                source_chain_id: None,
+                shares: None,
+            };
+            let packet_data = InterchainSwapPacketData {
+                r#type: InterchainMessageType::LeftSwap,
+                data: to_binary(&swap).unwrap(),
+                state_change: Some(to_binary(&state_change).unwrap()),
+                memo: None,
+            };
+            mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet
+        };
+
+        // the recomputed output no longer meets the slippage
+        do_ibc_packet_receive(receiver.as_mut(), mock_env(), &packet(95_000)).unwrap_err();
+
+        let fresh = InterchainMarketMaker::new(&pool, 0)
+            .compute_swap(coin(100_000, "uosmo"), "uatom")
+            .unwrap();
+        let fee = fresh.amount.multiply_ratio(30u128, 10000u128);
+        let settled = coin((fresh.amount - fee).u128(), "uatom");
+        let packet = packet(91_000);
+        let res = do_ibc_packet_receive(receiver.as_mut(), mock_env(), &packet).unwrap();
+        let result = match from_binary(&res.acknowledgement).unwrap() {
+            InterchainSwapPacketAcknowledgement::Result(result) => result,
+            InterchainSwapPacketAcknowledgement::Error(err) => panic!("{}", err),
+        };
+        let acked: crate::types::SettledSwap = from_binary(&result).unwrap();
+        assert_eq!((acked.token(), acked.fee), (settled.clone(), fee));
+
+        // the sender mirrors the settled output, not its own quote
+        on_packet_success(sender.as_mut(), mock_env(), packet, Some(result)).unwrap();
+        let receiver_pool = POOLS.load(&receiver.storage, &pool_id).unwrap();
+        let sender_pool = POOLS.load(&sender.storage, &pool_id).unwrap();
+        assert_eq!(receiver_pool, sender_pool);
+        let uatom = receiver_pool.find_asset_by_denom("uatom").unwrap();
+        assert_eq!(uatom.balance.amount, Uint128::new(1_000_000) - settled.amount - fee);
+
+        // the receiving chain's fee is booked to the pool fees on both chains
+        for deps in [&receiver, &sender] {
+            let booked: Uint128 = [&COLLECTED_FEES, &PROTOCOL_FEES]
+                .iter()
+                .flat_map(|ledger| ledger.may_load(&deps.storage, &pool_id).unwrap())
+                .flatten()
+                .filter(|accrued| accrued.denom == "uatom")
+                .map(|accrued| accrued.amount)
+                .sum();
+            assert_eq!(booked, fee);
+        }
+    }
+
+    #[test]
+    fn test_multi_deposit_by_ratio() {
+        use cosmwasm_std::BankMsg;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(2000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        // half of the value in each asset, 400uosmo are worth 200uatom
+        let msg = ExecuteMsg::MakeMultiAssetDepositByRatio(MsgMakeMultiAssetDepositByRatioRequest {
+            pool_id: pool_id.clone(),
+            total_value: coin(800, "uosmo"),
+            tolerance: 500,
+            counterparty_sender: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            chain_id: "chain-a".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            recipient: None,
+            permit_pubkey: None,
+            refund_to: None,
+        });
+        for sent in [150, 300] {
+            let info = mock_info("maker", &[coin(sent, "uatom")]);
+            let err = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::DepositRatioMoved {
+                    needed: coin(200, "uatom"),
+                    sent: coin(sent, "uatom"),
+                }
+            );
+        }
+
+        // the excess is refunded right away, so the refund address has to be valid
+        let mut invalid = msg.clone();
+        if let ExecuteMsg::MakeMultiAssetDepositByRatio(msg) = &mut invalid {
+            msg.refund_to = Some("x".to_string());
+        }
+        let info = mock_info("maker", &[coin(210, "uatom")]);
+        let err = execute(deps.as_mut(), mock_env(), info, invalid).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAddress { .. }));
+
+        let info = mock_info("maker", &[coin(210, "uatom")]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert!(res.messages.iter().any(|sub| sub.msg
+            == CosmosMsg::Bank(BankMsg::Send {
+                to_address: "maker".to_string(),
+                amount: vec![coin(10, "uatom")],
+            })));
+        let (_, order) = MULTI_ASSET_DEPOSIT_ORDERS
+            .range(&deps.storage, None, None, Order::Ascending)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(order.deposits, vec![coin(200, "uatom"), coin(400, "uosmo")]);
+    }
+
+    #[test]
+    fn test_required_counter_deposit() {
+        let mut deps = setup();
+
+        // taker chain, the maker's asset is on the other side
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        let mut order = MultiAssetDepositOrder {
+            id: "order1".to_string(),
+            pool_id: pool_id.clone(),
+            chain_id: "chain-a".to_string(),
+            source_maker: "maker".to_string(),
+            destination_taker: "taker".to_string(),
+            deposits: vec![coin(10, "uatom"), coin(12, "uosmo")],
+            status: OrderStatus::Pending,
+            created_at: 0,
+            recipient: None,
+            permit_pubkey: None,
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let order_key = format!("{}-{}", pool_id, order.id);
+        MULTI_ASSET_DEPOSIT_ORDERS.save(deps.as_mut().storage, order_key.clone(), &order).unwrap();
+
+        let msg = QueryMsg::RequiredCounterDeposit {
+            pool_id: pool_id.clone(),
+            order_id: "order1".to_string(),
+        };
+        let required: RequiredCounterDepositResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg.clone()).unwrap()).unwrap();
+        assert_eq!(
+            required,
+            RequiredCounterDepositResponse {
+                funds: vec![coin(12, "uosmo")],
+                taker: "taker".to_string(),
+            }
+        );
+
+        let take = ExecuteMsg::TakeMultiAssetDeposit(MsgTakeMultiAssetDepositRequest {
+            sender: "taker".to_string(),
+            pool_id: pool_id.clone(),
+            order_id: "order1".to_string(),
+            lp_allocation: LPAllocation::MakerChain,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            recipient: None,
+            permit: None,
+            refund_to: None,
+        });
+        let info = mock_info("taker", &[coin(10, "uatom")]);
+        execute(deps.as_mut(), mock_env(), info, take.clone()).unwrap_err();
+        let info = mock_info("taker", &required.funds);
+        execute(deps.as_mut(), mock_env(), info, take).unwrap();
+
+        order.status = OrderStatus::Complete;
+        MULTI_ASSET_DEPOSIT_ORDERS.save(deps.as_mut().storage, order_key, &order).unwrap();
+        query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    }
+
+    #[test]
+    fn test_failure_ack_context() {
+        use crate::interchainswap_handler::{ack_fail_for, on_packet_failure};
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use ibcswap_packets::{FailureAck, InterchainSwapPacketAcknowledgement};
+
+        let mut deps = mock_dependencies();
+        let msg = MsgMakeMultiAssetDepositRequest {
+            pool_id: "pool1".to_string(),
+            deposits: vec![
+                DepositAsset {
+                    sender: "maker".to_string(),
+                    balance: coin(10, "uatom"),
+                },
+                DepositAsset {
+                    sender: "taker".to_string(),
+                    balance: coin(10, "uosmo"),
+                },
+            ],
+            chain_id: "chain-a".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            recipient: None,
+            permit_pubkey: None,
+            refund_to: None,
+        };
+        let order = MultiAssetDepositOrder {
+            id: "order1".to_string(),
+            pool_id: "pool1".to_string(),
+            chain_id: "chain-a".to_string(),
+            source_maker: "maker".to_string(),
+            destination_taker: "taker".to_string(),
+            deposits: vec![coin(10, "uatom"), coin(10, "uosmo")],
+            status: OrderStatus::Pending,
+            created_at: 0,
+            recipient: None,
+            permit_pubkey: None,
+        };
+        let order_key = "pool1-order1".to_string();
+        MULTI_ASSET_DEPOSIT_ORDERS.save(deps.as_mut().storage, order_key.clone(), &order).unwrap();
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: None,
+            pool_tokens: None,
+            pool_id: None,
+            multi_deposit_order_id: Some("order1".to_string()),
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::MakeMultiDeposit,
+            data: to_binary(&msg).unwrap(),
+            state_change: Some(to_binary(&state_change).unwrap()),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+
+        // the receiving chain names the pool and order in its failure ack
+        let ack = ack_fail_for(&packet, "pool is frozen".to_string());
+        let err = match from_binary(&ack).unwrap() {
+            InterchainSwapPacketAcknowledgement::Error(err) => err,
+            InterchainSwapPacketAcknowledgement::Result(_) => panic!("not a failure"),
+        };
+        let failure = FailureAck::decode(&err).unwrap();
+        assert_eq!(failure.pool_id.as_deref(), Some("pool1"));
+        assert_eq!(failure.order_id.as_deref(), Some("order1"));
+
+        let res = on_packet_failure(deps.as_mut(), packet, err).unwrap();
+        assert!(!MULTI_ASSET_DEPOSIT_ORDERS.has(&deps.storage, order_key));
+        let attribute = |key: &str| {
+            res.attributes.iter().find(|attr| attr.key == key).map(|attr| attr.value.clone())
+        };
+        assert_eq!(attribute("error").as_deref(), Some("pool is frozen"));
+        assert_eq!(attribute("order_id").as_deref(), Some("order1"));
+    }
+
+    #[test]
+    fn test_revert_packet_keeps_later_packets() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use crate::state::UNDO_RETENTION_SECONDS;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1_000_000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1_000_000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let swap_packet = |sequence: u64, amount_in: u128, amount_out: u128| {
+            let swap = MsgSwapRequest {
+                swap_type: SwapMsgType::LEFT,
+                sender: "sender".to_string(),
+                pool_id: pool_id.clone(),
+                token_in: coin(amount_in, "uosmo"),
+                token_out: coin(amount_out, "uatom"),
+                slippage: 100,
+                recipient: "recipient".to_string(),
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                route: None,
+                memo: None,
+                refund_to: None,
+                forward: None,
+            };
+            let state_change = StateChange {
+                in_tokens: None,
+                out_tokens: Some(vec![coin(amount_out, "uatom")]),
+                pool_tokens: None,
+                pool_id: None,
+                multi_deposit_order_id: None,
+                source_chain_id: None,
+                shares: None,
+            };
+            let packet_data = InterchainSwapPacketData {
+                r#type: InterchainMessageType::LeftSwap,
+                data: to_binary(&swap).unwrap(),
+                state_change: Some(to_binary(&state_change).unwrap()),
+                memo: None,
+            };
+            let mut packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+            packet.sequence = sequence;
+            packet
+        };
+        let balance = |deps: &OwnedDeps<_, _, _>, denom: &str| {
+            let pool = POOLS.load(deps.as_ref().storage, &pool_id).unwrap();
+            pool.find_asset_by_denom(denom).unwrap().balance.amount.u128()
+        };
+
+        do_ibc_packet_receive(deps.as_mut(), mock_env(), &swap_packet(1, 1000, 900)).unwrap();
+        let record = UNDO_LOG.load(deps.as_ref().storage, ("channel-0", 1)).unwrap();
+        assert_eq!(record.sent, vec![coin(900, "uatom")]);
+        let first_in = balance(&deps, "uosmo") - 1_000_000;
+        do_ibc_packet_receive(deps.as_mut(), mock_env(), &swap_packet(2, 2000, 1700)).unwrap();
+        let (atom, osmo) = (balance(&deps, "uatom"), balance(&deps, "uosmo"));
+
+        // the first swap is taken back, the second stays and the 900uatom paid out are gone
+        let revert = ExecuteMsg::RevertPacket { channel_id: "channel-0".to_string(), sequence: 1 };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), revert).unwrap();
+        assert_eq!(balance(&deps, "uatom"), atom);
+        assert_eq!(balance(&deps, "uosmo"), osmo - first_in);
+        assert!(!UNDO_LOG.has(deps.as_ref().storage, ("channel-0", 1)));
+
+        // records past the retention window are dropped as packets arrive
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(UNDO_RETENTION_SECONDS);
+        do_ibc_packet_receive(deps.as_mut(), env, &swap_packet(3, 1000, 800)).unwrap();
+        assert!(!UNDO_LOG.has(deps.as_ref().storage, ("channel-0", 2)));
+        assert!(UNDO_LOG.has(deps.as_ref().storage, ("channel-0", 3)));
+    }
+
+    #[test]
+    fn test_swap_claims() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use cosmwasm_std::{BankMsg, CosmosMsg};
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1_000_000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1_000_000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: pool_id.clone(),
+            token_in: coin(1000, "uosmo"),
+            token_out: coin(900, "uatom"),
+            slippage: 100,
+            recipient: "".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![coin(1000, "uatom")]),
+            pool_tokens: None,
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: to_binary(&swap).unwrap(),
+            state_change: Some(to_binary(&state_change).unwrap()),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+
+        // the swap still settles, the output is held for the sender
+        for _ in 0..2 {
+            let res = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+            assert!(res.events.iter().any(|event| event.ty == "swap_output_claimable"));
+            RECEIVED_PACKETS.clear(deps.as_mut().storage);
+        }
+        let claims_query = QueryMsg::SwapClaims {
+            channel_id: "channel-0".to_string(),
+            counterparty: "sender".to_string(),
+        };
+        let claims: Vec<Coin> =
+            from_binary(&query(deps.as_ref(), mock_env(), claims_query).unwrap()).unwrap();
+        assert_eq!(claims, vec![coin(2000, "uatom")]);
+
+        let claim = ExecuteMsg::ClaimSwapOutput {
+            channel_id: "channel-0".to_string(),
+            counterparty: "sender".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), claim.clone()).unwrap_err();
+
+        let set_address = ExecuteMsg::SetClaimAddress {
+            channel_id: "channel-0".to_string(),
+            counterparty: "sender".to_string(),
+            address: "owner".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), set_address.clone())
+            .unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), set_address).unwrap();
+
+        let res =
+            execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), claim.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "owner".to_string(),
+                amount: vec![coin(2000, "uatom")],
+            })
+        );
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), claim).unwrap_err();
+    }
+
+    #[test]
+    fn test_schema_in_sync() {
+        use cosmwasm_schema::schema_for;
+        use serde_json::Value;
+
+        let checked_in = |json: &str| serde_json::from_str::<Value>(json).unwrap();
+        let stale = "schema out of date, run `cargo run --example schema`";
+        assert_eq!(
+            serde_json::to_value(schema_for!(InstantiateMsg)).unwrap(),
+            checked_in(include_str!("../schema/instantiate_msg.json")),
+            "{}",
+            stale
+        );
+        assert_eq!(
+            serde_json::to_value(schema_for!(ExecuteMsg)).unwrap(),
+            checked_in(include_str!("../schema/execute_msg.json")),
+            "{}",
+            stale
+        );
+        assert_eq!(
+            serde_json::to_value(schema_for!(QueryMsg)).unwrap(),
+            checked_in(include_str!("../schema/query_msg.json")),
+            "{}",
+            stale
+        );
+        assert_eq!(
+            serde_json::to_value(schema_for!(MigrateMsg)).unwrap(),
+            checked_in(include_str!("../schema/migrate_msg.json")),
+            "{}",
+            stale
+        );
+    }
+
+    #[test]
+    fn test_counterparty_binding() {
+        use crate::ibc::{ibc_channel_connect, ibc_channel_open};
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use crate::utils::ICS101_VERSION;
+        use cosmwasm_std::testing::{
+            mock_ibc_channel, mock_ibc_channel_connect_ack, mock_ibc_packet_recv,
+        };
+        use cosmwasm_std::{IbcChannelOpenMsg, IbcOrder};
+
+        let mut deps = setup();
+
+        let msg = mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, ICS101_VERSION);
+        ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap();
+
+        // another contract cannot open a channel on the same connection
+        let mut channel = mock_ibc_channel("channel-1", IbcOrder::Unordered, ICS101_VERSION);
+        channel.counterparty_endpoint.port_id = "wasm.impostor".to_string();
+        let msg = IbcChannelOpenMsg::new_try(channel.clone(), ICS101_VERSION);
+        let err = ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::UnexpectedCounterparty {
+                connection_id: "connection-2".to_string(),
+                port_id: "wasm.impostor".to_string(),
+                expected: "their_port".to_string(),
+            }
+        );
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            counter_party_channel: "channel-1".to_string(),
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let set_status = MsgSetPoolStatusRequest {
+            pool_id: pool_id.clone(),
+            status: PoolStatus::Frozen,
+            drain_deadline: None,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::SetPoolStatus,
+            data: to_binary(&set_status).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let mut packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let err = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap_err();
+        assert!(matches!(err, ContractError::UnexpectedCounterparty { .. }));
+
+        // the pool was made over another channel
+        packet.src = channel.counterparty_endpoint.clone();
+        packet.src.port_id = "their_port".to_string();
+        let err = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::UnexpectedPoolChannel {
+                pool_id: pool_id.clone(),
+                channel_id: "channel-0".to_string(),
+                expected: "channel-1".to_string(),
+            }
+        );
+
+        // the channel manager can rebind the connection, e.g. to a redeployed contract
+        let rebind = ExecuteMsg::SetCounterpartyPort {
+            connection_id: "connection-2".to_string(),
+            port_id: "wasm.impostor".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), rebind.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), rebind).unwrap();
+        let msg = IbcChannelOpenMsg::new_try(channel, ICS101_VERSION);
+        ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap();
+    }
+
+    #[test]
+    fn test_packet_veto() {
+        use crate::ibc::ibc_packet_receive;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use ibcswap_packets::{FailureAck, InterchainSwapPacketAcknowledgement};
+
+        let mut deps = setup();
+
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::Unspecified,
+            data: Binary::default(),
+            state_change: None,
+            memo: None,
+        };
+        let msg = mock_ibc_packet_recv("channel-0", &packet_data).unwrap();
+        let sequence = msg.packet.sequence;
+        let veto = |vetoed: bool| ExecuteMsg::SetPacketVeto {
+            channel_id: "channel-0".to_string(),
+            sequence,
+            vetoed,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), veto(true)).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), veto(true)).unwrap();
+        let query_msg = QueryMsg::PacketVetoed {
+            channel_id: "channel-0".to_string(),
+            sequence,
+        };
+        let vetoed: bool =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert!(vetoed);
+
+        // the vetoed packet is acknowledged with a failure, so the sender refunds it
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let err = match from_binary(&res.acknowledgement).unwrap() {
+            InterchainSwapPacketAcknowledgement::Error(err) => err,
+            InterchainSwapPacketAcknowledgement::Result(_) => panic!("expected a failure ack"),
+        };
+        let failure = FailureAck::decode(&err).unwrap();
+        assert_eq!(
+            failure.error,
+            ContractError::PacketVetoed {
+                channel_id: "channel-0".to_string(),
+                sequence,
+            }
+            .to_string()
+        );
+
+        // a received packet cannot be vetoed anymore
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), veto(false)).unwrap_err();
+    }
+
+    #[test]
+    fn test_storage_version() {
+        use crate::msg::StorageVersionResponse;
+        use crate::state::CURRENT_STORAGE_VERSION;
+
+        let mut deps = setup();
+
+        let msg = QueryMsg::StorageVersion {};
+        let version: StorageVersionResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            version,
+            StorageVersionResponse {
+                version: CURRENT_STORAGE_VERSION,
+                pending: vec![],
+            }
+        );
+
+        let msg = ExecuteMsg::MigrateStorage { limit: 10 };
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn test_lp_checkpoints() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use crate::msg::LpCheckpointsResponse;
+        use crate::state::{
+            LpChange, LpCheckpoint, LP_CHECKPOINTS, LP_CHECKPOINT_COUNT, MAX_LP_CHECKPOINTS,
+        };
+        use cosmwasm_std::attr;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1_000_000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1_000_000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            supply: coin(0, pool_id.clone()),
+            ..test_pool(&pool_id, PoolStatus::Initialized)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        POOL_TOKENS_LIST.save(deps.as_mut().storage, &pool_id, &"lptoken".to_string()).unwrap();
+
+        // the log is full, the oldest checkpoint makes room
+        let oldest = LpCheckpoint {
+            id: 0,
+            address: "lp".to_string(),
+            change: LpChange::Mint,
+            amount: Uint128::new(1),
+            time: 0,
+        };
+        LP_CHECKPOINTS.save(deps.as_mut().storage, (&pool_id, 0), &oldest).unwrap();
+        LP_CHECKPOINT_COUNT.save(deps.as_mut().storage, &pool_id, &MAX_LP_CHECKPOINTS).unwrap();
+
+        let take_pool = MsgTakePoolRequest {
+            counter_creator: "lp".to_string(),
+            creator: "taker".to_string(),
+            pool_id: pool_id.clone(),
+            lp_allocation: LPAllocation::MakerChain,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            refund_to: None,
+        };
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: None,
+            pool_tokens: None,
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: Some(Uint128::new(2_000_000)),
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::TakePool,
+            data: to_binary(&take_pool).unwrap(),
+            state_change: Some(to_binary(&state_change).unwrap()),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let res = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+
+        let minted = unlocked_shares(Uint128::new(2_000_000)).unwrap();
+        let event = res.events.iter().find(|event| event.ty == "lp_checkpoint").unwrap();
+        assert!(event.attributes.contains(&attr("address", "lp")));
+        assert!(event.attributes.contains(&attr("change", "mint")));
+        assert!(event.attributes.contains(&attr("amount", minted)));
+
+        let msg = QueryMsg::LpCheckpoints { pool_id, start_after: None, limit: None };
+        let res: LpCheckpointsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            res.checkpoints,
+            vec![LpCheckpoint {
+                id: MAX_LP_CHECKPOINTS,
+                address: "lp".to_string(),
+                change: LpChange::Mint,
+                amount: minted,
+                time: mock_env().block.time.seconds(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_swap_forward() {
+        use crate::icq::{encode, read_bytes_field};
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use crate::msg::{SwapForward, SwapRoute};
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use cosmwasm_std::{from_slice, SubMsgResponse};
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1_000_000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1_000_000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let forward = SwapForward {
+            channel: "channel-9".to_string(),
+            receiver: "juno1receiver".to_string(),
+        };
+        let mut swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: pool_id.clone(),
+            token_in: coin(1000, "uatom"),
+            token_out: coin(900, "uosmo"),
+            slippage: 100,
+            recipient: "".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: Some(SwapRoute {
+                requests: vec![],
+                minimum_receive: None,
+            }),
+            memo: None,
+            refund_to: None,
+            forward: Some(forward.clone()),
+        };
+        let info = mock_info("sender", &[coin(1000, "uatom")]);
+        let msg = ExecuteMsg::Swap(swap.clone());
+        execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        swap.route = None;
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Swap(swap.clone())).unwrap();
+
+        // the counterparty pays the output on to the third chain
+        swap.token_in = coin(1000, "uosmo");
+        swap.token_out = coin(900, "uatom");
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![coin(1000, "uatom")]),
+            pool_tokens: None,
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: to_binary(&swap).unwrap(),
+            state_change: Some(to_binary(&state_change).unwrap()),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let res = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+        let transfer = res
+            .messages
+            .iter()
+            .find_map(|sub_msg| match &sub_msg.msg {
+                CosmosMsg::Stargate { value, .. } if sub_msg.id == FORWARD_TRANSFER_REPLY_ID => {
+                    Some(value.clone())
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(read_bytes_field(&transfer, 2).unwrap(), forward.channel.as_bytes());
+        assert_eq!(read_bytes_field(&transfer, 5).unwrap(), forward.receiver.as_bytes());
+        let token = read_bytes_field(&transfer, 3).unwrap();
+        assert_eq!(read_bytes_field(&token, 2).unwrap(), b"1000");
+        let memo = read_bytes_field(&transfer, 8).unwrap();
+        assert_eq!(memo, br#"{"ibc_callback":"cosmos2contract"}"#);
+
+        // the reply records the sequence the transfer module assigned
+        let data = encode(|os| os.write_uint64(1, 7)).unwrap();
+        let result = SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(Binary::from(data)),
+        });
+        reply(deps.as_mut(), mock_env(), Reply { id: FORWARD_TRANSFER_REPLY_ID, result }).unwrap();
+
+        // a transfer that timed out was refunded, the sender can claim the output
+        let timeout: SudoMsg = from_slice(
+            br#"{"ibc_lifecycle_complete":{"ibc_timeout":{"channel":"channel-9","sequence":7}}}"#,
+        )
+        .unwrap();
+        let escrow = escrowed_funds(&deps.storage, "uatom").unwrap();
+        let res = sudo(deps.as_mut(), mock_env(), timeout.clone()).unwrap();
+        assert_eq!(res.events[0].ty, "swap_output_claimable");
+        let claims = SWAP_CLAIMS.load(&deps.storage, ("channel-0", "sender")).unwrap();
+        assert_eq!(claims, vec![coin(1000, "uatom")]);
+        assert_eq!(escrowed_funds(&deps.storage, "uatom").unwrap(), escrow + Uint128::new(1000));
+
+        // the result of a transfer is only settled once
+        sudo(deps.as_mut(), mock_env(), timeout).unwrap();
+        let claims = SWAP_CLAIMS.load(&deps.storage, ("channel-0", "sender")).unwrap();
+        assert_eq!(claims, vec![coin(1000, "uatom")]);
+    }
+
+    #[test]
+    fn test_match_orders() {
+        use crate::interchainswap_handler::{do_ibc_packet_receive, on_packet_success};
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        // maker_a made order1 here, maker_b made the mirror order2 on the counterparty
+        let order = |id: &str, maker: &str, taker: &str, deposits: Vec<Coin>| {
+            MultiAssetDepositOrder {
+                id: id.to_string(),
+                pool_id: pool_id.clone(),
+                chain_id: "chain-a".to_string(),
+                source_maker: maker.to_string(),
+                destination_taker: taker.to_string(),
+                deposits,
+                status: OrderStatus::Pending,
+                created_at: 0,
+                recipient: None,
+                permit_pubkey: None,
+            }
+        };
+        let order1 = order(
+            "order1",
+            "maker_a",
+            "taker_b",
+            vec![coin(10, "uatom"), coin(10, "uosmo")],
+        );
+        let order2 = order(
+            "order2",
+            "maker_b",
+            "taker_a",
+            vec![coin(10, "uosmo"), coin(10, "uatom")],
+        );
+        let reset = |storage: &mut dyn Storage| {
+            POOLS.save(storage, &pool_id, &pool).unwrap();
+            POOL_TOKENS_LIST.save(storage, &pool_id, &"lp_token".to_string()).unwrap();
+            for order in [&order1, &order2] {
+                let key = format!("{}-{}", pool_id, order.id);
+                MULTI_ASSET_DEPOSIT_ORDERS.save(storage, key, order).unwrap();
+                let active_key =
+                    format!("{}-{}-{}", order.source_maker, pool_id, order.destination_taker);
+                ACTIVE_ORDERS.save(storage, active_key, order).unwrap();
+            }
+        };
+        reset(deps.as_mut().storage);
+
+        let match_msg = |order_id: &str, counter_order_id: &str| {
+            MsgMatchMultiAssetDepositRequest {
+                sender: "anyone".to_string(),
+                pool_id: pool_id.clone(),
+                order_id: order_id.to_string(),
+                counter_order_id: counter_order_id.to_string(),
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                memo: None,
+            }
+        };
+
+        // the order made here goes first
+        let msg = ExecuteMsg::MatchOrders(match_msg("order2", "order1"));
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap_err();
+
+        // the deposits have to mirror each other
+        let deposits = vec![coin(20, "uosmo"), coin(10, "uatom")];
+        let uneven = order("order3", "maker_b", "taker_a", deposits);
+        MULTI_ASSET_DEPOSIT_ORDERS
+            .save(deps.as_mut().storage, format!("{}-order3", pool_id), &uneven)
+            .unwrap();
+        let msg = ExecuteMsg::MatchOrders(match_msg("order1", "order3"));
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap_err();
+
+        let msg = ExecuteMsg::MatchOrders(match_msg("order1", "order2"));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap();
+        let mut packet_data: InterchainSwapPacketData = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => from_binary(data).unwrap(),
+            _ => panic!("expected packet"),
+        };
+        assert_eq!(packet_data.r#type, InterchainMessageType::MatchMultiDeposit);
+        let state_change: StateChange =
+            from_binary(packet_data.state_change.as_ref().unwrap()).unwrap();
+        let shares = state_change.shares.unwrap();
+        assert!(!shares.is_zero());
+
+        // the orders settle once the counterparty acknowledges the match
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let res = on_packet_success(deps.as_mut(), mock_env(), packet.clone(), None).unwrap();
+        let minted = |messages: &[SubMsg]| match &messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => match from_binary(msg).unwrap() {
+                Cw20ExecuteMsg::Mint { recipient, amount } => (recipient, amount),
+                _ => panic!("expected mint"),
+            },
+            _ => panic!("expected wasm execute"),
+        };
+        assert_eq!(minted(&res.messages), ("maker_a".to_string(), shares / Uint128::new(2)));
+        let settled = POOLS.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(settled.supply, coin(1000 + shares.u128(), pool_id.clone()));
+        assert_eq!(settled.assets[0].balance, coin(1010, "uatom"));
+        assert_eq!(settled.assets[1].balance, coin(1010, "uosmo"));
+        for order in [&order1, &order2] {
+            let key = format!("{}-{}", pool_id, order.id);
+            let status = MULTI_ASSET_DEPOSIT_ORDERS.load(&deps.storage, key).unwrap().status;
+            assert_eq!(status, OrderStatus::Complete);
+            let active_key =
+                format!("{}-{}-{}", order.source_maker, pool_id, order.destination_taker);
+            assert!(!ACTIVE_ORDERS.has(&deps.storage, active_key));
+        }
+        on_packet_success(deps.as_mut(), mock_env(), packet, None).unwrap_err();
+
+        // receiving the match of the counterparty settles the order made here as its mirror
+        reset(deps.as_mut().storage);
+        packet_data.data = to_binary(&match_msg("order2", "order1")).unwrap();
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let res = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+        assert_eq!(minted(&res.messages), ("maker_a".to_string(), shares / Uint128::new(2)));
+        let settled = POOLS.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(settled.supply, coin(1000 + shares.u128(), pool_id.clone()));
+    }
+
+    #[test]
+    fn test_withdrawal_queue() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use crate::msg::WithdrawalQueueResponse;
+        use crate::state::QueuedWithdrawal;
+        use cosmwasm_std::testing::{mock_ibc_packet_recv, MOCK_CONTRACT_ADDR};
+        use cosmwasm_std::{attr, BankMsg};
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        // a pending order escrows 100uatom, the balance drifted below the pool's 1000uatom
+        let order = MultiAssetDepositOrder {
+            id: "order1".to_string(),
+            pool_id: pool_id.clone(),
+            chain_id: "chain-a".to_string(),
+            source_maker: "maker".to_string(),
+            destination_taker: "taker".to_string(),
+            deposits: vec![coin(100, "uatom"), coin(100, "uosmo")],
+            status: OrderStatus::Pending,
+            created_at: 0,
+            recipient: None,
+            permit_pubkey: None,
+        };
+        let order_key = format!("{}-{}", pool_id, order.id);
+        save_order(deps.as_mut().storage, order_key, &order).unwrap();
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![coin(120, "uatom")]);
+
+        let withdraw = MsgMultiAssetWithdrawRequest {
+            pool_id: pool_id.clone(),
+            receiver: "lp_b".to_string(),
+            counterparty_receiver: "lp_a".to_string(),
+            pool_token: coin(50, pool_id.clone()),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::MultiWithdraw,
+            data: to_binary(&withdraw).unwrap(),
+            state_change: Some(
+                to_binary(&StateChange {
+                    in_tokens: None,
+                    out_tokens: Some(vec![coin(50, "uatom"), coin(50, "uosmo")]),
+                    pool_tokens: Some(vec![coin(50, pool_id.clone())]),
+                    pool_id: None,
+                    multi_deposit_order_id: None,
+                    source_chain_id: None,
+                    shares: None,
+                })
+                .unwrap(),
+            ),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let res = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+        assert!(res.messages.is_empty());
+        let event = res.events.iter().find(|event| event.ty == "withdrawal_queued").unwrap();
+        assert!(event.attributes.contains(&attr("receiver", "lp_a")));
+        assert_eq!(
+            POOLS.load(&deps.storage, &pool_id).unwrap().assets[0].balance,
+            coin(950, "uatom")
+        );
+
+        let msg = QueryMsg::WithdrawalQueue {
+            denom: "uatom".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let queue: WithdrawalQueueResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg.clone()).unwrap()).unwrap();
+        assert_eq!(
+            queue.withdrawals,
+            vec![QueuedWithdrawal {
+                id: 1,
+                pool_id: pool_id.clone(),
+                receiver: "lp_a".to_string(),
+                token: coin(50, "uatom"),
+                queued_at: mock_env().block.time.seconds(),
+            }]
+        );
+        let reconcile_query = QueryMsg::Reconcile {
+            pool_id: pool_id.clone(),
+            denom: None,
+            start_after: None,
+            limit: None,
+        };
+        let reconcile: ReconcileResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), reconcile_query).unwrap()).unwrap();
+        assert_eq!(reconcile.escrow, Uint128::new(150));
+
+        // the queue waits until the balance covers the escrow again
+        let process = ExecuteMsg::ProcessWithdrawalQueue {
+            denom: "uatom".to_string(),
+            limit: None,
+        };
+        let info = mock_info("anyone", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), process.clone()).unwrap();
+        assert!(res.messages.is_empty());
+
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![coin(150, "uatom")]);
+        let res = execute(deps.as_mut(), mock_env(), info, process).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "lp_a".to_string(),
+                amount: vec![coin(50, "uatom")],
+            })
+        );
+        let queue: WithdrawalQueueResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(queue.withdrawals.is_empty());
+    }
+
+    #[test]
+    fn test_escrowed_funds() {
+        let mut deps = mock_dependencies();
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        // only the maker's deposits on this chain are held
+        let mut order = MultiAssetDepositOrder {
+            id: "order1".to_string(),
+            pool_id: pool_id.clone(),
+            chain_id: "chain-a".to_string(),
+            source_maker: "maker".to_string(),
+            destination_taker: "taker".to_string(),
+            deposits: vec![coin(100, "uatom"), coin(100, "uosmo")],
+            status: OrderStatus::Pending,
+            created_at: 0,
+            recipient: None,
+            permit_pubkey: None,
+        };
+        let order_key = format!("{}-{}", pool_id, order.id);
+        save_order(deps.as_mut().storage, order_key.clone(), &order).unwrap();
+        assert_eq!(escrowed_funds(&deps.storage, "uatom").unwrap(), Uint128::new(100));
+        assert_eq!(escrowed_funds(&deps.storage, "uosmo").unwrap(), Uint128::zero());
+
+        // claims stored before the totals were kept are picked up by the recount
+        let key = ("channel-0", "sender");
+        SWAP_CLAIMS.save(deps.as_mut().storage, key, &vec![coin(5, "uatom")]).unwrap();
+        recount_escrowed_funds(deps.as_mut().storage).unwrap();
+        assert_eq!(escrowed_funds(&deps.storage, "uatom").unwrap(), Uint128::new(105));
+
+        order.status = OrderStatus::Complete;
+        save_order(deps.as_mut().storage, order_key, &order).unwrap();
+        assert_eq!(escrowed_funds(&deps.storage, "uatom").unwrap(), Uint128::new(5));
+    }
+
+    #[test]
+    fn test_channels() {
+        use crate::ibc::{ibc_channel_close, ibc_channel_connect};
+        use crate::msg::{ChannelResponse, ChannelsResponse};
+        use crate::utils::ICS101_VERSION;
+        use cosmwasm_std::testing::{mock_ibc_channel, mock_ibc_channel_connect_ack};
+        use cosmwasm_std::{IbcChannelCloseMsg, IbcEndpoint, IbcOrder};
+
+        let mut deps = setup();
+        for channel_id in ["channel-0", "channel-1"] {
+            let msg = mock_ibc_channel_connect_ack(channel_id, IbcOrder::Unordered, ICS101_VERSION);
+            ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap();
+        }
+
+        let pool = |id: &str| test_pool(id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, "pool1", &pool("pool1")).unwrap();
+        POOLS.save(deps.as_mut().storage, "pool2", &pool("pool2")).unwrap();
+
+        // closing is refused from this end, a confirmed close is recorded
+        let channel = mock_ibc_channel("channel-1", IbcOrder::Unordered, ICS101_VERSION);
+        let msg = IbcChannelCloseMsg::new_init(channel.clone());
+        let err = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(err, ContractError::CannotCloseChannel {});
+        let msg = IbcChannelCloseMsg::new_confirm(channel);
+        ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let res: ChannelsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Channels {}).unwrap()).unwrap();
+        let counterparty = IbcEndpoint {
+            port_id: "their_port".to_string(),
+            channel_id: "channel-7".to_string(),
+        };
+        assert_eq!(
+            res.channels,
+            vec![
+                ChannelResponse {
+                    id: "channel-0".to_string(),
+                    counterparty_endpoint: counterparty.clone(),
+                    connection_id: "connection-2".to_string(),
+                    counterparty_chain_id: None,
+                    open: true,
+                    pool_count: 2,
+                },
+                ChannelResponse {
+                    id: "channel-1".to_string(),
+                    counterparty_endpoint: counterparty,
+                    connection_id: "connection-2".to_string(),
+                    counterparty_chain_id: None,
+                    open: false,
+                    pool_count: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ownership() {
+        let mut deps = setup();
+
+        let msg = ExecuteMsg::UpdateConfig {
+            token_code_id: Some(2),
+            router: None,
+            default_timeout_seconds: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), msg.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        let config = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(config.token_code_id, 2);
+        assert_eq!(config.router, "");
+
+        // ownership moves only once the new admin accepts it
+        let msg = ExecuteMsg::TransferOwnership { address: "new_admin".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), msg.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        let config: QueryConfigResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.admin, "admin");
+        assert_eq!(config.pending_admin, Some("new_admin".to_string()));
+
+        let msg = ExecuteMsg::AcceptOwnership {};
+        execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), msg.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("new_admin", &[]), msg.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("new_admin", &[]), msg).unwrap_err();
+        let config: QueryConfigResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.admin, "new_admin");
+        assert_eq!(config.pending_admin, None);
+
+        let msg = ExecuteMsg::UpdateConfig {
+            token_code_id: None,
+            router: Some("router".into()),
+            default_timeout_seconds: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("new_admin", &[]), msg).unwrap();
+        assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().router, "router");
+    }
+
+    #[test]
+    fn test_pool_status_matrix() {
+        let mut deps = setup();
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "trader".to_string(),
+            pool_id: "pool1".to_string(),
+            token_in: coin(100, "uatom"),
+            token_out: coin(90, "uosmo"),
+            slippage: 100,
+            recipient: "trader".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let messages = [
+            ExecuteMsg::SingleAssetDeposit(MsgSingleAssetDepositRequest {
+                pool_id: "pool1".to_string(),
+                sender: "lp".to_string(),
+                token: coin(100, "uatom"),
+                lp_allocation: LPAllocation::TakerChain,
+                lp_taker: String::new(),
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                memo: None,
+                recipient: None,
+                refund_to: None,
+            }),
+            ExecuteMsg::SingleSidedDeposit {
+                pool_id: "pool1".to_string(),
+                token_in: coin(100, "uatom"),
+                min_shares: Uint128::zero(),
+            },
+            ExecuteMsg::MakeMultiAssetDeposit(MsgMakeMultiAssetDepositRequest {
+                pool_id: "pool1".to_string(),
+                deposits: vec![
+                    DepositAsset { sender: "maker".to_string(), balance: coin(100, "uatom") },
+                    DepositAsset { sender: "taker".to_string(), balance: coin(100, "uosmo") },
+                ],
+                chain_id: "chain-a".to_string(),
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                memo: None,
+                recipient: None,
+                permit_pubkey: None,
+                refund_to: None,
+            }),
+            ExecuteMsg::TakeMultiAssetDeposit(MsgTakeMultiAssetDepositRequest {
+                sender: "taker".to_string(),
+                pool_id: "pool1".to_string(),
+                order_id: "1".to_string(),
+                lp_allocation: LPAllocation::MakerChain,
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                memo: None,
+                recipient: None,
+                permit: None,
+                refund_to: None,
+            }),
+            ExecuteMsg::MatchOrders(MsgMatchMultiAssetDepositRequest {
+                sender: "anyone".to_string(),
+                pool_id: "pool1".to_string(),
+                order_id: "1".to_string(),
+                counter_order_id: "2".to_string(),
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                memo: None,
+            }),
+            ExecuteMsg::Swap(swap.clone()),
+            ExecuteMsg::BatchSwap { legs: vec![swap] },
+        ];
+
+        let statuses = [
+            (PoolStatus::Initialized, ContractError::PoolNotTaken),
+            (PoolStatus::Bootstrap, ContractError::NotReadyForSwap),
+            (PoolStatus::Cancelled, ContractError::NotReadyForSwap),
+            (PoolStatus::Frozen, ContractError::PoolNotOpen { status: PoolStatus::Frozen }),
+            (PoolStatus::Draining, ContractError::PoolNotOpen { status: PoolStatus::Draining }),
+            (PoolStatus::Retired, ContractError::PoolNotOpen { status: PoolStatus::Retired }),
+        ];
+        for (status, expected) in statuses {
+            let pool = test_pool("pool1", status.clone());
+            POOLS.save(deps.as_mut().storage, "pool1", &pool).unwrap();
+
+            for msg in messages.iter() {
+                let res = execute(deps.as_mut(), mock_env(), mock_info("lp", &[]), msg.clone());
+                let bootstrap_deposit = status == PoolStatus::Bootstrap
+                    && matches!(
+                        msg,
+                        ExecuteMsg::SingleAssetDeposit(_) | ExecuteMsg::SingleSidedDeposit { .. }
+                    );
+                if bootstrap_deposit {
+                    // passes the status check, fails on the funds sent
+                    assert_ne!(res.unwrap_err(), expected, "{:?}", msg);
+                } else {
+                    assert_eq!(res.unwrap_err(), expected, "{:?} in a {:?} pool", msg, status);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_received_packet_status() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        // the maker chain copy of a pool not taken yet
+        let pool = test_pool("pool1", PoolStatus::Initialized);
+        POOLS.save(deps.as_mut().storage, "pool1", &pool).unwrap();
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "trader".to_string(),
+            pool_id: "pool1".to_string(),
+            token_in: coin(100, "uosmo"),
+            token_out: coin(90, "uatom"),
+            slippage: 100,
+            recipient: "trader".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let set_status = MsgSetPoolStatusRequest {
+            pool_id: "pool1".to_string(),
+            status: PoolStatus::Frozen,
+            drain_deadline: None,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let receive = |deps: DepsMut, r#type, data| {
+            let packet_data = InterchainSwapPacketData {
+                r#type,
+                data,
+                state_change: None,
+                memo: None,
+            };
+            let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+            do_ibc_packet_receive(deps, mock_env(), &packet)
+        };
+
+        let swap_data = to_binary(&swap).unwrap();
+        let status_data = to_binary(&set_status).unwrap();
+        let err = receive(deps.as_mut(), InterchainMessageType::LeftSwap, swap_data.clone())
+            .unwrap_err();
+        assert_eq!(err, ContractError::PoolNotTaken);
+        let err = receive(deps.as_mut(), InterchainMessageType::SetPoolStatus, status_data)
+            .unwrap_err();
+        assert_eq!(err, ContractError::InvalidStatus);
+
+        let mut pool = pool;
+        pool.status = PoolStatus::Frozen;
+        POOLS.save(deps.as_mut().storage, "pool1", &pool).unwrap();
+        let err = receive(deps.as_mut(), InterchainMessageType::LeftSwap, swap_data).unwrap_err();
+        assert_eq!(err, ContractError::PoolNotOpen { status: PoolStatus::Frozen });
+    }
+
+    #[test]
+    fn test_packet_timeouts() {
+        use cosmwasm_std::IbcTimeoutBlock;
+
+        let mut deps = mock_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            token_code_id: 1,
+            router: "".to_string(),
+            default_timeout_seconds: Some(30),
+            lp_token_kind: None,
+        };
+        let err = instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), instantiate_msg)
+            .unwrap_err();
+        assert_eq!(err, ContractError::InvalidTimeout { timeout: 30, min: 60, max: 86400 });
+        let instantiate_msg = InstantiateMsg {
+            token_code_id: 1,
+            router: "".to_string(),
+            default_timeout_seconds: Some(900),
+            lp_token_kind: None,
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), instantiate_msg).unwrap();
+
+        let mut pool = InterchainLiquidityPool {
+            source_chain_id: mock_env().block.chain_id,
+            destination_chain_id: "osmosis-1".to_string(),
+            ..test_pool("pool1", PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, "pool1", &pool).unwrap();
+
+        let swap = |timeout_height, timeout_timestamp| MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: "pool1".to_string(),
+            token_in: coin(10, "uatom"),
+            token_out: coin(1, "uosmo"),
+            slippage: 100,
+            recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height,
+            timeout_timestamp,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let timeout = |deps: DepsMut, msg: MsgSwapRequest| -> Result<IbcTimeout, ContractError> {
+            let info = mock_info("sender", &[coin(10, "uatom")]);
+            let res = execute(deps, mock_env(), info, ExecuteMsg::Swap(msg))?;
+            match &res.messages[0].msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket { timeout, .. }) => Ok(timeout.clone()),
+                _ => panic!("expected ibc packet"),
+            }
+        };
+        let now = mock_env().block.time;
+
+        // the contract default, then the pool override
+        let res = timeout(deps.as_mut(), swap(0, 0)).unwrap();
+        assert_eq!(res, IbcTimeout::with_timestamp(now.plus_seconds(900)));
+        pool.packet_timeout_seconds = Some(1800);
+        POOLS.save(deps.as_mut().storage, "pool1", &pool).unwrap();
+        let res = timeout(deps.as_mut(), swap(0, 0)).unwrap();
+        assert_eq!(res, IbcTimeout::with_timestamp(now.plus_seconds(1800)));
+
+        // the timeouts of the message take precedence
+        let at = now.plus_seconds(3600);
+        let res = timeout(deps.as_mut(), swap(0, at.nanos())).unwrap();
+        assert_eq!(res, IbcTimeout::with_timestamp(at));
+        let res = timeout(deps.as_mut(), swap(500, 0)).unwrap();
+        assert_eq!(res, IbcTimeout::with_block(IbcTimeoutBlock { revision: 1, height: 500 }));
+        let err = timeout(deps.as_mut(), swap(0, now.minus_seconds(1).nanos())).unwrap_err();
+        assert_eq!(err, ContractError::InvalidTimeout { timeout: 0, min: 60, max: 86400 });
+
+        let msg = ExecuteMsg::UpdateConfig {
+            token_code_id: None,
+            router: None,
+            default_timeout_seconds: Some(100000),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+        let msg = ExecuteMsg::UpdateConfig {
+            token_code_id: None,
+            router: None,
+            default_timeout_seconds: Some(120),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().default_timeout_seconds, 120);
+    }
+
+    #[test]
+    fn test_counterparty_chain_id() {
+        use crate::interchainswap_handler::on_received_make_pool;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let make_pool = |denom: &str, source_chain_id: &str, destination_chain_id: &str| {
+            MsgMakePoolRequest {
+                source_chain_id: source_chain_id.to_string(),
+                destination_chain_id: destination_chain_id.to_string(),
+                ..make_pool_request(denom)
+            }
+        };
+        let chain_id = mock_env().block.chain_id;
+
+        // the first pool binds the counterparty chain of the channel
+        execute_make_pool(deps.as_mut(), make_pool("uatom", "", "chain-b")).unwrap();
+        let pool_id = get_pool_id_with_tokens(
+            &[coin(1000, "uatom"), coin(1000, "uosmo")],
+            chain_id.clone(),
+            "chain-b".to_string(),
+        );
+        let pool = POOLS.load(deps.as_ref().storage, &pool_id).unwrap();
+        assert_eq!(pool.source_chain_id, chain_id);
+        assert_eq!(pool.destination_chain_id, "chain-b");
+
+        let err = execute_make_pool(deps.as_mut(), make_pool("ujuno", "", "chain-c")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::UnexpectedChainId {
+                channel_id: "channel-0".to_string(),
+                chain_id: "chain-c".to_string(),
+                expected: "chain-b".to_string(),
+            }
+        );
+        execute_make_pool(deps.as_mut(), make_pool("ujuno", "", "")).unwrap();
+        let pool_id = get_pool_id_with_tokens(
+            &[coin(1000, "ujuno"), coin(1000, "uosmo")],
+            chain_id.clone(),
+            "chain-b".to_string(),
+        );
+        assert!(POOLS.has(deps.as_ref().storage, &pool_id));
+
+        // pools made on the counterparty have to come from the bound chain
+        let msg = make_pool("uatom", "chain-c", &chain_id);
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::MakePool,
+            data: to_binary(&msg).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let err = on_received_make_pool(deps.as_mut(), mock_env(), &packet, msg).unwrap_err();
+        assert!(matches!(err, ContractError::UnexpectedChainId { .. }));
+    }
+
+    #[test]
+    fn test_lp_token_marketing() {
+        use crate::msg::{LpTokenMarketing, TokenInstantiateMsg};
+        use cw20::Logo;
+
+        let mut deps = setup();
+
+        let make_pool = |denom: &str, lp_token_marketing: Option<LpTokenMarketing>| {
+            ExecuteMsg::MakePool(MsgMakePoolRequest {
+                lp_token_marketing,
+                ..make_pool_request(denom)
+            })
+        };
+        let lp_token = |res: Response| -> (String, TokenInstantiateMsg) {
+            match &res.messages[0].msg {
+                CosmosMsg::Wasm(WasmMsg::Instantiate { label, msg, .. }) => {
+                    (label.clone(), from_binary(msg).unwrap())
+                }
+                _ => panic!("expected token instantiation"),
+            }
+        };
+        let pool_id = |denom: &str| {
+            get_pool_id_with_tokens(
+                &[coin(1000, denom), coin(1000, "uosmo")],
+                "chain-a".to_string(),
+                "chain-b".to_string(),
+            )
+        };
+
+        let marketing = LpTokenMarketing {
+            project: Some("Atom pool".to_string()),
+            description: None,
+            logo_url: Some("https://example.com/atom.png".to_string()),
+        };
+        let info = mock_info("creator", &[coin(1000, "uatom")]);
+        let res = execute(deps.as_mut(), mock_env(), info, make_pool("uatom", Some(marketing)))
+            .unwrap();
+        let (label, token) = lp_token(res);
+        assert_eq!(label, format!("ics101 LP token {}", pool_id("uatom")));
+        let info = token.marketing.unwrap();
+        assert_eq!(info.project, Some("Atom pool".to_string()));
+        assert_eq!(
+            info.description,
+            Some(format!("Liquidity of ics101 pool {}", pool_id("uatom")))
+        );
+        assert_eq!(info.logo, Some(Logo::Url("https://example.com/atom.png".to_string())));
+
+        // pools without marketing info still get a label and description of their own
+        let info = mock_info("creator", &[coin(1000, "ujuno")]);
+        let res = execute(deps.as_mut(), mock_env(), info, make_pool("ujuno", None)).unwrap();
+        let (other_label, token) = lp_token(res);
+        assert_ne!(other_label, label);
+        let info = token.marketing.unwrap();
+        assert_eq!(info.project, None);
+        assert_eq!(
+            info.description,
+            Some(format!("Liquidity of ics101 pool {}", pool_id("ujuno")))
+        );
+        assert_eq!(info.logo, None);
+    }
+
+    #[test]
+    fn test_weighted_pool_assets() {
+        let mut deps = setup();
+
+        let asset = |side: PoolSide, balance: Coin, weight: u32| PoolAsset {
+            side,
+            balance,
+            weight,
+            decimal: 6,
+        };
+        let liquidity = vec![
+            asset(PoolSide::SOURCE, coin(1000, "uatom"), 40),
+            asset(PoolSide::SOURCE, coin(500, "ujuno"), 20),
+            asset(PoolSide::DESTINATION, coin(2000, "uosmo"), 40),
+        ];
+        let make_pool = ExecuteMsg::MakePool(MsgMakePoolRequest {
+            liquidity: liquidity.clone(),
+            ..make_pool_request("uatom")
+        });
+
+        // every asset of this chain is funded when making the pool
+        let info = mock_info("creator", &[coin(1000, "uatom")]);
+        execute(deps.as_mut(), mock_env(), info, make_pool.clone()).unwrap_err();
+        let info = mock_info("creator", &[coin(1000, "uatom"), coin(500, "ujuno")]);
+        execute(deps.as_mut(), mock_env(), info, make_pool).unwrap();
+        let pool_id = get_pool_id_with_tokens(
+            &[coin(1000, "uatom"), coin(500, "ujuno"), coin(2000, "uosmo")],
+            "chain-a".to_string(),
+            "chain-b".to_string(),
+        );
+        let mut pool = POOLS.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(pool.assets, liquidity);
+
+        pool.status = PoolStatus::Active;
+        pool.supply = coin(1000, pool_id.clone());
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        assert_eq!(pool.local_shares(Uint128::new(1000)), Uint128::new(600));
+
+        // both assets of a swap can't be on the same chain
+        let swap = ExecuteMsg::Swap(MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "trader".to_string(),
+            pool_id: pool_id.clone(),
+            token_in: coin(100, "uatom"),
+            token_out: coin(40, "ujuno"),
+            slippage: 100,
+            recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            forward: None,
+            memo: None,
+            refund_to: None,
+        });
+        let info = mock_info("trader", &[coin(100, "uatom")]);
+        let err = execute(deps.as_mut(), mock_env(), info, swap).unwrap_err();
+        assert!(err.to_string().contains("Swaps go from an asset of this chain"));
+
+        // a deposit by ratio takes every asset at the pool ratio, the local ones first
+        let msg = ExecuteMsg::MakeMultiAssetDepositByRatio(MsgMakeMultiAssetDepositByRatioRequest {
+            pool_id: pool_id.clone(),
+            total_value: coin(800, "uosmo"),
+            tolerance: 500,
+            counterparty_sender: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            chain_id: "chain-a".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            recipient: None,
+            permit_pubkey: None,
+            refund_to: None,
+        });
+        let info = mock_info("maker", &[coin(160, "uatom")]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::DepositRatioMoved {
+                needed: coin(80, "ujuno"),
+                sent: coin(0, "ujuno"),
+            }
+        );
+        let info = mock_info("maker", &[coin(160, "uatom"), coin(80, "ujuno")]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let (_, order) = MULTI_ASSET_DEPOSIT_ORDERS
+            .range(&deps.storage, None, None, Order::Ascending)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            order.deposits,
+            vec![coin(160, "uatom"), coin(80, "ujuno"), coin(320, "uosmo")]
+        );
+
+        // the taker of an order made on the counterparty attaches every local asset
+        let counter = MultiAssetDepositOrder {
+            id: "counter".to_string(),
+            chain_id: "chain-b".to_string(),
+            source_maker: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            destination_taker: "maker".to_string(),
+            deposits: vec![coin(320, "uosmo"), coin(160, "uatom"), coin(80, "ujuno")],
+            ..order.clone()
+        };
+        assert!(order.mirrors(&counter, &pool));
+        let key = format!("{}-{}", pool_id, counter.id);
+        MULTI_ASSET_DEPOSIT_ORDERS.save(deps.as_mut().storage, key, &counter).unwrap();
+        let msg = QueryMsg::RequiredCounterDeposit {
+            pool_id,
+            order_id: "counter".to_string(),
+        };
+        let required: RequiredCounterDepositResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(required.funds, vec![coin(160, "uatom"), coin(80, "ujuno")]);
+    }
+
+    #[test]
+    fn test_weighted_pool_withdraw_receive() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use cosmwasm_std::testing::{mock_ibc_packet_recv, MOCK_CONTRACT_ADDR};
+        use cosmwasm_std::BankMsg;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, "uatom"),
+                    weight: 40,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(500, "ujuno"),
+                    weight: 20,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(2000, "uosmo"),
+                    weight: 40,
+                    decimal: 6,
+                },
+            ],
+            source_chain_id: "chain-b".to_string(),
+            destination_chain_id: "chain-a".to_string(),
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(1000, "uatom"), coin(500, "ujuno")]);
+
+        // the withdrawal made on the counterparty lists every asset of the pool
+        let amm = InterchainMarketMaker::new(&pool, pool.swap_fee);
+        let out_tokens = amm.multi_asset_withdraw(coin(100, pool_id.clone())).unwrap();
+        assert_eq!(out_tokens.len(), 3);
+        let withdraw = MsgMultiAssetWithdrawRequest {
+            pool_id: pool_id.clone(),
+            receiver: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            counterparty_receiver: "lp_a".to_string(),
+            pool_token: coin(100, pool_id.clone()),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::MultiWithdraw,
+            data: to_binary(&withdraw).unwrap(),
+            state_change: Some(
+                to_binary(&StateChange {
+                    in_tokens: None,
+                    out_tokens: Some(out_tokens.clone()),
+                    pool_tokens: Some(vec![coin(100, pool_id.clone())]),
+                    pool_id: None,
+                    multi_deposit_order_id: None,
+                    source_chain_id: None,
+                    shares: None,
+                })
+                .unwrap(),
+            ),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let res = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+
+        // both local assets are paid out, the remote one on the counterparty
+        let paid: Vec<Coin> = res
+            .messages
+            .iter()
+            .flat_map(|sub_msg| match &sub_msg.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                    assert_eq!(to_address, "lp_a");
+                    amount.clone()
+                }
+                _ => vec![],
+            })
+            .collect();
+        assert_eq!(paid, out_tokens[..2].to_vec());
+        let pool = POOLS.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(pool.supply, coin(900, pool_id.clone()));
+        for (asset, out) in pool.assets.iter().zip(out_tokens) {
+            let before = amm.pool.find_asset_by_denom(&out.denom).unwrap();
+            assert_eq!(asset.balance.amount + out.amount, before.balance.amount);
+        }
+    }
+
+    #[test]
+    fn test_estimate_swap() {
+        let mut deps = mock_dependencies();
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(2000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let quote = |msg: QueryMsg| -> Coin {
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+        };
+
+        // the estimates match the coin based queries, whatever the unused amount
+        let left = quote(QueryMsg::LeftSwap {
+            pool_id: pool_id.clone(),
+            token_in: coin(100, "uatom"),
+            token_out: coin(1, "uosmo"),
+        });
+        let estimate = quote(QueryMsg::EstimateLeftSwap {
+            pool_id: pool_id.clone(),
+            denom_in: "uatom".to_string(),
+            amount_in: Uint128::new(100),
+            denom_out: "uosmo".to_string(),
+        });
+        assert_eq!(estimate, left);
+        assert_eq!(estimate.denom, "uosmo");
+
+        let right = quote(QueryMsg::RightSwap {
+            pool_id: pool_id.clone(),
+            token_in: coin(1, "uatom"),
+            token_out: coin(100, "uosmo"),
+        });
+        let estimate = quote(QueryMsg::EstimateRightSwap {
+            pool_id,
+            denom_in: "uatom".to_string(),
+            denom_out: "uosmo".to_string(),
+            amount_out: Uint128::new(100),
+        });
+        assert_eq!(estimate, right);
+        assert_eq!(estimate.denom, "uatom");
+    }
+
+    #[test]
+    fn test_stable_pool() {
+        let mut deps = setup();
+
+        let make_pool = |pool_type: PoolType| {
+            ExecuteMsg::MakePool(MsgMakePoolRequest {
+                liquidity: vec![
+                    PoolAsset {
+                        side: PoolSide::SOURCE,
+                        balance: coin(1_000_000_000, "uusdc"),
+                        weight: 50,
+                        decimal: 6,
+                    },
+                    PoolAsset {
+                        side: PoolSide::DESTINATION,
+                        balance: coin(1_000_000_000, "uusdc.axl"),
+                        weight: 50,
+                        decimal: 6,
+                    },
+                ],
+                pool_type,
+                ..make_pool_request("uatom")
+            })
+        };
+        let info = mock_info("creator", &[coin(1_000_000_000, "uusdc")]);
+        let msg = make_pool(PoolType::Stable { amplification: 0 });
+        execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        let msg = make_pool(PoolType::Stable { amplification: 100 });
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let pool_id = get_pool_id_with_tokens(
+            &[coin(1_000_000_000, "uusdc"), coin(1_000_000_000, "uusdc.axl")],
+            "chain-a".to_string(),
+            "chain-b".to_string(),
+        );
+        let mut pool = POOLS.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!(pool.pool_type, PoolType::Stable { amplification: 100 });
+        pool.status = PoolStatus::Active;
+        pool.supply = coin(2_000_000_000, pool_id.clone());
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        assert_eq!(pool.spot_price(), Some(Decimal::one()));
+
+        let estimate = |deps: Deps| -> Coin {
+            let msg = QueryMsg::EstimateLeftSwap {
+                pool_id: pool_id.clone(),
+                denom_in: "uusdc".to_string(),
+                amount_in: Uint128::new(100_000_000),
+                denom_out: "uusdc.axl".to_string(),
+            };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        // a tenth of the pool moves the stable curve far less than the weighted one
+        let stable = estimate(deps.as_ref());
+        pool.pool_type = PoolType::Weighted;
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let weighted = estimate(deps.as_ref());
+        assert!(stable.amount > Uint128::new(99_000_000));
+        assert!(weighted.amount < Uint128::new(91_000_000));
+
+        // taking the quoted output back costs the input, within the fee and rounding
+        pool.pool_type = PoolType::Stable { amplification: 100 };
+        let amm = InterchainMarketMaker::new(&pool, pool.swap_fee);
+        let offer = amm.compute_offer_amount(coin(0, "uusdc"), stable.clone()).unwrap();
+        assert!(offer.amount.abs_diff(Uint128::new(100_000_000)) <= Uint128::new(2));
+
+        // shares of a single asset deposit follow the invariant
+        let shares = amm.deposit_single_asset(&coin(10_000_000, "uusdc")).unwrap();
+        assert!(shares.amount > Uint128::new(9_990_000));
+        assert!(shares.amount <= Uint128::new(10_000_000));
+    }
+
+    #[test]
+    fn test_take_pool_required_funds() {
+        let mut deps = setup();
+
+        // taker chain, waiting for the taker's assets
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1000, "uatom"),
+                    weight: 40,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, "uosmo"),
+                    weight: 30,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(500, "ujuno"),
+                    weight: 30,
+                    decimal: 6,
+                },
+            ],
+            supply: coin(0, pool_id.clone()),
+            ..test_pool(&pool_id, PoolStatus::Initialized)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let msg = QueryMsg::TakePoolRequiredFunds {
+            pool_id: pool_id.clone(),
+        };
+        let required: TakePoolRequiredFundsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            required,
+            TakePoolRequiredFundsResponse {
+                funds: vec![coin(1000, "uosmo"), coin(500, "ujuno")],
+                taker: "taker".to_string(),
+            }
+        );
+
+        let take = ExecuteMsg::TakePool(MsgTakePoolRequest {
+            counter_creator: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            creator: "taker".to_string(),
+            pool_id,
+            lp_allocation: LPAllocation::TakerChain,
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+            refund_to: None,
+        });
+        let info = mock_info("taker", &[coin(1000, "uosmo")]);
+        execute(deps.as_mut(), mock_env(), info, take.clone()).unwrap_err();
+        let info = mock_info("taker", &required.funds);
+        execute(deps.as_mut(), mock_env(), info, take).unwrap();
+    }
+
+    #[test]
+    fn test_protocol_fees() {
+        use crate::interchainswap_handler::on_packet_success;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use cosmwasm_std::BankMsg;
+
+        let mut deps = setup();
+
+        let set_fee = ExecuteMsg::SetProtocolFee {
+            share_bps: 2000,
+            collector: Some("dao".to_string()),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("user", &[]), set_fee.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), set_fee).unwrap();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            swap_fee: 100,
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let swap = MsgSwapRequest {
+            swap_type: SwapMsgType::LEFT,
+            sender: "sender".to_string(),
+            pool_id: pool_id.clone(),
+            token_in: coin(10000, "uatom"),
+            token_out: coin(400, "uosmo"),
+            slippage: 100,
+            recipient: "recipient".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            route: None,
+            memo: None,
+            refund_to: None,
+            forward: None,
+        };
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![coin(400, "uosmo")]),
+            pool_tokens: None,
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: to_binary(&swap).unwrap(),
+            state_change: Some(to_binary(&state_change).unwrap()),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        on_packet_success(deps.as_mut(), mock_env(), packet, None).unwrap();
+
+        // a fifth of the 100 uatom fee is skimmed for the protocol
+        let msg = QueryMsg::CollectedFees { pool_id: pool_id.clone() };
+        let fees: Vec<Coin> = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(fees, vec![coin(80, "uatom")]);
+        let msg = QueryMsg::ProtocolFees { pool_id: pool_id.clone() };
+        let res: ProtocolFeesResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(res.share_bps, 2000);
+        assert_eq!(res.collector, Some("dao".to_string()));
+        assert_eq!(res.fees, vec![coin(20, "uatom")]);
+
+        let collect = ExecuteMsg::CollectProtocolFees { pool_id: pool_id.clone() };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), collect.clone()).unwrap_err();
+        let info = mock_info("dao", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, collect.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "dao".to_string(),
+                amount: vec![coin(20, "uatom")],
+            })
+        );
+        assert!(!PROTOCOL_FEES.has(&deps.storage, &pool_id));
+        execute(deps.as_mut(), mock_env(), mock_info("dao", &[]), collect).unwrap_err();
+    }
+
+    #[test]
+    fn test_update_pool_fee() {
+        use crate::interchainswap_handler::{do_ibc_packet_receive, on_packet_success};
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let proposal = |fee_rate: u32| MarketFeeUpdateProposal {
+            title: "Lower fee".to_string(),
+            description: "Attract volume".to_string(),
+            pool_id: pool_id.clone(),
+            fee_rate,
+            right_fee_rate: None,
+        };
+        let msg = ExecuteMsg::UpdatePoolFee(proposal(10));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nobody", &[]), msg.clone())
+            .unwrap_err();
+        assert_eq!(err, ContractError::MissingRole { role: "fee_setter".to_string() });
+        // the pool manager and fee setters propose fees too
+        execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), msg.clone()).unwrap();
+        let grant = ExecuteMsg::GrantRole {
+            address: "fees".to_string(),
+            role: Role::FeeSetter,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), grant).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("fees", &[]), msg).unwrap();
+        let msg = ExecuteMsg::UpdatePoolFee(proposal(FEE_PRECISION.into()));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwapFee { .. }));
+
+        // the fee changes once the counterparty acknowledges it
+        let msg = ExecuteMsg::UpdatePoolFee(proposal(10));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        assert!(matches!(res.messages[0].msg, CosmosMsg::Ibc(IbcMsg::SendPacket { .. })));
+        assert_eq!(POOLS.load(&deps.storage, &pool_id).unwrap().swap_fee, 30);
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::UpdateFee,
+            data: to_binary(&proposal(10)).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        on_packet_success(deps.as_mut(), mock_env(), packet, None).unwrap();
+        assert_eq!(POOLS.load(&deps.storage, &pool_id).unwrap().swap_fee, 10);
+
+        // the counterparty applies a received proposal right away
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::UpdateFee,
+            data: to_binary(&proposal(50)).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+        assert_eq!(POOLS.load(&deps.storage, &pool_id).unwrap().swap_fee, 50);
+    }
+
+    #[test]
+    fn test_pool_whitelist() {
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let set_whitelist = |addresses: Option<Vec<&str>>| ExecuteMsg::SetPoolWhitelist {
+            pool_id: pool_id.clone(),
+            addresses: addresses.map(|addresses| addresses.iter().map(|a| a.to_string()).collect()),
+        };
+        let msg = set_whitelist(Some(vec!["trader"]));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nobody", &[]), msg.clone())
+            .unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+        execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), msg).unwrap();
+
+        let swap = |sender: &str| {
+            ExecuteMsg::Swap(MsgSwapRequest {
+                swap_type: SwapMsgType::LEFT,
+                sender: sender.to_string(),
+                pool_id: pool_id.clone(),
+                token_in: coin(10, "uatom"),
+                token_out: coin(1, "uosmo"),
+                slippage: 100,
+                recipient: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+                timeout_height: 0,
+                timeout_timestamp: 0,
+                route: None,
+                memo: None,
+                refund_to: None,
+                forward: None,
+            })
+        };
+        let info = mock_info("stranger", &[coin(10, "uatom")]);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), swap("stranger")).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+        let trader = mock_info("trader", &[coin(10, "uatom")]);
+        execute(deps.as_mut(), mock_env(), trader, swap("trader")).unwrap();
+
+        // withdrawals stay open to everyone
+        let withdraw = ExecuteMsg::MultiAssetWithdraw(MsgMultiAssetWithdrawRequest {
+            pool_id: pool_id.clone(),
+            receiver: "stranger".to_string(),
+            counterparty_receiver: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            pool_token: coin(10, pool_id.clone()),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("stranger", &[]), withdraw)
+            .unwrap_err();
+        assert_ne!(err, ContractError::InvalidSender);
+
+        // the admin lifts the whitelist
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), set_whitelist(None)).unwrap();
+        execute(deps.as_mut(), mock_env(), info, swap("stranger")).unwrap();
+    }
+
+    #[test]
+    fn test_set_lp_token_marketing() {
+        use crate::msg::LpTokenMarketing;
+        use cw20::Logo;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = test_pool(&pool_id, PoolStatus::Active);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let marketing = LpTokenMarketing {
+            project: Some("Atom pool".to_string()),
+            description: None,
+            logo_url: Some("https://example.com/atom.png".to_string()),
+        };
+        let msg = ExecuteMsg::SetLpTokenMarketing {
+            pool_id: pool_id.clone(),
+            marketing: marketing.clone(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nobody", &[]), msg.clone())
+            .unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+
+        // without a token yet, the marketing applies when it is instantiated
+        let res = execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), msg.clone()).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(LP_TOKEN_MARKETING.load(&deps.storage, &pool_id).unwrap(), marketing);
+
+        // an existing cw20 token is updated by the contract, its marketing admin
+        POOL_TOKENS_LIST.save(deps.as_mut().storage, &pool_id, &"lptoken".to_string()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("maker", &[]), msg).unwrap();
+        // the last message releases the reentrancy guard
+        let executed: Vec<Cw20ExecuteMsg> = res.messages[..2]
+            .iter()
+            .map(|sub_msg| match &sub_msg.msg {
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) => {
+                    assert_eq!(contract_addr, "lptoken");
+                    from_binary(msg).unwrap()
+                }
+                _ => panic!("expected a token update"),
+            })
+            .collect();
+        assert_eq!(
+            executed,
+            vec![
+                Cw20ExecuteMsg::UpdateMarketing {
+                    project: Some("Atom pool".to_string()),
+                    description: Some("".to_string()),
+                    marketing: None,
+                },
+                Cw20ExecuteMsg::UploadLogo(Logo::Url("https://example.com/atom.png".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_initialized_pool_limit() {
+        use crate::interchainswap_handler::on_packet_failure;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+        let msg = ExecuteMsg::SetInitializedPoolLimit { limit: 1 };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+        let make_pool = |denom: &str| MsgMakePoolRequest {
+            source_chain_id: "".to_string(),
+            ..make_pool_request(denom)
+        };
+
+        // the cap can't be dodged by naming another creator
+        let info = mock_info("other", &[coin(1000, "uatom")]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::MakePool(make_pool("uatom")))
+            .unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+
+        execute_make_pool(deps.as_mut(), make_pool("uatom")).unwrap();
+        let err = execute_make_pool(deps.as_mut(), make_pool("ujuno")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::TooManyInitializedPools { creator: "creator".to_string(), limit: 1 }
+        );
+
+        // a pool that timed out no longer counts
+        let mut msg = make_pool("uatom");
+        msg.source_chain_id = mock_env().block.chain_id;
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::MakePool,
+            data: to_binary(&msg).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        on_packet_failure(deps.as_mut(), packet, "timeout".to_string()).unwrap();
+        execute_make_pool(deps.as_mut(), make_pool("ujuno")).unwrap();
+    }
+
+    #[test]
+    fn test_share_math_queries() {
+        let mut deps = mock_dependencies();
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(4000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            supply: coin(2000, pool_id.clone()),
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+
+        let msg = QueryMsg::SharesForDeposit {
+            pool_id: pool_id.clone(),
+            deposits: vec![coin(100, "uatom"), coin(400, "uosmo")],
+        };
+        let res: SharesForDepositResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(res.pool_tokens, vec![coin(100, "pool1"), coin(100, "pool1")]);
+        assert_eq!(res.shares, Uint128::new(200));
+
+        let msg = QueryMsg::AssetsForShares { pool_id: pool_id.clone(), shares: Uint128::new(200) };
+        let assets: Vec<Coin> =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(assets, vec![coin(100, "uatom"), coin(400, "uosmo")]);
+
+        let msg = QueryMsg::AssetsForShares { pool_id, shares: Uint128::new(2001) };
+        query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    }
+
+    #[test]
+    fn test_out_of_range_decimal() {
+        for decimal in [60, 300] {
+            let pool = InterchainLiquidityPool {
+                assets: vec![
+                    PoolAsset {
+                        side: PoolSide::SOURCE,
+                        balance: coin(1000, "uatom"),
+                        weight: 50,
+                        decimal,
+                    },
+                    PoolAsset {
+                        side: PoolSide::DESTINATION,
+                        balance: coin(1000, "uosmo"),
+                        weight: 50,
+                        decimal: 6,
+                    },
+                ],
+                ..test_pool("pool1", PoolStatus::Active)
+            };
+            let amm = InterchainMarketMaker::new(&pool, pool.swap_fee);
+            amm.compute_swap(coin(10, "uatom"), "uosmo").unwrap_err();
+            amm.compute_swap(coin(10, "uosmo"), "uatom").unwrap_err();
+            amm.compute_offer_amount(coin(10, "uatom"), coin(5, "uosmo")).unwrap_err();
+
+            let pool = InterchainLiquidityPool {
+                supply: coin(0, "pool1"),
+                ..test_pool("pool1", PoolStatus::Initialized)
+            };
+            let pool = InterchainLiquidityPool {
+                assets: vec![
+                    PoolAsset { decimal, ..pool.assets[0].clone() },
+                    pool.assets[1].clone(),
+                ],
+                ..pool
+            };
+            let amm = InterchainMarketMaker::new(&pool, pool.swap_fee);
+            amm.deposit_multi_asset(&[coin(1000, "uatom"), coin(1000, "uosmo")]).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn test_deposit_into_drained_asset() {
+        let pool = test_pool("pool1", PoolStatus::Active);
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset { balance: coin(0, "uatom"), ..pool.assets[0].clone() },
+                pool.assets[1].clone(),
+            ],
+            ..pool
+        };
+        let amm = InterchainMarketMaker::new(&pool, pool.swap_fee);
+        let err = amm.deposit_multi_asset(&[coin(1000, "uatom"), coin(1000, "uosmo")]).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Pool has no uatom to price the deposit against"));
+        amm.deposit_multi_asset(&[coin(1000, "uosmo")]).unwrap();
+    }
+
+    #[test]
+    fn test_directional_swap_fee() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            assets: vec![
+                PoolAsset {
+                    side: PoolSide::SOURCE,
+                    balance: coin(1_000_000, "uatom"),
+                    weight: 50,
+                    decimal: 6,
+                },
+                PoolAsset {
+                    side: PoolSide::DESTINATION,
+                    balance: coin(1_000_000, "uosmo"),
+                    weight: 50,
+                    decimal: 6,
+                },
+            ],
+            supply: coin(1_000_000, pool_id.clone()),
+            right_swap_fee: Some(300),
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        assert_eq!(pool.swap_fee_for(&SwapMsgType::LEFT), 30);
+        assert_eq!(pool.swap_fee_for(&SwapMsgType::RIGHT), 300);
+
+        let estimate = |deps: Deps, msg: QueryMsg| -> Coin {
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        let left = estimate(
+            deps.as_ref(),
+            QueryMsg::EstimateLeftSwap {
+                pool_id: pool_id.clone(),
+                denom_in: "uatom".to_string(),
+                amount_in: Uint128::new(10_000),
+                denom_out: "uosmo".to_string(),
+            },
+        );
+        let expected = InterchainMarketMaker::new(&pool, 30)
+            .compute_swap(coin(10_000, "uatom"), "uosmo")
+            .unwrap();
+        assert_eq!(left, expected);
+
+        // right swaps pay the higher fee
+        let right = estimate(
+            deps.as_ref(),
+            QueryMsg::EstimateRightSwap {
+                pool_id: pool_id.clone(),
+                denom_in: "uatom".to_string(),
+                denom_out: "uosmo".to_string(),
+                amount_out: Uint128::new(10_000),
+            },
+        );
+        let at_fee = |fee_rate: u32| {
+            InterchainMarketMaker::new(&pool, fee_rate)
+                .compute_offer_amount(coin(0, "uatom"), coin(10_000, "uosmo"))
+                .unwrap()
+        };
+        assert_eq!(right, at_fee(300));
+        assert!(right.amount > at_fee(30).amount);
+
+        // a fee proposal of the counterparty carries the fee of right swaps along
+        let proposal = MarketFeeUpdateProposal {
+            title: "Rebalance".to_string(),
+            description: "Discourage draining uosmo".to_string(),
+            pool_id: pool_id.clone(),
+            fee_rate: 20,
+            right_fee_rate: Some(100),
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::UpdateFee,
+            data: to_binary(&proposal).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+        let pool = POOLS.load(&deps.storage, &pool_id).unwrap();
+        assert_eq!((pool.swap_fee, pool.right_swap_fee), (20, Some(100)));
+    }
+
+    #[test]
+    fn test_tokenfactory_lp_token() {
+        use crate::utils::mint_tokens_cw20;
+        use cosmwasm_std::testing::MOCK_CONTRACT_ADDR;
+
+        let mut deps = mock_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            token_code_id: 1,
+            router: "".to_string(),
+            default_timeout_seconds: None,
+            lp_token_kind: Some(LpTokenKind::TokenFactory),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), instantiate_msg).unwrap();
+
+        let msg = MsgMakePoolRequest {
+            source_chain_id: "".to_string(),
+            ..make_pool_request("uatom")
+        };
+        let info = mock_info("creator", &[coin(1000, "uatom")]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::MakePool(msg)).unwrap();
+
+        // the denom is recorded right away, no reply has to wait for an address
+        let (pool_id, mut pool) = POOLS
+            .range(deps.as_ref().storage, None, None, Order::Ascending)
+            .next()
+            .unwrap()
+            .unwrap();
+        let denom = POOL_TOKENS_LIST.load(deps.as_ref().storage, &pool_id).unwrap();
+        assert_eq!(denom, format!("factory/{}/{}", MOCK_CONTRACT_ADDR, &pool_id[..44]));
+        assert_eq!(
+            LP_TOKEN_STATUS.load(deps.as_ref().storage, &pool_id).unwrap(),
+            LpTokenStatus::Ready
+        );
+        assert!(TEMP.may_load(deps.as_ref().storage).unwrap().is_none());
+        match &res.messages[0].msg {
+            CosmosMsg::Stargate { type_url, .. } => {
+                assert_eq!(type_url, "/osmosis.tokenfactory.v1beta1.MsgCreateDenom")
+            }
+            _ => panic!("expected the denom to be created"),
+        }
+
+        // shares are minted and burnt by the tokenfactory module
+        let mint = mint_tokens_cw20("maker".to_string(), denom.clone(), Uint128::new(100)).unwrap();
+        assert!(matches!(mint[0].msg, CosmosMsg::Stargate { .. }));
+
+        // withdrawals send the shares along instead of approving a transfer
+        pool.status = PoolStatus::Active;
+        pool.supply.amount = Uint128::new(1000);
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let zap = ExecuteMsg::ZapOut {
+            pool_id: pool_id.clone(),
+            lp_amount: Uint128::new(100),
+            denom_out: "uatom".to_string(),
+            min_out: Uint128::zero(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("zapper", &[]), zap.clone()).unwrap_err();
+        let info = mock_info("zapper", &[coin(100, &denom)]);
+        let res = execute(deps.as_mut(), mock_env(), info, zap).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(matches!(res.messages[0].msg, CosmosMsg::Ibc(IbcMsg::SendPacket { .. })));
+    }
+
+    #[test]
+    fn test_channel_capabilities() {
+        use crate::ibc::{ibc_channel_connect, ibc_channel_open};
+        use crate::msg::ChannelCapabilitiesResponse;
+        use crate::state::{ChannelCapabilities, COMPACT_CHANNELS};
+        use cosmwasm_std::testing::{mock_ibc_channel, mock_ibc_channel_connect_confirm};
+        use cosmwasm_std::{from_slice, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcOrder};
+
+        let mut deps = mock_dependencies();
+        let version = r#"{"version":"ics101-1","compact":true,"fee_middleware":true}"#;
+
+        // only ics101 packet versions are accepted
+        let channel = mock_ibc_channel("channel-0", IbcOrder::Unordered, version);
+        let msg = IbcChannelOpenMsg::new_try(channel.clone(), r#"{"version":"ics20-1"}"#);
+        ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap_err();
+
+        // the counterparty learns the capabilities of this end in return
+        let msg = IbcChannelOpenMsg::new_try(channel, version);
+        let res = ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap().unwrap();
+        let local: ChannelCapabilities = from_slice(res.version.as_bytes()).unwrap();
+        assert_eq!(local, local_capabilities());
+
+        let msg = mock_ibc_channel_connect_confirm("channel-0", IbcOrder::Unordered, &res.version);
+        ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(COMPACT_CHANNELS.has(deps.as_ref().storage, "channel-0"));
+
+        let query_msg = QueryMsg::ChannelCapabilities {
+            channel_id: "channel-0".to_string(),
+        };
+        let res: ChannelCapabilitiesResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        let counterparty = res.counterparty.unwrap();
+        assert!(counterparty.fee_middleware);
+        let negotiated = res.negotiated.unwrap();
+        assert!(negotiated.compact);
+        assert!(!negotiated.fee_middleware);
+
+        // an init end that proposed the plain version keeps the full packets
+        let channel = mock_ibc_channel("channel-1", IbcOrder::Unordered, "ics101-1");
+        let msg = IbcChannelConnectMsg::new_ack(channel, version.to_string());
+        ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(!COMPACT_CHANNELS.has(deps.as_ref().storage, "channel-1"));
+    }
+
+    #[test]
+    fn test_withdraw_pays_fee_share() {
+        use crate::interchainswap_handler::do_ibc_packet_receive;
+        use cosmwasm_std::testing::{mock_ibc_packet_recv, MOCK_CONTRACT_ADDR};
+        use cosmwasm_std::BankMsg;
+
+        let mut deps = setup();
+
+        let pool_id = "pool1".to_string();
+        let pool = InterchainLiquidityPool {
+            source_chain_id: "chain-b".to_string(),
+            destination_chain_id: "chain-a".to_string(),
+            ..test_pool(&pool_id, PoolStatus::Active)
+        };
+        POOLS.save(deps.as_mut().storage, &pool_id, &pool).unwrap();
+        let fees = vec![coin(50, "uatom"), coin(20, "uosmo")];
+        COLLECTED_FEES.save(deps.as_mut().storage, &pool_id, &fees).unwrap();
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![coin(1050, "uatom")]);
+
+        let withdraw = MsgMultiAssetWithdrawRequest {
+            pool_id: pool_id.clone(),
+            receiver: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_string(),
+            counterparty_receiver: "lp_a".to_string(),
+            pool_token: coin(100, pool_id.clone()),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let packet_data = InterchainSwapPacketData {
+            r#type: InterchainMessageType::MultiWithdraw,
+            data: to_binary(&withdraw).unwrap(),
+            state_change: Some(
+                to_binary(&StateChange {
+                    in_tokens: None,
+                    out_tokens: Some(vec![coin(100, "uatom"), coin(100, "uosmo")]),
+                    pool_tokens: Some(vec![coin(100, pool_id.clone())]),
+                    pool_id: None,
+                    multi_deposit_order_id: None,
+                    source_chain_id: None,
+                    shares: None,
+                })
+                .unwrap(),
+            ),
+            memo: None,
+        };
+        let packet = mock_ibc_packet_recv("channel-0", &packet_data).unwrap().packet;
+        let res = do_ibc_packet_receive(deps.as_mut(), mock_env(), &packet).unwrap();
+
+        // a tenth of the supply earns a tenth of the fees, the local ones are paid here
+        let paid: Vec<Coin> = res
+            .messages
+            .iter()
+            .flat_map(|sub_msg| match &sub_msg.msg {
+                CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount.clone(),
+                _ => vec![],
+            })
+            .collect();
+        assert_eq!(paid, vec![coin(100, "uatom"), coin(5, "uatom")]);
+        assert_eq!(
+            COLLECTED_FEES.load(&deps.storage, &pool_id).unwrap(),
+            vec![coin(45, "uatom"), coin(18, "uosmo")]
+        );
     }
 }
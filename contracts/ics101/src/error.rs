@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Coin, StdError, Uint128};
+use ibcswap_packets::{InterchainMessageType, PoolStatus};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -42,6 +43,45 @@ pub enum ContractError {
     #[error("Invalid sender address")]
     InvalidSender,
 
+    #[error("Sender lacks the {role} role")]
+    MissingRole { role: String },
+
+    #[error("{message_type:?} messages are disabled")]
+    MessageDisabled { message_type: InterchainMessageType },
+
+    #[error("Packet {sequence} on {channel_id} was vetoed")]
+    PacketVetoed { channel_id: String, sequence: u64 },
+
+    #[error("A later packet changed {key}, revert it first")]
+    RevertConflict { key: String },
+
+    #[error("Pool uses {claimed} decimals for {denom}, the registry has {registered}")]
+    DecimalMismatch { denom: String, claimed: u32, registered: u32 },
+
+    #[error("Take permit signature doesn't match the order's key")]
+    InvalidPermit,
+
+    #[error("Amount {amount} is below the pool minimum of {min}")]
+    BelowMinimum { amount: Uint128, min: Uint128 },
+
+    #[error("Invalid address {address}: {reason}")]
+    InvalidAddress { address: String, reason: String },
+
+    #[error("Invalid address prefix {prefix}")]
+    InvalidAddressPrefix { prefix: String },
+
+    #[error("Invalid packet timeout {timeout}s, must be within {min}s and {max}s")]
+    InvalidTimeout { timeout: u64, min: u64, max: u64 },
+
+    #[error("Invalid swap fee {fee}bps, must be within {min}bps and {max}bps")]
+    InvalidSwapFee { fee: u32, min: u32, max: u32 },
+
+    #[error("{creator} already has {limit} pools waiting to be taken")]
+    TooManyInitializedPools { creator: String, limit: u32 },
+
+    #[error("Malformed packet: missing or invalid {field}")]
+    MalformedPacket { field: String },
+
     #[error("Invalid status")]
     InvalidStatus,
 
@@ -51,12 +91,27 @@ pub enum ContractError {
     #[error("Pool is not ready for swap!")]
     NotReadyForSwap,
 
+    #[error("Pool is initialized, it takes no deposits or swaps until it is taken")]
+    PoolNotTaken,
+
+    #[error("Pool is {status:?}, only withdrawals are accepted")]
+    PoolNotOpen { status: PoolStatus },
+
+    #[error("Pool is bootstrapping, only unfunded assets can be deposited")]
+    OnlyBootstrapDeposit,
+
+    #[error("Pool {pool_id} already has a bootstrap deposit in flight")]
+    BootstrapDepositInFlight { pool_id: String },
+
     #[error("Only supports channel with ibc version ics100-1, got {version}")]
     InvalidIbcVersion { version: String },
 
     #[error("Only supports unordered channel")]
     OnlyOrderedChannel {},
 
+    #[error("Only supports unordered channel")]
+    OnlyUnorderedChannel {},
+
     #[error("Only accepts tokens that originate on this chain, not native tokens of remote chain")]
     NoForeignTokens {},
 
@@ -110,4 +165,45 @@ pub enum ContractError {
 
     #[error("Error failed multi asset deposit")]
     ErrFailedMultiAssetDeposit,
+
+    #[error("Contract calls of a previous message are still being processed")]
+    Reentrancy,
+
+    #[error("Quote of height {quote_height} expired, it was valid until height {valid_until}")]
+    QuoteExpired { quote_height: u64, valid_until: u64 },
+
+    #[error("Pool changed since the quote: quoted {quoted}, now {current}")]
+    QuoteChanged { quoted: Coin, current: Coin },
+
+    #[error("Treasury holds {available} {denom}, less than the {requested} requested")]
+    InsufficientTreasury { denom: String, available: Uint128, requested: Uint128 },
+
+    #[error("Withdrawal {id} is timelocked until {executable_at}")]
+    WithdrawalTimelocked { id: u64, executable_at: u64 },
+
+    #[error("Pool ratio moved: the deposit needs {needed}, out of tolerance of the {sent} sent")]
+    DepositRatioMoved { needed: Coin, sent: Coin },
+
+    #[error("Connection {connection_id} is bound to port {expected}, not {port_id}")]
+    UnexpectedCounterparty { connection_id: String, port_id: String, expected: String },
+
+    #[error("Channel {channel_id} leads to chain {expected}, not {chain_id}")]
+    UnexpectedChainId { channel_id: String, chain_id: String, expected: String },
+
+    #[error("Pool {pool_id} is bound to channel {expected}, the packet came over {channel_id}")]
+    UnexpectedPoolChannel { pool_id: String, channel_id: String, expected: String },
+
+    #[error("Cannot close the channel, pools bound to it would lock their funds")]
+    CannotCloseChannel {},
+}
+
+impl ContractError {
+    /// Name of the error variant, stable across changes of the message.
+    pub fn code(&self) -> String {
+        format!("{:?}", self)
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    }
 }
@@ -0,0 +1,124 @@
+//! Swap outputs forwarded to a third chain.
+//!
+//! The output leaves as an ICS-20 `MsgTransfer` whose memo asks the ibc-hooks module to
+//! report the result of the transfer back to the contract. A transfer that fails or times out
+//! is refunded here, the output is then held for the sender of the swap to claim, as outputs
+//! whose recipient is invalid are. The chain has to run ibc-hooks with callbacks enabled.
+
+use cosmwasm_std::{
+    Binary, Coin, CosmosMsg, DepsMut, Env, Event, Response, StdError, StdResult, Storage, SubMsg,
+    SubMsgResult,
+};
+
+use crate::error::ContractError;
+use crate::icq::{encode, read_uint64_field};
+use crate::interchainswap_handler::credit_swap_claim;
+use crate::msg::{IbcLifecycleComplete, SwapForward};
+use crate::state::{ForwardedTransfer, FORWARDED_TRANSFERS, PENDING_FORWARD};
+use crate::utils::FORWARD_TRANSFER_REPLY_ID;
+
+const TRANSFER_TYPE_URL: &str = "/ibc.applications.transfer.v1.MsgTransfer";
+const TRANSFER_PORT: &str = "transfer";
+
+/// Transfers `token` on to the third chain, the reply records the transfer so its failure
+/// credits the output to `sender` of the swap that came in on `channel_id`.
+pub(crate) fn forward_output(
+    storage: &mut dyn Storage,
+    env: &Env,
+    channel_id: &str,
+    sender: &str,
+    forward: SwapForward,
+    token: Coin,
+    timeout_seconds: u64,
+) -> StdResult<SubMsg> {
+    let contract = env.contract.address.as_str();
+    let memo = format!(r#"{{"ibc_callback":"{}"}}"#, contract);
+    let timeout = env.block.time.plus_seconds(timeout_seconds).nanos();
+    let coin = encode(|os| {
+        os.write_string(1, &token.denom)?;
+        os.write_string(2, &token.amount.to_string())
+    })?;
+    let value = encode(|os| {
+        os.write_string(1, TRANSFER_PORT)?;
+        os.write_string(2, &forward.channel)?;
+        os.write_bytes(3, &coin)?;
+        os.write_string(4, contract)?;
+        os.write_string(5, &forward.receiver)?;
+        os.write_uint64(7, timeout)?;
+        os.write_string(8, &memo)
+    })?;
+
+    PENDING_FORWARD.save(
+        storage,
+        &ForwardedTransfer {
+            channel_id: channel_id.to_string(),
+            sender: sender.to_string(),
+            token,
+            forward_channel: forward.channel,
+        },
+    )?;
+    let msg = CosmosMsg::Stargate {
+        type_url: TRANSFER_TYPE_URL.to_string(),
+        value: Binary::from(value),
+    };
+    Ok(SubMsg::reply_on_success(msg, FORWARD_TRANSFER_REPLY_ID))
+}
+
+// reply to a forwarded transfer, keyed by the sequence the transfer module assigned it
+pub(crate) fn on_forward_transfer_reply(
+    deps: DepsMut,
+    result: SubMsgResult,
+) -> Result<Response, ContractError> {
+    let forwarded = PENDING_FORWARD.load(deps.storage)?;
+    PENDING_FORWARD.remove(deps.storage);
+
+    let data = result
+        .into_result()
+        .map_err(StdError::generic_err)?
+        .data
+        .ok_or_else(|| StdError::generic_err("Missing transfer reply data"))?;
+    let sequence = read_uint64_field(data.as_slice(), 1)?;
+    let key = (forwarded.forward_channel.as_str(), sequence);
+    FORWARDED_TRANSFERS.save(deps.storage, key, &forwarded)?;
+    Ok(Response::new()
+        .add_attribute("action", "forward_swap_output")
+        .add_attribute("channel_id", &forwarded.forward_channel)
+        .add_attribute("sequence", sequence.to_string()))
+}
+
+/// Settles a forwarded transfer once ibc-hooks reports its acknowledgement or timeout.
+pub(crate) fn on_lifecycle_complete(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcLifecycleComplete,
+) -> Result<Response, ContractError> {
+    let (channel, sequence, failure) = match msg {
+        IbcLifecycleComplete::IbcAck { channel, sequence, ack, success } => {
+            (channel, sequence, (!success).then_some(ack))
+        }
+        IbcLifecycleComplete::IbcTimeout { channel, sequence } => {
+            (channel, sequence, Some("timeout".to_string()))
+        }
+    };
+    let key = (channel.as_str(), sequence);
+    let forwarded = match FORWARDED_TRANSFERS.may_load(deps.storage, key)? {
+        Some(forwarded) => forwarded,
+        // transfers of the contract that aren't forwarded swap outputs
+        None => return Ok(Response::new()),
+    };
+    FORWARDED_TRANSFERS.remove(deps.storage, key);
+
+    let reason = match failure {
+        Some(reason) => reason,
+        None => return Ok(Response::new().add_attribute("action", "forward_acknowledged")),
+    };
+    // the transfer module refunded the output to the contract
+    credit_swap_claim(deps.storage, &forwarded.channel_id, &forwarded.sender, &forwarded.token)?;
+    Ok(Response::new().add_event(
+        Event::new("swap_output_claimable")
+            .add_attribute("channel_id", forwarded.channel_id)
+            .add_attribute("counterparty", forwarded.sender)
+            .add_attribute("amount", forwarded.token.to_string())
+            .add_attribute("reason", reason),
+    ))
+}
@@ -2,31 +2,79 @@
 
 use crate::{
     error::{ContractError, Never},
+    icq::{
+        enforce_icq_order_and_version, is_icq_channel, on_icq_packet_failure,
+        on_icq_packet_success,
+    },
     interchainswap_handler::{
-        ack_fail, do_ibc_packet_receive, on_packet_failure, on_packet_success,
+        ack_fail_for, do_ibc_packet_receive, on_packet_failure, on_packet_success,
+        record_lp_checkpoints, record_packet, record_received_packet,
+        InterchainSwapPacketAcknowledgement,
+    },
+    utils::{
+        enforce_order_and_version, is_plain_version, local_capabilities, parse_channel_version,
+        try_get_ack_error,
     },
-    utils::{enforce_order_and_version, try_get_ack_error},
 };
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, entry_point, DepsMut, Env, IbcBasicResponse, IbcChannel, IbcChannelCloseMsg,
-    IbcChannelConnectMsg, IbcChannelOpenMsg, IbcPacketAckMsg, IbcPacketReceiveMsg,
-    IbcPacketTimeoutMsg, IbcReceiveResponse,
+    attr, from_binary, to_vec, DepsMut, Env, Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannel,
+    IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcChannelOpenResponse,
+    IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, Storage,
 };
 
-use crate::state::{ChannelInfo, CHANNEL_INFO};
+use crate::state::{
+    ChannelInfo, PacketResult, CHANNEL_INFO, CLOSED_CHANNELS, COMPACT_CHANNELS,
+    COUNTERPARTY_CAPABILITIES, COUNTERPARTY_PORTS, ICQ_CHANNEL_INFO,
+};
 
 pub const RECEIVE_ID: u64 = 1337;
 pub const ACK_FAILURE_ID: u64 = 0xfa17;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-/// enforces ordering and versioning constraints
+/// enforces ordering and versioning constraints, answers a counterparty advertising its
+/// capabilities with ours
 pub fn ibc_channel_open(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
     msg: IbcChannelOpenMsg,
-) -> Result<(), ContractError> {
+) -> Result<IbcChannelOpenResponse, ContractError> {
+    if is_icq_channel(msg.channel(), msg.counterparty_version()) {
+        enforce_icq_order_and_version(msg.channel(), msg.counterparty_version())?;
+        return Ok(None);
+    }
     enforce_order_and_version(msg.channel(), msg.counterparty_version())?;
-    Ok(())
+    enforce_counterparty(deps.storage, msg.channel())?;
+
+    // the try end learns the capabilities of the counterparty here, the connect only
+    // carries them to the init end
+    if let Some(version) = msg.counterparty_version() {
+        let channel_id = &msg.channel().endpoint.channel_id;
+        let capabilities = parse_channel_version(version)?;
+        COUNTERPARTY_CAPABILITIES.save(deps.storage, channel_id, &capabilities)?;
+        if !is_plain_version(version) {
+            let version = to_vec(&local_capabilities())?;
+            return Ok(Some(Ibc3ChannelOpenResponse {
+                version: String::from_utf8_lossy(&version).into_owned(),
+            }));
+        }
+    }
+    Ok(None)
+}
+
+// Only the contract behind the first channel of a connection may open more channels on
+// it, so another contract on the counterparty chain cannot pose as it over a fresh one.
+fn enforce_counterparty(storage: &dyn Storage, channel: &IbcChannel) -> Result<(), ContractError> {
+    let port_id = &channel.counterparty_endpoint.port_id;
+    match COUNTERPARTY_PORTS.may_load(storage, &channel.connection_id)? {
+        Some(expected) if &expected != port_id => Err(ContractError::UnexpectedCounterparty {
+            connection_id: channel.connection_id.clone(),
+            port_id: port_id.clone(),
+            expected,
+        }),
+        _ => Ok(()),
+    }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -37,43 +85,90 @@ pub fn ibc_channel_connect(
     msg: IbcChannelConnectMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     // we need to check the counter party version in try and ack (sometimes here)
-    enforce_order_and_version(msg.channel(), msg.counterparty_version())?;
+    let icq = is_icq_channel(msg.channel(), msg.counterparty_version());
+    if icq {
+        enforce_icq_order_and_version(msg.channel(), msg.counterparty_version())?;
+    } else {
+        enforce_order_and_version(msg.channel(), msg.counterparty_version())?;
+        enforce_counterparty(deps.storage, msg.channel())?;
+    }
+
+    let counterparty = match msg.counterparty_version() {
+        Some(version) if !icq => Some(parse_channel_version(version)?),
+        _ => None,
+    };
 
     let channel: IbcChannel = msg.into();
+    if !icq {
+        COUNTERPARTY_PORTS.save(
+            deps.storage,
+            &channel.connection_id,
+            &channel.counterparty_endpoint.port_id,
+        )?;
+
+        let channel_id = &channel.endpoint.channel_id;
+        let counterparty = match counterparty {
+            Some(capabilities) => capabilities,
+            None => match COUNTERPARTY_CAPABILITIES.may_load(deps.storage, channel_id)? {
+                Some(capabilities) => capabilities,
+                None => parse_channel_version(&channel.version)?,
+            },
+        };
+        COUNTERPARTY_CAPABILITIES.save(deps.storage, channel_id, &counterparty)?;
+        // only what this end advertised on the channel counts, not all it could support
+        let advertised = parse_channel_version(&channel.version)?;
+        if counterparty.intersect(&advertised).compact {
+            COMPACT_CHANNELS.save(deps.storage, channel_id, &true)?;
+        }
+    }
     let info = ChannelInfo {
         id: channel.endpoint.channel_id,
         counterparty_endpoint: channel.counterparty_endpoint,
         connection_id: channel.connection_id,
     };
-    CHANNEL_INFO.save(deps.storage, &info.id, &info)?;
+    if icq {
+        ICQ_CHANNEL_INFO.save(deps.storage, &info.id, &info)?;
+    } else {
+        CHANNEL_INFO.save(deps.storage, &info.id, &info)?;
+    }
 
     Ok(IbcBasicResponse::default())
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
+/// refuse to close channels from this end, record a close the counterparty confirmed
 pub fn ibc_channel_close(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
-    _channel: IbcChannelCloseMsg,
+    msg: IbcChannelCloseMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    // TODO: what to do here?
-    // we will have locked funds that need to be returned somehow
-    unimplemented!();
+    // TODO: return the funds locked in the pools of a closed channel
+    if let IbcChannelCloseMsg::CloseInit { .. } = msg {
+        return Err(ContractError::CannotCloseChannel {});
+    }
+    let channel_id = &msg.channel().endpoint.channel_id;
+    CLOSED_CHANNELS.save(deps.storage, channel_id, &true)?;
+
+    Ok(IbcBasicResponse::new()
+        .add_attribute("action", "ibc_channel_close")
+        .add_attribute("channel_id", channel_id))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 /// Check to see if we have any balance here
 /// We should not return an error if possible, but rather an acknowledgement of failure
 pub fn ibc_packet_receive(
-    deps: DepsMut,
-    _env: Env,
+    mut deps: DepsMut,
+    env: Env,
     msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, Never> {
     let packet = msg.packet;
 
-    do_ibc_packet_receive(deps, _env, &packet).or_else(|err| {
+    let res = do_ibc_packet_receive(deps.branch(), env.clone(), &packet);
+    let journaled = record_received_packet(deps.storage, &env, &packet, res.as_ref().err());
+    res.and_then(|res| journaled.map(|_| res)).or_else(|err| {
         Ok(IbcReceiveResponse::new()
-            .set_ack(ack_fail(err.to_string()))
+            .set_ack(ack_fail_for(&packet, err.to_string()))
             .add_attributes(vec![
                 attr("action", "receive"),
                 attr("success", "false"),
@@ -85,14 +180,37 @@ pub fn ibc_packet_receive(
 #[cfg_attr(not(feature = "library"), entry_point)]
 // check if success or failure and update balance, or return funds
 pub fn ibc_packet_ack(
-    deps: DepsMut,
-    _env: Env,
+    mut deps: DepsMut,
+    env: Env,
     msg: IbcPacketAckMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
+    if ICQ_CHANNEL_INFO.has(deps.storage, &msg.original_packet.src.channel_id) {
+        return match from_binary(&msg.acknowledgement.data)? {
+            InterchainSwapPacketAcknowledgement::Result(result) => {
+                on_icq_packet_success(deps, env, msg.original_packet, result)
+            }
+            InterchainSwapPacketAcknowledgement::Error(error) => {
+                on_icq_packet_failure(msg.original_packet, error)
+            }
+        };
+    }
     if let Some(error) = try_get_ack_error(&msg.acknowledgement) {
+        let result = PacketResult::Failure {
+            error: error.clone(),
+        };
+        record_packet(deps.storage, &env, &msg.original_packet, result)?;
         on_packet_failure(deps, msg.original_packet, error)
     } else {
-        on_packet_success(deps, msg.original_packet)
+        record_packet(deps.storage, &env, &msg.original_packet, PacketResult::Success)?;
+        let result = match from_binary(&msg.acknowledgement.data) {
+            Ok(InterchainSwapPacketAcknowledgement::Result(result)) => Some(result),
+            _ => None,
+        };
+        let res =
+            on_packet_success(deps.branch(), env.clone(), msg.original_packet.clone(), result)?;
+        let checkpoints =
+            record_lp_checkpoints(deps.storage, &env, &msg.original_packet, &res.messages)?;
+        Ok(res.add_events(checkpoints))
     }
 }
 
@@ -100,9 +218,13 @@ pub fn ibc_packet_ack(
 /// return fund to original sender (same as failure in ibc_packet_ack)
 pub fn ibc_packet_timeout(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     msg: IbcPacketTimeoutMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     let packet = msg.packet;
+    if ICQ_CHANNEL_INFO.has(deps.storage, &packet.src.channel_id) {
+        return on_icq_packet_failure(packet, "timeout".to_string());
+    }
+    record_packet(deps.storage, &env, &packet, PacketResult::Timeout)?;
     on_packet_failure(deps, packet, "timeout".to_string())
 }
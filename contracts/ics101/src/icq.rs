@@ -0,0 +1,390 @@
+//! Interchain queries (async-icq) against the counterparty ics101 contract.
+//!
+//! A channel with version `icq-1` to the `icqhost` port of the counterparty chain
+//! lets the contract read the counterparty's copy of a pool and compare it to the
+//! local mirror. The host chain has to allow the
+//! `/cosmwasm.wasm.v1.Query/SmartContractState` query path.
+
+use cosmwasm_std::{
+    from_slice, to_vec, Binary, Coin, DepsMut, Env, Event, IbcBasicResponse, IbcChannel,
+    IbcOrder, IbcPacket, Order, StdError, StdResult, Storage, Uint128,
+};
+use protobuf::{
+    wire_format::WireType, CodedInputStream, CodedOutputStream, ProtobufError, ProtobufResult,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::ContractError,
+    market::{InterchainLiquidityPool, FEE_PRECISION},
+    msg::{InterchainPoolResponse, QueryMsg},
+    state::{CounterpartyPool, COUNTERPARTY_POOLS, DRIFT_TOLERANCE, ICQ_CHANNEL_INFO, POOLS},
+};
+
+pub const ICQ_VERSION: &str = "icq-1";
+pub const ICQ_ORDERING: IbcOrder = IbcOrder::Unordered;
+/// Drift alarm threshold used until the admin sets one, 1% in [`FEE_PRECISION`] units.
+pub const DEFAULT_DRIFT_TOLERANCE: u32 = 100;
+
+const SMART_QUERY_PATH: &str = "/cosmwasm.wasm.v1.Query/SmartContractState";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct InterchainQueryPacketData {
+    pub data: Binary,
+    pub memo: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct InterchainQueryPacketAckData {
+    pub data: Binary,
+}
+
+pub fn is_icq_channel(channel: &IbcChannel, counterparty_version: Option<&str>) -> bool {
+    channel.version == ICQ_VERSION || counterparty_version == Some(ICQ_VERSION)
+}
+
+pub(crate) fn enforce_icq_order_and_version(
+    channel: &IbcChannel,
+    counterparty_version: Option<&str>,
+) -> Result<(), ContractError> {
+    if channel.version != ICQ_VERSION {
+        return Err(ContractError::InvalidIbcVersion {
+            version: channel.version.clone(),
+        });
+    }
+    if let Some(version) = counterparty_version {
+        if version != ICQ_VERSION {
+            return Err(ContractError::InvalidIbcVersion {
+                version: version.to_string(),
+            });
+        }
+    }
+    if channel.order != ICQ_ORDERING {
+        return Err(ContractError::OnlyUnorderedChannel {});
+    }
+    Ok(())
+}
+
+/// Finds the interchain query channel opened on the same connection as an ics101 channel.
+pub fn icq_channel_for_connection(
+    storage: &dyn Storage,
+    connection_id: &str,
+) -> StdResult<Option<String>> {
+    for item in ICQ_CHANNEL_INFO.range(storage, None, None, Order::Ascending) {
+        let (channel_id, info) = item?;
+        if info.connection_id == connection_id {
+            return Ok(Some(channel_id));
+        }
+    }
+    Ok(None)
+}
+
+/// Builds the packet asking the counterparty ics101 contract for its copy of `pool_id`.
+pub fn pool_query_packet(contract: &str, pool_id: &str) -> StdResult<InterchainQueryPacketData> {
+    let query = to_vec(&QueryMsg::InterchainPool {
+        pool_id: pool_id.to_string(),
+    })?;
+    let request = encode(|os| {
+        os.write_string(1, contract)?;
+        os.write_bytes(2, &query)
+    })?;
+    let abci_query = encode(|os| {
+        os.write_bytes(1, &request)?;
+        os.write_string(2, SMART_QUERY_PATH)
+    })?;
+    let cosmos_query = encode(|os| os.write_bytes(1, &abci_query))?;
+
+    Ok(InterchainQueryPacketData {
+        data: cosmos_query.into(),
+        memo: String::new(),
+    })
+}
+
+/// Recovers the pool id from a packet built by [`pool_query_packet`].
+fn pool_id_from_packet(packet: &InterchainQueryPacketData) -> StdResult<String> {
+    let abci_query = read_bytes_field(packet.data.as_slice(), 1)?;
+    let request = read_bytes_field(&abci_query, 1)?;
+    let query = read_bytes_field(&request, 2)?;
+    match from_slice(&query)? {
+        QueryMsg::InterchainPool { pool_id } => Ok(pool_id),
+        _ => Err(StdError::generic_err("Unexpected interchain query")),
+    }
+}
+
+/// Extracts the smart query response from the result of an acknowledgement.
+fn pool_from_ack(result: &Binary) -> StdResult<InterchainPoolResponse> {
+    let ack: InterchainQueryPacketAckData = from_slice(result)?;
+    let response = read_bytes_field(ack.data.as_slice(), 1)?;
+    let code = read_uint32_field(&response, 1)?;
+    if code != 0 {
+        return Err(StdError::generic_err(format!(
+            "Interchain query failed with code {}",
+            code
+        )));
+    }
+    let value = read_bytes_field(&response, 7)?;
+    let data = read_bytes_field(&value, 1)?;
+    from_slice(&data)
+}
+
+pub(crate) fn on_icq_packet_success(
+    deps: DepsMut,
+    env: Env,
+    packet: IbcPacket,
+    result: Binary,
+) -> Result<IbcBasicResponse, ContractError> {
+    let query: InterchainQueryPacketData = from_slice(&packet.data)?;
+    let pool_id = pool_id_from_packet(&query)?;
+    let remote = pool_from_ack(&result)?;
+
+    let counterparty = CounterpartyPool {
+        assets: remote.assets,
+        supply: remote.supply,
+        status: remote.status,
+        updated_at: env.block.time.seconds(),
+    };
+    COUNTERPARTY_POOLS.save(deps.storage, &pool_id, &counterparty)?;
+
+    let mut res = IbcBasicResponse::new()
+        .add_attribute("action", "counterparty_pool_synced")
+        .add_attribute("pool_id", &pool_id);
+
+    if let Some(pool) = POOLS.may_load(deps.storage, &pool_id)? {
+        let tolerance = DRIFT_TOLERANCE
+            .may_load(deps.storage)?
+            .unwrap_or(DEFAULT_DRIFT_TOLERANCE);
+        res = res.add_events(drift_events(&pool, &counterparty, tolerance));
+    }
+
+    Ok(res)
+}
+
+pub(crate) fn on_icq_packet_failure(
+    packet: IbcPacket,
+    error: String,
+) -> Result<IbcBasicResponse, ContractError> {
+    let query: InterchainQueryPacketData = from_slice(&packet.data)?;
+    let pool_id = pool_id_from_packet(&query)?;
+
+    Ok(IbcBasicResponse::new()
+        .add_attribute("action", "counterparty_pool_sync_failed")
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("error", error))
+}
+
+/// Emits a `counterparty_pool_drift` event for every balance that diverges by
+/// more than `tolerance` between the local pool and the counterparty's copy.
+pub fn drift_events(
+    pool: &InterchainLiquidityPool,
+    counterparty: &CounterpartyPool,
+    tolerance: u32,
+) -> Vec<Event> {
+    let mut pairs: Vec<(Coin, Uint128)> = pool
+        .assets
+        .iter()
+        .map(|asset| {
+            let remote = counterparty
+                .assets
+                .iter()
+                .find(|a| a.balance.denom == asset.balance.denom)
+                .map(|a| a.balance.amount)
+                .unwrap_or_default();
+            (asset.balance.clone(), remote)
+        })
+        .collect();
+    pairs.push((pool.supply.clone(), counterparty.supply.amount));
+
+    pairs
+        .into_iter()
+        .filter_map(|(local, remote)| {
+            let max = local.amount.max(remote);
+            if max.is_zero() {
+                return None;
+            }
+            let diff = if local.amount > remote {
+                local.amount - remote
+            } else {
+                remote - local.amount
+            };
+            let drift = diff.multiply_ratio(FEE_PRECISION, max);
+            if drift <= Uint128::from(tolerance) {
+                return None;
+            }
+            Some(
+                Event::new("counterparty_pool_drift")
+                    .add_attribute("pool_id", &pool.id)
+                    .add_attribute("denom", local.denom)
+                    .add_attribute("local_amount", local.amount)
+                    .add_attribute("counterparty_amount", remote)
+                    .add_attribute("drift", drift),
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn encode<F>(write: F) -> StdResult<Vec<u8>>
+where
+    F: FnOnce(&mut CodedOutputStream) -> ProtobufResult<()>,
+{
+    let mut bytes = vec![];
+    let mut os = CodedOutputStream::vec(&mut bytes);
+    write(&mut os)
+        .and_then(|_| os.flush())
+        .map_err(proto_err)?;
+    drop(os);
+    Ok(bytes)
+}
+
+/// Returns the first length delimited value of `field`, or empty bytes when absent.
+pub(crate) fn read_bytes_field(bytes: &[u8], field: u32) -> StdResult<Vec<u8>> {
+    let mut is = CodedInputStream::from_bytes(bytes);
+    while !is.eof().map_err(proto_err)? {
+        let (number, wire_type) = is.read_tag_unpack().map_err(proto_err)?;
+        if number == field && wire_type == WireType::WireTypeLengthDelimited {
+            return is.read_bytes().map_err(proto_err);
+        }
+        is.skip_field(wire_type).map_err(proto_err)?;
+    }
+    Ok(vec![])
+}
+
+/// Returns the value of the varint `field`, or zero when absent.
+fn read_uint32_field(bytes: &[u8], field: u32) -> StdResult<u32> {
+    let mut is = CodedInputStream::from_bytes(bytes);
+    while !is.eof().map_err(proto_err)? {
+        let (number, wire_type) = is.read_tag_unpack().map_err(proto_err)?;
+        if number == field && wire_type == WireType::WireTypeVarint {
+            return is.read_uint32().map_err(proto_err);
+        }
+        is.skip_field(wire_type).map_err(proto_err)?;
+    }
+    Ok(0)
+}
+
+/// Returns the value of the varint `field`, or zero when absent.
+pub(crate) fn read_uint64_field(bytes: &[u8], field: u32) -> StdResult<u64> {
+    let mut is = CodedInputStream::from_bytes(bytes);
+    while !is.eof().map_err(proto_err)? {
+        let (number, wire_type) = is.read_tag_unpack().map_err(proto_err)?;
+        if number == field && wire_type == WireType::WireTypeVarint {
+            return is.read_uint64().map_err(proto_err);
+        }
+        is.skip_field(wire_type).map_err(proto_err)?;
+    }
+    Ok(0)
+}
+
+pub(crate) fn proto_err(err: ProtobufError) -> StdError {
+    StdError::parse_err("protobuf", err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::mock_ibc_channel;
+    use cosmwasm_std::to_binary;
+    use crate::market::{FeeApplication, PoolAsset, PoolSide, PoolStatus, PoolType};
+
+    fn asset(side: PoolSide, denom: &str, amount: u128) -> PoolAsset {
+        PoolAsset {
+            side,
+            balance: Coin::new(amount, denom),
+            weight: 50,
+            decimal: 6,
+        }
+    }
+
+    #[test]
+    fn test_enforce_icq_order_and_version() {
+        let channel = mock_ibc_channel("channel-1", IbcOrder::Unordered, ICQ_VERSION);
+        enforce_icq_order_and_version(&channel, Some(ICQ_VERSION)).unwrap();
+
+        let channel = mock_ibc_channel("channel-1", IbcOrder::Ordered, ICQ_VERSION);
+        let err = enforce_icq_order_and_version(&channel, Some(ICQ_VERSION)).unwrap_err();
+        assert_eq!(err, ContractError::OnlyUnorderedChannel {});
+
+        let channel = mock_ibc_channel("channel-1", IbcOrder::Unordered, "ics101-1");
+        let err = enforce_icq_order_and_version(&channel, None).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidIbcVersion {
+                version: "ics101-1".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_pool_query_round_trip_and_drift() {
+        let packet = pool_query_packet("wasm1contract", "pool1").unwrap();
+        assert_eq!(pool_id_from_packet(&packet).unwrap(), "pool1");
+
+        // ack as returned by the icq host: CosmosResponse { ResponseQuery { value } }
+        let remote = InterchainPoolResponse {
+            id: "pool1".to_string(),
+            source_creator: "maker".to_string(),
+            source_chain_id: "chain-b".to_string(),
+            destination_chain_id: "chain-a".to_string(),
+            destination_creator: "taker".to_string(),
+            assets: vec![
+                asset(PoolSide::DESTINATION, "aside", 1000),
+                asset(PoolSide::SOURCE, "bside", 1050),
+            ],
+            swap_fee: 30,
+            right_swap_fee: None,
+            supply: Coin::new(2000, "pool1"),
+            status: PoolStatus::Active,
+            counter_party_port: "wasm.wasm1contract".to_string(),
+            counter_party_channel: "channel-1".to_string(),
+            packet_timeout_seconds: None,
+            manager: None,
+            lp_token: None,
+            spot_price: None,
+            min_swap_fee_bps: 0,
+            max_swap_fee_bps: 0,
+            pending_packets: 0,
+        };
+        let smart_response = encode(|os| os.write_bytes(1, &to_vec(&remote).unwrap())).unwrap();
+        let response = encode(|os| os.write_bytes(7, &smart_response)).unwrap();
+        let cosmos_response = encode(|os| os.write_bytes(1, &response)).unwrap();
+        let ack = to_binary(&InterchainQueryPacketAckData {
+            data: cosmos_response.into(),
+        })
+        .unwrap();
+        assert_eq!(pool_from_ack(&ack).unwrap(), remote);
+
+        let local = InterchainLiquidityPool {
+            assets: vec![
+                asset(PoolSide::SOURCE, "aside", 1000),
+                asset(PoolSide::DESTINATION, "bside", 1000),
+            ],
+            counter_party_channel: "channel-0".to_string(),
+            counter_party_port: "wasm.wasm1other".to_string(),
+            destination_creator: "taker".to_string(),
+            destination_chain_id: "chain-b".to_string(),
+            id: "pool1".to_string(),
+            source_chain_id: "chain-a".to_string(),
+            source_creator: "maker".to_string(),
+            status: PoolStatus::Active,
+            supply: Coin::new(2000, "pool1"),
+            swap_fee: 30,
+            pool_price: 0,
+            packet_timeout_seconds: None,
+            fee_application: FeeApplication::Source,
+            pool_type: PoolType::Weighted,
+            right_swap_fee: None,
+        };
+        let counterparty = CounterpartyPool {
+            assets: remote.assets,
+            supply: remote.supply,
+            status: remote.status,
+            updated_at: 0,
+        };
+
+        // "bside" drifted by 50 / 1050 ~ 4.7%
+        let events = drift_events(&local, &counterparty, DEFAULT_DRIFT_TOLERANCE);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].attributes[1].value, "bside");
+        assert!(drift_events(&local, &counterparty, 500).is_empty());
+    }
+}
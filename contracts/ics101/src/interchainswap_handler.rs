@@ -1,67 +1,229 @@
 use std::vec;
 
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+pub use ibcswap_packets::InterchainSwapPacketAcknowledgement;
+
+use cw20::Cw20ExecuteMsg;
+use cw_storage_plus::Map;
+use ibcswap_packets::{FailureAck, PacketError};
 
 use crate::market::FEE_PRECISION;
+use crate::math::multiply_ratio_ceil;
 use crate::msg::LPAllocation;
 use crate::msg::LogExecuteMsg::LogObservation;
 use crate::msg::RouterExecuteMsg::MultiSwap;
 use crate::{
+    address::validate_local_address,
+    codec::{decode_packet, decode_payload, encode_payload},
     error::ContractError,
+    forward::forward_output,
     market::{
-        InterchainLiquidityPool, PoolSide,
-        PoolStatus::{Active, Cancelled, Initialized},
+        unlocked_shares, InterchainLiquidityPool, InterchainMarketMaker, MarketFeeUpdateProposal,
+        PoolSide, MINIMUM_LIQUIDITY,
+        PoolStatus::{Active, Bootstrap, Cancelled, Draining, Initialized, Retired},
     },
     msg::{
-        MsgCancelMultiAssetDepositRequest, MsgCancelPoolRequest, MsgMakeMultiAssetDepositRequest,
-        MsgMakePoolRequest, MsgMultiAssetWithdrawRequest, MsgSingleAssetDepositRequest,
-        MsgSwapRequest, MsgTakeMultiAssetDepositRequest, MsgTakePoolRequest,
+        MsgCancelMultiAssetDepositRequest, MsgCancelPoolRequest, MsgDecommissionPoolRequest,
+        MsgMakeMultiAssetDepositRequest, MsgMakePoolRequest, MsgMatchMultiAssetDepositRequest,
+        MsgMultiAssetWithdrawRequest, MsgSetPoolManagerRequest, MsgSetPoolStatusRequest,
+        MsgSingleAssetDepositRequest, MsgSwapRequest, MsgTakeMultiAssetDepositRequest,
+        MsgTakePoolRequest, PacketMessage, SwapMsgType,
     },
     state::{
-        ACTIVE_ORDERS, CONFIG, LOG_VOLUME, MULTI_ASSET_DEPOSIT_ORDERS, POOLS, POOL_TOKENS_LIST,
+        DepositStatus, JournalEntry, LpChange, LpCheckpoint, PacketResult, QueuedWithdrawal,
+        ReceivedPacket, TakeMultiDepositRollback, UndoRecord, ACTIVE_ORDERS, BOOTSTRAP_DEPOSITS,
+        BOOTSTRAP_TARGETS, CHANNEL_INFO, COLLECTED_FEES, CONFIG, DEPOSIT_RECEIPTS, DRAIN_DEADLINES,
+        DUST_THRESHOLDS, ESCROWED_FUNDS, INITIALIZED_POOLS, IN_FLIGHT_PACKETS, LOG_VOLUME,
+        LP_CHECKPOINTS, LP_CHECKPOINT_COUNT, LP_TOKEN_MARKETING, LP_TOKEN_STATUS,
+        MAX_LP_CHECKPOINTS, MULTI_ASSET_DEPOSIT_ORDERS, PACKET_JOURNAL, PACKET_JOURNAL_COUNT,
+        PENDING_DEPOSIT_RECEIPTS, PENDING_JOURNAL_ENTRIES, POOLS, POOL_MANAGERS, POOL_TOKENS_LIST,
+        PROTOCOL_FEES, RECEIVED_PACKETS, SWAP_CLAIMS, SWAP_ESCROW, TAKE_MULTI_DEPOSIT_ROLLBACK,
+        TREASURY, UNDO_EXPIRY, UNDO_LOG, UNDO_RETENTION_SECONDS, VETOED_PACKETS, WITHDRAWAL_QUEUE,
+        WITHDRAWAL_QUEUE_COUNT,
     },
+    tokenfactory,
     types::{
         InterchainMessageType, InterchainSwapPacketData, MultiAssetDepositOrder, OrderStatus,
-        StateChange,
+        SettledSwap, StateChange,
     },
+    undo::UndoStorage,
     utils::{
-        burn_tokens_cw20, get_coins_from_deposits, get_pool_id_with_tokens, mint_tokens_cw20,
-        send_tokens_coin, send_tokens_cw20,
+        assert_allowed, assert_swap_slippage, bind_counterparty_chain_id, burn_tokens_cw20,
+        coins_to_string, get_coins_from_deposits, get_pool_id_with_tokens,
+        instantiate_lp_token_once, is_lifecycle_status, mint_tokens_cw20, packet_data_hash,
+        send_tokens_coin, send_tokens_cw20, validate_asset_decimals,
+        TAKE_MULTI_DEPOSIT_MINT_REPLY_ID,
     },
 };
 
 use cosmwasm_std::{
-    attr, from_binary, from_slice, to_binary, Addr, Binary, Coin, DepsMut, Env, IbcBasicResponse,
-    IbcPacket, IbcReceiveResponse, StdError, SubMsg, Uint128, WasmMsg,
+    attr, from_binary, to_binary, Attribute, BankMsg, Binary, Coin, CosmosMsg, Decimal, DepsMut,
+    Env, Event, IbcBasicResponse, IbcMsg, IbcPacket, IbcReceiveResponse, Order, Response,
+    StdError, StdResult, Storage, SubMsg, SubMsgResult, Uint128, WasmMsg,
 };
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
-#[serde(rename_all = "snake_case")]
-pub enum InterchainSwapPacketAcknowledgement {
-    Result(Binary),
-    Error(String),
-}
-
 // create a serialized success message
 pub(crate) fn ack_success() -> Binary {
     let res = InterchainSwapPacketAcknowledgement::Result(b"1".into());
     to_binary(&res).unwrap()
 }
 
+// create a serialized success message carrying a result for the sending chain
+pub(crate) fn ack_success_with(result: &impl serde::Serialize) -> StdResult<Binary> {
+    let res = InterchainSwapPacketAcknowledgement::Result(encode_payload(result)?);
+    to_binary(&res)
+}
+
 // create a serialized error message
 pub(crate) fn ack_fail(err: String) -> Binary {
     let res = InterchainSwapPacketAcknowledgement::Error(err);
     to_binary(&res).unwrap()
 }
 
+// create a serialized error message naming the pool and order of the failed packet
+pub(crate) fn ack_fail_for(packet: &IbcPacket, err: String) -> Binary {
+    let failure = decode_packet(&packet.data)
+        .ok()
+        .and_then(|packet_data| FailureAck::new(&packet_data, err.clone()).encode().ok());
+    ack_fail(failure.unwrap_or(err))
+}
+
+// handle a received packet, keeping the prior value of everything it changes
+// so an admin can revert the packet later
 pub(crate) fn do_ibc_packet_receive(
     deps: DepsMut,
     env: Env,
     packet: &IbcPacket,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let packet_data: InterchainSwapPacketData = from_slice(&packet.data)?;
+    if VETOED_PACKETS.has(deps.storage, (&packet.dest.channel_id, packet.sequence)) {
+        return Err(ContractError::PacketVetoed {
+            channel_id: packet.dest.channel_id.clone(),
+            sequence: packet.sequence,
+        });
+    }
+    let packet_data = decode_packet(&packet.data)?;
+    let packet_type = packet_data.r#type.clone();
+    if !CONFIG.load(deps.storage)?.is_enabled(&packet_type) {
+        return Err(ContractError::MessageDisabled {
+            message_type: packet_type,
+        });
+    }
+
+    assert_packet_counterparty(deps.storage, packet, &packet_data)?;
+
+    let mut storage = UndoStorage::new(deps.storage);
+    let tracked = DepsMut {
+        storage: &mut storage,
+        api: deps.api,
+        querier: deps.querier,
+    };
+    let res = receive_packet(tracked, env.clone(), packet, packet_data)?;
+
+    let received_at = env.block.time.seconds();
+    let record = UndoRecord {
+        packet_type,
+        received_at,
+        changes: storage.into_changes(),
+        sent: sent_tokens(&res.messages),
+    };
+    let key = (packet.dest.channel_id.as_str(), packet.sequence);
+    UNDO_LOG.save(deps.storage, key, &record)?;
+    UNDO_EXPIRY.save(deps.storage, (received_at, key.0, key.1), &true)?;
+    prune_undo_log(deps.storage, received_at)?;
+
+    let checkpoints = record_lp_checkpoints(deps.storage, &env, packet, &res.messages)?;
+    Ok(res.add_events(checkpoints))
+}
+
+// tokens paid out of the contract by the messages of a response
+fn sent_tokens(messages: &[SubMsg]) -> Vec<Coin> {
+    let mut sent: Vec<Coin> = vec![];
+    for submsg in messages {
+        let coins = match &submsg.msg {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount.clone(),
+            CosmosMsg::Ibc(IbcMsg::Transfer { amount, .. }) => vec![amount.clone()],
+            _ => continue,
+        };
+        for coin in coins {
+            match sent.iter_mut().find(|sent| sent.denom == coin.denom) {
+                Some(sent) => sent.amount += coin.amount,
+                None => sent.push(coin),
+            }
+        }
+    }
+    sent
+}
+
+// undo records past the retention window are dropped a few at a time as packets arrive
+fn prune_undo_log(storage: &mut dyn Storage, now: u64) -> StdResult<()> {
+    let expired = UNDO_EXPIRY
+        .keys(storage, None, None, Order::Ascending)
+        .take(10)
+        .take_while(|key| {
+            key.as_ref().map_or(true, |(received_at, _, _)| {
+                received_at.saturating_add(UNDO_RETENTION_SECONDS) <= now
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    for (received_at, channel_id, sequence) in expired {
+        UNDO_EXPIRY.remove(storage, (received_at, &channel_id, sequence));
+        UNDO_LOG.remove(storage, (&channel_id, sequence));
+    }
+    Ok(())
+}
+
+// a packet has to come from the contract the channel was opened with, and act on a pool
+// over the channel the pool was made on, in a status that allows the packet type
+fn assert_packet_counterparty(
+    storage: &dyn Storage,
+    packet: &IbcPacket,
+    packet_data: &InterchainSwapPacketData,
+) -> Result<(), ContractError> {
+    if let Some(channel) = CHANNEL_INFO.may_load(storage, &packet.dest.channel_id)? {
+        if channel.counterparty_endpoint != packet.src {
+            return Err(ContractError::UnexpectedCounterparty {
+                connection_id: channel.connection_id,
+                port_id: packet.src.port_id.clone(),
+                expected: channel.counterparty_endpoint.port_id,
+            });
+        }
+    }
+
+    let message = packet_data.decode_message()?;
+    let pool_id = match &message {
+        PacketMessage::MakePool(msg) => {
+            if msg.counterparty_channel != packet.dest.channel_id
+                || msg.source_channel != packet.src.channel_id
+                || msg.source_port != packet.src.port_id
+            {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Pool endpoints differ from the packet channel".to_string(),
+                )));
+            }
+            None
+        }
+        message => message.pool_id(),
+    };
+    if let Some(pool_id) = pool_id {
+        if let Some(pool) = POOLS.may_load(storage, pool_id)? {
+            if pool.counter_party_channel != packet.dest.channel_id {
+                return Err(ContractError::UnexpectedPoolChannel {
+                    pool_id: pool.id,
+                    channel_id: packet.dest.channel_id.clone(),
+                    expected: pool.counter_party_channel,
+                });
+            }
+            assert_allowed(&pool.status, &packet_data.r#type)?;
+        }
+    }
+    Ok(())
+}
 
+fn receive_packet(
+    deps: DepsMut,
+    env: Env,
+    packet: &IbcPacket,
+    packet_data: InterchainSwapPacketData,
+) -> Result<IbcReceiveResponse, ContractError> {
     match packet_data.r#type {
         InterchainMessageType::Unspecified => {
             let res = IbcReceiveResponse::new()
@@ -72,60 +234,139 @@ pub(crate) fn do_ibc_packet_receive(
         }
         // Save pool data
         InterchainMessageType::MakePool => {
-            let msg: MsgMakePoolRequest = from_slice(&packet_data.data)?;
+            let msg: MsgMakePoolRequest = decode_payload(&packet_data.data)?;
             on_received_make_pool(deps, env, packet, msg)
         }
         InterchainMessageType::TakePool => {
-            let msg: MsgTakePoolRequest = from_slice(&packet_data.data)?;
-            let state_change_data: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgTakePoolRequest = decode_payload(&packet_data.data)?;
+            let state_change_data = decode_state_change(&packet_data)?;
             on_received_take_pool(deps, env, packet, msg, state_change_data)
         }
         InterchainMessageType::CancelPool => {
-            let msg: MsgCancelPoolRequest = from_slice(&packet_data.data)?;
+            let msg: MsgCancelPoolRequest = decode_payload(&packet_data.data)?;
             on_received_cancel_pool(deps, env, packet, msg)
         }
         InterchainMessageType::SingleAssetDeposit => {
-            let msg: MsgSingleAssetDepositRequest = from_slice(&packet_data.data)?;
-            let state_change_data: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgSingleAssetDepositRequest = decode_payload(&packet_data.data)?;
+            let state_change_data = decode_state_change(&packet_data)?;
             on_received_single_deposit(deps, env, packet, msg, state_change_data)
         }
         InterchainMessageType::MakeMultiDeposit => {
-            let msg: MsgMakeMultiAssetDepositRequest = from_slice(&packet_data.data)?;
-            let state_change_data: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgMakeMultiAssetDepositRequest = decode_payload(&packet_data.data)?;
+            let state_change_data = decode_state_change(&packet_data)?;
             on_received_make_multi_deposit(deps, env, packet, msg, state_change_data)
         }
         InterchainMessageType::TakeMultiDeposit => {
-            let msg: MsgTakeMultiAssetDepositRequest = from_slice(&packet_data.data)?;
-            let state_change_data: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgTakeMultiAssetDepositRequest = decode_payload(&packet_data.data)?;
+            let state_change_data = decode_state_change(&packet_data)?;
             on_received_take_multi_deposit(deps, env, packet, msg, state_change_data)
         }
         InterchainMessageType::CancelMultiDeposit => {
-            let msg: MsgCancelMultiAssetDepositRequest = from_slice(&packet_data.data)?;
+            let msg: MsgCancelMultiAssetDepositRequest = decode_payload(&packet_data.data)?;
             on_received_cancel_multi_deposit(deps, env, packet, msg)
         }
+        InterchainMessageType::MatchMultiDeposit => {
+            let msg: MsgMatchMultiAssetDepositRequest = decode_payload(&packet_data.data)?;
+            let state_change_data = decode_state_change(&packet_data)?;
+            on_received_match_multi_deposit(deps, env, packet, msg, state_change_data)
+        }
         InterchainMessageType::MultiWithdraw => {
-            let msg: MsgMultiAssetWithdrawRequest = from_slice(&packet_data.data)?;
-            let state_change_data: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgMultiAssetWithdrawRequest = decode_payload(&packet_data.data)?;
+            let state_change_data = decode_state_change(&packet_data)?;
             on_received_multi_withdraw(deps, env, packet, msg, state_change_data)
         }
         InterchainMessageType::LeftSwap => {
-            let msg: MsgSwapRequest = from_binary(&packet_data.data)?;
-            let state_change_data: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgSwapRequest = decode_payload(&packet_data.data)?;
+            let state_change_data = decode_state_change(&packet_data)?;
             on_received_swap(deps, env, packet, msg, state_change_data)
         }
         InterchainMessageType::RightSwap => {
-            let msg: MsgSwapRequest = from_binary(&packet_data.data)?;
-            let state_change_data: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgSwapRequest = decode_payload(&packet_data.data)?;
+            let state_change_data = decode_state_change(&packet_data)?;
             on_received_swap(deps, env, packet, msg, state_change_data)
         }
+        InterchainMessageType::SetPoolManager => {
+            let msg: MsgSetPoolManagerRequest = decode_payload(&packet_data.data)?;
+            on_received_set_pool_manager(deps, env, packet, msg)
+        }
+        InterchainMessageType::SetPoolStatus => {
+            let msg: MsgSetPoolStatusRequest = decode_payload(&packet_data.data)?;
+            on_received_set_pool_status(deps, env, packet, msg)
+        }
+        InterchainMessageType::DecommissionPool => {
+            let msg: MsgDecommissionPoolRequest = decode_payload(&packet_data.data)?;
+            on_received_decommission_pool(deps, env, packet, msg)
+        }
+        InterchainMessageType::UpdateFee => {
+            let msg: MarketFeeUpdateProposal = decode_payload(&packet_data.data)?;
+            on_received_update_fee(deps, env, packet, msg)
+        }
     }
 }
 
+// decode and validate the state change of a counterparty packet
+fn decode_state_change(
+    packet_data: &InterchainSwapPacketData,
+) -> Result<StateChange, ContractError> {
+    packet_data
+        .decode_valid_state_change()
+        .map_err(|err| ContractError::MalformedPacket {
+            field: match err {
+                PacketError::InvalidStateChange { field, .. } => field,
+                _ => "StateChange".to_string(),
+            },
+        })
+}
+
+// decode the state change of a packet sent by this contract
+fn packet_state_change(
+    packet_data: &InterchainSwapPacketData,
+) -> Result<StateChange, ContractError> {
+    let state_change = required(packet_data.state_change.as_ref(), "StateChange")?;
+    decode_payload(state_change).map_err(|_| ContractError::MalformedPacket {
+        field: "StateChange".to_string(),
+    })
+}
+
+// take a field the packet has to carry
+/// Values of a handler and the pool balances around it, for incident investigation.
+/// Empty unless the admin enabled debug attributes.
+fn debug_attributes(
+    storage: &dyn Storage,
+    before: &InterchainLiquidityPool,
+    after: &InterchainLiquidityPool,
+    values: Vec<Attribute>,
+) -> StdResult<Vec<Attribute>> {
+    if !CONFIG.load(storage)?.debug_attributes {
+        return Ok(vec![]);
+    }
+    let mut attributes = values;
+    for (state, pool) in [("before", before), ("after", after)] {
+        for asset in &pool.assets {
+            let key = format!("debug_{}_{}", state, asset.balance.denom);
+            attributes.push(attr(key, asset.balance.amount));
+        }
+        attributes.push(attr(format!("debug_{}_supply", state), pool.supply.amount));
+    }
+    Ok(attributes)
+}
+
+fn required<T>(value: Option<T>, field: &str) -> Result<T, ContractError> {
+    value.ok_or_else(|| ContractError::MalformedPacket {
+        field: field.to_string(),
+    })
+}
+
+// take the first token of a token list the packet has to carry
+fn first_token(tokens: Option<Vec<Coin>>, field: &str) -> Result<Coin, ContractError> {
+    required(tokens.and_then(|tokens| tokens.into_iter().next()), field)
+}
+
 pub(crate) fn on_received_make_pool(
     deps: DepsMut,
-    _env: Env,
-    _packet: &IbcPacket,
-    msg: MsgMakePoolRequest,
+    env: Env,
+    packet: &IbcPacket,
+    mut msg: MsgMakePoolRequest,
 ) -> Result<IbcReceiveResponse, ContractError> {
     // get pool asset from tokens and weight
     if let Err(err) = msg.validate_basic() {
@@ -134,10 +375,11 @@ pub(crate) fn on_received_make_pool(
             err
         ))));
     }
+    validate_asset_decimals(deps.storage, &msg.liquidity)?;
+    msg.source_chain_id =
+        bind_counterparty_chain_id(deps.storage, &packet.dest.channel_id, &msg.source_chain_id)?;
 
-    let mut tokens: [Coin; 2] = Default::default();
-    tokens[0] = msg.liquidity[0].balance.clone();
-    tokens[1] = msg.liquidity[1].balance.clone();
+    let tokens: Vec<Coin> = msg.liquidity.iter().map(|asset| asset.balance.clone()).collect();
 
     let pool_id = get_pool_id_with_tokens(
         &tokens,
@@ -153,6 +395,7 @@ pub(crate) fn on_received_make_pool(
         )));
     }
 
+    let bootstrap = msg.is_bootstrap();
     let mut liquidity = vec![];
     for mut asset in msg.liquidity {
         if asset.side == PoolSide::SOURCE {
@@ -163,11 +406,22 @@ pub(crate) fn on_received_make_pool(
         liquidity.push(asset);
     }
 
+    // the counterparty enforces its own bounds, reject fees outside of ours
+    let config = CONFIG.load(deps.storage)?;
+    for fee in [Some(msg.swap_fee), msg.right_swap_fee].into_iter().flatten() {
+        if !config.swap_fee_in_bounds(fee) {
+            return Err(ContractError::InvalidSwapFee {
+                fee,
+                min: config.min_swap_fee_bps,
+                max: config.max_swap_fee_bps,
+            });
+        }
+    }
     let supply: Coin = Coin {
         amount: Uint128::from(0u64),
         denom: pool_id.clone(),
     };
-    let interchain_pool: InterchainLiquidityPool = InterchainLiquidityPool {
+    let mut interchain_pool: InterchainLiquidityPool = InterchainLiquidityPool {
         id: pool_id.clone(),
         source_creator: msg.creator,
         destination_creator: msg.counterparty_creator,
@@ -180,11 +434,43 @@ pub(crate) fn on_received_make_pool(
         source_chain_id: msg.source_chain_id,
         destination_chain_id: msg.destination_chain_id,
         pool_price: 0,
+        packet_timeout_seconds: msg
+            .packet_timeout_seconds
+            .map(|seconds| config.clamp_timeout(seconds)),
+        fee_application: msg.fee_application,
+        pool_type: msg.pool_type,
+        right_swap_fee: msg.right_swap_fee,
     };
 
+    if let Some(marketing) = &msg.lp_token_marketing {
+        LP_TOKEN_MARKETING.save(deps.storage, &pool_id, marketing)?;
+    }
+
+    // A bootstrap pool is never taken, so the LP token of this chain is created
+    // here and the maker shares are accounted right away.
+    let mut sub_messages = vec![];
+    if bootstrap {
+        let amm = InterchainMarketMaker::new(&interchain_pool, interchain_pool.swap_fee);
+        let shares = amm.bootstrap_shares()?;
+        interchain_pool
+            .add_supply(shares)
+            .map_err(|err| StdError::generic_err(format!("Failed to add supply: {}", err)))?;
+        interchain_pool.status = Bootstrap;
+        // the unfunded assets of the maker are local here, their deposits are priced here
+        BOOTSTRAP_TARGETS.save(deps.storage, &pool_id, &msg.bootstrap_targets.unwrap_or_default())?;
+
+        sub_messages.extend(instantiate_lp_token_once(
+            deps.storage,
+            &pool_id,
+            &config,
+            env.contract.address.to_string(),
+        )?);
+    }
+
     POOLS.save(deps.storage, &pool_id, &interchain_pool)?;
 
     let res = IbcReceiveResponse::new()
+        .add_submessages(sub_messages)
         .add_attribute("pool_id", pool_id.clone())
         .add_attribute("action", "make_pool_receive")
         .add_attribute("ics101-lp-instantiate", pool_id)
@@ -213,14 +499,15 @@ pub(crate) fn on_received_take_pool(
         )));
     }
 
-    let new_shares = state_change.shares.unwrap();
+    let new_shares = required(state_change.shares, "Shares")?;
+    let minted_shares = unlocked_shares(new_shares)?;
     // mint new_shares in take receive
     let sub_message;
     // Mint tokens (cw20) to the sender
     if let Some(lp_token) = POOL_TOKENS_LIST.may_load(deps.storage, &msg.pool_id.clone())? {
         match msg.lp_allocation {
             LPAllocation::MakerChain => {
-                sub_message = mint_tokens_cw20(msg.counter_creator, lp_token, new_shares)?;
+                sub_message = mint_tokens_cw20(msg.counter_creator, lp_token, minted_shares)?;
             }
             LPAllocation::TakerChain => {
                 // do nothing
@@ -228,13 +515,7 @@ pub(crate) fn on_received_take_pool(
             }
             LPAllocation::Split => {
                 // split shares
-                let token = interchain_pool
-                    .find_asset_by_side(PoolSide::SOURCE)
-                    .map_err(|err| {
-                        StdError::generic_err(format!("Failed to find asset: {}", err))
-                    })?;
-                let splitted_shares =
-                    (new_shares * Uint128::from(token.weight)) / Uint128::from(100u64);
+                let splitted_shares = interchain_pool.local_shares(minted_shares);
                 sub_message = mint_tokens_cw20(msg.counter_creator, lp_token, splitted_shares)?;
             }
         }
@@ -255,6 +536,7 @@ pub(crate) fn on_received_take_pool(
     interchain_pool.status = Active;
 
     POOLS.save(deps.storage, &msg.pool_id, &interchain_pool)?;
+    INITIALIZED_POOLS.remove(deps.storage, (&interchain_pool.source_creator, &msg.pool_id));
 
     let res = IbcReceiveResponse::new()
         .set_ack(ack_success())
@@ -294,6 +576,199 @@ pub(crate) fn on_received_cancel_pool(
     Ok(res)
 }
 
+pub(crate) fn on_received_set_pool_manager(
+    deps: DepsMut,
+    _env: Env,
+    _packet: &IbcPacket,
+    msg: MsgSetPoolManagerRequest,
+) -> Result<IbcReceiveResponse, ContractError> {
+    if !POOLS.has(deps.storage, &msg.pool_id) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Pool not found".to_string(),
+        )));
+    }
+    save_pool_manager(deps.storage, &msg)?;
+
+    let res = IbcReceiveResponse::new()
+        .set_ack(ack_success())
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("action", "set_pool_manager_receive")
+        .add_attribute("success", "true");
+
+    Ok(res)
+}
+
+// the manager is kept on both chains so each side checks the same address
+fn save_pool_manager(
+    storage: &mut dyn Storage,
+    msg: &MsgSetPoolManagerRequest,
+) -> Result<(), ContractError> {
+    match &msg.manager {
+        Some(manager) => POOL_MANAGERS.save(storage, &msg.pool_id, manager)?,
+        None => POOL_MANAGERS.remove(storage, &msg.pool_id),
+    }
+    Ok(())
+}
+
+pub(crate) fn on_received_set_pool_status(
+    deps: DepsMut,
+    _env: Env,
+    _packet: &IbcPacket,
+    msg: MsgSetPoolStatusRequest,
+) -> Result<IbcReceiveResponse, ContractError> {
+    save_pool_status(deps.storage, &msg)?;
+
+    let res = IbcReceiveResponse::new()
+        .set_ack(ack_success())
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("action", "set_pool_status_receive")
+        .add_attribute("success", "true");
+
+    Ok(res)
+}
+
+// governance moves running pools between active, frozen and draining on both chains
+fn save_pool_status(
+    storage: &mut dyn Storage,
+    msg: &MsgSetPoolStatusRequest,
+) -> Result<(), ContractError> {
+    let mut interchain_pool = POOLS.load(storage, &msg.pool_id)?;
+    assert_allowed(&interchain_pool.status, &InterchainMessageType::SetPoolStatus)?;
+    if !is_lifecycle_status(&msg.status) {
+        return Err(ContractError::InvalidStatus);
+    }
+    interchain_pool.status = msg.status.clone();
+    POOLS.save(storage, &msg.pool_id, &interchain_pool)?;
+    match msg.drain_deadline {
+        Some(deadline) => DRAIN_DEADLINES.save(storage, &msg.pool_id, &deadline)?,
+        None => DRAIN_DEADLINES.remove(storage, &msg.pool_id),
+    }
+    Ok(())
+}
+
+pub(crate) fn on_received_update_fee(
+    deps: DepsMut,
+    _env: Env,
+    _packet: &IbcPacket,
+    msg: MarketFeeUpdateProposal,
+) -> Result<IbcReceiveResponse, ContractError> {
+    // the counterparty enforces its own bounds, reject fees outside of ours
+    let config = CONFIG.load(deps.storage)?;
+    for fee in [Some(msg.fee_rate), msg.right_fee_rate].into_iter().flatten() {
+        if !config.swap_fee_in_bounds(fee) {
+            return Err(ContractError::InvalidSwapFee {
+                fee,
+                min: config.min_swap_fee_bps,
+                max: config.max_swap_fee_bps,
+            });
+        }
+    }
+    save_pool_fee(deps.storage, &msg)?;
+
+    let res = IbcReceiveResponse::new()
+        .set_ack(ack_success())
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("fee_rate", msg.fee_rate.to_string())
+        .add_attribute("action", "update_fee_receive")
+        .add_attribute("success", "true");
+
+    Ok(res)
+}
+
+fn save_pool_fee(
+    storage: &mut dyn Storage,
+    msg: &MarketFeeUpdateProposal,
+) -> Result<(), ContractError> {
+    let mut interchain_pool = POOLS.load(storage, &msg.pool_id)?;
+    assert_allowed(&interchain_pool.status, &InterchainMessageType::UpdateFee)?;
+    interchain_pool.swap_fee = msg.fee_rate;
+    interchain_pool.right_swap_fee = msg.right_fee_rate;
+    POOLS.save(storage, &msg.pool_id, &interchain_pool)?;
+    Ok(())
+}
+
+pub(crate) fn on_received_decommission_pool(
+    deps: DepsMut,
+    _env: Env,
+    _packet: &IbcPacket,
+    msg: MsgDecommissionPoolRequest,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let residual = retire_pool(deps.storage, &msg.pool_id)?;
+
+    let res = IbcReceiveResponse::new()
+        .set_ack(ack_success())
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("residual", coins_to_string(&residual))
+        .add_attribute("action", "decommission_pool_receive")
+        .add_attribute("success", "true");
+
+    Ok(res)
+}
+
+// delete a draining pool, what is left of its local assets and fees goes to the treasury. A pool
+// with LP left is retired instead, its LP holders keep withdrawing their share and only the
+// protocol fees go to the treasury until the last of them did.
+fn retire_pool(storage: &mut dyn Storage, pool_id: &str) -> Result<Vec<Coin>, ContractError> {
+    let mut interchain_pool = POOLS.load(storage, pool_id)?;
+    if interchain_pool.status != Draining && interchain_pool.status != Retired {
+        return Err(ContractError::InvalidStatus);
+    }
+    if interchain_pool.supply.amount > Uint128::new(MINIMUM_LIQUIDITY) {
+        let protocol_fees = PROTOCOL_FEES.may_load(storage, pool_id)?.unwrap_or_default();
+        let mut residual = vec![];
+        for fee in protocol_fees {
+            if interchain_pool.side_of(&fee.denom)? != PoolSide::SOURCE {
+                continue;
+            }
+            TREASURY.update(storage, &fee.denom, |amount| -> StdResult<_> {
+                Ok(amount.unwrap_or_default() + fee.amount)
+            })?;
+            residual.push(fee);
+        }
+        PROTOCOL_FEES.remove(storage, pool_id);
+        DRAIN_DEADLINES.remove(storage, pool_id);
+        interchain_pool.status = Retired;
+        POOLS.save(storage, pool_id, &interchain_pool)?;
+        return Ok(residual);
+    }
+
+    let mut fees = COLLECTED_FEES.may_load(storage, pool_id)?.unwrap_or_default();
+    fees.extend(PROTOCOL_FEES.may_load(storage, pool_id)?.unwrap_or_default());
+    let mut residual = vec![];
+    for asset in interchain_pool.assets_by_side(PoolSide::SOURCE) {
+        let mut coin = asset.balance;
+        for fee in fees.iter().filter(|fee| fee.denom == coin.denom) {
+            coin.amount += fee.amount;
+        }
+        TREASURY.update(storage, &coin.denom, |amount| -> StdResult<_> {
+            Ok(amount.unwrap_or_default() + coin.amount)
+        })?;
+        residual.push(coin);
+    }
+
+    POOLS.remove(storage, pool_id);
+    POOL_TOKENS_LIST.remove(storage, pool_id);
+    LP_TOKEN_STATUS.remove(storage, pool_id);
+    LP_TOKEN_MARKETING.remove(storage, pool_id);
+    POOL_MANAGERS.remove(storage, pool_id);
+    COLLECTED_FEES.remove(storage, pool_id);
+    PROTOCOL_FEES.remove(storage, pool_id);
+    DRAIN_DEADLINES.remove(storage, pool_id);
+    DUST_THRESHOLDS.remove(storage, pool_id);
+    Ok(residual)
+}
+
+// a retired pool is deleted once its LP is withdrawn
+fn delete_withdrawn_pool(
+    storage: &mut dyn Storage,
+    pool: &InterchainLiquidityPool,
+) -> Result<(), ContractError> {
+    if pool.status == Retired && pool.supply.amount <= Uint128::new(MINIMUM_LIQUIDITY) {
+        retire_pool(storage, &pool.id)?;
+    }
+    Ok(())
+}
+
 pub(crate) fn on_received_single_deposit(
     deps: DepsMut,
     _env: Env,
@@ -317,9 +792,10 @@ pub(crate) fn on_received_single_deposit(
             "Pool not found".to_string(),
         )));
     }
-    let pool_tokens = &state_change.pool_tokens.unwrap()[0];
+    let before = interchain_pool.clone();
+    let pool_tokens = &first_token(state_change.pool_tokens, "PoolTokens")?;
 
-    let new_shares = state_change.shares.unwrap();
+    let new_shares = required(state_change.shares, "Shares")?;
     // mint new_shares in take receive
     let sub_message;
     // Mint tokens (cw20) to the sender
@@ -334,13 +810,7 @@ pub(crate) fn on_received_single_deposit(
             }
             LPAllocation::Split => {
                 // split shares
-                let token = interchain_pool
-                    .find_asset_by_side(PoolSide::SOURCE)
-                    .map_err(|err| {
-                        StdError::generic_err(format!("Failed to find asset: {}", err))
-                    })?;
-                let splitted_shares =
-                    (new_shares * Uint128::from(token.weight)) / Uint128::from(100u64);
+                let splitted_shares = interchain_pool.local_shares(new_shares);
                 sub_message = mint_tokens_cw20(msg.lp_taker, lp_token, splitted_shares)?;
             }
         }
@@ -358,16 +828,25 @@ pub(crate) fn on_received_single_deposit(
     interchain_pool
         .add_supply(pool_tokens.clone())
         .map_err(|err| StdError::generic_err(format!("Failed to add supply: {}", err)))?;
+    interchain_pool.activate_if_funded();
 
     // save pool.
     POOLS.save(deps.storage, &msg.pool_id, &interchain_pool)?;
 
+    let debug = vec![
+        attr("debug_new_shares", new_shares),
+        attr(
+            "debug_share_ratio",
+            Decimal::from_ratio(new_shares, interchain_pool.supply.amount).to_string(),
+        ),
+    ];
     let res = IbcReceiveResponse::new()
         .add_submessages(sub_message)
         .set_ack(ack_success())
         .add_attribute("pool_id", msg.pool_id)
         .add_attribute("action", "single_asset_deposit")
-        .add_attribute("success", "true");
+        .add_attribute("success", "true")
+        .add_attributes(debug_attributes(deps.storage, &before, &interchain_pool, debug)?);
 
     Ok(res)
 }
@@ -380,36 +859,48 @@ pub(crate) fn on_received_make_multi_deposit(
     state_change: StateChange,
 ) -> Result<IbcReceiveResponse, ContractError> {
     // load pool throw error if found
-    let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
-    if let Some(_pool) = interchain_pool_temp {
-        // Do nothing
-    } else {
-        return Err(ContractError::Std(StdError::generic_err(
-            "Pool not found".to_string(),
-        )));
+    let interchain_pool = POOLS
+        .may_load(deps.storage, &msg.pool_id)?
+        .ok_or_else(|| StdError::generic_err("Pool not found".to_string()))?;
+
+    // the maker's deposits are the counterparty's assets
+    let deposits = get_coins_from_deposits(msg.deposits.clone());
+    if interchain_pool.maker_side(&deposits).ok() != Some(PoolSide::DESTINATION) {
+        return Err(ContractError::MalformedPacket {
+            field: "deposits".to_string(),
+        });
     }
+    let taker = &msg.deposits[interchain_pool.assets_by_side(PoolSide::DESTINATION).len()];
 
     let mut config = CONFIG.load(deps.storage)?;
-    config.counter += 1;
+    config.increment_counter()?;
 
     let multi_asset_order = MultiAssetDepositOrder {
-        id: state_change.multi_deposit_order_id.unwrap(),
+        id: required(state_change.multi_deposit_order_id, "MultiDepositOrderId")?,
         chain_id: msg.chain_id.clone(),
         pool_id: msg.pool_id.clone(),
         source_maker: msg.deposits[0].sender.clone(),
-        destination_taker: msg.deposits[1].sender.clone(),
-        deposits: get_coins_from_deposits(msg.deposits.clone()),
+        destination_taker: taker.sender.clone(),
+        deposits,
         status: OrderStatus::Pending,
         created_at: env.block.height,
+        recipient: msg.recipient.clone(),
+        permit_pubkey: msg.permit_pubkey.clone(),
     };
     let key = msg.pool_id.clone() + "-" + &multi_asset_order.id;
+    if MULTI_ASSET_DEPOSIT_ORDERS.has(deps.storage, key.clone()) {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Order already exists {}",
+            multi_asset_order.id
+        ))));
+    }
 
-    MULTI_ASSET_DEPOSIT_ORDERS.save(deps.storage, key, &multi_asset_order)?;
+    save_order(deps.storage, key, &multi_asset_order)?;
     let ac_key = msg.deposits[0].sender.clone()
         + "-"
         + &msg.pool_id.clone()
         + "-"
-        + &msg.deposits[1].sender.clone();
+        + &taker.sender.clone();
     ACTIVE_ORDERS.save(deps.storage, ac_key, &multi_asset_order)?;
     CONFIG.save(deps.storage, &config)?;
 
@@ -446,27 +937,35 @@ pub(crate) fn on_received_take_multi_deposit(
     let key = msg.pool_id.clone() + "-" + &msg.order_id;
     let multi_asset_order_temp = MULTI_ASSET_DEPOSIT_ORDERS.may_load(deps.storage, key.clone())?;
     let mut multi_asset_order;
+    let rollback;
     if let Some(order) = multi_asset_order_temp {
         multi_asset_order = order;
-        multi_asset_order.status = OrderStatus::Complete;
         let ac_key = multi_asset_order.source_maker.clone()
             + "-"
             + &msg.pool_id
             + "-"
             + &multi_asset_order.destination_taker;
+        rollback = TakeMultiDepositRollback {
+            order_key: key.clone(),
+            order: multi_asset_order.clone(),
+            active_order_key: ac_key.clone(),
+            active_order: ACTIVE_ORDERS.may_load(deps.storage, ac_key.clone())?,
+            pool: interchain_pool.clone(),
+        };
+        multi_asset_order.status = OrderStatus::Complete;
         ACTIVE_ORDERS.remove(deps.storage, ac_key);
     } else {
         return Err(ContractError::ErrOrderNotFound);
     }
 
-    let new_shares = state_change.shares.unwrap();
+    let new_shares = required(state_change.shares, "Shares")?;
     let sub_message;
     // Mint tokens (cw20) to the sender
     if let Some(lp_token) = POOL_TOKENS_LIST.may_load(deps.storage, &msg.pool_id)? {
         match msg.lp_allocation {
             LPAllocation::MakerChain => {
                 sub_message =
-                    mint_tokens_cw20(multi_asset_order.source_maker.clone(), lp_token, new_shares)?;
+                    mint_tokens_cw20(multi_asset_order.lp_recipient(), lp_token, new_shares)?;
             }
             LPAllocation::TakerChain => {
                 // do nothing
@@ -474,18 +973,9 @@ pub(crate) fn on_received_take_multi_deposit(
             }
             LPAllocation::Split => {
                 // split shares
-                let token = interchain_pool
-                    .find_asset_by_side(PoolSide::SOURCE)
-                    .map_err(|err| {
-                        StdError::generic_err(format!("Failed to find asset: {}", err))
-                    })?;
-                let splitted_shares =
-                    (new_shares * Uint128::from(token.weight)) / Uint128::from(100u64);
-                sub_message = mint_tokens_cw20(
-                    multi_asset_order.source_maker.clone(),
-                    lp_token,
-                    splitted_shares,
-                )?;
+                let splitted_shares = interchain_pool.local_shares(new_shares);
+                sub_message =
+                    mint_tokens_cw20(multi_asset_order.lp_recipient(), lp_token, splitted_shares)?;
             }
         }
 
@@ -511,9 +1001,17 @@ pub(crate) fn on_received_take_multi_deposit(
         )));
     }
 
-    MULTI_ASSET_DEPOSIT_ORDERS.save(deps.storage, key, &multi_asset_order)?;
+    save_order(deps.storage, key, &multi_asset_order)?;
     POOLS.save(deps.storage, &msg.pool_id, &interchain_pool)?;
 
+    // the mint reply restores the order and pool if the mint fails
+    if !sub_message.is_empty() {
+        TAKE_MULTI_DEPOSIT_ROLLBACK.save(deps.storage, &rollback)?;
+    }
+    let sub_message = sub_message
+        .into_iter()
+        .map(|sub_msg| SubMsg::reply_always(sub_msg.msg, TAKE_MULTI_DEPOSIT_MINT_REPLY_ID));
+
     let res = IbcReceiveResponse::new()
         .set_ack(ack_success())
         .add_submessages(sub_message)
@@ -524,6 +1022,131 @@ pub(crate) fn on_received_take_multi_deposit(
     Ok(res)
 }
 
+// reply to the LP mint of a take multi deposit, reverting the receive when it failed
+pub(crate) fn on_take_multi_deposit_mint_reply(
+    deps: DepsMut,
+    result: SubMsgResult,
+) -> Result<Response, ContractError> {
+    let rollback = TAKE_MULTI_DEPOSIT_ROLLBACK.load(deps.storage)?;
+    TAKE_MULTI_DEPOSIT_ROLLBACK.remove(deps.storage);
+
+    match result {
+        SubMsgResult::Ok(_) => Ok(Response::new()),
+        SubMsgResult::Err(err) => {
+            save_order(deps.storage, rollback.order_key, &rollback.order)?;
+            if let Some(active_order) = rollback.active_order {
+                ACTIVE_ORDERS.save(deps.storage, rollback.active_order_key, &active_order)?;
+            }
+            POOLS.save(deps.storage, &rollback.pool.id, &rollback.pool)?;
+
+            // fail the ack so the counterparty refunds the taker
+            let failure = FailureAck {
+                error: err,
+                message_type: InterchainMessageType::TakeMultiDeposit,
+                pool_id: Some(rollback.pool.id.clone()),
+                order_id: Some(rollback.order.id.clone()),
+            };
+            Ok(Response::new()
+                .set_data(ack_fail(failure.encode()?))
+                .add_attribute("action", "take_multi_asset_deposit_rollback")
+                .add_attribute("order_id", rollback.order.id))
+        }
+    }
+}
+
+pub(crate) fn on_received_match_multi_deposit(
+    deps: DepsMut,
+    _env: Env,
+    _packet: &IbcPacket,
+    msg: MsgMatchMultiAssetDepositRequest,
+    state_change: StateChange,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let new_shares = required(state_change.shares, "Shares")?;
+    // the counter order was made on this chain, its maker gets the LP here
+    let sub_message = settle_matched_orders(
+        deps.storage,
+        &msg.pool_id,
+        &msg.counter_order_id,
+        &msg.order_id,
+        new_shares,
+    )?;
+
+    let res = IbcReceiveResponse::new()
+        .set_ack(ack_success())
+        .add_submessages(sub_message)
+        .add_attribute("pool_id", msg.pool_id)
+        .add_attribute("action", "match_multi_asset_deposit")
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("counter_order_id", msg.counter_order_id)
+        .add_attribute("success", "true");
+
+    Ok(res)
+}
+
+/// Completes `local_order_id`, made on this chain, and its mirror `counter_order_id`, adds
+/// their deposits and `new_shares` to the pool and mints the local maker's share of the LP.
+fn settle_matched_orders(
+    storage: &mut dyn Storage,
+    pool_id: &str,
+    local_order_id: &str,
+    counter_order_id: &str,
+    new_shares: Uint128,
+) -> Result<Vec<SubMsg>, ContractError> {
+    let mut interchain_pool = POOLS
+        .may_load(storage, pool_id)?
+        .ok_or_else(|| StdError::generic_err("Pool not found".to_string()))?;
+
+    let mut orders = vec![];
+    for order_id in [local_order_id, counter_order_id] {
+        let key = pool_id.to_string() + "-" + order_id;
+        let order = MULTI_ASSET_DEPOSIT_ORDERS
+            .may_load(storage, key.clone())?
+            .ok_or(ContractError::ErrOrderNotFound)?;
+        if order.status != OrderStatus::Pending {
+            return Err(ContractError::ErrOrderAlreadyCompleted);
+        }
+        orders.push((key, order));
+    }
+    let (local, counter) = (&orders[0].1, &orders[1].1);
+    if !local.mirrors(counter, &interchain_pool)
+        || interchain_pool.maker_side(&local.deposits)? != PoolSide::SOURCE
+    {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Order {} doesn't mirror order {}",
+            local.id, counter.id
+        ))));
+    }
+
+    // each maker gets the share of its assets' weight, as with a split allocation
+    let lp_token = POOL_TOKENS_LIST
+        .may_load(storage, pool_id)?
+        .ok_or_else(|| StdError::generic_err("LP Token is not initialized".to_string()))?;
+    let local_shares = interchain_pool.local_shares(new_shares);
+    let sub_message = mint_tokens_cw20(local.lp_recipient(), lp_token, local_shares)?;
+
+    interchain_pool
+        .add_supply(Coin {
+            denom: pool_id.to_string(),
+            amount: new_shares,
+        })
+        .map_err(|err| StdError::generic_err(format!("Failed to add supply: {}", err)))?;
+    for asset in local.deposits.clone() {
+        interchain_pool
+            .add_asset(asset)
+            .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
+    }
+    POOLS.save(storage, pool_id, &interchain_pool)?;
+
+    for (key, mut order) in orders {
+        let ac_key = order.source_maker.clone() + "-" + pool_id + "-" + &order.destination_taker;
+        ACTIVE_ORDERS.remove(storage, ac_key);
+        order.status = OrderStatus::Complete;
+        save_order(storage, key, &order)?;
+    }
+
+    Ok(sub_message)
+}
+
 pub(crate) fn on_received_cancel_multi_deposit(
     deps: DepsMut,
     _env: Env,
@@ -558,7 +1181,7 @@ pub(crate) fn on_received_cancel_multi_deposit(
         return Err(ContractError::ErrOrderNotFound);
     }
 
-    MULTI_ASSET_DEPOSIT_ORDERS.save(deps.storage, key, &multi_asset_order)?;
+    save_order(deps.storage, key, &multi_asset_order)?;
 
     let res = IbcReceiveResponse::new()
         .set_ack(ack_success())
@@ -570,8 +1193,8 @@ pub(crate) fn on_received_cancel_multi_deposit(
 }
 
 pub(crate) fn on_received_multi_withdraw(
-    deps: DepsMut,
-    _env: Env,
+    mut deps: DepsMut,
+    env: Env,
     _packet: &IbcPacket,
     msg: MsgMultiAssetWithdrawRequest,
     state_change: StateChange,
@@ -587,50 +1210,290 @@ pub(crate) fn on_received_multi_withdraw(
         )));
     }
 
-    let out_assets = state_change.out_tokens.unwrap();
-    let pool_tokens = state_change.pool_tokens.unwrap();
-    let token = interchain_pool
-        .find_asset_by_side(PoolSide::SOURCE)
-        .map_err(|err| StdError::generic_err(format!("Failed to find asset: {}", err)))?;
+    let before = interchain_pool.clone();
+    let out_assets = required(state_change.out_tokens, "Out")?;
+    let pool_tokens = required(state_change.pool_tokens, "PoolTokens")?;
     let mut sub_messages = vec![];
+    let mut events = vec![];
 
     // Update pool status by subtracting the supplied pool coin and output token
     for pool_asset in out_assets {
-        if token.balance.denom == pool_asset.denom {
+        if interchain_pool.side_of(&pool_asset.denom)? == PoolSide::SOURCE {
             // Unlock tokens for this chain
-            sub_messages = send_tokens_coin(
-                &Addr::unchecked(msg.counterparty_receiver.clone()),
+            let (messages, queued) = send_or_queue_withdrawal(
+                deps.branch(),
+                &env,
+                &msg.pool_id,
+                &msg.counterparty_receiver,
                 pool_asset.clone(),
             )?;
+            sub_messages.extend(messages);
+            events.extend(queued);
         }
         interchain_pool
             .subtract_asset(pool_asset.clone())
             .map_err(|err| StdError::generic_err(format!("Failed to subtract asset: {}", err)))?;
     }
 
+    // a zap out has no receiver here, it only takes its fees in the denoms of its own chain
+    let zap_out = msg.counterparty_receiver.is_empty();
+    let (messages, queued) = withdraw_fee_share(
+        deps.branch(),
+        &env,
+        &before,
+        msg.pool_token.amount,
+        &msg.counterparty_receiver,
+        zap_out.then_some(PoolSide::DESTINATION),
+    )?;
+    sub_messages.extend(messages);
+    events.extend(queued);
+
+    let mut burned_shares = Uint128::zero();
     for pool_token in pool_tokens {
+        burned_shares += pool_token.amount;
         interchain_pool
             .subtract_supply(pool_token)
             .map_err(|err| StdError::generic_err(format!("Failed to subtract supply: {}", err)))?;
     }
+    before.assert_share_price_floor(&interchain_pool)?;
 
     // Save pool
     POOLS.save(deps.storage, &msg.pool_id, &interchain_pool)?;
-
+    delete_withdrawn_pool(deps.storage, &interchain_pool)?;
+
+    let debug = vec![
+        attr("debug_burned_shares", burned_shares),
+        attr(
+            "debug_share_ratio",
+            Decimal::from_ratio(burned_shares, before.supply.amount).to_string(),
+        ),
+    ];
     let res = IbcReceiveResponse::new()
         .set_ack(ack_success())
         .add_submessages(sub_messages)
+        .add_events(events)
         .add_attribute("pool_id", msg.pool_id)
         .add_attribute("action", "multi_asset_withdraw")
-        .add_attribute("success", "true");
+        .add_attribute("success", "true")
+        .add_attributes(debug_attributes(deps.storage, &before, &interchain_pool, debug)?);
 
     Ok(res)
 }
 
+/// Funds of `denom` held here for others than the pools, e.g. deposits of pending orders.
+/// Queued withdrawals are not included.
+pub(crate) fn escrowed_funds(storage: &dyn Storage, denom: &str) -> StdResult<Uint128> {
+    Ok(ESCROWED_FUNDS.may_load(storage, denom)?.unwrap_or_default())
+}
+
+/// Books `token` as held for others.
+pub(crate) fn add_escrow(storage: &mut dyn Storage, token: &Coin) -> StdResult<()> {
+    ESCROWED_FUNDS.update(storage, &token.denom, |held| -> StdResult<_> {
+        Ok(held.unwrap_or_default().checked_add(token.amount)?)
+    })?;
+    Ok(())
+}
+
+/// Releases `token` once it is paid out, refunded or part of a pool.
+pub(crate) fn release_escrow(storage: &mut dyn Storage, token: &Coin) -> StdResult<()> {
+    let held = escrowed_funds(storage, &token.denom)?.saturating_sub(token.amount);
+    if held.is_zero() {
+        ESCROWED_FUNDS.remove(storage, &token.denom);
+    } else {
+        ESCROWED_FUNDS.save(storage, &token.denom, &held)?;
+    }
+    Ok(())
+}
+
+// the maker's deposits stay here until the order is taken or cancelled
+fn order_escrow(storage: &dyn Storage, order: &MultiAssetDepositOrder) -> StdResult<Vec<Coin>> {
+    if order.status != OrderStatus::Pending {
+        return Ok(vec![]);
+    }
+    let pool = match POOLS.may_load(storage, &order.pool_id)? {
+        Some(pool) => pool,
+        None => return Ok(vec![]),
+    };
+    if pool.maker_side(&order.deposits).ok() != Some(PoolSide::SOURCE) {
+        return Ok(vec![]);
+    }
+    Ok(order
+        .deposits
+        .iter()
+        .filter(|deposit| pool.side_of(&deposit.denom).ok() == Some(PoolSide::SOURCE))
+        .cloned()
+        .collect())
+}
+
+/// Saves a multi asset deposit order, moving the escrow of its deposits along with its status.
+pub(crate) fn save_order(
+    storage: &mut dyn Storage,
+    key: String,
+    order: &MultiAssetDepositOrder,
+) -> StdResult<()> {
+    remove_order(storage, key.clone())?;
+    for deposit in order_escrow(storage, order)? {
+        add_escrow(storage, &deposit)?;
+    }
+    MULTI_ASSET_DEPOSIT_ORDERS.save(storage, key, order)
+}
+
+/// Removes a multi asset deposit order and releases the escrow of its deposits.
+pub(crate) fn remove_order(storage: &mut dyn Storage, key: String) -> StdResult<()> {
+    if let Some(order) = MULTI_ASSET_DEPOSIT_ORDERS.may_load(storage, key.clone())? {
+        for deposit in order_escrow(storage, &order)? {
+            release_escrow(storage, &deposit)?;
+        }
+        MULTI_ASSET_DEPOSIT_ORDERS.remove(storage, key);
+    }
+    Ok(())
+}
+
+/// Rebuilds the escrow of every denom from the swaps, deposits, orders and claims it holds.
+pub(crate) fn recount_escrowed_funds(storage: &mut dyn Storage) -> StdResult<()> {
+    let mut escrow: Vec<Coin> = vec![];
+    for item in SWAP_ESCROW.range(storage, None, None, Order::Ascending) {
+        let ((_, denom), held) = item?;
+        escrow.push(Coin::new(held.u128(), denom));
+    }
+    for item in DEPOSIT_RECEIPTS.range(storage, None, None, Order::Ascending) {
+        let (_, receipt) = item?;
+        if receipt.status == DepositStatus::Pending {
+            escrow.push(receipt.token);
+        }
+    }
+    for item in MULTI_ASSET_DEPOSIT_ORDERS.range(storage, None, None, Order::Ascending) {
+        let (_, order) = item?;
+        escrow.extend(order_escrow(storage, &order)?);
+    }
+    for item in SWAP_CLAIMS.range(storage, None, None, Order::Ascending) {
+        let (_, claims) = item?;
+        escrow.extend(claims);
+    }
+
+    let denoms = ESCROWED_FUNDS
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for denom in denoms {
+        ESCROWED_FUNDS.remove(storage, &denom);
+    }
+    for token in escrow {
+        add_escrow(storage, &token)?;
+    }
+    Ok(())
+}
+
+/// Sends `token` withdrawn from a pool to `receiver`. The payout is queued instead when it
+/// would leave less than the escrowed funds of its denom, or earlier payouts are queued.
+pub(crate) fn send_or_queue_withdrawal(
+    deps: DepsMut,
+    env: &Env,
+    pool_id: &str,
+    receiver: &str,
+    token: Coin,
+) -> Result<(Vec<SubMsg>, Vec<Event>), ContractError> {
+    let receiver = validate_local_address(deps.api, receiver)?;
+    let queued = WITHDRAWAL_QUEUE
+        .prefix(&token.denom)
+        .keys_raw(deps.storage, None, None, Order::Ascending)
+        .next()
+        .is_some();
+    let balance = deps
+        .querier
+        .query_balance(&env.contract.address, &token.denom)?
+        .amount;
+    let escrow = escrowed_funds(deps.storage, &token.denom)?;
+    if !queued && balance >= token.amount + escrow {
+        return Ok((send_tokens_coin(&receiver, token)?, vec![]));
+    }
+
+    let id = WITHDRAWAL_QUEUE_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    WITHDRAWAL_QUEUE_COUNT.save(deps.storage, &id)?;
+    let withdrawal = QueuedWithdrawal {
+        id,
+        pool_id: pool_id.to_string(),
+        receiver: receiver.to_string(),
+        token: token.clone(),
+        queued_at: env.block.time.seconds(),
+    };
+    WITHDRAWAL_QUEUE.save(deps.storage, (&token.denom, id), &withdrawal)?;
+    let event = Event::new("withdrawal_queued")
+        .add_attribute("id", id.to_string())
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("receiver", receiver)
+        .add_attribute("amount", token.to_string());
+    Ok((vec![], vec![event]))
+}
+
+// pays the share of the collected swap fees `shares` of the pool's supply are worth, the
+// fees are accounted on both chains and each pays those in the denoms it holds. With a side
+// set only the fees in the denoms of that side are settled, the others stay collected.
+fn withdraw_fee_share(
+    mut deps: DepsMut,
+    env: &Env,
+    pool: &InterchainLiquidityPool,
+    shares: Uint128,
+    receiver: &str,
+    side: Option<PoolSide>,
+) -> Result<(Vec<SubMsg>, Vec<Event>), ContractError> {
+    let mut fees = COLLECTED_FEES.may_load(deps.storage, &pool.id)?.unwrap_or_default();
+    if fees.is_empty() || pool.supply.amount.is_zero() {
+        return Ok((vec![], vec![]));
+    }
+
+    let mut sub_messages = vec![];
+    let mut events = vec![];
+    for fee in fees.iter_mut() {
+        let fee_side = pool.side_of(&fee.denom)?;
+        if side.as_ref().is_some_and(|side| *side != fee_side) {
+            continue;
+        }
+        let share = fee.amount.multiply_ratio(shares.min(pool.supply.amount), pool.supply.amount);
+        if share.is_zero() {
+            continue;
+        }
+        fee.amount -= share;
+        if fee_side == PoolSide::SOURCE {
+            let token = Coin {
+                denom: fee.denom.clone(),
+                amount: share,
+            };
+            let (messages, queued) =
+                send_or_queue_withdrawal(deps.branch(), env, &pool.id, receiver, token)?;
+            sub_messages.extend(messages);
+            events.extend(queued);
+        }
+    }
+    fees.retain(|fee| !fee.amount.is_zero());
+    if fees.is_empty() {
+        COLLECTED_FEES.remove(deps.storage, &pool.id);
+    } else {
+        COLLECTED_FEES.save(deps.storage, &pool.id, &fees)?;
+    }
+    Ok((sub_messages, events))
+}
+
+/// Holds `token` for the counterparty `sender` of a swap until it claims it.
+pub(crate) fn credit_swap_claim(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    sender: &str,
+    token: &Coin,
+) -> StdResult<()> {
+    let key = (channel_id, sender);
+    let mut claims = SWAP_CLAIMS.may_load(storage, key)?.unwrap_or_default();
+    match claims.iter_mut().find(|claim| claim.denom == token.denom) {
+        Some(claim) => claim.amount += token.amount,
+        None => claims.push(token.clone()),
+    }
+    SWAP_CLAIMS.save(storage, key, &claims)?;
+    add_escrow(storage, token)
+}
+
 pub(crate) fn on_received_swap(
     deps: DepsMut,
-    _env: Env,
-    _packet: &IbcPacket,
+    env: Env,
+    packet: &IbcPacket,
     msg: MsgSwapRequest,
     state_change: StateChange,
 ) -> Result<IbcReceiveResponse, ContractError> {
@@ -644,26 +1507,73 @@ pub(crate) fn on_received_swap(
             "Pool not found".to_string(),
         )));
     }
+    let before = interchain_pool.clone();
 
-    let token_out = state_change.out_tokens.unwrap();
+    let quoted = first_token(state_change.out_tokens, "Out")?;
+    let settled = settle_swap_output(&interchain_pool, &msg, &quoted)?;
+    let ack = match &settled {
+        Some(settled) => ack_success_with(settled)?,
+        None => ack_success(),
+    };
+    let receiver_fee = match &settled {
+        Some(settled) => settled.fee_token(),
+        None => Coin { denom: quoted.denom.clone(), amount: Uint128::zero() },
+    };
+    let token_out = settled.map(|settled| settled.token()).unwrap_or(quoted);
     let cfg = CONFIG.load(deps.storage)?;
     let mut sub_messages: Vec<SubMsg>;
     // Deduct fees
-    let fee_charged = token_out.get(0).unwrap().clone().amount.checked_div(FEE_PRECISION.into()).unwrap().checked_mul(interchain_pool.swap_fee.into()).unwrap();
+    let fee_charged = token_out
+        .amount
+        .checked_div(FEE_PRECISION.into())
+        .map_err(StdError::from)?
+        .checked_mul(interchain_pool.swap_fee_for(&msg.swap_type).into())
+        .map_err(StdError::from)?;
     let output_token = Coin {
-        denom: token_out.get(0).unwrap().clone().denom,
-        amount: token_out.get(0).unwrap().clone().amount.checked_sub(fee_charged).unwrap(),
+        denom: token_out.denom.clone(),
+        amount: token_out.amount.checked_sub(fee_charged).map_err(StdError::from)?,
     };
+    let debug = vec![
+        attr("debug_token_out", token_out.to_string()),
+        attr("debug_fee_charged", fee_charged),
+        attr("debug_output", output_token.to_string()),
+    ];
     sub_messages = send_tokens_coin(
-        &Addr::unchecked(cfg.admin),
+        &validate_local_address(deps.api, &cfg.admin)?,
         Coin { denom: output_token.denom.clone(), amount: fee_charged },
     )?;
 
-    // Handle routing here
-    if let Some(route) = msg.route {
+    let mut events = vec![];
+    let recipient = validate_local_address(deps.api, &msg.recipient);
+    if let Some(forward) = msg.forward {
+        // packet-forward style, the output goes on to a third chain
+        let timeout = interchain_pool.packet_timeout_seconds.unwrap_or(cfg.default_timeout_seconds);
+        sub_messages.push(forward_output(
+            deps.storage,
+            &env,
+            &packet.dest.channel_id,
+            &msg.sender,
+            forward,
+            output_token,
+            timeout,
+        )?);
+    } else if let Err(err) = recipient {
+        // the input already left the sending chain, hold the output for the sender to claim
+        let channel_id = packet.dest.channel_id.as_str();
+        credit_swap_claim(deps.storage, channel_id, &msg.sender, &output_token)?;
+        events.push(
+            Event::new("swap_output_claimable")
+                .add_attribute("pool_id", &msg.pool_id)
+                .add_attribute("channel_id", channel_id)
+                .add_attribute("counterparty", &msg.sender)
+                .add_attribute("amount", output_token.to_string())
+                .add_attribute("reason", err.to_string()),
+        );
+    } else if let Some(route) = msg.route {
+        // Handle routing here
         let route_msg = MultiSwap {
             requests: route.requests, offer_amount: output_token.amount,
-            receiver: Some(Addr::unchecked(msg.recipient)),
+            receiver: Some(recipient?),
             minimum_receive: route.minimum_receive 
         };
     
@@ -675,10 +1585,7 @@ pub(crate) fn on_received_swap(
         }));
     } else {
         // send tokens
-        let send_tokens_msg = send_tokens_coin(
-            &Addr::unchecked(msg.recipient),
-            output_token,
-        )?;
+        let send_tokens_msg = send_tokens_coin(&recipient?, output_token)?;
         sub_messages.append(&mut send_tokens_msg.clone());
     }
 
@@ -687,27 +1594,24 @@ pub(crate) fn on_received_swap(
     // Update pool status by subtracting output token and adding input token
     match msg.swap_type {
         crate::msg::SwapMsgType::LEFT => {
+            add_swap_input(deps.storage, &mut interchain_pool, &msg.swap_type, &msg.token_in)?;
             interchain_pool
-                .add_asset(msg.token_in.clone())
-                .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
-            interchain_pool
-                .subtract_asset(token_out.get(0).unwrap().clone())
+                .subtract_asset(token_out.clone())
                 .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
             log_token_1 = msg.token_in;
-            log_token_2 = token_out.get(0).unwrap().clone();
+            log_token_2 = token_out.clone();
         }
         crate::msg::SwapMsgType::RIGHT => {
             // token_out here is offer amount that is needed to get msg.token_out
-            interchain_pool
-                .add_asset(token_out.get(0).unwrap().clone())
-                .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
+            add_swap_input(deps.storage, &mut interchain_pool, &msg.swap_type, &token_out)?;
             interchain_pool
                 .subtract_asset(msg.token_out.clone())
                 .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
             log_token_1 = msg.token_out;
-            log_token_2 = token_out.get(0).unwrap().clone()
+            log_token_2 = token_out.clone()
         }
     }
+    take_receiver_fee(deps.storage, &mut interchain_pool, receiver_fee)?;
 
     POOLS.save(deps.storage, &msg.pool_id, &interchain_pool)?;
 
@@ -728,21 +1632,77 @@ pub(crate) fn on_received_swap(
     }
 
     let res = IbcReceiveResponse::new()
-        .set_ack(ack_success())
+        .set_ack(ack)
         .add_submessages(sub_messages)
         .add_attribute("pool_id", msg.pool_id)
         .add_attribute("action", "swap_asset")
-        .add_attribute("success", "true");
+        .add_attribute("success", "true")
+        .add_events(events)
+        .add_attributes(debug_attributes(deps.storage, &before, &interchain_pool, debug)?);
     Ok(res)
 }
 
+// Recomputes a swap on the pool state of this chain when it takes a part of the swap fee.
+// The sending chain may quote on outdated balances, so the settled output is never better
+// than its quote, and the receiving part of the fee comes on top. Returns None when the
+// quote of the sending chain stands.
+fn settle_swap_output(
+    interchain_pool: &InterchainLiquidityPool,
+    msg: &MsgSwapRequest,
+    quoted: &Coin,
+) -> Result<Option<SettledSwap>, ContractError> {
+    let receiver_fee_rate = interchain_pool.receiver_fee_rate(&msg.swap_type);
+    if receiver_fee_rate == 0 {
+        return Ok(None);
+    }
+    let sender_fee_rate = interchain_pool.sender_fee_rate(&msg.swap_type);
+    let amm = InterchainMarketMaker::new(interchain_pool, sender_fee_rate);
+    match msg.swap_type {
+        crate::msg::SwapMsgType::LEFT => {
+            let fresh = amm.compute_swap(msg.token_in.clone(), &quoted.denom)?;
+            let output = fresh.amount.min(quoted.amount);
+            let fee = output.multiply_ratio(receiver_fee_rate, FEE_PRECISION);
+            let settled = SettledSwap {
+                denom: quoted.denom.clone(),
+                amount: output - fee,
+                fee,
+            };
+            assert_swap_slippage(msg, &settled.token())?;
+            Ok(Some(settled))
+        }
+        crate::msg::SwapMsgType::RIGHT => {
+            let fresh = amm.compute_offer_amount(msg.token_in.clone(), msg.token_out.clone())?;
+            let offer = fresh.amount.max(quoted.amount);
+            let amount = multiply_ratio_ceil(
+                offer,
+                FEE_PRECISION.into(),
+                (FEE_PRECISION as u32 - receiver_fee_rate).into(),
+            )?;
+            if amount > msg.token_in.amount {
+                return Err(ContractError::FailedOnSwapReceived {
+                    err: format!("offer {} exceeds the input {}", amount, msg.token_in),
+                });
+            }
+            Ok(Some(SettledSwap {
+                denom: quoted.denom.clone(),
+                amount,
+                fee: amount - offer,
+            }))
+        }
+    }
+}
+
 // update the balance stored on this (channel, denom) index
-// acknowledgement
+// acknowledgement, `result` is what the receiving chain acknowledged the packet with
 pub(crate) fn on_packet_success(
-    deps: DepsMut,
+    mut deps: DepsMut,
+    env: Env,
     packet: IbcPacket,
+    result: Option<Binary>,
 ) -> Result<IbcBasicResponse, ContractError> {
-    let packet_data: InterchainSwapPacketData = from_binary(&packet.data)?;
+    // a swap the receiving chain recomputed acknowledges the output it settled at
+    let settled: Option<SettledSwap> = result.and_then(|result| decode_payload(&result).ok());
+    let packet_data = decode_packet(&packet.data)?;
     // similar event messages like ibctransfer module
     let attributes = vec![attr("success", "true")];
 
@@ -751,18 +1711,39 @@ pub(crate) fn on_packet_success(
         // This logic is executed when Taker chain acknowledge the make swap packet.
         InterchainMessageType::Unspecified => Ok(IbcBasicResponse::new()),
         InterchainMessageType::MakePool => {
-            let state_change: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgMakePoolRequest = decode_payload(&packet_data.data)?;
+            let state_change: StateChange = packet_state_change(&packet_data)?;
+            let pool_id = required(state_change.pool_id, "PoolId")?;
             // pool is already saved when makePool is called.
             // mint lp tokens
             // tokens will be minted with takePool call because then only all the assets are deposited
+            let mut sub_message = vec![];
+            if msg.is_bootstrap() {
+                // nobody takes a bootstrap pool, the maker gets its shares right away
+                let mut interchain_pool = POOLS.load(deps.storage, &pool_id)?;
+                let amm = InterchainMarketMaker::new(&interchain_pool, interchain_pool.swap_fee);
+                let shares = amm.bootstrap_shares()?;
+                let lp_token = POOL_TOKENS_LIST.load(deps.storage, &pool_id)?;
+                sub_message =
+                    mint_tokens_cw20(msg.creator, lp_token, unlocked_shares(shares.amount)?)?;
+
+                interchain_pool
+                    .add_supply(shares)
+                    .map_err(|err| StdError::generic_err(format!("Failed to add supply: {}", err)))?;
+                interchain_pool.status = Bootstrap;
+                POOLS.save(deps.storage, &pool_id, &interchain_pool)?;
+                INITIALIZED_POOLS.remove(deps.storage, (&interchain_pool.source_creator, &pool_id));
+            }
+
             Ok(IbcBasicResponse::new()
-                .add_attribute("pool_id", state_change.pool_id.unwrap())
+                .add_submessages(sub_message)
+                .add_attribute("pool_id", pool_id)
                 .add_attribute("action", "make_pool_acknowledged")
                 .add_attributes(attributes))
         }
         InterchainMessageType::TakePool => {
-            let msg: MsgTakePoolRequest = from_binary(&packet_data.data)?;
-            let state_change: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgTakePoolRequest = decode_payload(&packet_data.data)?;
+            let state_change: StateChange = packet_state_change(&packet_data)?;
             // load pool throw error if found
             let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
             let mut interchain_pool;
@@ -774,7 +1755,8 @@ pub(crate) fn on_packet_success(
                 )));
             }
 
-            let new_shares = state_change.shares.unwrap();
+            let new_shares = required(state_change.shares, "Shares")?;
+            let minted_shares = unlocked_shares(new_shares)?;
             let sub_message;
             // Mint tokens (cw20) to the sender
             if let Some(lp_token) = POOL_TOKENS_LIST.may_load(deps.storage, &msg.pool_id.clone())? {
@@ -784,17 +1766,11 @@ pub(crate) fn on_packet_success(
                         sub_message = vec![];
                     }
                     LPAllocation::TakerChain => {
-                        sub_message = mint_tokens_cw20(msg.creator, lp_token, new_shares)?;
+                        sub_message = mint_tokens_cw20(msg.creator, lp_token, minted_shares)?;
                     }
                     LPAllocation::Split => {
                         // split shares
-                        let token = interchain_pool
-                            .find_asset_by_side(PoolSide::SOURCE)
-                            .map_err(|err| {
-                                StdError::generic_err(format!("Failed to find asset: {}", err))
-                            })?;
-                        let splitted_shares =
-                            (new_shares * Uint128::from(token.weight)) / Uint128::from(100u64);
+                        let splitted_shares = interchain_pool.local_shares(minted_shares);
                         sub_message = mint_tokens_cw20(msg.creator, lp_token, splitted_shares)?;
                     }
                 }
@@ -823,7 +1799,7 @@ pub(crate) fn on_packet_success(
                 .add_attributes(attributes))
         }
         InterchainMessageType::CancelPool => {
-            let msg: MsgCancelPoolRequest = from_binary(&packet_data.data)?;
+            let msg: MsgCancelPoolRequest = decode_payload(&packet_data.data)?;
             // load pool throw error if found
             let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
             let mut interchain_pool;
@@ -837,17 +1813,16 @@ pub(crate) fn on_packet_success(
             interchain_pool.status = Cancelled;
 
             // Refund tokens
-            let token = interchain_pool
-                .find_asset_by_side(PoolSide::SOURCE)
-                .map_err(|err| StdError::generic_err(format!("Failed to find asset: {}", err)))?;
-
-            send_tokens_coin(
-                &Addr::unchecked(interchain_pool.source_creator),
-                token.balance,
-            )?;
+            let creator = validate_local_address(deps.api, &interchain_pool.source_creator)?;
+            for token in interchain_pool.assets_by_side(PoolSide::SOURCE) {
+                send_tokens_coin(&creator, token.balance)?;
+            }
 
             POOL_TOKENS_LIST.remove(deps.storage, &msg.pool_id);
+            LP_TOKEN_STATUS.remove(deps.storage, &msg.pool_id);
+            LP_TOKEN_MARKETING.remove(deps.storage, &msg.pool_id);
             POOLS.remove(deps.storage, &msg.pool_id);
+            INITIALIZED_POOLS.remove(deps.storage, (&interchain_pool.source_creator, &msg.pool_id));
 
             Ok(IbcBasicResponse::new()
                 .add_attribute("pool_id", msg.pool_id)
@@ -855,8 +1830,9 @@ pub(crate) fn on_packet_success(
                 .add_attributes(attributes))
         }
         InterchainMessageType::SingleAssetDeposit => {
-            let msg: MsgSingleAssetDepositRequest = from_binary(&packet_data.data)?;
-            let state_change: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgSingleAssetDepositRequest = decode_payload(&packet_data.data)?;
+            let state_change: StateChange = packet_state_change(&packet_data)?;
+            settle_deposit_receipt(deps.storage, &packet, &msg.sender, DepositStatus::Minted)?;
 
             // load pool throw error if found
             let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
@@ -870,7 +1846,7 @@ pub(crate) fn on_packet_success(
             }
 
             // mint new_shares in take receive
-            let new_shares = state_change.shares.unwrap();
+            let new_shares = required(state_change.shares, "Shares")?;
             let sub_message;
             // Mint tokens (cw20) to the sender
             if let Some(lp_token) = POOL_TOKENS_LIST.may_load(deps.storage, &msg.pool_id.clone())? {
@@ -880,17 +1856,12 @@ pub(crate) fn on_packet_success(
                         sub_message = vec![];
                     }
                     LPAllocation::TakerChain => {
-                        sub_message = mint_tokens_cw20(msg.sender, lp_token, new_shares)?;
+                        sub_message = mint_tokens_cw20(msg.lp_recipient(), lp_token, new_shares)?;
                     }
                     LPAllocation::Split => {
-                        let token = interchain_pool
-                            .find_asset_by_side(PoolSide::SOURCE)
-                            .map_err(|err| {
-                                StdError::generic_err(format!("Failed to find asset: {}", err))
-                            })?;
-                        let splitted_shares =
-                            (new_shares * Uint128::from(token.weight)) / Uint128::from(100u64);
-                        sub_message = mint_tokens_cw20(msg.sender, lp_token, splitted_shares)?;
+                        let splitted_shares = interchain_pool.local_shares(new_shares);
+                        sub_message =
+                            mint_tokens_cw20(msg.lp_recipient(), lp_token, splitted_shares)?;
                     }
                 }
             } else {
@@ -905,8 +1876,12 @@ pub(crate) fn on_packet_success(
                 .add_asset(msg.token)
                 .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
             interchain_pool
-                .add_supply(state_change.pool_tokens.unwrap()[0].clone())
+                .add_supply(first_token(state_change.pool_tokens, "PoolTokens")?)
                 .map_err(|err| StdError::generic_err(format!("Failed to add supply: {}", err)))?;
+            interchain_pool.activate_if_funded();
+            if interchain_pool.status == Active {
+                BOOTSTRAP_TARGETS.remove(deps.storage, &msg.pool_id);
+            }
 
             POOLS.save(deps.storage, &msg.pool_id, &interchain_pool)?;
 
@@ -917,15 +1892,15 @@ pub(crate) fn on_packet_success(
                 .add_submessages(sub_message))
         }
         InterchainMessageType::MakeMultiDeposit => {
-            let msg: MsgMakeMultiAssetDepositRequest = from_binary(&packet_data.data)?;
+            let msg: MsgMakeMultiAssetDepositRequest = decode_payload(&packet_data.data)?;
             Ok(IbcBasicResponse::new()
                 .add_attribute("pool_id", msg.pool_id)
                 .add_attribute("action", "make_multi_deposit_acknowledged")
                 .add_attributes(attributes))
         }
         InterchainMessageType::TakeMultiDeposit => {
-            let msg: MsgTakeMultiAssetDepositRequest = from_binary(&packet_data.data)?;
-            let state_change: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgTakeMultiAssetDepositRequest = decode_payload(&packet_data.data)?;
+            let state_change: StateChange = packet_state_change(&packet_data)?;
             // Mint tokens in take only i.e after receiving all the assets
             // load pool throw error if found
             let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
@@ -958,7 +1933,7 @@ pub(crate) fn on_packet_success(
                 return Err(ContractError::ErrOrderNotFound);
             }
 
-            let new_shares = state_change.shares.unwrap();
+            let new_shares = required(state_change.shares, "Shares")?;
             let sub_message;
 
             // Mint tokens (cw20) to the sender
@@ -970,17 +1945,12 @@ pub(crate) fn on_packet_success(
                     }
                     LPAllocation::TakerChain => {
                         sub_message =
-                            mint_tokens_cw20(msg.sender, lp_token, state_change.shares.unwrap())?;
+                            mint_tokens_cw20(msg.lp_recipient(), lp_token, new_shares)?;
                     }
                     LPAllocation::Split => {
-                        let token = interchain_pool
-                            .find_asset_by_side(PoolSide::SOURCE)
-                            .map_err(|err| {
-                                StdError::generic_err(format!("Failed to find asset: {}", err))
-                            })?;
-                        let splitted_shares =
-                            (new_shares * Uint128::from(token.weight)) / Uint128::from(100u64);
-                        sub_message = mint_tokens_cw20(msg.sender, lp_token, splitted_shares)?;
+                        let splitted_shares = interchain_pool.local_shares(new_shares);
+                        sub_message =
+                            mint_tokens_cw20(msg.lp_recipient(), lp_token, splitted_shares)?;
                     }
                 }
 
@@ -1008,7 +1978,7 @@ pub(crate) fn on_packet_success(
                 )));
             }
 
-            MULTI_ASSET_DEPOSIT_ORDERS.save(deps.storage, key, &multi_asset_order)?;
+            save_order(deps.storage, key, &multi_asset_order)?;
             POOLS.save(deps.storage, &msg.pool_id, &interchain_pool)?;
             Ok(IbcBasicResponse::new()
                 .add_submessages(sub_message)
@@ -1017,7 +1987,7 @@ pub(crate) fn on_packet_success(
                 .add_attributes(attributes))
         }
         InterchainMessageType::CancelMultiDeposit => {
-            let msg: MsgCancelMultiAssetDepositRequest = from_binary(&packet_data.data)?;
+            let msg: MsgCancelMultiAssetDepositRequest = decode_payload(&packet_data.data)?;
             // load pool throw error if found
             let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
             let interchain_pool;
@@ -1050,30 +2020,42 @@ pub(crate) fn on_packet_success(
             }
 
             // Refund tokens
-            let token = interchain_pool
-                .find_asset_by_side(PoolSide::SOURCE)
-                .map_err(|err| StdError::generic_err(format!("Failed to find asset: {}", err)))?;
-
             for asset in multi_asset_order.deposits.clone() {
-                if asset.denom == token.balance.denom {
+                if interchain_pool.side_of(&asset.denom)? == PoolSide::SOURCE {
                     send_tokens_coin(
-                        &Addr::unchecked(multi_asset_order.source_maker.clone()),
+                        &validate_local_address(deps.api, &multi_asset_order.source_maker)?,
                         asset,
                     )?;
                 }
             }
 
-            MULTI_ASSET_DEPOSIT_ORDERS.save(deps.storage, key, &multi_asset_order)?;
+            save_order(deps.storage, key, &multi_asset_order)?;
             Ok(IbcBasicResponse::new()
                 .add_attribute("pool_id", msg.pool_id)
                 .add_attribute("action", "cancel_multi_deposit_acknowledged")
                 .add_attributes(attributes))
         }
+        InterchainMessageType::MatchMultiDeposit => {
+            let msg: MsgMatchMultiAssetDepositRequest = decode_payload(&packet_data.data)?;
+            let state_change: StateChange = packet_state_change(&packet_data)?;
+            let new_shares = required(state_change.shares, "Shares")?;
+            let sub_message = settle_matched_orders(
+                deps.storage,
+                &msg.pool_id,
+                &msg.order_id,
+                &msg.counter_order_id,
+                new_shares,
+            )?;
+            Ok(IbcBasicResponse::new()
+                .add_submessages(sub_message)
+                .add_attribute("pool_id", msg.pool_id)
+                .add_attribute("action", "match_multi_deposit_acknowledged")
+                .add_attributes(attributes))
+        }
         InterchainMessageType::MultiWithdraw => {
             // Unlock tokens for user
-            let msg: MsgMultiAssetWithdrawRequest = from_binary(&packet_data.data)?;
-            //let state_change = packet_data.state_change.unwrap();
-            let state_change: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgMultiAssetWithdrawRequest = decode_payload(&packet_data.data)?;
+            let state_change: StateChange = packet_state_change(&packet_data)?;
 
             // load pool throw error if found
             let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
@@ -1086,21 +2068,24 @@ pub(crate) fn on_packet_success(
                 )));
             }
 
-            let out_assets = state_change.out_tokens.unwrap();
-            let pool_tokens = state_change.pool_tokens.unwrap();
-            let token = interchain_pool
-                .find_asset_by_side(PoolSide::SOURCE)
-                .map_err(|err| StdError::generic_err(format!("Failed to find asset: {}", err)))?;
+            let out_assets = required(state_change.out_tokens, "Out")?;
+            let pool_tokens = required(state_change.pool_tokens, "PoolTokens")?;
             let mut sub_messages = vec![];
+            let mut events = vec![];
 
             // Update pool status by subtracting the supplied pool coin and output token
             for pool_asset in out_assets {
-                if token.balance.denom == pool_asset.denom {
+                if interchain_pool.side_of(&pool_asset.denom)? == PoolSide::SOURCE {
                     // Unlock tokens for this chain
-                    sub_messages = send_tokens_coin(
-                        &Addr::unchecked(msg.receiver.clone()),
+                    let (messages, queued) = send_or_queue_withdrawal(
+                        deps.branch(),
+                        &env,
+                        &msg.pool_id,
+                        &msg.receiver,
                         pool_asset.clone(),
                     )?;
+                    sub_messages.extend(messages);
+                    events.extend(queued);
                 }
                 interchain_pool
                     .subtract_asset(pool_asset.clone())
@@ -1109,6 +2094,19 @@ pub(crate) fn on_packet_success(
                     })?;
             }
 
+            // fees in the counterparty denoms of a zap out stay with the pool
+            let zap_out = msg.counterparty_receiver.is_empty();
+            let (messages, queued) = withdraw_fee_share(
+                deps.branch(),
+                &env,
+                &interchain_pool,
+                msg.pool_token.amount,
+                &msg.receiver,
+                zap_out.then_some(PoolSide::SOURCE),
+            )?;
+            sub_messages.extend(messages);
+            events.extend(queued);
+
             for pool_token in pool_tokens {
                 interchain_pool.subtract_supply(pool_token).map_err(|err| {
                     StdError::generic_err(format!("Failed to subtract supply: {}", err))
@@ -1127,16 +2125,18 @@ pub(crate) fn on_packet_success(
             }
             // Save pool
             POOLS.save(deps.storage, &msg.pool_id, &interchain_pool)?;
+            delete_withdrawn_pool(deps.storage, &interchain_pool)?;
 
             Ok(IbcBasicResponse::new()
                 .add_attribute("pool_id", msg.pool_id)
                 .add_attribute("action", "multi_asset_withdraw_acknowledged")
                 .add_attributes(attributes)
-                .add_submessages(sub_messages))
+                .add_submessages(sub_messages)
+                .add_events(events))
         }
         InterchainMessageType::LeftSwap => {
-            let msg: MsgSwapRequest = from_binary(&packet_data.data)?;
-            let state_change: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgSwapRequest = decode_payload(&packet_data.data)?;
+            let state_change: StateChange = packet_state_change(&packet_data)?;
 
             // load pool throw error if found
             let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
@@ -1150,13 +2150,16 @@ pub(crate) fn on_packet_success(
             }
 
             let mut sub_messages: Vec<SubMsg> = vec![];
-            let token_out = state_change.out_tokens.unwrap();
+            let (token_out, receiver_fee) = match settled {
+                Some(settled) => (settled.token(), Some(settled.fee_token())),
+                None => (first_token(state_change.out_tokens, "Out")?, None),
+            };
             // Log swap values
             let log_volume = LOG_VOLUME.may_load(deps.storage, msg.pool_id.clone())?;
             if let Some(val) = log_volume {
                 let log_msg = LogObservation {
                     token1: msg.token_in.clone(),
-                    token2: token_out.get(0).unwrap().clone(),
+                    token2: token_out.clone(),
                 };
 
                 // log message
@@ -1168,12 +2171,13 @@ pub(crate) fn on_packet_success(
             }
 
             // Update pool status by subtracting output token and adding input token
+            add_swap_input(deps.storage, &mut interchain_pool, &msg.swap_type, &msg.token_in)?;
             interchain_pool
-                .add_asset(msg.token_in)
-                .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
-            interchain_pool
-                .subtract_asset(token_out.get(0).unwrap().clone())
+                .subtract_asset(token_out.clone())
                 .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
+            if let Some(fee) = receiver_fee {
+                take_receiver_fee(deps.storage, &mut interchain_pool, fee)?;
+            }
 
             POOLS.save(deps.storage, &msg.pool_id, &interchain_pool)?;
 
@@ -1184,8 +2188,8 @@ pub(crate) fn on_packet_success(
                 .add_attributes(attributes))
         }
         InterchainMessageType::RightSwap => {
-            let msg: MsgSwapRequest = from_binary(&packet_data.data)?;
-            let state_change: StateChange = from_slice(&packet_data.state_change.unwrap())?;
+            let msg: MsgSwapRequest = decode_payload(&packet_data.data)?;
+            let state_change: StateChange = packet_state_change(&packet_data)?;
 
             // load pool throw error if found
             let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
@@ -1198,13 +2202,16 @@ pub(crate) fn on_packet_success(
                 )));
             }
 
-            let token_out = state_change.out_tokens.unwrap();
+            let (token_out, receiver_fee) = match settled {
+                Some(settled) => (settled.token(), Some(settled.fee_token())),
+                None => (first_token(state_change.out_tokens, "Out")?, None),
+            };
             let mut sub_messages: Vec<SubMsg> = vec![];
             // Log swap values
             let log_volume = LOG_VOLUME.may_load(deps.storage, msg.pool_id.clone())?;
             if let Some(val) = log_volume {
                 let log_msg = LogObservation {
-                    token1: token_out.get(0).unwrap().clone(),
+                    token1: token_out.clone(),
                     token2: msg.token_out.clone(),
                 };
 
@@ -1218,12 +2225,13 @@ pub(crate) fn on_packet_success(
 
             // Update pool status by subtracting output token and adding input token
             // token_out here is offer amount that is needed to get msg.token_out
-            interchain_pool
-                .add_asset(token_out.get(0).unwrap().clone())
-                .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
+            add_swap_input(deps.storage, &mut interchain_pool, &msg.swap_type, &token_out)?;
             interchain_pool
                 .subtract_asset(msg.token_out)
                 .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
+            if let Some(fee) = receiver_fee {
+                take_receiver_fee(deps.storage, &mut interchain_pool, fee)?;
+            }
 
             POOLS.save(deps.storage, &msg.pool_id, &interchain_pool)?;
             Ok(IbcBasicResponse::new()
@@ -1232,7 +2240,328 @@ pub(crate) fn on_packet_success(
                 .add_attribute("action", "swap_asset_acknowledged")
                 .add_attributes(attributes))
         }
+        InterchainMessageType::SetPoolManager => {
+            let msg: MsgSetPoolManagerRequest = decode_payload(&packet_data.data)?;
+            save_pool_manager(deps.storage, &msg)?;
+
+            Ok(IbcBasicResponse::new()
+                .add_attribute("pool_id", msg.pool_id)
+                .add_attribute("action", "set_pool_manager_acknowledged")
+                .add_attributes(attributes))
+        }
+        InterchainMessageType::SetPoolStatus => {
+            let msg: MsgSetPoolStatusRequest = decode_payload(&packet_data.data)?;
+            save_pool_status(deps.storage, &msg)?;
+
+            Ok(IbcBasicResponse::new()
+                .add_attribute("pool_id", msg.pool_id)
+                .add_attribute("action", "set_pool_status_acknowledged")
+                .add_attributes(attributes))
+        }
+        InterchainMessageType::UpdateFee => {
+            let msg: MarketFeeUpdateProposal = decode_payload(&packet_data.data)?;
+            save_pool_fee(deps.storage, &msg)?;
+
+            Ok(IbcBasicResponse::new()
+                .add_attribute("pool_id", msg.pool_id)
+                .add_attribute("fee_rate", msg.fee_rate.to_string())
+                .add_attribute("action", "update_fee_acknowledged")
+                .add_attributes(attributes))
+        }
+        InterchainMessageType::DecommissionPool => {
+            let msg: MsgDecommissionPoolRequest = decode_payload(&packet_data.data)?;
+            let residual = retire_pool(deps.storage, &msg.pool_id)?;
+
+            Ok(IbcBasicResponse::new()
+                .add_attribute("pool_id", msg.pool_id)
+                .add_attribute("residual", coins_to_string(&residual))
+                .add_attribute("action", "decommission_pool_acknowledged")
+                .add_attributes(attributes))
+        }
+    }
+}
+
+// add a swap input to the pool, the fee of the sending chain goes to the pool's collected fees,
+// paid out to LPs as they withdraw, but for the protocol share, which goes to its protocol fees
+fn add_swap_input(
+    storage: &mut dyn Storage,
+    interchain_pool: &mut InterchainLiquidityPool,
+    swap_type: &SwapMsgType,
+    token_in: &Coin,
+) -> Result<(), ContractError> {
+    let sender_fee_rate = interchain_pool.sender_fee_rate(swap_type);
+    let amm = InterchainMarketMaker::new(interchain_pool, sender_fee_rate);
+    let (net, fee) = amm.split_fee(token_in);
+    interchain_pool
+        .add_asset(net)
+        .map_err(|err| StdError::generic_err(format!("Failed to add asset: {}", err)))?;
+    book_swap_fee(storage, &interchain_pool.id, fee)
+}
+
+// move the fee the receiving chain took on a swap out of the pool balance, it was left in the
+// balance with the output of a left swap and the input of a right swap
+fn take_receiver_fee(
+    storage: &mut dyn Storage,
+    interchain_pool: &mut InterchainLiquidityPool,
+    fee: Coin,
+) -> Result<(), ContractError> {
+    if fee.amount.is_zero() {
+        return Ok(());
+    }
+    interchain_pool
+        .subtract_asset(fee.clone())
+        .map_err(|err| StdError::generic_err(format!("Failed to subtract asset: {}", err)))?;
+    book_swap_fee(storage, &interchain_pool.id, fee)
+}
+
+// book a swap fee to the pool's collected fees, but for the protocol share, which goes to its
+// protocol fees
+fn book_swap_fee(
+    storage: &mut dyn Storage,
+    pool_id: &str,
+    mut fee: Coin,
+) -> Result<(), ContractError> {
+    let share = CONFIG.load(storage)?.protocol_fee_share_bps;
+    let protocol_fee = Coin {
+        denom: fee.denom.clone(),
+        amount: fee.amount.multiply_ratio(share, FEE_PRECISION),
+    };
+    fee.amount -= protocol_fee.amount;
+    accrue_fee(storage, &PROTOCOL_FEES, pool_id, protocol_fee)?;
+    accrue_fee(storage, &COLLECTED_FEES, pool_id, fee)?;
+    Ok(())
+}
+
+pub(crate) fn accrue_fee(
+    storage: &mut dyn Storage,
+    ledger: &Map<&str, Vec<Coin>>,
+    pool_id: &str,
+    fee: Coin,
+) -> StdResult<()> {
+    if fee.amount.is_zero() {
+        return Ok(());
+    }
+    ledger.update(storage, pool_id, |fees| -> StdResult<_> {
+        let mut fees = fees.unwrap_or_default();
+        match fees.iter_mut().find(|accrued| accrued.denom == fee.denom) {
+            Some(accrued) => accrued.amount += fee.amount,
+            None => fees.push(fee),
+        }
+        Ok(fees)
+    })?;
+    Ok(())
+}
+
+// settle the receipt of a single asset deposit whose packet got its result
+fn settle_deposit_receipt(
+    storage: &mut dyn Storage,
+    packet: &IbcPacket,
+    sender: &str,
+    status: DepositStatus,
+) -> Result<(), ContractError> {
+    let hash = packet_data_hash(&packet.data);
+    let mut pending = PENDING_DEPOSIT_RECEIPTS
+        .may_load(storage, &hash)?
+        .unwrap_or_default();
+    if pending.is_empty() {
+        // deposits sent before receipts were introduced
+        return Ok(());
+    }
+
+    let receipt_id = pending.remove(0);
+    if pending.is_empty() {
+        PENDING_DEPOSIT_RECEIPTS.remove(storage, &hash);
+    } else {
+        PENDING_DEPOSIT_RECEIPTS.save(storage, &hash, &pending)?;
+    }
+    let receipt = DEPOSIT_RECEIPTS.update(storage, (sender, receipt_id), |receipt| match receipt {
+        Some(mut receipt) => {
+            receipt.status = status;
+            Ok(receipt)
+        }
+        None => Err(StdError::not_found("DepositReceipt")),
+    })?;
+    release_escrow(storage, &receipt.token)?;
+    // the pool takes the next bootstrap deposit once this one got its result
+    if BOOTSTRAP_DEPOSITS.may_load(storage, &receipt.pool_id)? == Some(hash) {
+        BOOTSTRAP_DEPOSITS.remove(storage, &receipt.pool_id);
+    }
+    Ok(())
+}
+
+// journal an outgoing packet once its result is known
+/// Pool a packet belongs to, from its message or else its state change.
+pub(crate) fn packet_pool_id(
+    packet_data: &InterchainSwapPacketData,
+) -> Result<Option<String>, ContractError> {
+    let message = packet_data.decode_message()?;
+    match message.pool_id() {
+        Some(pool_id) => Ok(Some(pool_id.to_string())),
+        None => Ok(packet_data.decode_state_change()?.and_then(|sc| sc.pool_id)),
+    }
+}
+
+pub(crate) fn record_packet(
+    storage: &mut dyn Storage,
+    env: &Env,
+    packet: &IbcPacket,
+    result: PacketResult,
+) -> Result<(), ContractError> {
+    let packet_data = decode_packet(&packet.data)?;
+    let message = packet_data.decode_message()?;
+    let pool_id = match packet_pool_id(&packet_data)? {
+        Some(pool_id) => pool_id,
+        None => return Ok(()),
+    };
+    IN_FLIGHT_PACKETS.update(storage, &pool_id, |count| -> StdResult<_> {
+        Ok(count.unwrap_or_default().saturating_sub(1))
+    })?;
+
+    // the swap input is now either in the pool or refunded
+    if let PacketMessage::Swap(msg) = &message {
+        let key = (pool_id.as_str(), msg.token_in.denom.as_str());
+        SWAP_ESCROW.update(storage, key, |held| -> StdResult<_> {
+            Ok(held.unwrap_or_default().saturating_sub(msg.token_in.amount))
+        })?;
+        release_escrow(storage, &msg.token_in)?;
+    }
+
+    let hash = packet_data_hash(&packet.data);
+    let mut pending = PENDING_JOURNAL_ENTRIES.may_load(storage, &hash)?.unwrap_or_default();
+    let entry = if pending.is_empty() {
+        // packets sent before they were journaled at send time
+        let id = PACKET_JOURNAL_COUNT.may_load(storage, &pool_id)?.unwrap_or_default() + 1;
+        PACKET_JOURNAL_COUNT.save(storage, &pool_id, &id)?;
+        JournalEntry {
+            id,
+            sequence: None,
+            channel_id: packet.src.channel_id.clone(),
+            packet_type: packet_data.r#type,
+            tokens: message.tokens(),
+            sent_at: None,
+            result: None,
+            completed_at: None,
+        }
+    } else {
+        let id = pending.remove(0);
+        if pending.is_empty() {
+            PENDING_JOURNAL_ENTRIES.remove(storage, &hash);
+        } else {
+            PENDING_JOURNAL_ENTRIES.save(storage, &hash, &pending)?;
+        }
+        PACKET_JOURNAL.load(storage, (&pool_id, id))?
+    };
+    let entry = JournalEntry {
+        sequence: Some(packet.sequence),
+        result: Some(result),
+        completed_at: Some(env.block.time.seconds()),
+        ..entry
+    };
+    PACKET_JOURNAL.save(storage, (&pool_id, entry.id), &entry)?;
+    Ok(())
+}
+
+/// Records the LP mints and burns among the messages of a packet response as checkpoints
+/// of the holders' balances, returned as events for incentive distributors. Burns count
+/// against the receiver of the withdrawal.
+pub(crate) fn record_lp_checkpoints(
+    storage: &mut dyn Storage,
+    env: &Env,
+    packet: &IbcPacket,
+    messages: &[SubMsg],
+) -> Result<Vec<Event>, ContractError> {
+    let packet_data = decode_packet(&packet.data)?;
+    let pool_id = match packet_pool_id(&packet_data)? {
+        Some(pool_id) => pool_id,
+        None => return Ok(vec![]),
+    };
+    let lp_token = match POOL_TOKENS_LIST.may_load(storage, &pool_id)? {
+        Some(lp_token) => lp_token,
+        None => return Ok(vec![]),
+    };
+
+    let mut events = vec![];
+    for sub_msg in messages {
+        let msg = match &sub_msg.msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. })
+                if *contract_addr == lp_token =>
+            {
+                from_binary(msg)?
+            }
+            CosmosMsg::Stargate { type_url, value } => {
+                match tokenfactory::decode_lp_msg(type_url, value)? {
+                    Some(msg) => msg,
+                    None => continue,
+                }
+            }
+            _ => continue,
+        };
+        let (address, change, amount) = match msg {
+            Cw20ExecuteMsg::Mint { recipient, amount } => (recipient, LpChange::Mint, amount),
+            Cw20ExecuteMsg::Burn { amount } => match packet_data.decode_message()? {
+                PacketMessage::MultiWithdraw(withdraw) => {
+                    (withdraw.receiver, LpChange::Burn, amount)
+                }
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        let id = LP_CHECKPOINT_COUNT.may_load(storage, &pool_id)?.unwrap_or_default();
+        LP_CHECKPOINT_COUNT.save(storage, &pool_id, &(id + 1))?;
+        if id >= MAX_LP_CHECKPOINTS {
+            LP_CHECKPOINTS.remove(storage, (&pool_id, id - MAX_LP_CHECKPOINTS));
+        }
+        let checkpoint = LpCheckpoint {
+            id,
+            address,
+            change,
+            amount,
+            time: env.block.time.seconds(),
+        };
+        LP_CHECKPOINTS.save(storage, (&pool_id, id), &checkpoint)?;
+        events.push(
+            Event::new("lp_checkpoint")
+                .add_attribute("pool_id", &pool_id)
+                .add_attribute("id", id.to_string())
+                .add_attribute("address", checkpoint.address)
+                .add_attribute("change", format!("{:?}", change).to_lowercase())
+                .add_attribute("amount", amount)
+                .add_attribute("time", checkpoint.time.to_string()),
+        );
     }
+    Ok(events)
+}
+
+/// Journals a packet received on this chain along with the outcome of processing it.
+pub(crate) fn record_received_packet(
+    storage: &mut dyn Storage,
+    env: &Env,
+    packet: &IbcPacket,
+    error: Option<&ContractError>,
+) -> Result<(), ContractError> {
+    let packet_data = decode_packet(&packet.data).ok();
+    let pool_id = packet_data
+        .as_ref()
+        .and_then(|data| packet_pool_id(data).ok().flatten());
+
+    let result = match error {
+        Some(err) => PacketResult::Failure {
+            error: err.to_string(),
+        },
+        None => PacketResult::Success,
+    };
+    let record = ReceivedPacket {
+        sequence: packet.sequence,
+        channel_id: packet.dest.channel_id.clone(),
+        packet_type: packet_data.map(|data| data.r#type),
+        pool_id,
+        result,
+        error_code: error.map(ContractError::code),
+        received_at: env.block.time.seconds(),
+    };
+    RECEIVED_PACKETS.save(storage, (&packet.dest.channel_id, packet.sequence), &record)?;
+    Ok(())
 }
 
 pub(crate) fn on_packet_failure(
@@ -1240,14 +2569,32 @@ pub(crate) fn on_packet_failure(
     packet: IbcPacket,
     err: String,
 ) -> Result<IbcBasicResponse, ContractError> {
-    let packet_data: InterchainSwapPacketData = from_binary(&packet.data)?;
-    let submsg = refund_packet_token(deps, packet_data)?;
+    let packet_data = decode_packet(&packet.data)?;
+    if packet_data.r#type == InterchainMessageType::SingleAssetDeposit {
+        let msg: MsgSingleAssetDepositRequest = decode_payload(&packet_data.data)?;
+        settle_deposit_receipt(deps.storage, &packet, &msg.sender, DepositStatus::Refunded)?;
+    }
+    // context of the failure, unless it timed out or the counterparty sent a plain error
+    let failure =
+        FailureAck::decode(&err).filter(|failure| failure.message_type == packet_data.r#type);
+    let submsg = refund_packet_token(deps, packet_data, failure.as_ref())?;
 
-    let res = IbcBasicResponse::new()
+    let mut res = IbcBasicResponse::new()
         .add_submessages(submsg)
         .add_attribute("action", "acknowledge")
-        .add_attribute("success", "false")
-        .add_attribute("error", err);
+        .add_attribute("success", "false");
+    match failure {
+        Some(failure) => {
+            res = res.add_attribute("error", failure.error);
+            if let Some(pool_id) = failure.pool_id {
+                res = res.add_attribute("pool_id", pool_id);
+            }
+            if let Some(order_id) = failure.order_id {
+                res = res.add_attribute("order_id", order_id);
+            }
+        }
+        None => res = res.add_attribute("error", err),
+    }
 
     Ok(res)
 }
@@ -1255,27 +2602,37 @@ pub(crate) fn on_packet_failure(
 pub(crate) fn refund_packet_token(
     deps: DepsMut,
     packet: InterchainSwapPacketData,
+    failure: Option<&FailureAck>,
 ) -> Result<Vec<SubMsg>, ContractError> {
     match packet.r#type {
         InterchainMessageType::Unspecified => Ok(vec![]),
         InterchainMessageType::MakePool => {
             // remove from map and refund make tokens
-            let msg: MsgMakePoolRequest = from_binary(&packet.data)?;
-            let mut tokens: [Coin; 2] = Default::default();
-            tokens[0] = msg.liquidity[0].balance.clone();
-            tokens[1] = msg.liquidity[1].balance.clone();
-
-            let pool_id =
-                get_pool_id_with_tokens(&tokens, msg.source_chain_id, msg.destination_chain_id);
-            let sub_messages = send_tokens_coin(&Addr::unchecked(msg.creator), tokens[0].clone())?;
+            let msg: MsgMakePoolRequest = decode_payload(&packet.data)?;
+            let tokens: Vec<Coin> =
+                msg.liquidity.iter().map(|asset| asset.balance.clone()).collect();
+
+            let refund_to = validate_local_address(deps.api, &msg.refund_address())?;
+            let pool_id = get_pool_id_with_tokens(
+                &tokens,
+                msg.source_chain_id.clone(),
+                msg.destination_chain_id.clone(),
+            );
+            let mut sub_messages = vec![];
+            for asset in msg.liquidity.iter().filter(|asset| asset.side == PoolSide::SOURCE) {
+                sub_messages.extend(send_tokens_coin(&refund_to, asset.balance.clone())?);
+            }
 
             POOLS.remove(deps.storage, &pool_id);
             POOL_TOKENS_LIST.remove(deps.storage, &pool_id);
+            LP_TOKEN_STATUS.remove(deps.storage, &pool_id);
+            LP_TOKEN_MARKETING.remove(deps.storage, &pool_id);
+            INITIALIZED_POOLS.remove(deps.storage, (&msg.creator, &pool_id));
 
             Ok(sub_messages)
         }
         InterchainMessageType::TakePool => {
-            let msg: MsgTakePoolRequest = from_binary(&packet.data)?;
+            let msg: MsgTakePoolRequest = decode_payload(&packet.data)?;
             // load pool throw error if found
             let interchain_pool_temp = POOLS.may_load(deps.storage, &msg.pool_id)?;
             let interchain_pool;
@@ -1287,53 +2644,84 @@ pub(crate) fn refund_packet_token(
                 )));
             }
 
-            let mut tokens: [Coin; 2] = Default::default();
-            tokens[0] = interchain_pool.assets[0].balance.clone();
-            tokens[1] = interchain_pool.assets[1].balance.clone();
-
-            let sub_messages = send_tokens_coin(&Addr::unchecked(msg.creator), tokens[1].clone())?;
+            let refund_to = validate_local_address(deps.api, &msg.refund_address())?;
+            let mut sub_messages = vec![];
+            for asset in interchain_pool.assets_by_side(PoolSide::SOURCE) {
+                sub_messages.extend(send_tokens_coin(&refund_to, asset.balance)?);
+            }
 
             Ok(sub_messages)
         }
-        InterchainMessageType::CancelPool => {
+        InterchainMessageType::CancelPool
+        | InterchainMessageType::SetPoolManager
+        | InterchainMessageType::SetPoolStatus
+        | InterchainMessageType::DecommissionPool
+        | InterchainMessageType::UpdateFee => {
             // do nothing
             Ok(vec![])
         }
         InterchainMessageType::SingleAssetDeposit => {
-            let msg: MsgSingleAssetDepositRequest = from_binary(&packet.data)?;
-            let sub_messages = send_tokens_coin(&Addr::unchecked(msg.sender), msg.token)?;
+            let msg: MsgSingleAssetDepositRequest = decode_payload(&packet.data)?;
+            let sub_messages = send_tokens_coin(
+                &validate_local_address(deps.api, &msg.refund_address())?,
+                msg.token,
+            )?;
 
             Ok(sub_messages)
         }
         InterchainMessageType::MakeMultiDeposit => {
-            let msg: MsgMakeMultiAssetDepositRequest = from_binary(&packet.data)?;
-            let sub_messages = send_tokens_coin(
-                &Addr::unchecked(msg.deposits[0].clone().sender),
-                msg.deposits.get(0).unwrap().clone().balance,
-            )?;
+            let msg: MsgMakeMultiAssetDepositRequest = decode_payload(&packet.data)?;
+            let deposits = get_coins_from_deposits(msg.deposits.clone());
+            let maker_assets = match POOLS.may_load(deps.storage, &msg.pool_id)? {
+                Some(pool) if pool.maker_side(&deposits).ok() == Some(PoolSide::SOURCE) => {
+                    pool.assets_by_side(PoolSide::SOURCE).len()
+                }
+                Some(_) => 0,
+                // the pool is gone, the maker's deposits are the ones it sent
+                None => msg
+                    .deposits
+                    .iter()
+                    .take_while(|deposit| deposit.sender == msg.deposits[0].sender)
+                    .count(),
+            };
+            if maker_assets == 0 || maker_assets >= deposits.len() {
+                return Err(ContractError::MalformedPacket {
+                    field: "deposits".to_string(),
+                });
+            }
+            let refund_to = msg.refund_to.clone().unwrap_or_else(|| msg.deposits[0].sender.clone());
+            let refund_to = validate_local_address(deps.api, &refund_to)?;
+            let mut sub_messages = vec![];
+            for deposit in deposits[..maker_assets].iter() {
+                sub_messages.extend(send_tokens_coin(&refund_to, deposit.clone())?);
+            }
             let ac_key = msg.deposits[0].sender.clone()
                 + "-"
                 + &msg.pool_id.clone()
                 + "-"
-                + &msg.deposits[1].sender.clone();
+                + &msg.deposits[maker_assets].sender.clone();
 
-            let state_change: StateChange = from_slice(&packet.state_change.unwrap())?;
-            let key = msg.pool_id + &state_change.multi_deposit_order_id.unwrap();
+            let order_id = match failure.and_then(|failure| failure.order_id.clone()) {
+                Some(order_id) => order_id,
+                None => {
+                    let state_change: StateChange = packet_state_change(&packet)?;
+                    required(state_change.multi_deposit_order_id, "MultiDepositOrderId")?
+                }
+            };
+            let key = msg.pool_id + "-" + &order_id;
 
-            let mut config = CONFIG.load(deps.storage)?;
-            config.counter -= 1;
-            MULTI_ASSET_DEPOSIT_ORDERS.remove(deps.storage, key);
+            remove_order(deps.storage, key)?;
 
             if let Ok(Some(_active_order)) = ACTIVE_ORDERS.may_load(deps.storage, ac_key.clone()) {
                 ACTIVE_ORDERS.remove(deps.storage, ac_key);
             }
-            CONFIG.save(deps.storage, &config)?;
             Ok(sub_messages)
         }
         InterchainMessageType::TakeMultiDeposit => {
-            let msg: MsgTakeMultiAssetDepositRequest = from_binary(&packet.data)?;
+            let msg: MsgTakeMultiAssetDepositRequest = decode_payload(&packet.data)?;
 
-            let key = msg.pool_id.clone() + "-" + &msg.order_id;
+            let order_id = failure.and_then(|failure| failure.order_id.as_deref());
+            let key = msg.pool_id.clone() + "-" + order_id.unwrap_or(&msg.order_id);
             let multi_asset_order_temp = MULTI_ASSET_DEPOSIT_ORDERS.may_load(deps.storage, key)?;
             let multi_asset_order;
             if let Some(order) = multi_asset_order_temp {
@@ -1343,40 +2731,45 @@ pub(crate) fn refund_packet_token(
                 return Err(ContractError::ErrOrderNotFound);
             }
 
-            let sub_messages = send_tokens_coin(
-                &Addr::unchecked(msg.sender),
-                multi_asset_order.deposits.get(1).unwrap().clone(),
-            )?;
+            // the taker's deposits are the assets of this chain
+            let interchain_pool = POOLS.load(deps.storage, &msg.pool_id)?;
+            let refund_to = validate_local_address(deps.api, &msg.refund_address())?;
+            let mut sub_messages = vec![];
+            for deposit in multi_asset_order.deposits {
+                if interchain_pool.side_of(&deposit.denom)? == PoolSide::SOURCE {
+                    sub_messages.extend(send_tokens_coin(&refund_to, deposit)?);
+                }
+            }
 
             Ok(sub_messages)
         }
-        InterchainMessageType::CancelMultiDeposit => {
-            // do nothing
+        InterchainMessageType::CancelMultiDeposit | InterchainMessageType::MatchMultiDeposit => {
+            // do nothing, the maker deposits stay with their pending orders
             Ok(vec![])
         }
         InterchainMessageType::MultiWithdraw => {
-            let msg: MsgMultiAssetWithdrawRequest = from_binary(&packet.data)?;
+            let msg: MsgMultiAssetWithdrawRequest = decode_payload(&packet.data)?;
             // Send tokens (cw20) to the sender
-            let lp_token = POOL_TOKENS_LIST
-                .may_load(deps.storage, &msg.pool_id)?
-                .unwrap();
+            let lp_token = POOL_TOKENS_LIST.load(deps.storage, &msg.pool_id)?;
             let sub_message = send_tokens_cw20(msg.receiver, lp_token, msg.pool_token.amount)?;
 
             Ok(sub_message)
         }
         InterchainMessageType::LeftSwap => {
-            let msg: MsgSwapRequest = from_binary(&packet.data)?;
-            let sub_messages = send_tokens_coin(&Addr::unchecked(msg.sender), msg.token_in)?;
+            let msg: MsgSwapRequest = decode_payload(&packet.data)?;
+            let sub_messages = send_tokens_coin(
+                &validate_local_address(deps.api, &msg.refund_address())?,
+                msg.token_in,
+            )?;
 
             Ok(sub_messages)
         }
         InterchainMessageType::RightSwap => {
-            //let state_change = packet.state_change.unwrap();
-            let state_change: StateChange = from_slice(&packet.state_change.unwrap())?;
-            let msg: MsgSwapRequest = from_binary(&packet.data)?;
+            let state_change: StateChange = packet_state_change(&packet)?;
+            let msg: MsgSwapRequest = decode_payload(&packet.data)?;
             let sub_messages = send_tokens_coin(
-                &Addr::unchecked(msg.sender),
-                state_change.out_tokens.unwrap().get(0).unwrap().clone(),
+                &validate_local_address(deps.api, &msg.refund_address())?,
+                first_token(state_change.out_tokens, "Out")?,
             )?;
             Ok(sub_messages)
         }
@@ -1,14 +1,33 @@
-mod approx_pow;
+pub mod address;
+mod codec;
 pub mod contract;
 mod error;
+mod forward;
 pub mod ibc;
+pub mod icq;
 pub mod interchainswap_handler;
 pub mod market;
-mod math;
 pub mod msg;
 pub mod response;
+mod simulate;
 pub mod state;
+#[cfg(test)]
+mod test_vectors;
+mod tokenfactory;
 pub mod types;
+mod undo;
 pub mod utils;
+pub mod versioned;
+
+// the `library` feature builds without entry points and exposes the pool math, so off-chain
+// quoting links the exact math of the contract
+#[cfg(feature = "library")]
+pub mod approx_pow;
+#[cfg(not(feature = "library"))]
+mod approx_pow;
+#[cfg(feature = "library")]
+pub mod math;
+#[cfg(not(feature = "library"))]
+mod math;
 
 pub use crate::error::ContractError;
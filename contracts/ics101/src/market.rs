@@ -4,10 +4,20 @@ use cosmwasm_std::{Coin, Decimal, Decimal256, StdError, StdResult, Uint128, Uint
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+pub use ibcswap_packets::{
+    FeeApplication, MarketFeeUpdateProposal, PoolAsset, PoolSide, PoolStatus, PoolType,
+};
+
 use crate::{
-    math::{calc_minted_shares_given_single_asset_in, solve_constant_function_invariant},
+    approx_pow::calculate_pow_unbounded,
+    msg::SwapMsgType,
+    math::{
+        adjust_precision_ceil, calc_minted_shares_given_single_asset_in, compute_stable_balance,
+        compute_stable_invariant, multiply_ratio_ceil, round_to_pool, round_to_user,
+        solve_constant_function_invariant, stable_spot_price,
+    },
     types::WeightedAsset,
-    utils::{adjust_precision, decimal2decimal256},
+    utils::adjust_precision,
 };
 
 pub const FEE_PRECISION: u16 = 10000;
@@ -16,29 +26,27 @@ pub const LP_TOKEN_PRECISION: u8 = 6;
 /// Number of LP tokens to mint when liquidity is provided for the first time to the pool.
 /// This does not include the token decimals.
 // const INIT_LP_TOKENS: u128 = 100;
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub enum PoolSide {
-    SOURCE = 0,
-    DESTINATION = 1,
+/// Shares of the initial liquidity that are never minted. They stay in the pool supply so
+/// the first depositor can't inflate the share price by donating to an almost empty pool.
+pub const MINIMUM_LIQUIDITY: u128 = 1_000;
+
+/// Shares handed out when a pool is activated, minus the permanently locked minimum.
+pub fn unlocked_shares(shares: Uint128) -> StdResult<Uint128> {
+    if shares <= Uint128::new(MINIMUM_LIQUIDITY) {
+        return Err(StdError::generic_err("Initial liquidity doesn't cover the locked minimum"));
+    }
+    Ok(shares - Uint128::new(MINIMUM_LIQUIDITY))
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub enum PoolStatus {
-    #[serde(rename = "INITIALIZED")]
-    Initialized = 0,
-    #[serde(rename = "ACTIVE")]
-    Active = 1,
-    #[serde(rename = "CANCELLED")]
-    Cancelled = 2,
+// decimals of an asset as a precision for `adjust_precision`
+fn precision(decimal: u32) -> StdResult<u8> {
+    u8::try_from(decimal)
+        .map_err(|_| StdError::generic_err(format!("Asset decimal {} is out of range", decimal)))
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub struct PoolAsset {
-    pub side: PoolSide,
-    pub balance: Coin,
-    pub weight: u32,
-    pub decimal: u32,
+// amount of an asset at the fixed precision the StableSwap curve works in
+fn normalize(amount: Uint128, decimal: u32) -> StdResult<Uint256> {
+    Ok(adjust_precision(amount, precision(decimal)?, FIXED_PRECISION)?.into())
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -55,9 +63,44 @@ pub struct InterchainLiquidityPool {
     pub supply: Coin,
     pub swap_fee: u32,
     pub pool_price: u64,
+    /// Timeout applied to all packets of the pool, the contract default when unset
+    pub packet_timeout_seconds: Option<u64>,
+    /// Chain charging the swap fee, agreed on by both chains when the pool is made
+    #[serde(default)]
+    pub fee_application: FeeApplication,
+    /// Curve the pool prices its assets on, agreed on by both chains when the pool is made
+    #[serde(default)]
+    pub pool_type: PoolType,
+    /// Swap fee of right swaps when it differs from `swap_fee`
+    #[serde(default)]
+    pub right_swap_fee: Option<u32>,
 }
 
 impl InterchainLiquidityPool {
+    /// Swap fee of a swap direction. Left swaps pay `swap_fee`, right swaps pay their own
+    /// fee if the pool sets one.
+    pub fn swap_fee_for(&self, swap_type: &SwapMsgType) -> u32 {
+        match (swap_type, self.right_swap_fee) {
+            (SwapMsgType::RIGHT, Some(right_swap_fee)) => right_swap_fee,
+            _ => self.swap_fee,
+        }
+    }
+
+    /// Part of the swap fee the chain sending a swap takes in its quote.
+    pub fn sender_fee_rate(&self, swap_type: &SwapMsgType) -> u32 {
+        let swap_fee = self.swap_fee_for(swap_type);
+        match self.fee_application {
+            FeeApplication::Source => swap_fee,
+            FeeApplication::Destination => 0,
+            FeeApplication::Split => swap_fee / 2,
+        }
+    }
+
+    /// Part of the swap fee the chain receiving a swap takes on its own pool state.
+    pub fn receiver_fee_rate(&self, swap_type: &SwapMsgType) -> u32 {
+        self.swap_fee_for(swap_type) - self.sender_fee_rate(swap_type)
+    }
+
     pub fn find_asset_by_denom(&self, denom: &str) -> StdResult<PoolAsset> {
         for asset in &self.assets {
             if asset.balance.denom == denom {
@@ -67,6 +110,22 @@ impl InterchainLiquidityPool {
         Err(StdError::generic_err("Denom not found in pool"))
     }
 
+    /// Position of `denom` in the assets of the pool.
+    pub fn asset_index(&self, denom: &str) -> StdResult<usize> {
+        self.assets
+            .iter()
+            .position(|asset| asset.balance.denom == denom)
+            .ok_or_else(|| StdError::generic_err("Denom not found in pool"))
+    }
+
+    /// Balances of the pool at a common precision, for the StableSwap curve.
+    pub fn normalized_balances(&self) -> StdResult<Vec<Uint256>> {
+        self.assets
+            .iter()
+            .map(|asset| normalize(asset.balance.amount, asset.decimal))
+            .collect()
+    }
+
     pub fn find_asset_by_side(&self, side: PoolSide) -> StdResult<PoolAsset> {
         for asset in &self.assets {
             if asset.side == side {
@@ -76,6 +135,47 @@ impl InterchainLiquidityPool {
         Err(StdError::generic_err("Asset side not found in pool"))
     }
 
+    /// Assets of the pool held on `side`, in pool order.
+    pub fn assets_by_side(&self, side: PoolSide) -> Vec<PoolAsset> {
+        self.assets.iter().filter(|asset| asset.side == side).cloned().collect()
+    }
+
+    /// Side of the pool holding `denom`.
+    pub fn side_of(&self, denom: &str) -> StdResult<PoolSide> {
+        Ok(self.find_asset_by_denom(denom)?.side)
+    }
+
+    /// Side of the maker of a multi asset deposit. The deposits cover every asset of the
+    /// pool once, those of the maker's side first.
+    pub fn maker_side(&self, deposits: &[Coin]) -> StdResult<PoolSide> {
+        if deposits.len() != self.assets.len() {
+            return Err(StdError::generic_err("Deposits must cover every asset of the pool"));
+        }
+        let maker_side = self.side_of(&deposits[0].denom)?;
+        let maker_assets = self.assets_by_side(maker_side.clone()).len();
+        for (i, deposit) in deposits.iter().enumerate() {
+            if deposits[..i].iter().any(|other| other.denom == deposit.denom) {
+                let err = format!("Duplicate deposit of {}", deposit.denom);
+                return Err(StdError::generic_err(err));
+            }
+            if (self.side_of(&deposit.denom)? == maker_side) != (i < maker_assets) {
+                return Err(StdError::generic_err("Deposits of the maker must come first"));
+            }
+        }
+        Ok(maker_side)
+    }
+
+    /// Part of `shares` matching the weight of the assets held on this chain, the shares
+    /// minted here for a split LP allocation.
+    pub fn local_shares(&self, shares: Uint128) -> Uint128 {
+        let weight: u32 = self
+            .assets_by_side(PoolSide::SOURCE)
+            .iter()
+            .map(|asset| asset.weight)
+            .sum();
+        shares.multiply_ratio(weight, 100u32)
+    }
+
     pub fn add_asset(&mut self, token: Coin) -> StdResult<Coin> {
         let mut indx = 0;
         let mut found = false;
@@ -119,6 +219,81 @@ impl InterchainLiquidityPool {
         Ok(token)
     }
 
+    /// Price of the first source asset in base units of the first destination asset,
+    /// (balance_dst / weight_dst) / (balance_src / weight_src), or the slope of the curve of
+    /// a stable pool.
+    pub fn spot_price(&self) -> Option<Decimal> {
+        let source = self.find_asset_by_side(PoolSide::SOURCE).ok()?;
+        let destination = self.find_asset_by_side(PoolSide::DESTINATION).ok()?;
+        if let PoolType::Stable { amplification } = self.pool_type {
+            // the slope of the curve, from normalized units back to base units
+            let price = stable_spot_price(
+                amplification,
+                &self.normalized_balances().ok()?,
+                self.asset_index(&source.balance.denom).ok()?,
+                self.asset_index(&destination.balance.denom).ok()?,
+            )
+            .ok()?;
+            let scale = Decimal256::from_ratio(
+                10u128.checked_pow(destination.decimal)?,
+                10u128.checked_pow(source.decimal)?,
+            );
+            let price = Uint128::try_from(price.checked_mul(scale).ok()?.atomics()).ok()?;
+            return Decimal::from_atomics(price, Decimal256::DECIMAL_PLACES).ok();
+        }
+        let numerator = destination.balance.amount.checked_mul(source.weight.into()).ok()?;
+        let denominator = source.balance.amount.checked_mul(destination.weight.into()).ok()?;
+        Decimal::checked_from_ratio(numerator, denominator).ok()
+    }
+
+    /// Weighted geometric mean of the balances in whole tokens. Swaps never lower it, deposits
+    /// and withdrawals move it with the supply.
+    /// For a stable pool, the StableSwap invariant D in whole tokens.
+    pub fn invariant(&self) -> StdResult<Decimal> {
+        if let PoolType::Stable { amplification } = self.pool_type {
+            let d = compute_stable_invariant(amplification, &self.normalized_balances()?)?;
+            return Decimal::from_atomics(Uint128::try_from(d)?, FIXED_PRECISION.into())
+                .map_err(|err| StdError::generic_err(err.to_string()));
+        }
+        self.assets.iter().try_fold(Decimal::one(), |invariant, asset| {
+            let balance = Decimal::from_atomics(asset.balance.amount, asset.decimal)
+                .map_err(|err| StdError::generic_err(err.to_string()))?;
+            let weight = Decimal::percent(asset.weight.into());
+            Ok(invariant.checked_mul(calculate_pow_unbounded(balance, weight)?)?)
+        })
+    }
+
+    /// Amounts of every asset of the pool worth `value` together, split by weight and at the
+    /// current balance ratio, in pool order.
+    pub fn balanced_amounts(&self, value: &Coin) -> StdResult<Vec<Coin>> {
+        let valued = self.find_asset_by_denom(&value.denom)?;
+        if !self.is_funded() {
+            return Err(StdError::generic_err("Pool holds no liquidity"));
+        }
+
+        let valued_amount = value.amount.multiply_ratio(valued.weight, 100u32);
+        Ok(self
+            .assets
+            .iter()
+            .map(|asset| Coin {
+                denom: asset.balance.denom.clone(),
+                amount: valued_amount.multiply_ratio(asset.balance.amount, valued.balance.amount),
+            })
+            .collect())
+    }
+
+    /// Returns true when every asset of the pool holds some liquidity.
+    pub fn is_funded(&self) -> bool {
+        self.assets.iter().all(|asset| !asset.balance.amount.is_zero())
+    }
+
+    /// Moves a bootstrap pool to active once the missing side has been deposited.
+    pub fn activate_if_funded(&mut self) {
+        if self.status == PoolStatus::Bootstrap && self.is_funded() {
+            self.status = PoolStatus::Active;
+        }
+    }
+
     pub fn subtract_supply(&mut self, token: Coin) -> StdResult<Coin> {
         if self.supply.denom == token.denom {
             self.supply.amount -= token.amount;
@@ -127,6 +302,39 @@ impl InterchainLiquidityPool {
             Err(StdError::generic_err("Denom not found"))
         }
     }
+
+    /// Fails when the LP shares left in `after` are worth less than they were in this pool.
+    /// Both states are valued at the spot prices of this pool, so a withdrawal keeps the
+    /// share price when sum(weight * after_balance / balance) >= sum(weight) * after_supply /
+    /// supply. One unit of each asset is tolerated for rounding.
+    pub fn assert_share_price_floor(&self, after: &InterchainLiquidityPool) -> StdResult<()> {
+        if after.supply.amount.is_zero() {
+            // the last LP left, there is nobody to dilute
+            return Ok(());
+        }
+
+        let mut value_after = Decimal256::zero();
+        let mut total_weight = Decimal256::zero();
+        for asset in &self.assets {
+            if asset.balance.amount.is_zero() {
+                continue;
+            }
+            let remaining = after.find_asset_by_denom(&asset.balance.denom)?.balance.amount;
+            let weight = Decimal256::from_atomics(asset.weight, 0)
+                .map_err(|err| StdError::generic_err(err.to_string()))?;
+            value_after +=
+                weight * Decimal256::from_ratio(remaining + Uint128::one(), asset.balance.amount);
+            total_weight += weight;
+        }
+        let supply_ratio = Decimal256::from_ratio(after.supply.amount, self.supply.amount);
+
+        if value_after < total_weight * supply_ratio {
+            return Err(StdError::generic_err(
+                "Withdraw would lower the share price of the remaining LPs",
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -159,6 +367,17 @@ impl InterchainMarketMaker {
 
         if self.pool.status != PoolStatus::Active {
             return Err(StdError::generic_err("Pool is not active!"));
+        } else if let PoolType::Stable { amplification } = self.pool.pool_type {
+            // shares grow with the invariant of the pool
+            let mut balances = self.pool.normalized_balances()?;
+            let d_before = compute_stable_invariant(amplification, &balances)?;
+            let index = self.pool.asset_index(&token.denom)?;
+            balances[index] += normalize(token.amount, asset.decimal)?;
+            let d_after = compute_stable_invariant(amplification, &balances)?;
+            issue_amount = Uint256::from(self.pool.supply.amount)
+                .checked_mul(d_after - d_before)?
+                .checked_div(d_before)?
+                .try_into()?;
         } else {
             let pool_asset_weighted = &WeightedAsset {
                 asset: asset.balance.clone(),
@@ -181,6 +400,46 @@ impl InterchainMarketMaker {
         Ok(output_token)
     }
 
+    /// Calculate the amount of LP tokens minted for a deposit funding an unfunded asset of a
+    /// bootstrap pool. `target` is the amount of the asset matching the funded side at the
+    /// pool weights, the deposit gets the shares of its weight in proportion to the target.
+    pub fn deposit_bootstrap_asset(&self, token: &Coin, target: &Coin) -> StdResult<Coin> {
+        if self.pool.status != PoolStatus::Bootstrap {
+            return Err(StdError::generic_err("Pool is not bootstrapping"));
+        }
+        let asset = self.pool.find_asset_by_denom(&token.denom)?;
+        if !asset.balance.amount.is_zero() || target.denom != token.denom {
+            return Err(StdError::generic_err(
+                "Pool is bootstrapping, only unfunded assets can be deposited",
+            ));
+        }
+        // a smaller deposit would open the pool at a price off its target
+        if token.amount < target.amount {
+            return Err(StdError::generic_err(format!(
+                "Deposit is below the bootstrap target {}",
+                target
+            )));
+        }
+
+        let funded_weight: u32 = self
+            .pool
+            .assets
+            .iter()
+            .filter(|a| !a.balance.amount.is_zero())
+            .map(|a| a.weight)
+            .sum();
+        let issue_amount: Uint128 = Uint256::from(self.pool.supply.amount)
+            .checked_mul(token.amount.into())?
+            .checked_mul(asset.weight.into())?
+            .checked_div(Uint256::from(target.amount).checked_mul(funded_weight.into())?)?
+            .try_into()?;
+
+        Ok(Coin {
+            amount: issue_amount,
+            denom: self.pool.supply.denom.clone(),
+        })
+    }
+
     // P_issued = P_supply * Wt * Dt/Bt
     pub fn deposit_multi_asset(&self, tokens: &[Coin]) -> StdResult<Vec<Coin>> {
         let mut out_tokens = vec![];
@@ -192,7 +451,7 @@ impl InterchainMarketMaker {
                 for asset in &self.pool.assets {
                     let dec_asset_amount = adjust_precision(
                         asset.balance.amount,
-                        asset.decimal.try_into().unwrap(),
+                        precision(asset.decimal)?,
                         LP_TOKEN_PRECISION,
                     )?;
                     total_asset_amount += dec_asset_amount;
@@ -200,6 +459,13 @@ impl InterchainMarketMaker {
                 let mult_amount = total_asset_amount.checked_mul(asset.weight.into())?;
                 issue_amount = Decimal::from_ratio(mult_amount, Uint128::from(100u128));
             } else {
+                // a drained asset has no price to issue shares against
+                if asset.balance.amount.is_zero() {
+                    return Err(StdError::generic_err(format!(
+                        "Pool has no {} to price the deposit against",
+                        token.denom
+                    )));
+                }
                 let ratio = Decimal::from_ratio(token.amount, asset.balance.amount);
                 issue_amount = Decimal::from_ratio(self.pool.supply.amount, Uint128::from(100u128));
                 issue_amount = issue_amount.checked_mul(ratio)?;
@@ -209,23 +475,38 @@ impl InterchainMarketMaker {
 
             let output_token = Coin {
                 denom: self.pool.supply.denom.clone(),
-                amount: issue_amount.to_uint_ceil(),
+                amount: round_to_user(issue_amount),
             };
             out_tokens.push(output_token)
         }
         Ok(out_tokens)
     }
 
+    /// Shares issued to the maker of a bootstrap pool for the side it funded.
+    pub fn bootstrap_shares(&self) -> StdResult<Coin> {
+        let funded: Vec<Coin> = self
+            .pool
+            .assets
+            .iter()
+            .filter(|asset| !asset.balance.amount.is_zero())
+            .map(|asset| asset.balance.clone())
+            .collect();
+        let shares = self.deposit_multi_asset(&funded)?;
+
+        Ok(Coin {
+            denom: self.pool.supply.denom.clone(),
+            amount: shares.iter().map(|share| share.amount).sum(),
+        })
+    }
+
     pub fn multi_asset_withdraw(&self, redeem: Coin) -> StdResult<Vec<Coin>> {
         let total_share = self.pool.supply.amount;
 
-        // % of share to be burnt from the pool
-        let share_out_ratio = Decimal::from_ratio(redeem.amount, total_share);
-
         // Vector of assets to be transferred to the user from the Vault contract
         let mut refund_assets: Vec<Coin> = vec![];
         for asset in &self.pool.assets {
-            let asset_out = asset.balance.amount * share_out_ratio;
+            // share of the asset burnt from the pool, rounded down
+            let asset_out = asset.balance.amount.multiply_ratio(redeem.amount, total_share);
             // Return a `Failure` response if the calculation of the amount of tokens to be burnt from the pool is not valid
             if asset_out > asset.balance.amount {
                 return Err(StdError::generic_err("Invalid asset out"));
@@ -257,10 +538,26 @@ impl InterchainMarketMaker {
         let asset_in = self.pool.clone().find_asset_by_denom(&amount_in.denom)?;
         let asset_out = self.pool.clone().find_asset_by_denom(denom_out)?;
 
-        let token_precision = asset_out.decimal as u8;
+        let token_precision = precision(asset_out.decimal)?;
 
         let pool_post_swap_in_balance =
-            asset_in.balance.amount + self.minus_fees(amount_in.amount).to_uint_floor();
+            asset_in.balance.amount + round_to_user(self.minus_fees(amount_in.amount));
+        if let PoolType::Stable { amplification } = self.pool.pool_type {
+            let mut balances = self.pool.normalized_balances()?;
+            let d = compute_stable_invariant(amplification, &balances)?;
+            let index_in = self.pool.asset_index(&amount_in.denom)?;
+            let index_out = self.pool.asset_index(denom_out)?;
+            balances[index_in] = normalize(pool_post_swap_in_balance, asset_in.decimal)?;
+            let balance_out = compute_stable_balance(amplification, &balances, index_out, d)?;
+            // one unit stays in the pool for the rounding of the iteration
+            let return_amount = balances[index_out].saturating_sub(balance_out + Uint256::one());
+            let return_amount =
+                adjust_precision(return_amount.try_into()?, FIXED_PRECISION, token_precision)?;
+            return Ok(Coin {
+                amount: return_amount,
+                denom: denom_out.to_string(),
+            });
+        }
 
         //         /**********************************************************************************************
         //         // outGivenIn                                                                                //
@@ -275,17 +572,17 @@ impl InterchainMarketMaker {
 
         let token_balance_fixed_before = adjust_precision(
             asset_in.balance.amount,
-            asset_in.decimal.try_into().unwrap(),
+            precision(asset_in.decimal)?,
             FIXED_PRECISION,
         )?;
         let token_balance_fixed_after = adjust_precision(
             pool_post_swap_in_balance,
-            asset_in.decimal.try_into().unwrap(),
+            precision(asset_in.decimal)?,
             FIXED_PRECISION,
         )?;
         let token_balance_unknown_before = adjust_precision(
             asset_out.balance.amount,
-            asset_out.decimal.try_into().unwrap(),
+            precision(asset_out.decimal)?,
             FIXED_PRECISION,
         )?;
 
@@ -299,7 +596,7 @@ impl InterchainMarketMaker {
 
         // adjust return amount to correct precision
         let return_amount = adjust_precision(
-            return_amount.to_uint_floor(),
+            round_to_user(return_amount),
             FIXED_PRECISION,
             token_precision,
         )?;
@@ -315,14 +612,25 @@ impl InterchainMarketMaker {
         let asset_out = self.pool.clone().find_asset_by_denom(&amount_out.denom)?;
 
         // get ask asset precisison
-        let token_precision = asset_in.decimal as u8;
-        let one_minus_commission = Decimal256::one()
-            - decimal2decimal256(Decimal::from_ratio(self.fee_rate, FEE_PRECISION))?;
-        let inv_one_minus_commission = Decimal256::one() / one_minus_commission;
+        let token_precision = precision(asset_in.decimal)?;
 
         let ask_asset_amount = &amount_out.amount.clone();
         // Ask pool balance after swap
         let pool_post_swap_out_balance = asset_out.balance.amount - ask_asset_amount;
+        if let PoolType::Stable { amplification } = self.pool.pool_type {
+            let mut balances = self.pool.normalized_balances()?;
+            let d = compute_stable_invariant(amplification, &balances)?;
+            let index_in = self.pool.asset_index(&amount_in.denom)?;
+            let index_out = self.pool.asset_index(&amount_out.denom)?;
+            balances[index_out] = normalize(pool_post_swap_out_balance, asset_out.decimal)?;
+            let balance_in = compute_stable_balance(amplification, &balances, index_in, d)?;
+            // one unit more goes to the pool for the rounding of the iteration
+            let real_offer = (balance_in + Uint256::one()).checked_sub(balances[index_in])?;
+            let real_offer =
+                adjust_precision_ceil(real_offer.try_into()?, FIXED_PRECISION, token_precision)?;
+            return self.offer_including_fee(real_offer, amount_in.denom);
+        }
+
 
         //         /**********************************************************************************************
         //         // inGivenOut                                                                                //
@@ -337,17 +645,17 @@ impl InterchainMarketMaker {
 
         let token_balance_fixed_before = adjust_precision(
             asset_out.balance.amount,
-            asset_out.decimal.try_into().unwrap(),
+            precision(asset_out.decimal)?,
             FIXED_PRECISION,
         )?;
         let token_balance_fixed_after = adjust_precision(
             pool_post_swap_out_balance,
-            asset_out.decimal.try_into().unwrap(),
+            precision(asset_out.decimal)?,
             FIXED_PRECISION,
         )?;
         let token_balance_unknown_before = adjust_precision(
             asset_in.balance.amount,
-            asset_in.decimal.try_into().unwrap(),
+            precision(asset_in.decimal)?,
             FIXED_PRECISION,
         )?;
 
@@ -360,18 +668,35 @@ impl InterchainMarketMaker {
         )?;
         // adjust return amount to correct precision
         let real_offer =
-            adjust_precision(real_offer.to_uint_floor(), FIXED_PRECISION, token_precision)?;
+            adjust_precision_ceil(round_to_pool(real_offer), FIXED_PRECISION, token_precision)?;
+
+        self.offer_including_fee(real_offer, amount_in.denom)
+    }
 
-        let offer_amount_including_fee =
-            (Uint256::from(real_offer) * inv_one_minus_commission).try_into()?;
-        let _total_fee = offer_amount_including_fee - real_offer;
+    // real_offer / (1 - fee_rate)
+    fn offer_including_fee(&self, real_offer: Uint128, denom: String) -> StdResult<Coin> {
+        let offer_amount_including_fee = multiply_ratio_ceil(
+            real_offer,
+            FEE_PRECISION.into(),
+            (u32::from(FEE_PRECISION) - self.fee_rate).into(),
+        )?;
 
         Ok(Coin {
             amount: offer_amount_including_fee,
-            denom: amount_in.denom,
+            denom,
         })
     }
 
+    /// Splits a swap input into the part added to the pool balance and the fee.
+    pub fn split_fee(&self, amount_in: &Coin) -> (Coin, Coin) {
+        let net = round_to_user(self.minus_fees(amount_in.amount));
+        let fee = amount_in.amount - net;
+        (
+            Coin { denom: amount_in.denom.clone(), amount: net },
+            Coin { denom: amount_in.denom.clone(), amount: fee },
+        )
+    }
+
     pub fn minus_fees(&self, amount: Uint128) -> Decimal {
         let amount_dec = Decimal::from_ratio(amount.u128(), Uint128::one());
         let fee_rate_dec = Decimal::from_ratio(self.fee_rate, Uint128::new(10000));
@@ -380,15 +705,3 @@ impl InterchainMarketMaker {
         amount_dec - fees
     }
 }
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub struct MarketFeeUpdateProposal {
-    #[serde(rename = "title")]
-    pub title: String,
-    #[serde(rename = "description")]
-    pub description: String,
-    #[serde(rename = "pool_id")]
-    pub pool_id: String,
-    #[serde(rename = "fee_rate")]
-    pub fee_rate: u32,
-}
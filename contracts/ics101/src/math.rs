@@ -1,6 +1,6 @@
-use crate::utils::adjust_precision;
+use crate::utils::{adjust_precision, precision_factor};
 use crate::{approx_pow::calculate_pow, types::WeightedAsset};
-use cosmwasm_std::{Decimal, StdError, StdResult, Uint128};
+use cosmwasm_std::{Decimal, Decimal256, StdError, StdResult, Uint128, Uint256};
 
 // Referenced from Balancer Weighted pool implementation by  Osmosis here - https://github.com/osmosis-labs/osmosis/blob/47a2366c5eeee474de9e1cb4777fab0ccfbb9592/x/gamm/pool-models/balancer/amm.go#L94
 // solveConstantFunctionInvariant solves the constant function of an AMM
@@ -43,6 +43,50 @@ pub fn solve_constant_function_invariant(
     Ok(amount_y)
 }
 
+// Rounding policy: every amount is rounded in favor of the pool. What the pool pays out
+// (swap output, withdrawn assets, minted shares) is rounded down and what it takes in
+// (swap offer) is rounded up, so repeated tiny operations can't drain it.
+
+/// Rounds an amount paid out by the pool down.
+pub fn round_to_user(amount: Decimal) -> Uint128 {
+    amount.to_uint_floor()
+}
+
+/// Rounds an amount owed to the pool up.
+pub fn round_to_pool(amount: Decimal) -> Uint128 {
+    amount.to_uint_ceil()
+}
+
+/// `amount * numerator / denominator` rounded up, for amounts owed to the pool.
+pub fn multiply_ratio_ceil(
+    amount: Uint128,
+    numerator: u128,
+    denominator: u128,
+) -> StdResult<Uint128> {
+    let product = amount.full_mul(numerator);
+    let denominator = Uint256::from(denominator);
+    let quotient = product.checked_div(denominator)?;
+    let rounded = if quotient * denominator == product {
+        quotient
+    } else {
+        quotient + Uint256::one()
+    };
+    Ok(rounded.try_into()?)
+}
+
+/// Same as [`adjust_precision`] but rounds up when digits are dropped, for amounts owed to
+/// the pool.
+pub fn adjust_precision_ceil(
+    value: Uint128,
+    current_precision: u8,
+    new_precision: u8,
+) -> StdResult<Uint128> {
+    if current_precision <= new_precision {
+        return adjust_precision(value, current_precision, new_precision);
+    }
+    multiply_ratio_ceil(value, 1, precision_factor(current_precision - new_precision)?)
+}
+
 /// ## Description - Inspired from Osmosis implementation here - https://github.com/osmosis-labs/osmosis/blob/main/x/gamm/pool-models/balancer/amm.go#L116
 /// Calculates the amount of LP shares to be minted for Single asset joins.
 pub fn calc_minted_shares_given_single_asset_in(
@@ -51,9 +95,10 @@ pub fn calc_minted_shares_given_single_asset_in(
     asset_weight_and_balance: &WeightedAsset,
     total_shares: Uint128,
 ) -> StdResult<Uint128> {
-    let in_decimal = Decimal::from_atomics(token_amount_in, in_precision).unwrap();
-    let balance_decimal =
-        Decimal::from_atomics(asset_weight_and_balance.asset.amount, in_precision).unwrap();
+    let in_decimal = Decimal::from_atomics(token_amount_in, in_precision)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+    let balance_decimal = Decimal::from_atomics(asset_weight_and_balance.asset.amount, in_precision)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
 
     // To figure out the number of shares we add, first notice that we can treat
     // the number of shares as linearly related to the `k` value function. This is due to the normalization.
@@ -67,7 +112,8 @@ pub fn calc_minted_shares_given_single_asset_in(
         balance_decimal + in_decimal,
         balance_decimal,
         asset_weight_and_balance.weight,
-        Decimal::from_atomics(total_shares, Decimal::DECIMAL_PLACES).unwrap(),
+        Decimal::from_atomics(total_shares, Decimal::DECIMAL_PLACES)
+            .map_err(|e| StdError::generic_err(e.to_string()))?,
         Decimal::one(),
     )?;
     let pool_amount_out_adj = adjust_precision(
@@ -79,6 +125,99 @@ pub fn calc_minted_shares_given_single_asset_in(
     Ok(pool_amount_out_adj)
 }
 
+// StableSwap curve, referenced from the Curve whitepaper
+// https://curve.fi/files/stableswap-paper.pdf
+// For n assets of normalized balances x_i and amplification A, the invariant D solves
+// A * n^n * sum(x_i) + D = A * n^n * D + D^(n+1) / (n^n * prod(x_i)).
+// Both the invariant and a balance given the others are found by Newton's method.
+const STABLE_SWAP_ITERATIONS: usize = 64;
+
+/// Invariant D of the normalized `balances` of a stable pool.
+pub fn compute_stable_invariant(amplification: u64, balances: &[Uint256]) -> StdResult<Uint256> {
+    let n = Uint256::from(balances.len() as u128);
+    let sum = balances.iter().fold(Uint256::zero(), |sum, balance| sum + balance);
+    if sum.is_zero() {
+        return Ok(Uint256::zero());
+    }
+    let ann = Uint256::from(amplification) * n.checked_pow(balances.len() as u32)?;
+
+    let mut d = sum;
+    for _ in 0..STABLE_SWAP_ITERATIONS {
+        // d_p = D^(n+1) / (n^n * prod(x_i))
+        let mut d_p = d;
+        for balance in balances {
+            d_p = d_p.checked_mul(d)?.checked_div(balance.checked_mul(n)?)?;
+        }
+        let previous = d;
+        let numerator = (ann * sum + d_p * n).checked_mul(d)?;
+        let denominator = (ann - Uint256::one()) * d + (n + Uint256::one()) * d_p;
+        d = numerator.checked_div(denominator)?;
+        if d.abs_diff(previous) <= Uint256::one() {
+            return Ok(d);
+        }
+    }
+    Err(StdError::generic_err("StableSwap invariant doesn't converge"))
+}
+
+/// Normalized balance of the asset at `index` keeping the invariant `d`, given the other
+/// `balances`. The balance at `index` itself is ignored.
+pub fn compute_stable_balance(
+    amplification: u64,
+    balances: &[Uint256],
+    index: usize,
+    d: Uint256,
+) -> StdResult<Uint256> {
+    let n = Uint256::from(balances.len() as u128);
+    let ann = Uint256::from(amplification) * n.checked_pow(balances.len() as u32)?;
+
+    // c = D^(n+1) / (n^n * prod(x_k) * Ann), b = sum(x_k) + D / Ann over k != index
+    let mut c = d;
+    let mut sum = Uint256::zero();
+    for (k, balance) in balances.iter().enumerate() {
+        if k == index {
+            continue;
+        }
+        sum += balance;
+        c = c.checked_mul(d)?.checked_div(balance.checked_mul(n)?)?;
+    }
+    c = c.checked_mul(d)?.checked_div(ann * n)?;
+    let b = sum + d.checked_div(ann)?;
+
+    let mut y = d;
+    for _ in 0..STABLE_SWAP_ITERATIONS {
+        let previous = y;
+        // y = (y^2 + c) / (2y + b - D)
+        y = (y.checked_mul(y)? + c).checked_div((y + y + b).checked_sub(d)?)?;
+        if y.abs_diff(previous) <= Uint256::one() {
+            return Ok(y);
+        }
+    }
+    Err(StdError::generic_err("StableSwap balance doesn't converge"))
+}
+
+/// Marginal price of the asset at `i` in units of the asset at `j` on the StableSwap curve
+/// through the normalized `balances`, the ratio of the partial derivatives of the invariant.
+pub fn stable_spot_price(
+    amplification: u64,
+    balances: &[Uint256],
+    i: usize,
+    j: usize,
+) -> StdResult<Decimal256> {
+    let n = Uint256::from(balances.len() as u128);
+    let ann = Uint256::from(amplification) * n.checked_pow(balances.len() as u32)?;
+    let d = compute_stable_invariant(amplification, balances)?;
+    let mut d_p = d;
+    for balance in balances {
+        d_p = d_p.checked_mul(d)?.checked_div(balance.checked_mul(n)?)?;
+    }
+    // (Ann + d_p / x_i) / (Ann + d_p / x_j), multiplied through by x_i * x_j
+    let product = ann.checked_mul(balances[i])?.checked_mul(balances[j])?;
+    let numerator = product + d_p.checked_mul(balances[j])?;
+    let denominator = product + d_p.checked_mul(balances[i])?;
+    Decimal256::checked_from_ratio(numerator, denominator)
+        .map_err(|err| StdError::generic_err(err.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -113,4 +252,59 @@ mod tests {
         let res = adjust_precision(amount_y.to_uint_floor(), 12, 6).unwrap();
         assert_eq!(res, Uint128::from(28301u128));
     }
+
+    #[test]
+    fn test_rounding_favors_pool() {
+        let amount = Decimal::from_str("1.5").unwrap();
+        assert_eq!(round_to_user(amount), Uint128::new(1));
+        assert_eq!(round_to_pool(amount), Uint128::new(2));
+
+        assert_eq!(multiply_ratio_ceil(Uint128::new(9), 1, 3).unwrap(), Uint128::new(3));
+        assert_eq!(multiply_ratio_ceil(Uint128::new(10), 1, 3).unwrap(), Uint128::new(4));
+
+        let value = Uint128::new(1_000_001);
+        assert_eq!(adjust_precision(value, 12, 6).unwrap(), Uint128::new(1));
+        assert_eq!(adjust_precision_ceil(value, 12, 6).unwrap(), Uint128::new(2));
+        assert_eq!(adjust_precision_ceil(value, 6, 6).unwrap(), value);
+    }
+
+    #[test]
+    fn test_out_of_range_precision() {
+        let value = Uint128::new(1_000_000);
+        adjust_precision(value, 60, 6).unwrap_err();
+        adjust_precision_ceil(value, 60, 6).unwrap_err();
+
+        // a balance beyond what a Decimal holds at the asset's precision
+        let asset = WeightedAsset {
+            asset: cosmwasm_std::Coin::new(u128::MAX, "uatom"),
+            weight: Decimal::percent(50),
+        };
+        calc_minted_shares_given_single_asset_in(value, 6, &asset, value).unwrap_err();
+    }
+
+    #[test]
+    fn test_stable_swap_curve() {
+        let unit = Uint256::from(1_000_000_000_000_000_000u128);
+        let balances = vec![unit, unit];
+        // a balanced pool holds its invariant
+        let d = compute_stable_invariant(100, &balances).unwrap();
+        assert!(d.abs_diff(unit + unit) <= Uint256::one());
+
+        // trading near the balanced point is close to one for one
+        let amount_in = Uint256::from(1_000_000_000_000_000u128);
+        let after = vec![unit + amount_in, unit];
+        let balance_out = compute_stable_balance(100, &after, 1, d).unwrap();
+        let amount_out = unit - balance_out;
+        assert!(amount_out < amount_in);
+        assert!(amount_out > amount_in.multiply_ratio(9999u128, 10000u128));
+        let d_after = compute_stable_invariant(100, &[unit + amount_in, balance_out]).unwrap();
+        assert!(d_after.abs_diff(d) <= Uint256::from(2u128));
+
+        // the price follows the balances, and flattens with the amplification
+        let skewed = vec![unit + unit, unit];
+        let low = stable_spot_price(1, &skewed, 0, 1).unwrap();
+        let high = stable_spot_price(1000, &skewed, 0, 1).unwrap();
+        assert!(low < high && high < Decimal256::one());
+        assert_eq!(stable_spot_price(100, &balances, 0, 1).unwrap(), Decimal256::one());
+    }
 }
@@ -2,17 +2,33 @@ use cw20::{Cw20Coin, Logo, MinterResponse};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Binary, Coin, Response, StdError, StdResult, Uint128};
-
-use crate::error::ContractError;
-use crate::market::{InterchainLiquidityPool, InterchainMarketMaker, PoolAsset, PoolStatus};
-use crate::types::MultiAssetDepositOrder;
-use crate::utils::{is_valid_name, is_valid_symbol};
+use cosmwasm_std::{
+    to_vec, Addr, Binary, Coin, Decimal, IbcEndpoint, StdError, StdResult, Timestamp, Uint128,
+};
+
+pub use ibcswap_packets::msg::*;
+pub use ibcswap_packets::PacketMessage;
+
+use crate::market::{InterchainLiquidityPool, InterchainMarketMaker};
+use crate::state::{
+    AssetMetadata, ChannelCapabilities, DepositReceipt, JournalEntry, LpCheckpoint, LpTokenKind,
+    LpTokenStatus, PendingWithdrawal, QueuedWithdrawal, ReceivedPacket, Role,
+};
+use crate::types::{
+    InterchainMessageType, InterchainSwapPacketData, MultiAssetDepositOrder, StateChange,
+};
+use crate::utils::{is_valid_name, is_valid_symbol, packet_data_hash};
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct InstantiateMsg {
     pub token_code_id: u64,
     pub router: String,
+    /// Timeout of packets whose message and pool set none, in seconds, 600 when unset
+    #[serde(default)]
+    pub default_timeout_seconds: Option<u64>,
+    /// Mints LP shares as tokenfactory denoms instead of cw20 tokens, cw20 when unset
+    #[serde(default)]
+    pub lp_token_kind: Option<LpTokenKind>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -22,18 +38,143 @@ pub enum ExecuteMsg {
     CancelPool(MsgCancelPoolRequest),
     SingleAssetDeposit(MsgSingleAssetDepositRequest),
     MakeMultiAssetDeposit(MsgMakeMultiAssetDepositRequest),
+    /// Makes a multi asset deposit from a total value, split at the pool ratio of execution
+    MakeMultiAssetDepositByRatio(MsgMakeMultiAssetDepositByRatioRequest),
     CancelMultiAssetDeposit(MsgCancelMultiAssetDepositRequest),
     TakeMultiAssetDeposit(MsgTakeMultiAssetDepositRequest),
+    /// Settles a pending order made here against its mirror made on the counterparty chain,
+    /// minting LP to both makers without waiting for a taker. Anyone may trigger it.
+    MatchOrders(MsgMatchMultiAssetDepositRequest),
     MultiAssetWithdraw(MsgMultiAssetWithdrawRequest),
     Swap(MsgSwapRequest),
+    /// Swaps in several pools at once, e.g. to split a large order across pools of the same pair
+    BatchSwap { legs: Vec<MsgSwapRequest> },
     RemovePool(MsgRemovePool),
+    /// Delegates management of a pool to another address, on both chains
+    SetPoolManager(MsgSetPoolManagerRequest),
+    /// Freezes, drains or reactivates a pool on both chains. Pool manager or pauser only.
+    SetPoolStatus(MsgSetPoolStatusRequest),
+    /// Changes the swap fee of a pool on both chains, on this chain once the counterparty
+    /// acknowledges the change. Pool manager or fee setter only.
+    UpdatePoolFee(MarketFeeUpdateProposal),
+    /// Deletes a draining pool on both chains once its LP is withdrawn, the residual goes to the
+    /// treasury. Once its drain deadline passed, a pool with LP left is retired instead: only
+    /// its protocol fees go to the treasury, its LP holders keep withdrawing their share and
+    /// the pool is deleted after the last of them did. Pool manager or pauser only.
+    DecommissionPool(MsgDecommissionPoolRequest),
     SetLogAddress { pool_id: String, address: String }, // Receive(Cw20ReceiveMsg)
     SetRouter {address: String},
+    /// Updates the contract parameters that are set, leaving the others as they are.
+    /// Admin only.
+    UpdateConfig {
+        token_code_id: Option<u64>,
+        router: Option<String>,
+        default_timeout_seconds: Option<u64>,
+    },
+    /// Offers ownership of the contract to an address, which takes effect once the address
+    /// accepts it. Admin only.
+    TransferOwnership { address: String },
+    /// Makes the sender admin if it was offered ownership
+    AcceptOwnership {},
+    /// Registers the bech32 prefix of addresses on the chain behind a channel
+    SetAddressPrefix { channel_id: String, prefix: String },
+    /// Binds a connection to the port of the counterparty contract, replacing the port bound
+    /// by its first channel
+    SetCounterpartyPort { connection_id: String, port_id: String },
+    /// Sets the local address the swap claims of a counterparty sender are paid to
+    SetClaimAddress { channel_id: String, counterparty: String, address: String },
+    /// Pays the swap claims of a counterparty sender to its claim address. Anyone may
+    /// trigger it once the address is set.
+    ClaimSwapOutput { channel_id: String, counterparty: String },
+    /// Sends queued withdrawals of a denom in order, as far as the balance covers them
+    /// beside the escrowed funds. Anyone may trigger it.
+    ProcessWithdrawalQueue { denom: String, limit: Option<u32> },
+    /// Fetches the counterparty copy of a pool over the interchain query channel
+    SyncCounterpartyPool { pool_id: String },
+    /// Sets the drift alarm threshold in FEE_PRECISION units
+    SetDriftTolerance { tolerance: u32 },
+    /// Sets the range pool creators can pick packet timeouts from
+    SetTimeoutBounds { min_seconds: u64, max_seconds: u64 },
+    /// Sets the range pool creators can pick swap fees from, in FEE_PRECISION units
+    SetSwapFeeBounds { min_bps: u32, max_bps: u32 },
+    /// Sets how many pools a creator may have waiting to be taken at once. Admin only.
+    SetInitializedPoolLimit { limit: u32 },
+    /// Swaps against a firm quote, failing if the quote expired or the pool changed since
+    FirmSwap { swap: MsgSwapRequest, quoted_out: Coin, quote_height: u64 },
+    /// Sets the blocks a firm quote stays valid for
+    SetQuoteValidity { blocks: u64 },
+    /// Queues a withdrawal of treasury funds and collected fees of a local denom, sendable
+    /// once the treasury timelock passed. Treasurer only.
+    WithdrawTreasury { denom: String, amount: Uint128, to: String },
+    /// Sends a queued withdrawal whose timelock passed. Treasurer only.
+    ExecuteTreasuryWithdrawal { id: u64 },
+    /// Drops a queued withdrawal and returns its funds to the protocol fees and the treasury
+    /// they were drawn from. Treasurer only.
+    CancelTreasuryWithdrawal { id: u64 },
+    /// Sets the seconds treasury withdrawals wait before they can be executed. Admin only.
+    SetTreasuryTimelock { seconds: u64 },
+    /// Sets the share of every swap fee skimmed for the protocol, in FEE_PRECISION units, and
+    /// the address allowed to collect it. Admin only.
+    SetProtocolFee { share_bps: u32, collector: Option<String> },
+    /// Sends the protocol fees a pool accrued in the local denoms to the protocol fee
+    /// collector. Collector only.
+    CollectProtocolFees { pool_id: String },
+    /// Releases the reentrancy guard once the contract calls of an execute ran. Only the
+    /// contract itself sends it, as the last message of such an execute.
+    ReleaseGuard {},
+    /// Enables the debug attributes of the packet handlers. Admin only.
+    SetDebugAttributes { enabled: bool },
+    /// Stops or resumes sending and receiving packets of a type
+    SetMessageEnabled { message_type: InterchainMessageType, enabled: bool },
+    /// Rejects an inbound packet with a failure ack when it is relayed, so its sender is
+    /// refunded, or lifts the veto. Pauser only.
+    SetPacketVeto { channel_id: String, sequence: u64, vetoed: bool },
+    /// Moves up to `limit` entries of namespaces being migrated. Admin only.
+    MigrateStorage { limit: u32 },
+    /// Gives an address one of the roles of the admin
+    GrantRole { address: String, role: Role },
+    RevokeRole { address: String, role: Role },
+    /// Takes back the state changes of a received packet, from its undo record. Records are
+    /// kept for `UNDO_RETENTION_SECONDS` after the packet was received.
+    RevertPacket { channel_id: String, sequence: u64 },
+    /// Registers the metadata of a denom, or removes it when unset
+    SetAssetMetadata { denom: String, metadata: Option<AssetMetadata> },
+    /// Single asset deposit of a token of this chain for LP tokens minted to the sender on this
+    /// chain, rejected when it would mint fewer than `min_shares`. Nothing is swapped, the whole
+    /// token joins the pool's balance of its asset and is priced by the single asset join.
+    #[serde(alias = "ZapIn")]
+    SingleSidedDeposit { pool_id: String, token_in: Coin, min_shares: Uint128 },
+    /// Withdraws liquidity entirely in the asset of this chain. The counterparty share of the
+    /// withdrawal is swapped against the pool, so it never leaves it. Only the collected fees
+    /// in the denoms of this chain are paid out, those in counterparty denoms stay in the pool.
+    ZapOut { pool_id: String, lp_amount: Uint128, denom_out: String, min_out: Uint128 },
+    /// Sets the smallest swap and deposit of the local asset a pool accepts
+    SetDustThresholds { pool_id: String, min_swap: Uint128, min_deposit: Uint128 },
+    /// Limits the swaps and deposits sent from this chain to a pool to the listed senders, or
+    /// lets anyone send them when unset
+    SetPoolWhitelist { pool_id: String, addresses: Option<Vec<String>> },
+    /// Sets the project, description and logo of the LP token of a pool
+    SetLpTokenMarketing { pool_id: String, marketing: LpTokenMarketing },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct MigrateMsg {}
 
+/// Messages the chain sends the contract, the results of forwarded transfers reported by the
+/// ibc-hooks module.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    IbcLifecycleComplete(IbcLifecycleComplete),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IbcLifecycleComplete {
+    IbcAck { channel: String, sequence: u64, ack: String, success: bool },
+    IbcTimeout { channel: String, sequence: u64 },
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub enum Cw20HookMsg {
     WithdrawLiquidity {
@@ -51,236 +192,28 @@ pub struct MsgRemovePool {
     pub pool_id: String,
 }
 
+/// Multi asset deposit worth `total_value`, in either asset of the pool. The amounts of both
+/// assets are computed from the pool balances when the message executes. The funds sent cap
+/// the local amount, the part not needed is refunded.
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub enum LPAllocation {
-    MakerChain, // All LP tokens are minted on maker chain
-    TakerChain, // All LP tokens are minted on taker chain
-    Split,      // LP tokens are minted on both chains and divided based on the pool ratio.
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgMakePoolRequest {
-    pub source_port: String,
-    pub source_channel: String,
-    pub source_chain_id: String,
-    pub destination_chain_id: String,
-    pub counterparty_channel: String,
-    pub creator: String,
-    pub counterparty_creator: String,
-    pub liquidity: Vec<PoolAsset>,
-    pub swap_fee: u32,
-    pub timeout_height: u64,
-    pub timeout_timestamp: u64,
-    pub memo: Option<Binary>,
-}
-
-impl MsgMakePoolRequest {
-    pub fn validate_basic(&self) -> Result<Response, ContractError> {
-        let denom_size = self.liquidity.len();
-        // // validation message
-        if denom_size != 2 {
-            return Err(ContractError::InvalidDenomPair);
-        }
-
-        let mut total_weight: u32 = 0;
-
-        for i in 0..self.liquidity.len() {
-            total_weight += self.liquidity[i].weight;
-        }
-
-        if total_weight != 100 {
-            return Err(ContractError::InvalidWeightPair);
-        }
-
-        Ok(Response::default())
-    }
-}
-
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgMakePoolResponse {
-    pool_id: String,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgTakePoolRequest {
-    pub counter_creator: String,
-    pub creator: String,
-    pub pool_id: String,
-    pub lp_allocation: LPAllocation,
-    pub timeout_height: u64,
-    pub timeout_timestamp: u64,
-    pub memo: Option<Binary>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgCancelPoolRequest {
-    pub pool_id: String,
-    pub timeout_height: u64,
-    pub timeout_timestamp: u64,
-    pub memo: Option<Binary>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgSingleAssetDepositRequest {
+pub struct MsgMakeMultiAssetDepositByRatioRequest {
     pub pool_id: String,
-    pub sender: String,
-    pub token: Coin,
-    pub lp_allocation: LPAllocation,
-    pub lp_taker: String,
-    pub timeout_height: u64,
-    pub timeout_timestamp: u64,
-    pub memo: Option<Binary>,
-}
-
-impl MsgSingleAssetDepositRequest {
-    pub fn validate_basic(&self) -> Result<Response, ContractError> {
-        if self.token.amount.is_zero() {
-            return Err(ContractError::Std(StdError::generic_err(
-                "Invalid token amount",
-            )));
-        }
-
-        Ok(Response::default())
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgSingleAssetDepositResponse {
-    pub pool_token: Coin,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct DepositAsset {
-    pub sender: String,
-    pub balance: Coin,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgMakeMultiAssetDepositRequest {
-    pub pool_id: String,
-    pub deposits: Vec<DepositAsset>,
+    pub total_value: Coin,
+    /// Share of the funds sent, out of MAXIMUM_SLIPPAGE, that may be refunded before the
+    /// ratio is considered to have moved too far
+    pub tolerance: u64,
+    /// Address of the taker on the counterparty chain
+    pub counterparty_sender: String,
     pub chain_id: String,
     pub timeout_height: u64,
     pub timeout_timestamp: u64,
     pub memo: Option<Binary>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgTakeMultiAssetDepositRequest {
-    pub sender: String,
-    pub pool_id: String,
-    pub order_id: String,
-    pub lp_allocation: LPAllocation,
-    pub timeout_height: u64,
-    pub timeout_timestamp: u64,
-    pub memo: Option<Binary>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgCancelMultiAssetDepositRequest {
-    pub sender: String,
-    pub pool_id: String,
-    pub order_id: String,
-    pub timeout_height: u64,
-    pub timeout_timestamp: u64,
-    pub memo: Option<Binary>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgMultiAssetDepositResponse {
-    pub pool_token: Vec<Coin>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct WithdrawAsset {
-    pub receiver: String,
-    pub balance: Coin,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgMultiAssetWithdrawRequest {
-    pub pool_id: String,
-    pub receiver: String,
-    pub counterparty_receiver: String,
-    pub pool_token: Coin,
-    pub timeout_height: u64,
-    pub timeout_timestamp: u64,
-    pub memo: Option<Binary>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgMultiAssetWithdrawResponse {
-    pub tokens: Vec<Coin>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct MsgSingleAssetWithdrawRequest {
-    pub sender: String,
-    pub denom_out: String,
-    pub pool_coin: Coin,
-    pub memo: Option<Binary>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub enum SwapMsgType {
-    LEFT = 0,
-    RIGHT = 1,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub struct MsgSwapRequest {
-    #[serde(rename = "swapType")]
-    pub swap_type: SwapMsgType,
-    pub sender: String,
-    #[serde(rename = "poolId")]
-    pub pool_id: String,
-    #[serde(rename = "tokenIn")]
-    pub token_in: Coin,
-    #[serde(rename = "tokenOut")]
-    pub token_out: Coin,
-    pub slippage: u64,
-    pub recipient: String,
-    #[serde(rename = "timeoutHeight")]
-    pub timeout_height: u64,
-    #[serde(rename = "timeoutTimestamp")]
-    pub timeout_timestamp: u64,
-    pub route: Option<SwapRoute>,
-    pub memo: Option<Binary>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub struct SwapRoute {
-    pub requests: Vec<SwapRequest>,
-    pub minimum_receive: Option<Uint128>,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-#[serde(rename_all = "snake_case")]
-pub struct SwapRequest {
-    /// Pool Id via which the swap is to be routed
-    pub pool_id: String,
-    /// The offer asset denom
-    pub asset_in: String,
-    ///  The ask asset denom
-    pub asset_out: String,
-    /// Contract address, if interchain request
-    pub contract_address: String,
+    /// Receiver of the maker's LP tokens, defaults to the maker
+    pub recipient: Option<String>,
+    /// Compressed secp256k1 key whose signature lets another taker take the order
+    pub permit_pubkey: Option<Binary>,
+    /// Receiver of the refunds, defaults to the maker
+    pub refund_to: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -366,8 +299,46 @@ pub enum QueryMsg {
         pool_id: String,
         order_id: String,
     },
+    /// Coins the destination creator attaches to TakePool. Return type is
+    /// TakePoolRequiredFundsResponse.
+    TakePoolRequiredFunds {
+        pool_id: String,
+    },
+    /// Coins to attach to TakeMultiAssetDeposit for a pending order.
+    /// Return type is RequiredCounterDepositResponse.
+    RequiredCounterDeposit {
+        pool_id: String,
+        order_id: String,
+    },
     /// Query config
     Config {},
+    /// Storage layout version and namespaces not migrated yet
+    StorageVersion {},
+    /// Roles granted to an address. Return type is RolesResponse.
+    Roles { address: String },
+    /// Registered metadata of a denom. Return type is Option<AssetMetadata>.
+    AssetMetadata { denom: String },
+    /// Smallest swap and deposit a pool accepts. Return type is DustThresholds.
+    DustThresholds { pool_id: String },
+    /// Swap fees a pool collected apart from its balances. Return type is Vec<Coin>.
+    CollectedFees { pool_id: String },
+    /// Protocol share of the swap fees of a pool not collected yet. Return type is
+    /// ProtocolFeesResponse.
+    ProtocolFees { pool_id: String },
+    /// Funds held for the treasury. Return type is Vec<Coin>.
+    Treasury {},
+    /// Queued treasury withdrawals by id. Return type is PendingWithdrawalsResponse.
+    PendingWithdrawals {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Pool withdrawals of a denom waiting for liquidity by id. Return type is
+    /// WithdrawalQueueResponse.
+    WithdrawalQueue {
+        denom: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
     /// Query all pool token list
     PoolTokenList {
         start_after: Option<String>,
@@ -376,6 +347,14 @@ pub enum QueryMsg {
     PoolAddressByToken {
         pool_id: String,
     },
+    /// Checks the invariants of a pool. Return type is VerifyPoolResponse.
+    VerifyPool {
+        pool_id: String,
+    },
+    /// Lifecycle of the LP token of a pool. Return type is LpTokenResponse.
+    LpToken {
+        pool_id: String,
+    },
     InterchainPool {
         pool_id: String,
     },
@@ -393,15 +372,143 @@ pub enum QueryMsg {
         token_in: Coin,
         token_out: Coin,
     },
+    /// Output of swapping `amount_in` of `denom_in` for `denom_out`, as LeftSwap without
+    /// a target coin. Return type is Coin.
+    EstimateLeftSwap {
+        pool_id: String,
+        denom_in: String,
+        amount_in: Uint128,
+        denom_out: String,
+    },
+    /// Input of `denom_in` needed to receive `amount_out` of `denom_out`, as RightSwap
+    /// without an offered coin. Return type is Coin.
+    EstimateRightSwap {
+        pool_id: String,
+        denom_in: String,
+        denom_out: String,
+        amount_out: Uint128,
+    },
+    /// LP shares a multi asset deposit of `deposits` mints on the current pool state.
+    /// Return type is SharesForDepositResponse.
+    SharesForDeposit { pool_id: String, deposits: Vec<Coin> },
+    /// Assets a withdrawal of `shares` LP tokens pays out on the current pool state.
+    /// Return type is Vec<Coin>.
+    AssetsForShares { pool_id: String, shares: Uint128 },
+    /// Quote of a swap that FirmSwap honors until the returned height. Return type is
+    /// FirmQuoteResponse.
+    FirmQuote {
+        pool_id: String,
+        swap_type: SwapMsgType,
+        token_in: Coin,
+        token_out: Coin,
+    },
+    /// Active orders of a maker, optionally narrowed to a pool and taker. Return type is OrderListResponse.
     QueryActiveOrders {
         source_maker: String,
-        destination_taker: String,
-        pool_id: String,
+        destination_taker: Option<String>,
+        pool_id: Option<String>,
+        /// `{pool_id}-{destination_taker}` of the last order of the previous page
+        start_after: Option<String>,
+        limit: Option<u32>,
     },
+    /// Tokens redeemed for an amount of LP. Return type is Vec<Coin>.
     Rate {
         amount: Uint128,
         pool_id: String,
     },
+    /// Same as Rate with the share price per denom. Return type is RateResponse.
+    RateDetail {
+        amount: Uint128,
+        pool_id: String,
+    },
+    /// Bech32 prefix registered for the counterparty chain of a channel
+    AddressPrefix {
+        channel_id: String,
+    },
+    /// Whether an inbound packet is vetoed
+    PacketVetoed {
+        channel_id: String,
+        sequence: u64,
+    },
+    /// Port of the counterparty contract a connection is bound to
+    CounterpartyPort {
+        connection_id: String,
+    },
+    /// Registered channels with their counterparty, status and number of pools. Return
+    /// type is ChannelsResponse.
+    Channels {},
+    /// Capabilities of both ends of a channel as advertised in the handshake. Return type
+    /// is ChannelCapabilitiesResponse.
+    ChannelCapabilities {
+        channel_id: String,
+    },
+    /// Swap outputs held for a counterparty sender whose recipient was invalid
+    SwapClaims {
+        channel_id: String,
+        counterparty: String,
+    },
+    /// Pool state last fetched from the counterparty chain
+    CounterpartyPool {
+        pool_id: String,
+    },
+    /// Decodes raw packet data into its typed message and state change
+    DecodePacket {
+        data: Binary,
+    },
+    /// Internal pool supply next to the supply of the local LP token
+    PoolSupply {
+        pool_id: String,
+    },
+    /// Single asset deposits of a sender by id. Return type is DepositReceiptsResponse.
+    DepositReceipts {
+        sender: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Compares what the contract should hold of a local denom of the pool with its bank
+    /// balance, the first local asset unless `denom` is given. Counts `limit` entries at a
+    /// time, pass the `next` cursor of a report as `start_after` until it is unset. Return type
+    /// is ReconcileResponse.
+    Reconcile {
+        pool_id: String,
+        #[serde(default)]
+        denom: Option<String>,
+        #[serde(default)]
+        start_after: Option<ReconcileCursor>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    /// Sent packets of a pool by journal id, including those in flight. Return type is
+    /// PacketJournalResponse.
+    PacketJournal {
+        pool_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// LP mints and burns of a pool by id, the latest MAX_LP_CHECKPOINTS are kept. Return
+    /// type is LpCheckpointsResponse.
+    LpCheckpoints {
+        pool_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Received packets of a channel by sequence. Return type is ReceivedPacketsResponse.
+    ReceivedPackets {
+        channel_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Undo record of a received packet. Return type is UndoRecord.
+    PacketUndo {
+        channel_id: String,
+        sequence: u64,
+    },
+    /// Dry-runs an execute message without changing state
+    Simulate {
+        msg: Box<ExecuteMsg>,
+        sender: String,
+        funds: Vec<Coin>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -413,10 +520,110 @@ pub struct InterchainPoolResponse {
     pub destination_creator: String,
     pub assets: Vec<PoolAsset>,
     pub swap_fee: u32,
+    /// Swap fee of right swaps when it differs from `swap_fee`
+    #[serde(default)]
+    pub right_swap_fee: Option<u32>,
     pub supply: Coin,
     pub status: PoolStatus,
     pub counter_party_port: String,
     pub counter_party_channel: String,
+    pub packet_timeout_seconds: Option<u64>,
+    /// Address the creator delegated management of the pool to
+    #[serde(default)]
+    pub manager: Option<String>,
+    /// LP token of this chain, if already instantiated
+    #[serde(default)]
+    pub lp_token: Option<String>,
+    /// Price of the source asset in destination asset base units, unset while a side is empty
+    #[serde(default)]
+    pub spot_price: Option<Decimal>,
+    /// Range the swap fee can be set in, in FEE_PRECISION units
+    #[serde(default)]
+    pub min_swap_fee_bps: u32,
+    #[serde(default)]
+    pub max_swap_fee_bps: u32,
+    /// Packets of the pool sent from this chain and not yet acknowledged or timed out
+    #[serde(default)]
+    pub pending_packets: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DecodedPacketResponse {
+    pub r#type: InterchainMessageType,
+    pub message: PacketMessage,
+    pub state_change: Option<StateChange>,
+    pub memo: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VerifyPoolResponse {
+    pub pool_id: String,
+    /// Weighted geometric mean of the balances, in whole tokens
+    pub invariant: Decimal,
+    /// Invariant backing one whole share of the supply
+    pub invariant_per_share: Decimal,
+    /// Pool supply against the supply of the local LP token
+    pub supply: PoolSupplyResponse,
+    /// Bank balance of each local asset against what the pools and escrows account for, the
+    /// first page of the reconciliation on contracts with many pools
+    pub reconcile: Vec<ReconcileResponse>,
+    /// Failed checks, empty for a sound pool
+    pub violations: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LpTokenResponse {
+    /// None while no instantiation was dispatched for the pool
+    pub status: Option<LpTokenStatus>,
+    /// Set once the token is Ready
+    pub address: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PoolSupplyResponse {
+    /// Shares accounted by the pool, minted on both chains
+    pub supply: Coin,
+    /// LP token of this chain, if already instantiated
+    pub lp_token: Option<String>,
+    /// Total supply of the local LP token
+    pub lp_token_supply: Uint128,
+    /// False when the local LP token holds more shares than the pool accounts for
+    pub consistent: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RateOutput {
+    pub denom: String,
+    pub amount: Uint128,
+    /// Tokens of `denom` redeemed per LP share
+    pub share_price: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RateResponse {
+    pub pool_id: String,
+    /// LP amount redeemed
+    pub lp_amount: Uint128,
+    pub outputs: Vec<RateOutput>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FirmQuoteResponse {
+    /// Output of a left swap, offer needed by a right swap
+    pub quoted_out: Coin,
+    /// Height the quote was made at, passed back to FirmSwap
+    pub quote_height: u64,
+    pub quote_time: Timestamp,
+    /// Last height FirmSwap accepts the quote at
+    pub valid_until_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SimulateResponse {
+    /// Packet the message would send, if any
+    pub packet: Option<InterchainSwapPacketData>,
+    /// Error the message would fail with
+    pub error: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -424,6 +631,133 @@ pub struct InterchainListResponse {
     pub pools: Vec<InterchainLiquidityPool>,
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DepositReceiptsResponse {
+    pub receipts: Vec<DepositReceipt>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RequiredCounterDepositResponse {
+    /// Exact funds TakeMultiAssetDeposit expects
+    pub funds: Vec<Coin>,
+    /// Taker named by the maker, others need a permit
+    pub taker: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TakePoolRequiredFundsResponse {
+    /// Exact funds TakePool expects, the assets of this chain
+    pub funds: Vec<Coin>,
+    /// Only creator allowed to take the pool
+    pub taker: String,
+}
+
+/// Payload a maker signs to let `taker` take an order in place of its destination taker.
+/// The signature is over the sha256 hash of the JSON encoding.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TakePermit {
+    /// Chain the order is taken on
+    pub chain_id: String,
+    pub pool_id: String,
+    pub order_id: String,
+    pub taker: String,
+}
+
+impl TakePermit {
+    pub fn hash(&self) -> StdResult<Vec<u8>> {
+        Ok(packet_data_hash(&to_vec(self)?))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ReconcileResponse {
+    pub denom: String,
+    /// Source side balance and collected fees of every pool trading the denom, plus the
+    /// treasury
+    pub recorded: Uint128,
+    /// Pending deposits, open orders, swaps in flight and unclaimed swap outputs
+    pub escrow: Uint128,
+    /// Bank balance of the contract
+    pub balance: Uint128,
+    /// Amount held above recorded + escrow
+    pub surplus: Uint128,
+    /// Amount missing to cover recorded + escrow
+    pub shortfall: Uint128,
+    /// Where the count continues while entries are left, surplus and shortfall are only set
+    /// once it is unset
+    pub next: Option<ReconcileCursor>,
+}
+
+/// Progress of a reconciliation counted over several queries, with the totals so far.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ReconcileCursor {
+    pub stage: ReconcileStage,
+    pub recorded: Uint128,
+    pub escrow: Uint128,
+}
+
+/// Entries a reconciliation counts, in order, with the last one counted.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconcileStage {
+    Pools { start_after: Option<String> },
+    PendingWithdrawals { start_after: Option<u64> },
+    QueuedWithdrawals { start_after: Option<u64> },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingWithdrawalsResponse {
+    pub withdrawals: Vec<PendingWithdrawal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct WithdrawalQueueResponse {
+    pub withdrawals: Vec<QueuedWithdrawal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ChannelResponse {
+    pub id: String,
+    pub counterparty_endpoint: IbcEndpoint,
+    pub connection_id: String,
+    /// Chain id bound by the first pool made over the channel
+    pub counterparty_chain_id: Option<String>,
+    /// False once the counterparty confirmed closing the channel
+    pub open: bool,
+    /// Pools bound to the channel
+    pub pool_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ChannelCapabilitiesResponse {
+    /// Capabilities of this end
+    pub local: ChannelCapabilities,
+    /// Capabilities the counterparty advertised, none before the handshake
+    pub counterparty: Option<ChannelCapabilities>,
+    /// Capabilities both ends support
+    pub negotiated: Option<ChannelCapabilities>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ChannelsResponse {
+    pub channels: Vec<ChannelResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ReceivedPacketsResponse {
+    pub packets: Vec<ReceivedPacket>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PacketJournalResponse {
+    pub entries: Vec<JournalEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LpCheckpointsResponse {
+    pub checkpoints: Vec<LpCheckpoint>,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct OrderListResponse {
     pub orders: Vec<MultiAssetDepositOrder>,
@@ -519,9 +853,47 @@ pub struct Params {
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct QueryConfigResponse {
     /// For order save in state
-    pub counter: u64,
+    pub counter: u128,
     /// For Instantiating cw20 tokens
     pub token_code_id: u64,
+    /// Whether the packet handlers emit debug attributes
+    pub debug_attributes: bool,
+    /// Owner of the contract
+    pub admin: String,
+    /// Address offered ownership, until it accepts
+    pub pending_admin: Option<String>,
+    /// Kind of LP token new pools get
+    pub lp_token_kind: LpTokenKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SharesForDepositResponse {
+    /// Shares minted for each deposit, in the order of the deposits
+    pub pool_tokens: Vec<Coin>,
+    /// Sum of the shares minted
+    pub shares: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProtocolFeesResponse {
+    /// Share of every swap fee skimmed for the protocol, in FEE_PRECISION units
+    pub share_bps: u32,
+    /// Address allowed to collect the fees
+    pub collector: Option<String>,
+    /// Fees accrued per denom, those of counterparty denoms are collected on its chain
+    pub fees: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StorageVersionResponse {
+    pub version: u32,
+    /// Namespaces with entries left to migrate
+    pub pending: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RolesResponse {
+    pub roles: Vec<Role>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
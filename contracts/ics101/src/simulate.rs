@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use cosmwasm_std::{Order, Record, Storage};
+
+/// Storage that reads through to the contract state but keeps all writes in
+/// memory, so execute handlers can be dry-run from a query.
+pub struct SimulationStorage<'a> {
+    base: &'a dyn Storage,
+    writes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<'a> SimulationStorage<'a> {
+    pub fn new(base: &'a dyn Storage) -> Self {
+        SimulationStorage {
+            base,
+            writes: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'a> Storage for SimulationStorage<'a> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.writes.get(key) {
+            Some(value) => value.clone(),
+            None => self.base.get(key),
+        }
+    }
+
+    fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'b> {
+        let mut records: BTreeMap<Vec<u8>, Vec<u8>> = self
+            .base
+            .range(start, end, Order::Ascending)
+            .collect();
+        let bounds = (
+            start.map_or(Bound::Unbounded, |s| Bound::Included(s.to_vec())),
+            end.map_or(Bound::Unbounded, |e| Bound::Excluded(e.to_vec())),
+        );
+        for (key, value) in self.writes.range(bounds) {
+            match value {
+                Some(value) => records.insert(key.clone(), value.clone()),
+                None => records.remove(key),
+            };
+        }
+
+        match order {
+            Order::Ascending => Box::new(records.into_iter()),
+            Order::Descending => Box::new(records.into_iter().rev()),
+        }
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.writes.insert(key.to_vec(), Some(value.to_vec()));
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.writes.insert(key.to_vec(), None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+
+    #[test]
+    fn test_writes_do_not_reach_base() {
+        let mut base = MockStorage::new();
+        base.set(b"a", b"1");
+        base.set(b"b", b"2");
+
+        let mut storage = SimulationStorage::new(&base);
+        storage.set(b"c", b"3");
+        storage.remove(b"a");
+
+        assert_eq!(storage.get(b"a"), None);
+        assert_eq!(storage.get(b"c"), Some(b"3".to_vec()));
+        let keys: Vec<Vec<u8>> = storage
+            .range(None, None, Order::Descending)
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec![b"c".to_vec(), b"b".to_vec()]);
+
+        assert_eq!(base.get(b"a"), Some(b"1".to_vec()));
+        assert_eq!(base.get(b"c"), None);
+    }
+}
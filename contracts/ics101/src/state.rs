@@ -1,10 +1,15 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::IbcEndpoint;
+use cosmwasm_std::{Addr, Binary, Coin, IbcEndpoint, StdError, StdResult, Uint128};
 use cw_storage_plus::{Item, Map};
 
-use crate::{market::InterchainLiquidityPool, types::MultiAssetDepositOrder};
+use crate::{
+    market::{InterchainLiquidityPool, PoolAsset, PoolStatus},
+    msg::LpTokenMarketing,
+    types::{InterchainMessageType, MultiAssetDepositOrder},
+    versioned::Migratable,
+};
 
 pub const CHANNEL_INFO: Map<&str, ChannelInfo> = Map::new("channel_info");
 
@@ -20,36 +25,582 @@ pub struct ChannelInfo {
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct Config {
-    // Counter to keep track of multiassetdeposit orders
-    pub counter: u64,
+    // Counter to keep track of multiassetdeposit orders, never decremented so ids are not reused
+    pub counter: u128,
     // Token code id  (Cw20)
     pub token_code_id: u64,
-    // Admin address
+    // Admin address, the owner of the contract
     pub admin: String,
+    // Address the admin offered ownership to, it becomes admin once it accepts
+    #[serde(default)]
+    pub pending_admin: Option<String>,
     // Router address
     pub router: String,
+    // Bounds for pool level packet timeouts, in seconds
+    #[serde(default = "default_min_timeout_seconds")]
+    pub min_timeout_seconds: u64,
+    #[serde(default = "default_max_timeout_seconds")]
+    pub max_timeout_seconds: u64,
+    // Timeout of packets whose message and pool set none, in seconds
+    #[serde(default = "default_timeout_seconds")]
+    pub default_timeout_seconds: u64,
+    // Bounds for pool swap fees, in FEE_PRECISION units (bps)
+    #[serde(default = "default_min_swap_fee_bps")]
+    pub min_swap_fee_bps: u32,
+    #[serde(default = "default_max_swap_fee_bps")]
+    pub max_swap_fee_bps: u32,
+    // Packet types neither sent nor accepted, e.g. during an incident
+    #[serde(default)]
+    pub disabled_messages: Vec<InterchainMessageType>,
+    // Emits detailed debug attributes from the packet handlers, off by default to save gas
+    #[serde(default)]
+    pub debug_attributes: bool,
+    // Blocks after its height a firm quote can still be swapped against
+    #[serde(default = "default_quote_validity_blocks")]
+    pub quote_validity_blocks: u64,
+    // Seconds a treasury withdrawal waits before it can be executed
+    #[serde(default = "default_treasury_timelock_seconds")]
+    pub treasury_timelock_seconds: u64,
+    // Share of every swap fee skimmed for the protocol, in FEE_PRECISION units (bps)
+    #[serde(default)]
+    pub protocol_fee_share_bps: u32,
+    // Local address allowed to collect the protocol fees, e.g. a DAO treasury
+    #[serde(default)]
+    pub protocol_fee_collector: Option<String>,
+    // Pools a creator may have waiting to be taken at once
+    #[serde(default = "default_max_initialized_pools")]
+    pub max_initialized_pools: u32,
+    // Kind of LP token new pools get, pools keep the kind they were made with
+    #[serde(default)]
+    pub lp_token_kind: LpTokenKind,
+}
+
+/// How the LP shares of a pool are minted on this chain.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LpTokenKind {
+    /// A cw20 contract instantiated for every pool
+    #[default]
+    Cw20,
+    /// The native denom `factory/{contract}/{subdenom}` of the tokenfactory module
+    TokenFactory,
+}
+
+/// Permissions the admin can hand out, the admin itself holds all of them.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Stops and resumes message handling during incidents
+    Pauser,
+    /// Sets the fee parameters
+    FeeSetter,
+    /// Sets channel parameters like address prefixes and timeouts
+    ChannelManager,
+    /// Moves funds collected by the contract
+    Treasurer,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Pauser => "pauser",
+            Role::FeeSetter => "fee_setter",
+            Role::ChannelManager => "channel_manager",
+            Role::Treasurer => "treasurer",
+        }
+    }
+}
+
+// Map from address to the roles granted to it
+pub const ROLES: Map<&str, Vec<Role>> = Map::new("roles");
+
+pub const DEFAULT_MIN_TIMEOUT_SECONDS: u64 = 60;
+pub const DEFAULT_MAX_TIMEOUT_SECONDS: u64 = 86400;
+
+fn default_min_timeout_seconds() -> u64 {
+    DEFAULT_MIN_TIMEOUT_SECONDS
+}
+
+fn default_max_timeout_seconds() -> u64 {
+    DEFAULT_MAX_TIMEOUT_SECONDS
+}
+
+pub const DEFAULT_MIN_SWAP_FEE_BPS: u32 = 0;
+pub const DEFAULT_MAX_SWAP_FEE_BPS: u32 = 1000;
+
+pub const DEFAULT_TIMEOUT_SECONDS: u64 = 600;
+
+fn default_timeout_seconds() -> u64 {
+    DEFAULT_TIMEOUT_SECONDS
+}
+
+fn default_min_swap_fee_bps() -> u32 {
+    DEFAULT_MIN_SWAP_FEE_BPS
 }
 
-// Each pool has it's pool token (cw20)
-// Map pool-id -> pool token address
+fn default_max_swap_fee_bps() -> u32 {
+    DEFAULT_MAX_SWAP_FEE_BPS
+}
+
+pub const DEFAULT_QUOTE_VALIDITY_BLOCKS: u64 = 10;
+
+fn default_quote_validity_blocks() -> u64 {
+    DEFAULT_QUOTE_VALIDITY_BLOCKS
+}
+
+pub const DEFAULT_TREASURY_TIMELOCK_SECONDS: u64 = 86400;
+
+fn default_treasury_timelock_seconds() -> u64 {
+    DEFAULT_TREASURY_TIMELOCK_SECONDS
+}
+
+pub const DEFAULT_MAX_INITIALIZED_POOLS: u32 = 10;
+
+fn default_max_initialized_pools() -> u32 {
+    DEFAULT_MAX_INITIALIZED_POOLS
+}
+
+impl Config {
+    /// Advances the order counter and returns the new value.
+    pub fn increment_counter(&mut self) -> StdResult<u128> {
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| StdError::generic_err("Order counter overflow"))?;
+        Ok(self.counter)
+    }
+
+    /// Whether a packet timeout lies within the governed bounds.
+    pub fn timeout_in_bounds(&self, seconds: u64) -> bool {
+        (self.min_timeout_seconds..=self.max_timeout_seconds).contains(&seconds)
+    }
+
+    /// Whether a pool swap fee lies within the governed bounds.
+    pub fn swap_fee_in_bounds(&self, swap_fee: u32) -> bool {
+        (self.min_swap_fee_bps..=self.max_swap_fee_bps).contains(&swap_fee)
+    }
+
+    /// Whether packets of a type may be sent and received.
+    pub fn is_enabled(&self, message_type: &InterchainMessageType) -> bool {
+        !self.disabled_messages.contains(message_type)
+    }
+
+    /// Brings a packet timeout chosen by the counterparty within the local bounds.
+    pub fn clamp_timeout(&self, seconds: u64) -> u64 {
+        seconds.clamp(self.min_timeout_seconds, self.max_timeout_seconds)
+    }
+}
+
+// Each pool has it's pool token (cw20 or tokenfactory denom)
+// Map pool-id -> pool token address, or denom of a tokenfactory LP token
 pub const POOL_TOKENS_LIST: Map<&str, String> = Map::new("pool_tokens_list");
 
+/// Lifecycle of the LP token of a pool on this chain.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub enum LpTokenStatus {
+    /// The instantiation was dispatched, its reply records the address
+    Instantiating,
+    /// The address is recorded in POOL_TOKENS_LIST
+    Ready,
+}
+
+// Map from pool_id to the status of its LP token, pools without an entry have none yet
+pub const LP_TOKEN_STATUS: Map<&str, LpTokenStatus> = Map::new("lp_token_status");
+
+// Map from pool_id to the marketing info its LP token is instantiated with
+pub const LP_TOKEN_MARKETING: Map<&str, LpTokenMarketing> = Map::new("lp_token_marketing");
+
+// Map from pool_id to the senders allowed to swap and deposit into it from this chain, anyone
+// when unset
+pub const POOL_WHITELISTS: Map<&str, Vec<String>> = Map::new("pool_whitelists");
+
 pub const CONFIG: Item<Config> = Item::new("config");
 
+/// Config as stored by versions that kept the order counter in a u64.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct LegacyConfig {
+    pub counter: u64,
+    pub token_code_id: u64,
+    pub admin: String,
+    pub router: String,
+}
+
+pub(crate) const LEGACY_CONFIG: Item<LegacyConfig> = Item::new("config");
+
 pub const TEMP: Item<String> = Item::new("temp");
 
+// Set while the contract calls of an execute run, calls back into the contract are rejected
+pub const PROCESSING: Item<bool> = Item::new("processing");
+
+/// State a take multi deposit receive changed, restored if minting its LP tokens fails.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TakeMultiDepositRollback {
+    pub order_key: String,
+    pub order: MultiAssetDepositOrder,
+    pub active_order_key: String,
+    pub active_order: Option<MultiAssetDepositOrder>,
+    pub pool: InterchainLiquidityPool,
+}
+
+// Pending rollback of the take multi deposit whose mint is being executed
+pub const TAKE_MULTI_DEPOSIT_ROLLBACK: Item<TakeMultiDepositRollback> =
+    Item::new("take_multi_deposit_rollback");
+
+/// Swap output forwarded to a third chain, held for the swap's sender if the transfer fails.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ForwardedTransfer {
+    /// Channel the swap packet came in on
+    pub channel_id: String,
+    /// Sender of the swap on the counterparty chain
+    pub sender: String,
+    pub token: Coin,
+    /// Transfer channel the output left on
+    pub forward_channel: String,
+}
+
+// Forward whose transfer is being sent, its reply records the sequence of the transfer
+pub const PENDING_FORWARD: Item<ForwardedTransfer> = Item::new("pending_forward");
+
+// Map from (transfer channel, sequence) to the forwarded output awaiting its result
+pub const FORWARDED_TRANSFERS: Map<(&str, u64), ForwardedTransfer> =
+    Map::new("forwarded_transfers");
+
 pub const POOLS: Map<&str, InterchainLiquidityPool> = Map::new("pools");
 
+// Map from pool_id to the address managing the pool in place of its creator
+pub const POOL_MANAGERS: Map<&str, String> = Map::new("pool_managers");
+
 // Map from key (pool_id + "-" + order_id) to value multi asset orders
 pub const MULTI_ASSET_DEPOSIT_ORDERS: Map<String, MultiAssetDepositOrder> =
     Map::new("multi_asset_deposit_orders");
 
+/// Value a storage key held before a received packet changed it.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct StorageChange {
+    pub key: Binary,
+    /// None when the key did not exist
+    pub previous: Option<Binary>,
+    /// Value the packet left, None when it removed the key
+    #[serde(default)]
+    pub current: Option<Binary>,
+}
+
+/// Everything a received packet changed, enough to revert its effects.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct UndoRecord {
+    pub packet_type: InterchainMessageType,
+    /// Block time (seconds) at which the packet was received
+    pub received_at: u64,
+    pub changes: Vec<StorageChange>,
+    /// Tokens the packet paid out of the contract, a revert can't take them back
+    #[serde(default)]
+    pub sent: Vec<Coin>,
+}
+
+/// Undo records are kept this long after the packet was received.
+pub const UNDO_RETENTION_SECONDS: u64 = 7 * 86400;
+
+// Map from (destination channel, packet sequence) to the undo record of the received packet
+pub const UNDO_LOG: Map<(&str, u64), UndoRecord> = Map::new("undo_log");
+
+// Map from (received at, destination channel, packet sequence) of undo records, oldest first
+pub const UNDO_EXPIRY: Map<(u64, &str, u64), bool> = Map::new("undo_expiry");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketResult {
+    Success,
+    Failure { error: String },
+    Timeout,
+}
+
+/// An outgoing packet, journaled when it is sent and completed once it was acknowledged or
+/// timed out.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct JournalEntry {
+    /// Id of the entry within the journal of its pool
+    #[serde(default)]
+    pub id: u64,
+    /// Sequence the chain assigned to the packet, known once it completed
+    pub sequence: Option<u64>,
+    pub channel_id: String,
+    pub packet_type: InterchainMessageType,
+    /// Tokens the sender put into the packet
+    pub tokens: Vec<Coin>,
+    /// Block time (seconds) at which the packet was sent
+    pub sent_at: Option<u64>,
+    /// None while the packet is in flight
+    pub result: Option<PacketResult>,
+    /// Block time (seconds) at which the result was received
+    pub completed_at: Option<u64>,
+}
+
+// Map from (pool_id, packet sequence) to the journal entry of a sent packet, before entries
+// were made at send time
+pub(crate) const LEGACY_PACKET_JOURNAL: Map<(&str, u64), JournalEntry> =
+    Map::new("packet_journal");
+
+// Map from (pool_id, journal id) to the journal entry of a sent packet
+pub const PACKET_JOURNAL: Map<(&str, u64), JournalEntry> = Map::new("packet_journal_entries");
+
+// Map from pool_id to the number of journal entries ever made
+pub const PACKET_JOURNAL_COUNT: Map<&str, u64> = Map::new("packet_journal_count");
+
+// Map from packet data hash to the journal ids of the packets in flight with that data,
+// oldest first. The pool is part of the data.
+pub const PENDING_JOURNAL_ENTRIES: Map<&[u8], Vec<u64>> = Map::new("pending_journal_entries");
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LpChange {
+    Mint,
+    Burn,
+}
+
+/// LP tokens of a pool minted to or burned for an address.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LpCheckpoint {
+    pub id: u64,
+    pub address: String,
+    pub change: LpChange,
+    pub amount: Uint128,
+    /// Block time (seconds) of the change
+    pub time: u64,
+}
+
+// Number of LP checkpoints kept per pool, older ones are dropped
+pub const MAX_LP_CHECKPOINTS: u64 = 1000;
+
+// Map from (pool_id, checkpoint id) to the LP checkpoints of the pool
+pub const LP_CHECKPOINTS: Map<(&str, u64), LpCheckpoint> = Map::new("lp_checkpoints");
+
+// Map from pool_id to the number of LP checkpoints ever recorded
+pub const LP_CHECKPOINT_COUNT: Map<&str, u64> = Map::new("lp_checkpoint_count");
+
+/// An incoming packet, journaled once it was processed.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ReceivedPacket {
+    pub sequence: u64,
+    pub channel_id: String,
+    /// Unset when the packet data could not be decoded
+    pub packet_type: Option<InterchainMessageType>,
+    pub pool_id: Option<String>,
+    pub result: PacketResult,
+    /// Kind of error the packet was refused with
+    pub error_code: Option<String>,
+    /// Block time (seconds) at which the packet was received
+    pub received_at: u64,
+}
+
+// (destination channel id, packet sequence) of inbound packets rejected with a failure ack
+pub const VETOED_PACKETS: Map<(&str, u64), bool> = Map::new("vetoed_packets");
+
+// Map from (destination channel id, packet sequence) to the record of a received packet
+pub const RECEIVED_PACKETS: Map<(&str, u64), ReceivedPacket> = Map::new("received_packets");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DepositStatus {
+    Pending,
+    Minted,
+    Refunded,
+}
+
+/// Display data of a denom, maintained by the admin.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AssetMetadata {
+    pub symbol: String,
+    pub decimals: u32,
+    pub logo: Option<String>,
+}
+
+// Map from denom to its registered metadata, pools must use the registered decimals
+pub const ASSET_METADATA: Map<&str, AssetMetadata> = Map::new("asset_metadata");
+
+/// Smallest amounts of the local asset a pool accepts, zero turns a check off.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, JsonSchema, Debug)]
+pub struct DustThresholds {
+    pub min_swap: Uint128,
+    pub min_deposit: Uint128,
+}
+
+// Map from pool_id to the dust thresholds set by its manager
+pub const DUST_THRESHOLDS: Map<&str, DustThresholds> = Map::new("dust_thresholds");
+
+/// Tracks a single asset deposit from execution until its packet settles.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DepositReceipt {
+    pub id: u64,
+    pub sender: String,
+    pub pool_id: String,
+    pub token: Coin,
+    pub status: DepositStatus,
+    /// Block time (seconds) of the deposit
+    pub created_at: u64,
+}
+
+// Map from (sender, receipt id) to single asset deposit receipts
+pub const DEPOSIT_RECEIPTS: Map<(&str, u64), DepositReceipt> = Map::new("deposit_receipts");
+
+pub const DEPOSIT_RECEIPT_COUNT: Item<u64> = Item::new("deposit_receipt_count");
+
+// Map from the hash of in-flight packet data to the receipts waiting for its result.
+// Deposits with identical data are interchangeable, so any of them may settle first.
+pub const PENDING_DEPOSIT_RECEIPTS: Map<&[u8], Vec<u64>> = Map::new("pending_deposit_receipts");
+
+// Map from pool_id to the bootstrap targets of its unfunded local assets
+pub const BOOTSTRAP_TARGETS: Map<&str, Vec<Coin>> = Map::new("bootstrap_targets");
+
+// Map from pool_id to the packet data hash of the bootstrap deposit in flight, a bootstrap
+// pool takes one deposit at a time
+pub const BOOTSTRAP_DEPOSITS: Map<&str, Vec<u8>> = Map::new("bootstrap_deposits");
+
+// Map from pool_id to the number of its packets not yet acknowledged or timed out
+pub const IN_FLIGHT_PACKETS: Map<&str, u32> = Map::new("in_flight_packets");
+
+// Map from pool_id and denom to swap input held by the contract while the swap packet is
+// in flight
+pub const SWAP_ESCROW: Map<(&str, &str), Uint128> = Map::new("swap_escrow_by_denom");
+
+// Swap escrow per pool_id, from before pools held more than one asset per chain
+pub(crate) const LEGACY_SWAP_ESCROW: Map<&str, Uint128> = Map::new("swap_escrow");
+
+// Map from denom to the funds held for swaps in flight, pending deposits, open orders and
+// unclaimed swap outputs, which payouts from the pools must leave in place
+pub const ESCROWED_FUNDS: Map<&str, Uint128> = Map::new("escrowed_funds");
+
+// Map from pool_id to the swap fees collected apart from the pool balances, per denom. They
+// belong to the LPs, a withdrawal pays the share of the fees its shares are worth
+pub const COLLECTED_FEES: Map<&str, Vec<Coin>> = Map::new("collected_fees");
+
+// Map from pool_id to the protocol share of its swap fees not collected yet, per denom
+pub const PROTOCOL_FEES: Map<&str, Vec<Coin>> = Map::new("protocol_fees");
+
+// Map from creator and pool_id to the pools made on this chain that are not taken yet
+pub const INITIALIZED_POOLS: Map<(&str, &str), bool> = Map::new("initialized_pools");
+
+// Map from pool_id to the time after which the draining pool can be decommissioned
+pub const DRAIN_DEADLINES: Map<&str, u64> = Map::new("drain_deadlines");
+
+// Map from denom to funds held for the treasury, e.g. the residuals of decommissioned pools
+pub const TREASURY: Map<&str, Uint128> = Map::new("treasury");
+
+/// Treasury funds on their way out, sendable once the timelock passed.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingWithdrawal {
+    pub id: u64,
+    pub denom: String,
+    pub amount: Uint128,
+    pub to: String,
+    pub executable_at: u64,
+    /// Part of the amount drawn from the protocol fees of each pool, the rest came from the
+    /// treasury
+    #[serde(default)]
+    pub protocol_fees: Vec<(String, Uint128)>,
+}
+
+// Map from withdrawal id to the treasury withdrawals waiting for their timelock
+pub const PENDING_WITHDRAWALS: Map<u64, PendingWithdrawal> = Map::new("pending_withdrawals");
+
+pub const PENDING_WITHDRAWAL_COUNT: Item<u64> = Item::new("pending_withdrawal_count");
+
+/// Pool withdrawal payout that would have spent funds escrowed here, sent once the balance
+/// covers it again.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct QueuedWithdrawal {
+    pub id: u64,
+    pub pool_id: String,
+    pub receiver: String,
+    pub token: Coin,
+    /// Block time (seconds) at which the payout was queued
+    pub queued_at: u64,
+}
+
+// Map from (denom, id) to the queued withdrawal payouts, paid out in id order
+pub const WITHDRAWAL_QUEUE: Map<(&str, u64), QueuedWithdrawal> = Map::new("withdrawal_queue");
+
+pub const WITHDRAWAL_QUEUE_COUNT: Item<u64> = Item::new("withdrawal_queue_count");
+
 // Map from key (source_makers + "-" + pool_id)
 pub const ACTIVE_ORDERS: Map<String, MultiAssetDepositOrder> = Map::new("active_order");
 
 // Map from pool_id to contract address
 pub const LOG_VOLUME: Map<String, String> = Map::new("log_volume");
 
+// Map from connection id to the port of the counterparty contract, bound by the first
+// channel opened on the connection
+pub const COUNTERPARTY_PORTS: Map<&str, String> = Map::new("counterparty_ports");
+
+// Channels negotiated with the compact packet version, their packets are sent compact
+pub const COMPACT_CHANNELS: Map<&str, bool> = Map::new("compact_channels");
+
+/// Capabilities a channel end advertises. A version may carry them as JSON in place of the
+/// plain `ics101-1`, so ends running different releases agree on what both support.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, JsonSchema, Debug)]
+pub struct ChannelCapabilities {
+    /// Packet version, `ics101-1`
+    pub version: String,
+    /// Packets in the compact encoding are understood
+    #[serde(default)]
+    pub compact: bool,
+    /// Acknowledgements may be written after the packet is received
+    #[serde(default)]
+    pub async_acks: bool,
+    /// Relayers are paid through the ics29 fee middleware
+    #[serde(default)]
+    pub fee_middleware: bool,
+}
+
+impl ChannelCapabilities {
+    /// Capabilities both ends support.
+    pub fn intersect(&self, other: &ChannelCapabilities) -> ChannelCapabilities {
+        ChannelCapabilities {
+            version: self.version.clone(),
+            compact: self.compact && other.compact,
+            async_acks: self.async_acks && other.async_acks,
+            fee_middleware: self.fee_middleware && other.fee_middleware,
+        }
+    }
+}
+
+// Map from channel id to the capabilities the counterparty advertised in the handshake
+pub const COUNTERPARTY_CAPABILITIES: Map<&str, ChannelCapabilities> =
+    Map::new("counterparty_capabilities");
+
+// Map from channel id to the chain id of the counterparty, bound by the first pool made over
+// the channel
+pub const COUNTERPARTY_CHAIN_IDS: Map<&str, String> = Map::new("counterparty_chain_ids");
+
+// Channels whose closing the counterparty confirmed
+pub const CLOSED_CHANNELS: Map<&str, bool> = Map::new("closed_channels");
+
+// Map from channel id to the bech32 prefix of addresses on the counterparty chain
+pub const COUNTERPARTY_ADDRESS_PREFIX: Map<&str, String> = Map::new("counterparty_address_prefix");
+
+// Map from (channel_id, counterparty sender) to swap outputs whose recipient was invalid
+pub const SWAP_CLAIMS: Map<(&str, &str), Vec<Coin>> = Map::new("swap_claims");
+
+// Map from (channel_id, counterparty sender) to the local address its claims are paid to
+pub const CLAIM_ADDRESSES: Map<(&str, &str), Addr> = Map::new("claim_addresses");
+
+// Map from channel id to interchain query (icq-1) channels
+pub const ICQ_CHANNEL_INFO: Map<&str, ChannelInfo> = Map::new("icq_channel_info");
+
+// Map from pool_id to the pool state last fetched from the counterparty chain
+pub const COUNTERPARTY_POOLS: Map<&str, CounterpartyPool> = Map::new("counterparty_pools");
+
+// Divergence (in FEE_PRECISION units) tolerated between a pool and its counterparty copy
+pub const DRIFT_TOLERANCE: Item<u32> = Item::new("drift_tolerance");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CounterpartyPool {
+    pub assets: Vec<PoolAsset>,
+    pub supply: Coin,
+    pub status: PoolStatus,
+    /// Block time (seconds) at which the query result was received
+    pub updated_at: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Status {
@@ -58,3 +609,10 @@ pub enum Status {
     Cancel,   // canceled
     Complete, // completed
 }
+
+// Layout of the contract storage, bumped when a namespace moves to a VersionedMap
+pub const STORAGE_VERSION: Item<u32> = Item::new("storage_version");
+pub const CURRENT_STORAGE_VERSION: u32 = 1;
+
+// Namespaces being moved, their entries are moved by MigrateStorage or when written
+pub const VERSIONED_MAPS: &[&dyn Migratable] = &[];
@@ -0,0 +1,168 @@
+//! Golden vectors of the pool math, shared with the reference implementation. The fixture
+//! in `testdata/amm_vectors.json` lists pools and the expected outcome of swaps, deposits
+//! and withdrawals on them.
+
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{Coin, Uint128};
+use serde::Deserialize;
+
+use crate::market::{
+    FeeApplication, InterchainLiquidityPool, InterchainMarketMaker, PoolAsset, PoolSide,
+    PoolStatus, PoolType,
+};
+
+#[derive(Deserialize)]
+struct Vectors {
+    pools: BTreeMap<String, VectorPool>,
+    swaps: Vec<SwapVector>,
+    single_asset_deposits: Vec<SingleDepositVector>,
+    multi_asset_deposits: Vec<MultiDepositVector>,
+    withdrawals: Vec<WithdrawalVector>,
+}
+
+#[derive(Deserialize)]
+struct VectorPool {
+    assets: Vec<VectorAsset>,
+    supply: Uint128,
+    fee_rate: u32,
+}
+
+#[derive(Deserialize)]
+struct VectorAsset {
+    denom: String,
+    balance: Uint128,
+    weight: u32,
+    decimal: u32,
+}
+
+#[derive(Deserialize)]
+struct SwapVector {
+    pool: String,
+    token_in: Coin,
+    denom_out: String,
+    expected: Coin,
+    tolerance: Uint128,
+}
+
+#[derive(Deserialize)]
+struct SingleDepositVector {
+    pool: String,
+    token: Coin,
+    expected: Coin,
+    tolerance: Uint128,
+}
+
+#[derive(Deserialize)]
+struct MultiDepositVector {
+    pool: String,
+    tokens: Vec<Coin>,
+    expected: Vec<Coin>,
+}
+
+#[derive(Deserialize)]
+struct WithdrawalVector {
+    pool: String,
+    redeem: Coin,
+    expected: Vec<Coin>,
+}
+
+fn load_vectors() -> Vectors {
+    serde_json::from_str(include_str!("../testdata/amm_vectors.json")).unwrap()
+}
+
+// market maker of an active pool, the first asset is the local one
+fn market_maker(vectors: &Vectors, name: &str) -> InterchainMarketMaker {
+    let pool = &vectors.pools[name];
+    let sides = [PoolSide::SOURCE, PoolSide::DESTINATION];
+    let pool = InterchainLiquidityPool {
+        id: name.to_string(),
+        source_creator: "maker".to_string(),
+        destination_creator: "taker".to_string(),
+        assets: pool
+            .assets
+            .iter()
+            .zip(sides)
+            .map(|(asset, side)| PoolAsset {
+                side,
+                balance: Coin {
+                    denom: asset.denom.clone(),
+                    amount: asset.balance,
+                },
+                weight: asset.weight,
+                decimal: asset.decimal,
+            })
+            .collect(),
+        supply: Coin {
+            denom: name.to_string(),
+            amount: pool.supply,
+        },
+        status: PoolStatus::Active,
+        counter_party_port: "port".to_string(),
+        counter_party_channel: "channel-0".to_string(),
+        swap_fee: pool.fee_rate,
+        source_chain_id: "chain-a".to_string(),
+        destination_chain_id: "chain-b".to_string(),
+        pool_price: 0,
+        packet_timeout_seconds: None,
+        fee_application: FeeApplication::Source,
+        pool_type: PoolType::Weighted,
+        right_swap_fee: None,
+    };
+    InterchainMarketMaker::new(&pool, pool.swap_fee)
+}
+
+fn assert_within(actual: &Coin, expected: &Coin, tolerance: Uint128, case: &str) {
+    assert_eq!(actual.denom, expected.denom, "{}", case);
+    let deviation = actual.amount.max(expected.amount) - actual.amount.min(expected.amount);
+    assert!(
+        deviation <= tolerance,
+        "{}: got {}, expected {} within {}",
+        case,
+        actual,
+        expected,
+        tolerance
+    );
+}
+
+#[test]
+fn test_swap_vectors() {
+    let vectors = load_vectors();
+    for vector in vectors.swaps.iter() {
+        let amm = market_maker(&vectors, &vector.pool);
+        let out = amm.compute_swap(vector.token_in.clone(), &vector.denom_out).unwrap();
+        let case = format!("swap of {} in {}", vector.token_in, vector.pool);
+        assert_within(&out, &vector.expected, vector.tolerance, &case);
+    }
+}
+
+#[test]
+fn test_single_asset_deposit_vectors() {
+    let vectors = load_vectors();
+    for vector in vectors.single_asset_deposits.iter() {
+        let amm = market_maker(&vectors, &vector.pool);
+        let shares = amm.deposit_single_asset(&vector.token).unwrap();
+        let case = format!("deposit of {} in {}", vector.token, vector.pool);
+        assert_within(&shares, &vector.expected, vector.tolerance, &case);
+    }
+}
+
+#[test]
+fn test_multi_asset_deposit_vectors() {
+    let vectors = load_vectors();
+    for vector in vectors.multi_asset_deposits.iter() {
+        let amm = market_maker(&vectors, &vector.pool);
+        let shares = amm.deposit_multi_asset(&vector.tokens).unwrap();
+        assert_eq!(shares, vector.expected, "deposit in {}", vector.pool);
+    }
+}
+
+#[test]
+fn test_withdrawal_vectors() {
+    let vectors = load_vectors();
+    for vector in vectors.withdrawals.iter() {
+        let amm = market_maker(&vectors, &vector.pool);
+        let assets = amm.multi_asset_withdraw(vector.redeem.clone()).unwrap();
+        assert_eq!(assets, vector.expected, "withdrawal of {}", vector.redeem);
+    }
+}
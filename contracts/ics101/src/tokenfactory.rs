@@ -0,0 +1,150 @@
+//! LP shares as native tokenfactory denoms.
+//!
+//! With [`LpTokenKind::TokenFactory`] the LP token of a pool is the denom
+//! `factory/{contract}/{subdenom}` instead of a cw20 contract. The denom is created in the
+//! same transaction as the pool, so no reply has to record it, and the shares can be sent
+//! over IBC like any other bank token. The chain has to run the tokenfactory module with
+//! the `osmosis.tokenfactory.v1beta1` messages and allow `mintToAddress`.
+//!
+//! [`LpTokenKind::TokenFactory`]: crate::state::LpTokenKind::TokenFactory
+
+use cosmwasm_std::{Binary, CosmosMsg, StdError, StdResult, Uint128};
+use cw20::Cw20ExecuteMsg;
+
+use crate::icq::{encode, read_bytes_field};
+
+const CREATE_DENOM_TYPE_URL: &str = "/osmosis.tokenfactory.v1beta1.MsgCreateDenom";
+const MINT_TYPE_URL: &str = "/osmosis.tokenfactory.v1beta1.MsgMint";
+const BURN_TYPE_URL: &str = "/osmosis.tokenfactory.v1beta1.MsgBurn";
+
+const DENOM_PREFIX: &str = "factory/";
+/// Longest subdenom the tokenfactory module accepts.
+const MAX_SUBDENOM_LENGTH: usize = 44;
+
+/// Denom of the LP token of `pool_id` minted by `contract`. Pool ids are longer than a
+/// subdenom may be, the leading characters of the hash are kept.
+pub fn lp_denom(contract: &str, pool_id: &str) -> String {
+    let subdenom: String = pool_id.chars().take(MAX_SUBDENOM_LENGTH).collect();
+    format!("{}{}/{}", DENOM_PREFIX, contract, subdenom)
+}
+
+/// Splits a tokenfactory denom in its creator and subdenom, `None` for other tokens such as
+/// the address of a cw20 LP token.
+pub fn split_denom(denom: &str) -> Option<(&str, &str)> {
+    denom.strip_prefix(DENOM_PREFIX)?.split_once('/')
+}
+
+/// Creates the denom `factory/{sender}/{subdenom}`.
+pub fn create_denom_msg(denom: &str) -> StdResult<CosmosMsg> {
+    let (sender, subdenom) = split(denom)?;
+    let value = encode(|os| {
+        os.write_string(1, sender)?;
+        os.write_string(2, subdenom)
+    })?;
+    Ok(stargate(CREATE_DENOM_TYPE_URL, value))
+}
+
+/// Mints `amount` of `denom` to `recipient`, the creator of the denom signs.
+pub fn mint_msg(denom: &str, amount: Uint128, recipient: &str) -> StdResult<CosmosMsg> {
+    let (sender, _) = split(denom)?;
+    let coin = encode_coin(denom, amount)?;
+    let value = encode(|os| {
+        os.write_string(1, sender)?;
+        os.write_bytes(2, &coin)?;
+        os.write_string(3, recipient)
+    })?;
+    Ok(stargate(MINT_TYPE_URL, value))
+}
+
+/// Burns `amount` of `denom` held by its creator.
+pub fn burn_msg(denom: &str, amount: Uint128) -> StdResult<CosmosMsg> {
+    let (sender, _) = split(denom)?;
+    let coin = encode_coin(denom, amount)?;
+    let value = encode(|os| {
+        os.write_string(1, sender)?;
+        os.write_bytes(2, &coin)
+    })?;
+    Ok(stargate(BURN_TYPE_URL, value))
+}
+
+/// Reads a mint or burn of LP shares back as the cw20 message it stands for, so the LP
+/// accounting handles both kinds of LP token alike. `None` for other messages.
+pub(crate) fn decode_lp_msg(type_url: &str, value: &[u8]) -> StdResult<Option<Cw20ExecuteMsg>> {
+    let amount = || -> StdResult<Uint128> {
+        let coin = read_bytes_field(value, 2)?;
+        let amount = String::from_utf8_lossy(&read_bytes_field(&coin, 2)?).into_owned();
+        amount
+            .parse::<u128>()
+            .map(Uint128::new)
+            .map_err(|_| StdError::parse_err("Uint128", amount))
+    };
+    match type_url {
+        MINT_TYPE_URL => {
+            let recipient = String::from_utf8_lossy(&read_bytes_field(value, 3)?).into_owned();
+            Ok(Some(Cw20ExecuteMsg::Mint {
+                recipient,
+                amount: amount()?,
+            }))
+        }
+        BURN_TYPE_URL => Ok(Some(Cw20ExecuteMsg::Burn { amount: amount()? })),
+        _ => Ok(None),
+    }
+}
+
+fn split(denom: &str) -> StdResult<(&str, &str)> {
+    split_denom(denom).ok_or_else(|| {
+        StdError::generic_err(format!("Not a tokenfactory denom: {}", denom))
+    })
+}
+
+fn encode_coin(denom: &str, amount: Uint128) -> StdResult<Vec<u8>> {
+    encode(|os| {
+        os.write_string(1, denom)?;
+        os.write_string(2, &amount.to_string())
+    })
+}
+
+fn stargate(type_url: &str, value: Vec<u8>) -> CosmosMsg {
+    CosmosMsg::Stargate {
+        type_url: type_url.to_string(),
+        value: Binary::from(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lp_denom_messages() {
+        let pool_id = format!("pool{}", "ab".repeat(32));
+        let denom = lp_denom("contract", &pool_id);
+        assert_eq!(denom, format!("factory/contract/pool{}", "ab".repeat(20)));
+        assert_eq!(split_denom(&denom), Some(("contract", &denom[17..])));
+        assert_eq!(split_denom("cw20token"), None);
+
+        let msg = mint_msg(&denom, Uint128::new(42), "maker").unwrap();
+        let (type_url, value) = match msg {
+            CosmosMsg::Stargate { type_url, value } => (type_url, value),
+            _ => panic!("not a stargate message"),
+        };
+        assert_eq!(type_url, MINT_TYPE_URL);
+        assert_eq!(read_bytes_field(&value, 1).unwrap(), b"contract");
+        assert_eq!(
+            decode_lp_msg(&type_url, &value).unwrap(),
+            Some(Cw20ExecuteMsg::Mint {
+                recipient: "maker".to_string(),
+                amount: Uint128::new(42)
+            })
+        );
+
+        let msg = burn_msg(&denom, Uint128::new(7)).unwrap();
+        if let CosmosMsg::Stargate { type_url, value } = msg {
+            assert_eq!(
+                decode_lp_msg(&type_url, &value).unwrap(),
+                Some(Cw20ExecuteMsg::Burn { amount: Uint128::new(7) })
+            );
+        }
+        assert!(create_denom_msg("cw20token").is_err());
+    }
+}
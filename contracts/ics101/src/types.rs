@@ -3,23 +3,9 @@ use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{Binary, Coin, Decimal, Uint128};
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct StateChange {
-    #[serde(rename = "In")]
-    pub in_tokens: Option<Vec<Coin>>,
-    #[serde(rename = "Out")]
-    pub out_tokens: Option<Vec<Coin>>,
-    #[serde(rename = "PoolTokens")]
-    pub pool_tokens: Option<Vec<Coin>>,
-    #[serde(rename = "PoolId")]
-    pub pool_id: Option<String>,
-    #[serde(rename = "MultiDepositOrderId")]
-    pub multi_deposit_order_id: Option<String>,
-    #[serde(rename = "SourceChainId")]
-    pub source_chain_id: Option<String>,
-    #[serde(rename = "Shares")]
-    pub shares: Option<Uint128>,
-}
+pub use ibcswap_packets::{InterchainMessageType, InterchainSwapPacketData, StateChange};
+
+use crate::market::InterchainLiquidityPool;
 
 #[derive(Serialize, Deserialize)]
 pub struct Forward {
@@ -37,44 +23,6 @@ pub struct Memo {
 }
 
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct InterchainSwapPacketData {
-    #[serde(rename = "Type")]
-    pub r#type: InterchainMessageType,
-    #[serde(rename = "Data")]
-    pub data: Binary,
-    #[serde(rename = "StateChange")]
-    pub state_change: Option<Binary>,
-    #[serde(rename = "Memo")]
-    pub memo: Option<Binary>
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub enum InterchainMessageType {
-    #[serde(rename = "UNSPECIFIED")]
-    Unspecified = 0,
-    #[serde(rename = "MAKE_POOL")]
-    MakePool = 1,
-    #[serde(rename = "TAKE_POOL")]
-    TakePool = 2,
-    #[serde(rename = "CANCEL_POOL")]
-    CancelPool = 3,
-    #[serde(rename = "SINGLE_ASSET_DEPOSIT")]
-    SingleAssetDeposit = 4,
-    #[serde(rename = "MAKE_MULTI_DEPOSIT")]
-    MakeMultiDeposit = 5,
-    #[serde(rename = "CANCEL_MULTI_DEPOSIT")]
-    CancelMultiDeposit = 6,
-    #[serde(rename = "TAKE_MULTI_DEPOSIT")]
-    TakeMultiDeposit = 7,
-    #[serde(rename = "MULTI_WITHDRAW")]
-    MultiWithdraw = 8,
-    #[serde(rename = "LEFT_SWAP")]
-    LeftSwap = 9,
-    #[serde(rename = "RIGHT_SWAP")]
-    RightSwap = 10,
-}
-
 pub const MULTI_DEPOSIT_PENDING_LIMIT: u64 = 10;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -96,6 +44,64 @@ pub struct MultiAssetDepositOrder {
     //pub pool_tokens: Vec<Coin>,
     pub status: OrderStatus,
     pub created_at: u64,
+    /// Receiver of the maker's LP tokens, defaults to the maker
+    #[serde(default)]
+    pub recipient: Option<String>,
+    /// Key of the maker that may sign take permits for the order
+    #[serde(default)]
+    pub permit_pubkey: Option<Binary>,
+}
+
+impl MultiAssetDepositOrder {
+    pub fn lp_recipient(&self) -> String {
+        self.recipient
+            .clone()
+            .unwrap_or_else(|| self.source_maker.clone())
+    }
+
+    /// Whether `other` deposits the same amounts of the assets of `pool` with the maker on
+    /// the other side, so the maker deposits of the two orders settle each other.
+    pub fn mirrors(&self, other: &MultiAssetDepositOrder, pool: &InterchainLiquidityPool) -> bool {
+        let sorted = |deposits: &[Coin]| {
+            let mut deposits = deposits.to_vec();
+            deposits.sort_by(|a, b| a.denom.cmp(&b.denom));
+            deposits
+        };
+        self.pool_id == other.pool_id
+            && sorted(&self.deposits) == sorted(&other.deposits)
+            && match (pool.maker_side(&self.deposits), pool.maker_side(&other.deposits)) {
+                (Ok(side), Ok(other_side)) => side != other_side,
+                _ => false,
+            }
+    }
+}
+
+/// Result a receiving chain that recomputed a swap acknowledges it with. The output of a left
+/// swap or the input of a right swap, both in `denom`, and the fee the receiving chain took.
+/// The fee is out of the output and part of the input, it goes to the fees of the pool.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SettledSwap {
+    pub denom: String,
+    pub amount: Uint128,
+    /// Zero in the acknowledgements of earlier versions, which left it in the pool balance
+    #[serde(default)]
+    pub fee: Uint128,
+}
+
+impl SettledSwap {
+    pub fn token(&self) -> Coin {
+        Coin {
+            denom: self.denom.clone(),
+            amount: self.amount,
+        }
+    }
+
+    pub fn fee_token(&self) -> Coin {
+        Coin {
+            denom: self.denom.clone(),
+            amount: self.fee,
+        }
+    }
 }
 
 /// ## Description - This struct describes a asset (native or CW20) and its normalized weight
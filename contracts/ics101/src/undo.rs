@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{from_slice, to_vec, Coin, Order, Record, StdResult, Storage, Uint128};
+
+use crate::{
+    error::ContractError,
+    market::InterchainLiquidityPool,
+    state::{StorageChange, UndoRecord, POOLS},
+};
+
+/// Storage that writes through to the contract state and remembers the value
+/// every key held before its first write, so the writes can be undone later.
+pub struct UndoStorage<'a> {
+    base: &'a mut dyn Storage,
+    previous: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<'a> UndoStorage<'a> {
+    pub fn new(base: &'a mut dyn Storage) -> Self {
+        UndoStorage {
+            base,
+            previous: BTreeMap::new(),
+        }
+    }
+
+    /// Prior and current values of all keys written so far.
+    pub fn into_changes(self) -> Vec<StorageChange> {
+        let base = self.base;
+        self.previous
+            .into_iter()
+            .map(|(key, previous)| StorageChange {
+                current: base.get(&key).map(Into::into),
+                key: key.into(),
+                previous: previous.map(Into::into),
+            })
+            .collect()
+    }
+
+    fn remember(&mut self, key: &[u8]) {
+        if !self.previous.contains_key(key) {
+            let value = self.base.get(key);
+            self.previous.insert(key.to_vec(), value);
+        }
+    }
+}
+
+impl<'a> Storage for UndoStorage<'a> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.base.get(key)
+    }
+
+    fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'b> {
+        self.base.range(start, end, order)
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.remember(key);
+        self.base.set(key, value);
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.remember(key);
+        self.base.remove(key);
+    }
+}
+
+/// Takes back the changes of an undo record. A key no later packet touched gets its value
+/// from before the packet back. Pools and amounts changed since get the difference the packet
+/// made undone on top of their current value. Tokens the packet sent out are gone, so the
+/// pool balances of their denoms are not restored by them. Any other key changed since is a
+/// conflict, the later packet has to be reverted first.
+pub fn revert(storage: &mut dyn Storage, record: &UndoRecord) -> Result<(), ContractError> {
+    let pools = POOLS.key("");
+    let mut sent = record.sent.clone();
+    let mut writes = vec![];
+    for change in &record.changes {
+        let key = change.key.as_slice();
+        let now = storage.get(key);
+        let unchanged = now.as_deref() == change.current.as_deref();
+        let value = if key.starts_with(&pools) {
+            let value = match (&change.previous, &change.current, &now) {
+                (previous, _, _) if unchanged => previous.as_deref().map(from_slice).transpose()?,
+                (Some(previous), Some(current), Some(now)) => Some(compensate_pool(
+                    from_slice(previous)?,
+                    from_slice(current)?,
+                    from_slice(now)?,
+                )?),
+                _ => return Err(conflict(key)),
+            };
+            value.map(|pool| to_vec(&deduct_sent(pool, &mut sent))).transpose()?
+        } else if unchanged {
+            change.previous.as_ref().map(|value| value.to_vec())
+        } else {
+            compensate_amounts(change.previous.as_deref(), change.current.as_deref(), &now)
+                .ok_or_else(|| conflict(key))?
+        };
+        writes.push((key, value));
+    }
+    for (key, value) in writes {
+        match value {
+            Some(value) => storage.set(key, &value),
+            None => storage.remove(key),
+        }
+    }
+    Ok(())
+}
+
+// `now` with the difference between `previous` and `current` undone, for keys holding an
+// amount or a list of coins. None when the values are not amounts or `now` is too small.
+fn compensate_amounts(
+    previous: Option<&[u8]>,
+    current: Option<&[u8]>,
+    now: &Option<Vec<u8>>,
+) -> Option<Option<Vec<u8>>> {
+    let single = [previous, current, now.as_deref()]
+        .into_iter()
+        .flatten()
+        .all(|value| from_slice::<Uint128>(value).is_ok());
+    let coins = |value: Option<&[u8]>| -> Option<Vec<Coin>> {
+        match value {
+            None => Some(vec![]),
+            Some(value) if single => from_slice(value).ok().map(|amount| vec![Coin {
+                denom: String::new(),
+                amount,
+            }]),
+            Some(value) => from_slice(value).ok(),
+        }
+    };
+    let (previous_coins, current) = (coins(previous)?, coins(current)?);
+    let mut result = coins(now.as_deref())?;
+    for coin in previous_coins {
+        match result.iter_mut().find(|held| held.denom == coin.denom) {
+            Some(held) => held.amount += coin.amount,
+            None => result.push(coin),
+        }
+    }
+    for coin in current {
+        let held = result.iter_mut().find(|held| held.denom == coin.denom)?;
+        held.amount = held.amount.checked_sub(coin.amount).ok()?;
+    }
+    result.retain(|coin| !coin.amount.is_zero());
+
+    if previous.is_none() && result.is_empty() {
+        return Some(None);
+    }
+    let value = if single {
+        to_vec(&result.first().map_or(Uint128::zero(), |coin| coin.amount))
+    } else {
+        to_vec(&result)
+    };
+    value.ok().map(Some)
+}
+
+// `now` with the difference between `previous` and `current` undone
+fn compensate_pool(
+    previous: InterchainLiquidityPool,
+    current: InterchainLiquidityPool,
+    mut now: InterchainLiquidityPool,
+) -> StdResult<InterchainLiquidityPool> {
+    let undo = |now: &mut Coin, previous: &Coin, current: &Coin| -> StdResult<()> {
+        now.amount = now.amount.checked_add(previous.amount)?.checked_sub(current.amount)?;
+        Ok(())
+    };
+    for asset in now.assets.iter_mut() {
+        let denom = asset.balance.denom.clone();
+        undo(
+            &mut asset.balance,
+            &previous.find_asset_by_denom(&denom)?.balance,
+            &current.find_asset_by_denom(&denom)?.balance,
+        )?;
+    }
+    undo(&mut now.supply, &previous.supply, &current.supply)?;
+    if now.status == current.status {
+        now.status = previous.status;
+    }
+    Ok(now)
+}
+
+// takes the tokens sent out by the packet off the restored balances, as far as they cover them
+fn deduct_sent(mut pool: InterchainLiquidityPool, sent: &mut [Coin]) -> InterchainLiquidityPool {
+    for asset in pool.assets.iter_mut() {
+        for coin in sent.iter_mut().filter(|coin| coin.denom == asset.balance.denom) {
+            let deducted = coin.amount.min(asset.balance.amount);
+            asset.balance.amount -= deducted;
+            coin.amount -= deducted;
+        }
+    }
+    pool
+}
+
+fn conflict(key: &[u8]) -> ContractError {
+    ContractError::RevertConflict {
+        key: key.iter().filter(|b| b.is_ascii_graphic()).map(|&b| b as char).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::InterchainMessageType;
+    use cosmwasm_std::testing::MockStorage;
+
+    #[test]
+    fn test_revert_restores_previous_values() {
+        let mut base = MockStorage::new();
+        base.set(b"a", b"1");
+        base.set(b"b", b"2");
+
+        let mut storage = UndoStorage::new(&mut base);
+        storage.set(b"a", b"3");
+        storage.set(b"a", b"4");
+        storage.remove(b"b");
+        storage.set(b"c", b"5");
+        let record = UndoRecord {
+            packet_type: InterchainMessageType::LeftSwap,
+            received_at: 0,
+            changes: storage.into_changes(),
+            sent: vec![],
+        };
+        assert_eq!(base.get(b"a"), Some(b"4".to_vec()));
+
+        revert(&mut base, &record).unwrap();
+        assert_eq!(base.get(b"a"), Some(b"1".to_vec()));
+        assert_eq!(base.get(b"b"), Some(b"2".to_vec()));
+        assert_eq!(base.get(b"c"), None);
+    }
+
+    #[test]
+    fn test_revert_keeps_later_changes() {
+        let mut base = MockStorage::new();
+        base.set(b"fees", br#""10""#);
+        base.set(b"owner", br#""alice""#);
+
+        let mut storage = UndoStorage::new(&mut base);
+        storage.set(b"fees", br#""15""#);
+        storage.set(b"owner", br#""bob""#);
+        let record = UndoRecord {
+            packet_type: InterchainMessageType::LeftSwap,
+            received_at: 0,
+            changes: storage.into_changes(),
+            sent: vec![],
+        };
+
+        // a later packet adds to the fees and hands the key to someone else
+        base.set(b"fees", br#""20""#);
+        base.set(b"owner", br#""carol""#);
+        let err = revert(&mut base, &record).unwrap_err();
+        assert_eq!(err, ContractError::RevertConflict { key: "owner".to_string() });
+
+        base.set(b"owner", br#""bob""#);
+        revert(&mut base, &record).unwrap();
+        assert_eq!(base.get(b"fees"), Some(br#""15""#.to_vec()));
+        assert_eq!(base.get(b"owner"), Some(br#""alice""#.to_vec()));
+    }
+}
@@ -1,21 +1,130 @@
 use std::{ops::Div, str::FromStr, vec};
 
 use cosmwasm_std::{
-    from_binary, to_binary, Addr, BankMsg, Coin, Decimal, Decimal256, IbcAcknowledgement,
-    IbcChannel, IbcOrder, StdError, StdResult, SubMsg, Uint128, WasmMsg,
+    from_binary, from_slice, to_binary, Addr, BankMsg, Coin, Decimal, Decimal256, Env,
+    IbcAcknowledgement, IbcChannel, IbcOrder, ReplyOn, StdError, StdResult, Storage, SubMsg,
+    Uint128, WasmMsg,
 };
-use cw20::Cw20ExecuteMsg;
+use cw20::{Cw20ExecuteMsg, Logo, MinterResponse};
 use sha2::{Digest, Sha256};
 
 use crate::{
-    interchainswap_handler::InterchainSwapPacketAcknowledgement, market::PoolAsset,
-    msg::DepositAsset, ContractError,
+    interchainswap_handler::InterchainSwapPacketAcknowledgement,
+    market::{PoolAsset, PoolStatus, LP_TOKEN_PRECISION},
+    msg::{
+        DepositAsset, InstantiateMarketingInfo, LpTokenMarketing, MsgSwapRequest,
+        TokenInstantiateMsg,
+    },
+    state::{
+        ChannelCapabilities, Config, LpTokenKind, LpTokenStatus, ASSET_METADATA,
+        COUNTERPARTY_CHAIN_IDS, LP_TOKEN_MARKETING, LP_TOKEN_STATUS, POOL_TOKENS_LIST, TEMP,
+    },
+    tokenfactory,
+    types::InterchainMessageType,
+    ContractError,
 };
 use hex;
 
 pub const MULTIPLIER: u128 = 1e18 as u128;
 pub const MAXIMUM_SLIPPAGE: u64 = 10000;
 pub const INSTANTIATE_TOKEN_REPLY_ID: u64 = 2000;
+pub const TAKE_MULTI_DEPOSIT_MINT_REPLY_ID: u64 = 2001;
+pub const FORWARD_TRANSFER_REPLY_ID: u64 = 2002;
+
+/// Revision of a chain id of the form `{name}-{revision}`, 0 for other chain ids, as IBC
+/// derives it for timeout heights.
+pub fn revision_number(chain_id: &str) -> u64 {
+    match chain_id.rsplit_once('-') {
+        Some((name, revision)) if !name.is_empty() && !revision.starts_with('0') => {
+            revision.parse().unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
+/// Chain id of the counterparty of a pool made over `channel_id`. The channel handshake
+/// carries no chain ids, so the first pool made over a channel binds the chain id it names
+/// and later pools have to name the same one or none.
+pub fn bind_counterparty_chain_id(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    chain_id: &str,
+) -> Result<String, ContractError> {
+    match COUNTERPARTY_CHAIN_IDS.may_load(storage, channel_id)? {
+        Some(expected) if chain_id.is_empty() || chain_id == expected => Ok(expected),
+        Some(expected) => Err(ContractError::UnexpectedChainId {
+            channel_id: channel_id.to_string(),
+            chain_id: chain_id.to_string(),
+            expected,
+        }),
+        None if chain_id.is_empty() => Err(ContractError::Std(StdError::generic_err(format!(
+            "Chain id of the counterparty of {} is unknown",
+            channel_id
+        )))),
+        None => {
+            COUNTERPARTY_CHAIN_IDS.save(storage, channel_id, &chain_id.to_string())?;
+            Ok(chain_id.to_string())
+        }
+    }
+}
+
+/// Statuses governance can move a running pool between.
+pub fn is_lifecycle_status(status: &PoolStatus) -> bool {
+    matches!(status, PoolStatus::Active | PoolStatus::Frozen | PoolStatus::Draining)
+}
+
+/// Whether a pool in `status` takes a message of `message_type`, when sending and when
+/// receiving it. Every packet type has a row, so a new type has to be placed in the table.
+pub fn assert_allowed(
+    status: &PoolStatus,
+    message_type: &InterchainMessageType,
+) -> Result<(), ContractError> {
+    use InterchainMessageType::*;
+    let in_status = |allowed: bool| match allowed {
+        true => Ok(()),
+        false => Err(ContractError::InvalidStatus),
+    };
+    match message_type {
+        // no pool exists yet
+        Unspecified | MakePool => Ok(()),
+        // refunds, withdrawals and management go through in any status
+        CancelMultiDeposit | MultiWithdraw | SetPoolManager | UpdateFee => Ok(()),
+        TakePool | CancelPool => in_status(*status == PoolStatus::Initialized),
+        SetPoolStatus => in_status(is_lifecycle_status(status)),
+        DecommissionPool => in_status(*status == PoolStatus::Draining),
+        SingleAssetDeposit | MakeMultiDeposit | TakeMultiDeposit | MatchMultiDeposit
+        | LeftSwap | RightSwap => match status {
+            PoolStatus::Active => Ok(()),
+            // a bootstrap pool is funded by single asset deposits of its empty asset
+            PoolStatus::Bootstrap if *message_type == SingleAssetDeposit => Ok(()),
+            PoolStatus::Initialized => Err(ContractError::PoolNotTaken),
+            PoolStatus::Frozen | PoolStatus::Draining | PoolStatus::Retired => {
+                Err(ContractError::PoolNotOpen { status: status.clone() })
+            }
+            PoolStatus::Bootstrap | PoolStatus::Cancelled => Err(ContractError::NotReadyForSwap),
+        },
+    }
+}
+
+/// Checks the decimals a pool uses against the asset metadata registry. Denoms
+/// without registered metadata are not checked.
+pub fn validate_asset_decimals(
+    storage: &dyn Storage,
+    assets: &[PoolAsset],
+) -> Result<(), ContractError> {
+    for asset in assets {
+        if let Some(metadata) = ASSET_METADATA.may_load(storage, &asset.balance.denom)? {
+            if metadata.decimals != asset.decimal {
+                return Err(ContractError::DecimalMismatch {
+                    denom: asset.balance.denom.clone(),
+                    claimed: asset.decimal,
+                    registered: metadata.decimals,
+                });
+            }
+        }
+    }
+    Ok(())
+}
 
 pub fn get_pool_id_with_tokens(tokens: &[Coin], source: String, destination: String) -> String {
     let mut denoms: Vec<String> = tokens.iter().map(|token| token.denom.clone()).collect();
@@ -34,20 +143,42 @@ pub fn get_pool_id_with_tokens(tokens: &[Coin], source: String, destination: Str
     pool_id
 }
 
+// comma separated coins, for event attributes
+pub fn coins_to_string(coins: &[Coin]) -> String {
+    coins.iter().map(|coin| coin.to_string()).collect::<Vec<_>>().join(",")
+}
+
 pub fn get_connection_id(mut chain_ids: Vec<String>) -> String {
     chain_ids.sort();
 
     chain_ids.join("/")
 }
 
-pub fn get_order_id(maker: String, count: u64) -> String {
-    let res = maker + &count.to_string();
+/// Order ids are scoped to the chain, contract, pool and block they were made in,
+/// so makers with the same address string on different chains can't collide.
+/// The packet sequence is not known before the packet is sent and is not part of the id.
+pub fn get_order_id(env: &Env, pool_id: &str, maker: &str, count: u128) -> String {
+    let res = [
+        env.block.chain_id.as_str(),
+        env.contract.address.as_str(),
+        pool_id,
+        maker,
+        &env.block.height.to_string(),
+        &count.to_string(),
+    ]
+    .join("/");
     let res_bytes = res.as_bytes();
     let hash = Sha256::digest(res_bytes);
     let order_id = format!("multi_deposit_order{}", hex::encode(hash));
     order_id
 }
 
+/// Identifies an in-flight packet by its data, which is all an ack carries
+/// back besides the sequence.
+pub fn packet_data_hash(data: &[u8]) -> Vec<u8> {
+    Sha256::digest(data).to_vec()
+}
+
 /// ## Description
 /// Return a value using a newly specified precision.
 /// ## Params
@@ -61,12 +192,17 @@ pub fn adjust_precision(
 ) -> StdResult<Uint128> {
     Ok(match current_precision.cmp(&new_precision) {
         std::cmp::Ordering::Equal => value,
-        std::cmp::Ordering::Less => value.checked_mul(Uint128::new(
-            10_u128.pow((new_precision - current_precision) as u32),
-        ))?,
-        std::cmp::Ordering::Greater => value.checked_div(Uint128::new(
-            10_u128.pow((current_precision - new_precision) as u32),
-        ))?,
+        std::cmp::Ordering::Less => value
+            .checked_mul(Uint128::new(precision_factor(new_precision - current_precision)?))?,
+        std::cmp::Ordering::Greater => value
+            .checked_div(Uint128::new(precision_factor(current_precision - new_precision)?))?,
+    })
+}
+
+/// `10^digits`, or an error when it doesn't fit into a [`Uint128`].
+pub fn precision_factor(digits: u8) -> StdResult<u128> {
+    10_u128.checked_pow(digits.into()).ok_or_else(|| {
+        StdError::generic_err(format!("Precision difference of {} digits is out of range", digits))
     })
 }
 
@@ -92,6 +228,24 @@ pub fn get_precision(assets: Vec<PoolAsset>, token: Coin) -> u32 {
     1
 }
 
+/// Fails when the output of a swap is below what the slippage of the request allows.
+pub(crate) fn assert_swap_slippage(
+    msg: &MsgSwapRequest,
+    output: &Coin,
+) -> Result<(), ContractError> {
+    let factor = MAXIMUM_SLIPPAGE - msg.slippage;
+    let expected = msg.token_out.amount.multiply_ratio(factor, MAXIMUM_SLIPPAGE);
+    if output.amount < expected {
+        return Err(ContractError::FailedOnSwapReceived {
+            err: format!(
+                "slippage check failed! expected: {}, output: {:?}, factor: {}",
+                expected, output, factor
+            ),
+        });
+    }
+    Ok(())
+}
+
 pub fn check_slippage(
     source_amount: Uint128,
     destination_amount: Uint128,
@@ -142,19 +296,57 @@ pub fn try_get_ack_error(ack: &IbcAcknowledgement) -> Option<String> {
 }
 
 pub const ICS101_VERSION: &str = "ics101-1";
+/// Version of channels whose packets use the compact encoding.
+pub const ICS101_COMPACT_VERSION: &str = "ics101-1+compact";
 pub const ICS101_ORDERING: IbcOrder = IbcOrder::Unordered;
 
+/// Capabilities the channel ends of this contract advertise.
+pub fn local_capabilities() -> ChannelCapabilities {
+    ChannelCapabilities {
+        version: ICS101_VERSION.to_string(),
+        compact: true,
+        async_acks: false,
+        fee_middleware: false,
+    }
+}
+
+/// Reads a channel version, a plain ics101 version or the JSON of the capabilities of an end.
+pub(crate) fn parse_channel_version(version: &str) -> Result<ChannelCapabilities, ContractError> {
+    let capabilities = match version {
+        ICS101_VERSION => ChannelCapabilities {
+            version: ICS101_VERSION.to_string(),
+            ..Default::default()
+        },
+        ICS101_COMPACT_VERSION => ChannelCapabilities {
+            version: ICS101_VERSION.to_string(),
+            compact: true,
+            ..Default::default()
+        },
+        _ => from_slice(version.as_bytes()).unwrap_or_default(),
+    };
+    if capabilities.version != ICS101_VERSION {
+        return Err(ContractError::InvalidIbcVersion {
+            version: version.to_string(),
+        });
+    }
+    Ok(capabilities)
+}
+
+pub(crate) fn is_plain_version(version: &str) -> bool {
+    version == ICS101_VERSION || version == ICS101_COMPACT_VERSION
+}
+
 pub(crate) fn enforce_order_and_version(
     channel: &IbcChannel,
     counterparty_version: Option<&str>,
 ) -> Result<(), ContractError> {
-    if channel.version != ICS101_VERSION {
-        return Err(ContractError::InvalidIbcVersion {
-            version: channel.version.clone(),
-        });
-    }
+    parse_channel_version(&channel.version)?;
     if let Some(version) = counterparty_version {
-        if version != ICS101_VERSION {
+        parse_channel_version(version)?;
+        // plain versions name the encoding, both ends have to agree on it, versions with
+        // capabilities negotiate it
+        let plain = is_plain_version(version) && is_plain_version(&channel.version);
+        if plain && version != channel.version {
             return Err(ContractError::InvalidIbcVersion {
                 version: version.to_string(),
             });
@@ -167,10 +359,7 @@ pub(crate) fn enforce_order_and_version(
 }
 
 pub fn get_coins_from_deposits(deposits: Vec<DepositAsset>) -> Vec<Coin> {
-    let mut tokens = vec![];
-    tokens.push(deposits[0].balance.clone());
-    tokens.push(deposits[1].balance.clone());
-    tokens
+    deposits.into_iter().map(|deposit| deposit.balance).collect()
 }
 
 pub(crate) fn send_tokens_coin(to: &Addr, amount: Coin) -> StdResult<Vec<SubMsg>> {
@@ -181,11 +370,86 @@ pub(crate) fn send_tokens_coin(to: &Addr, amount: Coin) -> StdResult<Vec<SubMsg>
     Ok(vec![SubMsg::new(msg)])
 }
 
+/// Builds the submessage instantiating the cw20 LP token of a pool, the
+/// address is stored by the `INSTANTIATE_TOKEN_REPLY_ID` reply handler.
+/// The label and marketing info name the pool so explorers tell the tokens apart.
+pub fn instantiate_lp_token(
+    code_id: u64,
+    minter: String,
+    pool_id: &str,
+    marketing: LpTokenMarketing,
+) -> StdResult<SubMsg> {
+    let marketing = InstantiateMarketingInfo {
+        project: marketing.project,
+        description: marketing
+            .description
+            .or_else(|| Some(format!("Liquidity of ics101 pool {}", pool_id))),
+        // lets pool managers update the marketing through the contract
+        marketing: Some(minter.clone()),
+        logo: marketing.logo_url.map(Logo::Url),
+    };
+    Ok(SubMsg {
+        msg: WasmMsg::Instantiate {
+            code_id,
+            msg: to_binary(&TokenInstantiateMsg {
+                name: "sideLP".to_string(),
+                symbol: "sideLP".to_string(),
+                decimals: LP_TOKEN_PRECISION,
+                initial_balances: vec![],
+                marketing: Some(marketing),
+                mint: Some(MinterResponse { minter, cap: None }),
+            })?,
+            funds: vec![],
+            admin: None,
+            label: format!("ics101 LP token {}", pool_id),
+        }
+        .into(),
+        id: INSTANTIATE_TOKEN_REPLY_ID,
+        gas_limit: None,
+        reply_on: ReplyOn::Success,
+    })
+}
+
+/// Instantiation of the LP token of a pool, unless it exists or is being instantiated. A
+/// tokenfactory LP token is recorded right away, only its denom has to be created.
+pub(crate) fn instantiate_lp_token_once(
+    storage: &mut dyn Storage,
+    pool_id: &str,
+    config: &Config,
+    minter: String,
+) -> StdResult<Vec<SubMsg>> {
+    if LP_TOKEN_STATUS.has(storage, pool_id) {
+        return Ok(vec![]);
+    }
+    // tokens instantiated before their status was tracked
+    if POOL_TOKENS_LIST.has(storage, pool_id) {
+        LP_TOKEN_STATUS.save(storage, pool_id, &LpTokenStatus::Ready)?;
+        return Ok(vec![]);
+    }
+    if config.lp_token_kind == LpTokenKind::TokenFactory {
+        let denom = tokenfactory::lp_denom(&minter, pool_id);
+        POOL_TOKENS_LIST.save(storage, pool_id, &denom)?;
+        LP_TOKEN_STATUS.save(storage, pool_id, &LpTokenStatus::Ready)?;
+        return Ok(vec![SubMsg::new(tokenfactory::create_denom_msg(&denom)?)]);
+    }
+    LP_TOKEN_STATUS.save(storage, pool_id, &LpTokenStatus::Instantiating)?;
+    // the reply reads the pool the token belongs to from here
+    TEMP.save(storage, &pool_id.to_string())?;
+    let marketing = LP_TOKEN_MARKETING
+        .may_load(storage, pool_id)?
+        .unwrap_or_default();
+    Ok(vec![instantiate_lp_token(config.token_code_id, minter, pool_id, marketing)?])
+}
+
+/// Mints LP shares, `lp_token` is the address of a cw20 token or a tokenfactory denom.
 pub fn mint_tokens_cw20(
     recipient: String,
     lp_token: String,
     amount: Uint128,
 ) -> StdResult<Vec<SubMsg>> {
+    if tokenfactory::split_denom(&lp_token).is_some() {
+        return Ok(vec![SubMsg::new(tokenfactory::mint_msg(&lp_token, amount, &recipient)?)]);
+    }
     let msg = Cw20ExecuteMsg::Mint { recipient, amount };
     let exec = WasmMsg::Execute {
         contract_addr: lp_token,
@@ -195,7 +459,11 @@ pub fn mint_tokens_cw20(
     Ok(vec![SubMsg::new(exec)])
 }
 
+/// Burns LP shares held by the contract.
 pub fn burn_tokens_cw20(lp_token: String, amount: Uint128) -> StdResult<SubMsg> {
+    if tokenfactory::split_denom(&lp_token).is_some() {
+        return Ok(SubMsg::new(tokenfactory::burn_msg(&lp_token, amount)?));
+    }
     let msg = Cw20ExecuteMsg::Burn { amount };
     let exec = WasmMsg::Execute {
         contract_addr: lp_token,
@@ -205,11 +473,15 @@ pub fn burn_tokens_cw20(lp_token: String, amount: Uint128) -> StdResult<SubMsg>
     Ok(SubMsg::new(exec))
 }
 
+/// Sends LP shares held by the contract.
 pub fn send_tokens_cw20(
     recipient: String,
     lp_token: String,
     amount: Uint128,
 ) -> StdResult<Vec<SubMsg>> {
+    if tokenfactory::split_denom(&lp_token).is_some() {
+        return send_tokens_coin(&Addr::unchecked(recipient), Coin { denom: lp_token, amount });
+    }
     let msg = Cw20ExecuteMsg::Transfer { recipient, amount };
     let exec = WasmMsg::Execute {
         contract_addr: lp_token,
@@ -0,0 +1,152 @@
+use cosmwasm_std::{Order, StdResult, Storage};
+use cw_storage_plus::{Map, PrimaryKey};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Map moved from a legacy namespace to a new one, e.g. `pools` to `pools_v2`. Reads fall
+/// back to the legacy namespace and writes go to the new one, so entries move lazily as
+/// they are written, or in batches with `migrate`.
+pub struct VersionedMap<'a, K, T> {
+    legacy_namespace: &'a str,
+    namespace: &'a str,
+    legacy: Map<'a, K, T>,
+    current: Map<'a, K, T>,
+}
+
+impl<'a, K, T> VersionedMap<'a, K, T>
+where
+    T: Serialize + DeserializeOwned,
+    K: PrimaryKey<'a> + Clone,
+{
+    pub const fn new(legacy_namespace: &'a str, namespace: &'a str) -> Self {
+        VersionedMap {
+            legacy_namespace,
+            namespace,
+            legacy: Map::new(legacy_namespace),
+            current: Map::new(namespace),
+        }
+    }
+
+    pub fn may_load(&self, storage: &dyn Storage, key: K) -> StdResult<Option<T>> {
+        match self.current.may_load(storage, key.clone())? {
+            Some(value) => Ok(Some(value)),
+            None => self.legacy.may_load(storage, key),
+        }
+    }
+
+    pub fn load(&self, storage: &dyn Storage, key: K) -> StdResult<T> {
+        match self.current.may_load(storage, key.clone())? {
+            Some(value) => Ok(value),
+            None => self.legacy.load(storage, key),
+        }
+    }
+
+    pub fn has(&self, storage: &dyn Storage, key: K) -> bool {
+        self.current.has(storage, key.clone()) || self.legacy.has(storage, key)
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage, key: K, value: &T) -> StdResult<()> {
+        self.legacy.remove(storage, key.clone());
+        self.current.save(storage, key, value)
+    }
+
+    pub fn remove(&self, storage: &mut dyn Storage, key: K) {
+        self.legacy.remove(storage, key.clone());
+        self.current.remove(storage, key);
+    }
+}
+
+/// A storage move that can be run incrementally, listed in `state::VERSIONED_MAPS`.
+pub trait Migratable {
+    /// Namespace the entries are moved to
+    fn namespace(&self) -> &str;
+
+    /// Whether entries are left in the legacy namespace
+    fn pending(&self, storage: &dyn Storage) -> bool;
+
+    /// Moves up to `limit` entries out of the legacy namespace, returns how many moved.
+    fn migrate(&self, storage: &mut dyn Storage, limit: usize) -> StdResult<usize>;
+}
+
+impl<'a, K, T> Migratable for VersionedMap<'a, K, T>
+where
+    T: Serialize + DeserializeOwned,
+    K: PrimaryKey<'a> + Clone,
+{
+    fn namespace(&self) -> &str {
+        self.namespace
+    }
+
+    fn pending(&self, storage: &dyn Storage) -> bool {
+        self.legacy
+            .keys_raw(storage, None, None, Order::Ascending)
+            .next()
+            .is_some()
+    }
+
+    fn migrate(&self, storage: &mut dyn Storage, limit: usize) -> StdResult<usize> {
+        // keys are copied as stored, composite keys keep their encoding
+        let entries = self
+            .legacy
+            .range_raw(storage, None, None, Order::Ascending)
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()?;
+        let legacy: Map<&[u8], T> = Map::new(self.legacy_namespace);
+        let current: Map<&[u8], T> = Map::new(self.namespace);
+        for (key, value) in entries.iter() {
+            if !current.has(storage, key) {
+                current.save(storage, key, value)?;
+            }
+            legacy.remove(storage, key);
+        }
+        Ok(entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+
+    #[test]
+    fn test_versioned_map() {
+        let mut storage = MockStorage::new();
+        let legacy: Map<(&str, u64), String> = Map::new("journal");
+        for sequence in 0..5u64 {
+            legacy.save(&mut storage, ("pool1", sequence), &sequence.to_string()).unwrap();
+        }
+
+        let map: VersionedMap<(&str, u64), String> = VersionedMap::new("journal", "journal_v2");
+        assert_eq!(map.load(&storage, ("pool1", 3)).unwrap(), "3");
+
+        // a write moves the entry, a newer value wins over the legacy one
+        map.save(&mut storage, ("pool1", 3), &"three".to_string()).unwrap();
+        assert!(!legacy.has(&storage, ("pool1", 3)));
+        assert_eq!(map.load(&storage, ("pool1", 3)).unwrap(), "three");
+
+        assert_eq!(map.migrate(&mut storage, 2).unwrap(), 2);
+        assert!(map.pending(&storage));
+        assert_eq!(map.migrate(&mut storage, 10).unwrap(), 2);
+        assert!(!map.pending(&storage));
+        assert_eq!(map.migrate(&mut storage, 10).unwrap(), 0);
+
+        let current: Map<(&str, u64), String> = Map::new("journal_v2");
+        let moved = current
+            .prefix("pool1")
+            .range(&storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            moved,
+            vec![
+                (0, "0".to_string()),
+                (1, "1".to_string()),
+                (2, "2".to_string()),
+                (3, "three".to_string()),
+                (4, "4".to_string()),
+            ]
+        );
+
+        map.remove(&mut storage, ("pool1", 0));
+        assert!(!map.has(&storage, ("pool1", 0)));
+    }
+}
@@ -6,7 +6,7 @@ use cosmwasm_std::{
 
 use crate::error::ContractError;
 use crate::interaction_gmm::SideMsg;
-use crate::msg::{SwapMsgType, SwapRoute};
+use crate::msg::{MsgSwapRequest, SwapMsgType, SwapRoute};
 use crate::msg::{ CallbackMsg, ExecuteMsg, InstantiateMsg, QueryMsg, SwapRequest, InterchainExecuteMsg::Swap};
 use crate::querier::SideQuerier;
 use crate::query::SideQuery;
@@ -143,7 +143,7 @@ fn hop_swap(
     
         // Handle interchain paths here
         if let Some(val) = next_hop.contract_address {
-            let swap_msg = Swap {
+            let swap_msg = Swap(MsgSwapRequest {
                 swap_type: SwapMsgType::LEFT,
                 sender: env.contract.address.to_string(),
                 pool_id: next_hop.pool_id,
@@ -151,10 +151,16 @@ fn hop_swap(
                 token_out: Coin { denom: next_hop.asset_out, amount: Uint128::from(0u64) },
                 slippage: 90,
                 recipient: recipient.to_string(),
-                route: Some(SwapRoute {requests, minimum_receive: None}),
+                route: Some(SwapRoute {
+                    requests: requests.into_iter().map(Into::into).collect(),
+                    minimum_receive: None,
+                }),
                 timeout_height: 100,
                 timeout_timestamp: 100,
-            };
+                memo: None,
+                refund_to: Some(recipient.to_string()),
+                forward: None,
+            });
 
             // router message
             execute_msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
@@ -259,7 +265,7 @@ fn multi_swap(
 
     // Handle interchain paths here
     if let Some(val) = first_hop.contract_address {
-        let swap_msg = Swap {
+        let swap_msg = Swap(MsgSwapRequest {
             swap_type: SwapMsgType::LEFT,
             sender: env.contract.address.to_string(),
             pool_id: first_hop.pool_id,
@@ -267,10 +273,16 @@ fn multi_swap(
             token_out: Coin { denom: first_hop.asset_out, amount: Uint128::from(0u64) },
             slippage: 90,
             recipient: recipient.to_string(),
-            route: Some(SwapRoute {requests, minimum_receive: None}),
+            route: Some(SwapRoute {
+                requests: requests.into_iter().map(Into::into).collect(),
+                minimum_receive: None,
+            }),
             timeout_height: 100,
             timeout_timestamp: 100,
-        };
+            memo: None,
+            refund_to: Some(info.sender.to_string()),
+            forward: None,
+        });
 
         // router message
         execute_msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
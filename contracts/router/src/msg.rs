@@ -1,7 +1,9 @@
-use cosmwasm_std::{Addr, Coin, Uint128};
+use cosmwasm_std::{Addr, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+pub use ibcswap_packets::{MsgSwapRequest, SwapMsgType, SwapRoute};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
 }
@@ -41,38 +43,21 @@ pub struct SwapRequest {
     pub contract_address: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub enum InterchainExecuteMsg {
-    Swap {
-        #[serde(rename = "swapType")]
-        swap_type: SwapMsgType,
-        sender: String,
-        #[serde(rename = "poolId")]
-        pool_id: String,
-        #[serde(rename = "tokenIn")]
-        token_in: Coin,
-        #[serde(rename = "tokenOut")]
-        token_out: Coin,
-        slippage: u64,
-        recipient: String,
-        #[serde(rename = "timeoutHeight")]
-        timeout_height: u64,
-        #[serde(rename = "timeoutTimestamp")]
-        timeout_timestamp: u64,
-        route: Option<SwapRoute>,
+// the packet route carries the hops with an empty address for local pools
+impl From<SwapRequest> for ibcswap_packets::SwapRequest {
+    fn from(request: SwapRequest) -> Self {
+        ibcswap_packets::SwapRequest {
+            pool_id: request.pool_id,
+            asset_in: request.asset_in,
+            asset_out: request.asset_out,
+            contract_address: request.contract_address.unwrap_or_default(),
+        }
     }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub struct SwapRoute {
-    pub requests: Vec<SwapRequest>,
-    pub minimum_receive: Option<Uint128>,
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub enum SwapMsgType {
-    LEFT = 0,
-    RIGHT = 1,
+pub enum InterchainExecuteMsg {
+    Swap(MsgSwapRequest),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -0,0 +1,30 @@
+use cosmwasm_std::StdError;
+use thiserror::Error;
+
+/// Errors returned by the stateless checks of the packet messages.
+#[derive(Error, Debug, PartialEq)]
+pub enum PacketError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Invalid denom pair")]
+    InvalidDenomPair,
+
+    #[error("Invalid weight pair")]
+    InvalidWeightPair,
+
+    #[error("Invalid amplification")]
+    InvalidAmplification,
+
+    #[error("Invalid amount")]
+    InvalidAmount,
+
+    #[error("Invalid token amount")]
+    InvalidTokenAmount,
+
+    #[error("Invalid bootstrap targets")]
+    InvalidBootstrapTargets,
+
+    #[error("Invalid state change {field}: {reason}")]
+    InvalidStateChange { field: String, reason: String },
+}
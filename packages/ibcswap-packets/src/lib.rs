@@ -0,0 +1,12 @@
+//! Wire types of the ICS-101 interchain swap protocol.
+//!
+//! Contracts that send or decode interchain swap packets depend on this crate
+//! instead of copying the definitions, so both ends always agree on the format.
+
+mod error;
+pub mod msg;
+pub mod packet;
+
+pub use crate::error::PacketError;
+pub use crate::msg::*;
+pub use crate::packet::*;
@@ -0,0 +1,527 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Binary, Coin, Uint128};
+
+use crate::error::PacketError;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub enum PoolSide {
+    SOURCE = 0,
+    DESTINATION = 1,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub enum PoolStatus {
+    #[serde(rename = "INITIALIZED")]
+    Initialized = 0,
+    #[serde(rename = "ACTIVE")]
+    Active = 1,
+    #[serde(rename = "CANCELLED")]
+    Cancelled = 2,
+    /// Pool was made with only the source asset funded. Only single asset
+    /// deposits of the unfunded asset are accepted until both sides hold liquidity.
+    #[serde(rename = "BOOTSTRAP")]
+    Bootstrap = 3,
+    /// Swaps and deposits are halted, liquidity can still be withdrawn.
+    #[serde(rename = "FROZEN")]
+    Frozen = 4,
+    /// Pool is being retired, only withdrawals are accepted.
+    #[serde(rename = "DRAINING")]
+    Draining = 5,
+    /// Pool was decommissioned with LP left. Only withdrawals are accepted, the pool is deleted
+    /// once the LP is withdrawn.
+    #[serde(rename = "RETIRED")]
+    Retired = 6,
+}
+
+/// Chain of a pool charging the swap fee of a swap.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeApplication {
+    /// The sending chain takes the fee in its quote
+    #[default]
+    Source,
+    /// The receiving chain recomputes the swap on its pool state and takes the fee
+    Destination,
+    /// Each chain takes half of the fee
+    Split,
+}
+
+/// Largest amplification coefficient of a stable pool.
+pub const MAX_AMPLIFICATION: u64 = 1_000_000;
+
+/// Curve a pool prices its assets on, the same on both chains.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolType {
+    /// Weighted constant product curve
+    #[default]
+    Weighted,
+    /// StableSwap curve for like-kind assets, flatter around the balanced point the higher
+    /// the amplification. The weights only split the LP allocation between the chains.
+    Stable { amplification: u64 },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PoolAsset {
+    pub side: PoolSide,
+    pub balance: Coin,
+    pub weight: u32,
+    pub decimal: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub enum LPAllocation {
+    MakerChain, // All LP tokens are minted on maker chain
+    TakerChain, // All LP tokens are minted on taker chain
+    Split,      // LP tokens are minted on both chains and divided based on the pool ratio.
+}
+
+/// Most assets a pool can hold, over both chains.
+pub const MAX_POOL_ASSETS: usize = 8;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgMakePoolRequest {
+    pub source_port: String,
+    pub source_channel: String,
+    pub source_chain_id: String,
+    pub destination_chain_id: String,
+    pub counterparty_channel: String,
+    pub creator: String,
+    pub counterparty_creator: String,
+    pub liquidity: Vec<PoolAsset>,
+    pub swap_fee: u32,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+    /// Timeout for all packets of the pool, overriding the contract default
+    pub packet_timeout_seconds: Option<u64>,
+    /// Receiver of the refund when the packet fails, defaults to the creator
+    pub refund_to: Option<String>,
+    /// Chain charging the swap fee, the same on both chains of the pool
+    #[serde(default)]
+    pub fee_application: FeeApplication,
+    /// Curve of the pool, the same on both chains
+    #[serde(default)]
+    pub pool_type: PoolType,
+    /// Swap fee of right swaps when it differs from `swap_fee`, which then applies to left
+    /// swaps only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_swap_fee: Option<u32>,
+    /// Marketing info of the LP tokens of the pool on both chains
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lp_token_marketing: Option<LpTokenMarketing>,
+    /// Amount of each unfunded asset of a bootstrap pool that matches the funded side at the
+    /// pool weights, the deposit funding the asset has to bring at least this much
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap_targets: Option<Vec<Coin>>,
+}
+
+impl MsgMakePoolRequest {
+    pub fn validate_basic(&self) -> Result<(), PacketError> {
+        let denom_size = self.liquidity.len();
+        // each chain has to hold at least one asset of the pool
+        if !(2..=MAX_POOL_ASSETS).contains(&denom_size)
+            || !self.liquidity.iter().any(|asset| asset.side == PoolSide::SOURCE)
+            || !self.liquidity.iter().any(|asset| asset.side == PoolSide::DESTINATION)
+        {
+            return Err(PacketError::InvalidDenomPair);
+        }
+        for (i, asset) in self.liquidity.iter().enumerate() {
+            if self.liquidity[..i]
+                .iter()
+                .any(|other| other.balance.denom == asset.balance.denom)
+            {
+                return Err(PacketError::InvalidDenomPair);
+            }
+        }
+
+        let mut total_weight: u32 = 0;
+
+        for i in 0..self.liquidity.len() {
+            if self.liquidity[i].weight == 0 {
+                return Err(PacketError::InvalidWeightPair);
+            }
+            total_weight += self.liquidity[i].weight;
+        }
+
+        if total_weight != 100 {
+            return Err(PacketError::InvalidWeightPair);
+        }
+
+        if let PoolType::Stable { amplification } = self.pool_type {
+            if !(1..=MAX_AMPLIFICATION).contains(&amplification) {
+                return Err(PacketError::InvalidAmplification);
+            }
+        }
+
+        // the maker always has to fund its own side, the counterparty side is either
+        // funded in full or left empty for a bootstrap pool
+        for asset in &self.liquidity {
+            if asset.side == PoolSide::SOURCE && asset.balance.amount.is_zero() {
+                return Err(PacketError::InvalidAmount);
+            }
+            if asset.side == PoolSide::DESTINATION
+                && !asset.balance.amount.is_zero()
+                && self.is_bootstrap()
+            {
+                return Err(PacketError::InvalidAmount);
+            }
+        }
+
+        // a bootstrap pool prices the deposits of its unfunded assets by their targets
+        let targets = self.bootstrap_targets.as_deref().unwrap_or_default();
+        let mut unfunded = self
+            .liquidity
+            .iter()
+            .filter(|asset| asset.side == PoolSide::DESTINATION && asset.balance.amount.is_zero());
+        if self.bootstrap_targets.is_some() != self.is_bootstrap()
+            || targets.len() != unfunded.clone().count()
+            || targets.iter().any(|target| target.amount.is_zero())
+            || !unfunded
+                .all(|asset| targets.iter().any(|target| target.denom == asset.balance.denom))
+        {
+            return Err(PacketError::InvalidBootstrapTargets);
+        }
+
+        Ok(())
+    }
+
+    /// A pool made without counterparty liquidity starts in bootstrap mode.
+    pub fn is_bootstrap(&self) -> bool {
+        self.liquidity
+            .iter()
+            .any(|asset| asset.side == PoolSide::DESTINATION && asset.balance.amount.is_zero())
+    }
+
+    pub fn refund_address(&self) -> String {
+        self.refund_to.clone().unwrap_or_else(|| self.creator.clone())
+    }
+}
+
+/// Project info explorers show for the LP tokens of a pool.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LpTokenMarketing {
+    pub project: Option<String>,
+    pub description: Option<String>,
+    /// Url of the token logo
+    pub logo_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgMakePoolResponse {
+    pool_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgTakePoolRequest {
+    pub counter_creator: String,
+    pub creator: String,
+    pub pool_id: String,
+    pub lp_allocation: LPAllocation,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+    /// Receiver of the refund when the packet fails, defaults to the creator
+    pub refund_to: Option<String>,
+}
+
+impl MsgTakePoolRequest {
+    pub fn refund_address(&self) -> String {
+        self.refund_to.clone().unwrap_or_else(|| self.creator.clone())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgCancelPoolRequest {
+    pub pool_id: String,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgSetPoolManagerRequest {
+    pub pool_id: String,
+    /// Address managing the pool in place of its creator, `None` hands control back
+    pub manager: Option<String>,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgSetPoolStatusRequest {
+    pub pool_id: String,
+    /// One of Active, Frozen or Draining
+    pub status: PoolStatus,
+    /// Time in unix seconds after which a draining pool can be decommissioned even though
+    /// LP tokens are still outstanding
+    pub drain_deadline: Option<u64>,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+}
+
+/// New swap fee of a pool, in FEE_PRECISION units. It takes effect on the counterparty
+/// chain when received and on the proposing chain once acknowledged.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MarketFeeUpdateProposal {
+    #[serde(rename = "title")]
+    pub title: String,
+    #[serde(rename = "description")]
+    pub description: String,
+    #[serde(rename = "pool_id")]
+    pub pool_id: String,
+    #[serde(rename = "fee_rate")]
+    pub fee_rate: u32,
+    /// New fee of right swaps, which pay `fee_rate` as well when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_fee_rate: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgDecommissionPoolRequest {
+    pub pool_id: String,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgSingleAssetDepositRequest {
+    pub pool_id: String,
+    pub sender: String,
+    pub token: Coin,
+    pub lp_allocation: LPAllocation,
+    pub lp_taker: String,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+    /// Receiver of the LP tokens minted on this chain, defaults to the sender
+    pub recipient: Option<String>,
+    /// Receiver of the refund when the packet fails, defaults to the sender
+    pub refund_to: Option<String>,
+}
+
+impl MsgSingleAssetDepositRequest {
+    pub fn validate_basic(&self) -> Result<(), PacketError> {
+        if self.token.amount.is_zero() {
+            return Err(PacketError::InvalidTokenAmount);
+        }
+
+        Ok(())
+    }
+
+    pub fn lp_recipient(&self) -> String {
+        self.recipient.clone().unwrap_or_else(|| self.sender.clone())
+    }
+
+    pub fn refund_address(&self) -> String {
+        self.refund_to.clone().unwrap_or_else(|| self.sender.clone())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgSingleAssetDepositResponse {
+    pub pool_token: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DepositAsset {
+    pub sender: String,
+    pub balance: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgMakeMultiAssetDepositRequest {
+    pub pool_id: String,
+    pub deposits: Vec<DepositAsset>,
+    pub chain_id: String,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+    /// Receiver of the maker's LP tokens, defaults to the maker
+    pub recipient: Option<String>,
+    /// Compressed secp256k1 key whose signature lets another taker take the order
+    pub permit_pubkey: Option<Binary>,
+    /// Receiver of the refund when the packet fails, defaults to the maker
+    pub refund_to: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgTakeMultiAssetDepositRequest {
+    pub sender: String,
+    pub pool_id: String,
+    pub order_id: String,
+    pub lp_allocation: LPAllocation,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+    /// Receiver of the taker's LP tokens, defaults to the sender
+    pub recipient: Option<String>,
+    /// Maker's signature over the TakePermit of the sender, when the sender is not the
+    /// destination taker of the order
+    pub permit: Option<Binary>,
+    /// Receiver of the refund when the packet fails, defaults to the sender
+    pub refund_to: Option<String>,
+}
+
+impl MsgTakeMultiAssetDepositRequest {
+    pub fn lp_recipient(&self) -> String {
+        self.recipient.clone().unwrap_or_else(|| self.sender.clone())
+    }
+
+    pub fn refund_address(&self) -> String {
+        self.refund_to.clone().unwrap_or_else(|| self.sender.clone())
+    }
+}
+
+/// Settles a pending order made on the sending chain against its mirror made on the
+/// counterparty chain, the maker deposits of both orders making up one multi asset deposit.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgMatchMultiAssetDepositRequest {
+    pub sender: String,
+    pub pool_id: String,
+    /// Order made on the sending chain
+    pub order_id: String,
+    /// Order made on the counterparty chain for the mirrored deposits
+    pub counter_order_id: String,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgCancelMultiAssetDepositRequest {
+    pub sender: String,
+    pub pool_id: String,
+    pub order_id: String,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgMultiAssetDepositResponse {
+    pub pool_token: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawAsset {
+    pub receiver: String,
+    pub balance: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgMultiAssetWithdrawRequest {
+    pub pool_id: String,
+    pub receiver: String,
+    pub counterparty_receiver: String,
+    pub pool_token: Coin,
+    pub timeout_height: u64,
+    pub timeout_timestamp: u64,
+    pub memo: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgMultiAssetWithdrawResponse {
+    pub tokens: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MsgSingleAssetWithdrawRequest {
+    pub sender: String,
+    pub denom_out: String,
+    pub pool_coin: Coin,
+    pub memo: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub enum SwapMsgType {
+    LEFT = 0,
+    RIGHT = 1,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MsgSwapRequest {
+    #[serde(rename = "swapType")]
+    pub swap_type: SwapMsgType,
+    pub sender: String,
+    #[serde(rename = "poolId")]
+    pub pool_id: String,
+    #[serde(rename = "tokenIn")]
+    pub token_in: Coin,
+    #[serde(rename = "tokenOut")]
+    pub token_out: Coin,
+    pub slippage: u64,
+    pub recipient: String,
+    #[serde(rename = "timeoutHeight")]
+    pub timeout_height: u64,
+    #[serde(rename = "timeoutTimestamp")]
+    pub timeout_timestamp: u64,
+    pub route: Option<SwapRoute>,
+    pub memo: Option<Binary>,
+    /// Receiver of the refund when the packet fails, defaults to the sender
+    #[serde(rename = "refundTo")]
+    pub refund_to: Option<String>,
+    /// Sends the output on to a third chain over ICS-20 instead of to `recipient`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forward: Option<SwapForward>,
+}
+
+impl MsgSwapRequest {
+    pub fn refund_address(&self) -> String {
+        self.refund_to.clone().unwrap_or_else(|| self.sender.clone())
+    }
+}
+
+/// ICS-20 transfer of a swap output from the pool counterparty chain to a third chain. A
+/// transfer that fails there is held on the counterparty chain for the sender to claim.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SwapForward {
+    /// Transfer channel on the chain the output is paid on
+    pub channel: String,
+    /// Receiver on the chain at the other end of `channel`
+    pub receiver: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SwapRoute {
+    pub requests: Vec<SwapRequest>,
+    pub minimum_receive: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SwapRequest {
+    /// Pool Id via which the swap is to be routed
+    pub pool_id: String,
+    /// The offer asset denom
+    pub asset_in: String,
+    ///  The ask asset denom
+    pub asset_out: String,
+    /// Contract address, if interchain request
+    pub contract_address: String,
+}
@@ -0,0 +1,586 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{from_slice, to_vec, Binary, Coin, StdError, StdResult, Uint128};
+
+use crate::error::PacketError;
+use crate::msg::{
+    MarketFeeUpdateProposal, MsgCancelMultiAssetDepositRequest, MsgCancelPoolRequest,
+    MsgDecommissionPoolRequest, MsgMakeMultiAssetDepositRequest, MsgMakePoolRequest,
+    MsgMatchMultiAssetDepositRequest, MsgMultiAssetWithdrawRequest, MsgSetPoolManagerRequest,
+    MsgSetPoolStatusRequest, MsgSingleAssetDepositRequest, MsgSwapRequest,
+    MsgTakeMultiAssetDepositRequest, MsgTakePoolRequest, MAX_POOL_ASSETS,
+};
+
+/// Most tokens a state change may list per field, a withdrawal lists every asset of a pool.
+pub const MAX_STATE_CHANGE_TOKENS: usize = MAX_POOL_ASSETS;
+/// Longest pool, order or chain id accepted in a state change.
+pub const MAX_STATE_CHANGE_ID_LENGTH: usize = 128;
+
+// Protobuf tags of the compact packet encoding, a JSON packet never starts with the first one
+const COMPACT_TYPE_TAG: u8 = 1 << 3;
+const COMPACT_DATA_TAG: u8 = 2 << 3 | 2;
+const COMPACT_STATE_CHANGE_TAG: u8 = 3 << 3 | 2;
+const COMPACT_MEMO_TAG: u8 = 4 << 3 | 2;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StateChange {
+    #[serde(rename = "In")]
+    pub in_tokens: Option<Vec<Coin>>,
+    #[serde(rename = "Out")]
+    pub out_tokens: Option<Vec<Coin>>,
+    #[serde(rename = "PoolTokens")]
+    pub pool_tokens: Option<Vec<Coin>>,
+    #[serde(rename = "PoolId")]
+    pub pool_id: Option<String>,
+    #[serde(rename = "MultiDepositOrderId")]
+    pub multi_deposit_order_id: Option<String>,
+    #[serde(rename = "SourceChainId")]
+    pub source_chain_id: Option<String>,
+    #[serde(rename = "Shares")]
+    pub shares: Option<Uint128>,
+}
+
+impl StateChange {
+    /// Checks the sizes of all fields and that the fields read by the
+    /// receiver of a `packet_type` packet are set.
+    pub fn validate(&self, packet_type: &InterchainMessageType) -> Result<(), PacketError> {
+        let tokens = [
+            ("In", &self.in_tokens),
+            ("Out", &self.out_tokens),
+            ("PoolTokens", &self.pool_tokens),
+        ];
+        for (field, coins) in tokens {
+            if let Some(coins) = coins {
+                if coins.len() > MAX_STATE_CHANGE_TOKENS {
+                    return Err(invalid_state_change(
+                        field,
+                        &format!("more than {} tokens", MAX_STATE_CHANGE_TOKENS),
+                    ));
+                }
+            }
+        }
+
+        let ids = [
+            ("PoolId", &self.pool_id),
+            ("MultiDepositOrderId", &self.multi_deposit_order_id),
+            ("SourceChainId", &self.source_chain_id),
+        ];
+        for (field, id) in ids {
+            if let Some(id) = id {
+                if id.len() > MAX_STATE_CHANGE_ID_LENGTH {
+                    return Err(invalid_state_change(
+                        field,
+                        &format!("longer than {} bytes", MAX_STATE_CHANGE_ID_LENGTH),
+                    ));
+                }
+            }
+        }
+
+        let has_tokens = |coins: &Option<Vec<Coin>>| coins.as_ref().is_some_and(|c| !c.is_empty());
+        let required = match packet_type {
+            InterchainMessageType::TakePool => vec![("Shares", self.shares.is_some())],
+            InterchainMessageType::SingleAssetDeposit
+            | InterchainMessageType::TakeMultiDeposit
+            | InterchainMessageType::MatchMultiDeposit => vec![
+                ("PoolTokens", has_tokens(&self.pool_tokens)),
+                ("Shares", self.shares.is_some()),
+            ],
+            InterchainMessageType::MakeMultiDeposit => vec![
+                ("PoolTokens", has_tokens(&self.pool_tokens)),
+                ("MultiDepositOrderId", self.multi_deposit_order_id.is_some()),
+            ],
+            InterchainMessageType::MultiWithdraw => vec![
+                ("Out", has_tokens(&self.out_tokens)),
+                ("PoolTokens", has_tokens(&self.pool_tokens)),
+            ],
+            InterchainMessageType::LeftSwap | InterchainMessageType::RightSwap => {
+                vec![("Out", has_tokens(&self.out_tokens))]
+            }
+            _ => vec![],
+        };
+        if let Some((field, _)) = required.into_iter().find(|(_, present)| !present) {
+            return Err(invalid_state_change(field, "missing"));
+        }
+
+        Ok(())
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn write_bytes_field(bytes: &mut Vec<u8>, tag: u8, value: &[u8]) {
+    bytes.push(tag);
+    write_varint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value);
+}
+
+fn read_varint(rest: &mut &[u8]) -> StdResult<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, tail) = rest
+            .split_first()
+            .ok_or_else(|| compact_err("truncated varint"))?;
+        *rest = tail;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return Ok(value);
+        }
+    }
+    Err(compact_err("varint overflow"))
+}
+
+fn read_bytes(rest: &mut &[u8]) -> StdResult<Binary> {
+    let len = read_varint(rest)? as usize;
+    if rest.len() < len {
+        return Err(compact_err("truncated field"));
+    }
+    let (value, tail) = rest.split_at(len);
+    *rest = tail;
+    Ok(Binary::from(value))
+}
+
+fn compact_err(reason: &str) -> StdError {
+    StdError::parse_err("InterchainSwapPacketData", reason)
+}
+
+fn invalid_state_change(field: &str, reason: &str) -> PacketError {
+    PacketError::InvalidStateChange {
+        field: field.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InterchainSwapPacketData {
+    #[serde(rename = "Type")]
+    pub r#type: InterchainMessageType,
+    #[serde(rename = "Data")]
+    pub data: Binary,
+    #[serde(rename = "StateChange")]
+    pub state_change: Option<Binary>,
+    #[serde(rename = "Memo")]
+    pub memo: Option<Binary>
+}
+
+impl InterchainSwapPacketData {
+    /// Encodes the packet as a protobuf message with the fields in declaration
+    /// order. The message and state change stay JSON but skip the base64 layers
+    /// of the JSON encoding.
+    pub fn to_compact(&self) -> Vec<u8> {
+        let mut bytes = vec![COMPACT_TYPE_TAG];
+        write_varint(&mut bytes, self.r#type.clone() as u64);
+        write_bytes_field(&mut bytes, COMPACT_DATA_TAG, &self.data);
+        if let Some(state_change) = &self.state_change {
+            write_bytes_field(&mut bytes, COMPACT_STATE_CHANGE_TAG, state_change);
+        }
+        if let Some(memo) = &self.memo {
+            write_bytes_field(&mut bytes, COMPACT_MEMO_TAG, memo);
+        }
+        bytes
+    }
+
+    /// Decodes packet data sent in either the JSON or the compact encoding.
+    pub fn from_wire(bytes: &[u8]) -> StdResult<Self> {
+        if bytes.first() != Some(&COMPACT_TYPE_TAG) {
+            return from_slice(bytes);
+        }
+        let mut packet = InterchainSwapPacketData {
+            r#type: InterchainMessageType::Unspecified,
+            data: Binary::default(),
+            state_change: None,
+            memo: None,
+        };
+        let mut rest = bytes;
+        while let Some((&tag, tail)) = rest.split_first() {
+            rest = tail;
+            match tag {
+                COMPACT_TYPE_TAG => {
+                    packet.r#type = InterchainMessageType::try_from(read_varint(&mut rest)?)?
+                }
+                COMPACT_DATA_TAG => packet.data = read_bytes(&mut rest)?,
+                COMPACT_STATE_CHANGE_TAG => packet.state_change = Some(read_bytes(&mut rest)?),
+                COMPACT_MEMO_TAG => packet.memo = Some(read_bytes(&mut rest)?),
+                _ => return Err(compact_err(&format!("unknown tag {}", tag))),
+            }
+        }
+        Ok(packet)
+    }
+
+    /// Decodes `data` into the message selected by the packet type.
+    pub fn decode_message(&self) -> StdResult<PacketMessage> {
+        let data = self.data.as_slice();
+        let msg = match self.r#type {
+            InterchainMessageType::Unspecified => PacketMessage::Unspecified {},
+            InterchainMessageType::MakePool => PacketMessage::MakePool(from_slice(data)?),
+            InterchainMessageType::TakePool => PacketMessage::TakePool(from_slice(data)?),
+            InterchainMessageType::CancelPool => PacketMessage::CancelPool(from_slice(data)?),
+            InterchainMessageType::SingleAssetDeposit => {
+                PacketMessage::SingleAssetDeposit(from_slice(data)?)
+            }
+            InterchainMessageType::MakeMultiDeposit => {
+                PacketMessage::MakeMultiDeposit(from_slice(data)?)
+            }
+            InterchainMessageType::CancelMultiDeposit => {
+                PacketMessage::CancelMultiDeposit(from_slice(data)?)
+            }
+            InterchainMessageType::TakeMultiDeposit => {
+                PacketMessage::TakeMultiDeposit(from_slice(data)?)
+            }
+            InterchainMessageType::MultiWithdraw => PacketMessage::MultiWithdraw(from_slice(data)?),
+            InterchainMessageType::LeftSwap | InterchainMessageType::RightSwap => {
+                PacketMessage::Swap(from_slice(data)?)
+            }
+            InterchainMessageType::SetPoolManager => {
+                PacketMessage::SetPoolManager(from_slice(data)?)
+            }
+            InterchainMessageType::SetPoolStatus => PacketMessage::SetPoolStatus(from_slice(data)?),
+            InterchainMessageType::DecommissionPool => {
+                PacketMessage::DecommissionPool(from_slice(data)?)
+            }
+            InterchainMessageType::MatchMultiDeposit => {
+                PacketMessage::MatchMultiDeposit(from_slice(data)?)
+            }
+            InterchainMessageType::UpdateFee => PacketMessage::UpdateFee(from_slice(data)?),
+        };
+        Ok(msg)
+    }
+
+    /// Decodes `state_change`, if the packet carries one.
+    pub fn decode_state_change(&self) -> StdResult<Option<StateChange>> {
+        self.state_change
+            .as_ref()
+            .map(|state_change| from_slice(state_change))
+            .transpose()
+    }
+
+    /// Decodes the required `state_change` and validates it against the
+    /// packet type, for packets coming from the counterparty.
+    pub fn decode_valid_state_change(&self) -> Result<StateChange, PacketError> {
+        let state_change: StateChange = self
+            .decode_state_change()?
+            .ok_or_else(|| invalid_state_change("StateChange", "missing"))?;
+        state_change.validate(&self.r#type)?;
+        Ok(state_change)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum InterchainMessageType {
+    #[serde(rename = "UNSPECIFIED")]
+    Unspecified = 0,
+    #[serde(rename = "MAKE_POOL")]
+    MakePool = 1,
+    #[serde(rename = "TAKE_POOL")]
+    TakePool = 2,
+    #[serde(rename = "CANCEL_POOL")]
+    CancelPool = 3,
+    #[serde(rename = "SINGLE_ASSET_DEPOSIT")]
+    SingleAssetDeposit = 4,
+    #[serde(rename = "MAKE_MULTI_DEPOSIT")]
+    MakeMultiDeposit = 5,
+    #[serde(rename = "CANCEL_MULTI_DEPOSIT")]
+    CancelMultiDeposit = 6,
+    #[serde(rename = "TAKE_MULTI_DEPOSIT")]
+    TakeMultiDeposit = 7,
+    #[serde(rename = "MULTI_WITHDRAW")]
+    MultiWithdraw = 8,
+    #[serde(rename = "LEFT_SWAP")]
+    LeftSwap = 9,
+    #[serde(rename = "RIGHT_SWAP")]
+    RightSwap = 10,
+    #[serde(rename = "SET_POOL_MANAGER")]
+    SetPoolManager = 11,
+    #[serde(rename = "SET_POOL_STATUS")]
+    SetPoolStatus = 12,
+    #[serde(rename = "DECOMMISSION_POOL")]
+    DecommissionPool = 13,
+    #[serde(rename = "MATCH_MULTI_DEPOSIT")]
+    MatchMultiDeposit = 14,
+    #[serde(rename = "UPDATE_FEE")]
+    UpdateFee = 15,
+}
+
+impl TryFrom<u64> for InterchainMessageType {
+    type Error = StdError;
+
+    fn try_from(value: u64) -> StdResult<Self> {
+        let r#type = match value {
+            0 => InterchainMessageType::Unspecified,
+            1 => InterchainMessageType::MakePool,
+            2 => InterchainMessageType::TakePool,
+            3 => InterchainMessageType::CancelPool,
+            4 => InterchainMessageType::SingleAssetDeposit,
+            5 => InterchainMessageType::MakeMultiDeposit,
+            6 => InterchainMessageType::CancelMultiDeposit,
+            7 => InterchainMessageType::TakeMultiDeposit,
+            8 => InterchainMessageType::MultiWithdraw,
+            9 => InterchainMessageType::LeftSwap,
+            10 => InterchainMessageType::RightSwap,
+            11 => InterchainMessageType::SetPoolManager,
+            12 => InterchainMessageType::SetPoolStatus,
+            13 => InterchainMessageType::DecommissionPool,
+            14 => InterchainMessageType::MatchMultiDeposit,
+            15 => InterchainMessageType::UpdateFee,
+            _ => return Err(compact_err(&format!("unknown packet type {}", value))),
+        };
+        Ok(r#type)
+    }
+}
+
+/// Typed content of the `data` field of a packet.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketMessage {
+    Unspecified {},
+    MakePool(MsgMakePoolRequest),
+    TakePool(MsgTakePoolRequest),
+    CancelPool(MsgCancelPoolRequest),
+    SingleAssetDeposit(MsgSingleAssetDepositRequest),
+    MakeMultiDeposit(MsgMakeMultiAssetDepositRequest),
+    CancelMultiDeposit(MsgCancelMultiAssetDepositRequest),
+    TakeMultiDeposit(MsgTakeMultiAssetDepositRequest),
+    MultiWithdraw(MsgMultiAssetWithdrawRequest),
+    Swap(MsgSwapRequest),
+    SetPoolManager(MsgSetPoolManagerRequest),
+    SetPoolStatus(MsgSetPoolStatusRequest),
+    DecommissionPool(MsgDecommissionPoolRequest),
+    MatchMultiDeposit(MsgMatchMultiAssetDepositRequest),
+    UpdateFee(MarketFeeUpdateProposal),
+}
+
+impl PacketMessage {
+    /// Pool the message acts on. Pool creation carries no id, the contract
+    /// derives it and reports it in the state change.
+    pub fn pool_id(&self) -> Option<&str> {
+        match self {
+            PacketMessage::Unspecified {} | PacketMessage::MakePool(_) => None,
+            PacketMessage::TakePool(msg) => Some(&msg.pool_id),
+            PacketMessage::CancelPool(msg) => Some(&msg.pool_id),
+            PacketMessage::SingleAssetDeposit(msg) => Some(&msg.pool_id),
+            PacketMessage::MakeMultiDeposit(msg) => Some(&msg.pool_id),
+            PacketMessage::CancelMultiDeposit(msg) => Some(&msg.pool_id),
+            PacketMessage::TakeMultiDeposit(msg) => Some(&msg.pool_id),
+            PacketMessage::MultiWithdraw(msg) => Some(&msg.pool_id),
+            PacketMessage::Swap(msg) => Some(&msg.pool_id),
+            PacketMessage::SetPoolManager(msg) => Some(&msg.pool_id),
+            PacketMessage::SetPoolStatus(msg) => Some(&msg.pool_id),
+            PacketMessage::DecommissionPool(msg) => Some(&msg.pool_id),
+            PacketMessage::MatchMultiDeposit(msg) => Some(&msg.pool_id),
+            PacketMessage::UpdateFee(msg) => Some(&msg.pool_id),
+        }
+    }
+
+    /// Multi asset deposit order the message acts on. A new order gets its id from the
+    /// maker chain, reported in the state change.
+    pub fn order_id(&self) -> Option<&str> {
+        match self {
+            PacketMessage::CancelMultiDeposit(msg) => Some(&msg.order_id),
+            PacketMessage::TakeMultiDeposit(msg) => Some(&msg.order_id),
+            PacketMessage::MatchMultiDeposit(msg) => Some(&msg.order_id),
+            _ => None,
+        }
+    }
+
+    /// Tokens the sender of the message put in.
+    pub fn tokens(&self) -> Vec<Coin> {
+        match self {
+            PacketMessage::MakePool(msg) => msg
+                .liquidity
+                .iter()
+                .map(|asset| asset.balance.clone())
+                .collect(),
+            PacketMessage::SingleAssetDeposit(msg) => vec![msg.token.clone()],
+            PacketMessage::MakeMultiDeposit(msg) => msg
+                .deposits
+                .iter()
+                .map(|deposit| deposit.balance.clone())
+                .collect(),
+            PacketMessage::MultiWithdraw(msg) => vec![msg.pool_token.clone()],
+            PacketMessage::Swap(msg) => vec![msg.token_in.clone()],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InterchainSwapPacketAcknowledgement {
+    Result(Binary),
+    Error(String),
+}
+
+/// Error of a failure acknowledgement with what the failed packet was for, so the sending
+/// chain cleans up without decoding its own packet. It travels JSON encoded in place of the
+/// plain error, chains that don't send it acknowledge the plain error.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FailureAck {
+    pub error: String,
+    pub message_type: InterchainMessageType,
+    pub pool_id: Option<String>,
+    pub order_id: Option<String>,
+}
+
+impl FailureAck {
+    /// Context of a packet failing with `error`, as far as the packet can be decoded.
+    pub fn new(packet_data: &InterchainSwapPacketData, error: String) -> Self {
+        let message = packet_data.decode_message().ok();
+        let state_change = packet_data.decode_state_change().ok().flatten();
+        let pool_id = message
+            .as_ref()
+            .and_then(|msg| msg.pool_id().map(str::to_string))
+            .or_else(|| state_change.as_ref().and_then(|sc| sc.pool_id.clone()));
+        let order_id = message
+            .as_ref()
+            .and_then(|msg| msg.order_id().map(str::to_string))
+            .or_else(|| state_change.and_then(|sc| sc.multi_deposit_order_id));
+        FailureAck {
+            error,
+            message_type: packet_data.r#type.clone(),
+            pool_id,
+            order_id,
+        }
+    }
+
+    pub fn encode(&self) -> StdResult<String> {
+        String::from_utf8(to_vec(self)?).map_err(|err| StdError::generic_err(err.to_string()))
+    }
+
+    /// Reads the error of an acknowledgement, None when it carries no context.
+    pub fn decode(error: &str) -> Option<Self> {
+        from_slice(error.as_bytes()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{to_binary, to_vec};
+
+    #[test]
+    fn test_packet_data_wire_format() {
+        let packet = InterchainSwapPacketData {
+            r#type: InterchainMessageType::LeftSwap,
+            data: Binary::from(b"{}".to_vec()),
+            state_change: None,
+            memo: None,
+        };
+        let json = String::from_utf8(to_vec(&packet).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Type":"LEFT_SWAP","Data":"e30=","StateChange":null,"Memo":null}"#
+        );
+        assert_eq!(from_slice::<InterchainSwapPacketData>(json.as_bytes()).unwrap(), packet);
+    }
+
+    #[test]
+    fn test_compact_encoding() {
+        let msg = MsgCancelPoolRequest {
+            pool_id: "pool1".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let mut packet = InterchainSwapPacketData {
+            r#type: InterchainMessageType::CancelPool,
+            data: to_binary(&msg).unwrap(),
+            state_change: Some(Binary::from(vec![7u8; 200])),
+            memo: None,
+        };
+        let json = to_vec(&packet).unwrap();
+        let compact = packet.to_compact();
+        assert!(compact.len() < json.len());
+        assert_eq!(InterchainSwapPacketData::from_wire(&compact).unwrap(), packet);
+        assert_eq!(InterchainSwapPacketData::from_wire(&json).unwrap(), packet);
+
+        packet.memo = Some(Binary::default());
+        let compact = packet.to_compact();
+        assert_eq!(InterchainSwapPacketData::from_wire(&compact).unwrap(), packet);
+        InterchainSwapPacketData::from_wire(&compact[..compact.len() - 1]).unwrap_err();
+    }
+
+    #[test]
+    fn test_decode_message() {
+        let msg = MsgCancelPoolRequest {
+            pool_id: "pool1".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let packet = InterchainSwapPacketData {
+            r#type: InterchainMessageType::CancelPool,
+            data: to_binary(&msg).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        assert_eq!(packet.decode_message().unwrap(), PacketMessage::CancelPool(msg));
+        assert_eq!(packet.decode_state_change().unwrap(), None);
+
+        let packet = InterchainSwapPacketData {
+            r#type: InterchainMessageType::MakePool,
+            ..packet
+        };
+        assert!(packet.decode_message().is_err());
+    }
+
+    #[test]
+    fn test_validate_state_change() {
+        let state_change = StateChange {
+            in_tokens: None,
+            out_tokens: Some(vec![Coin::new(1, "uatom")]),
+            pool_tokens: None,
+            pool_id: None,
+            multi_deposit_order_id: None,
+            source_chain_id: None,
+            shares: None,
+        };
+        assert!(state_change.validate(&InterchainMessageType::LeftSwap).is_ok());
+        assert!(state_change.validate(&InterchainMessageType::MultiWithdraw).is_err());
+
+        let too_many = StateChange {
+            out_tokens: Some(vec![Coin::new(1, "uatom"); MAX_STATE_CHANGE_TOKENS + 1]),
+            ..state_change.clone()
+        };
+        assert!(too_many.validate(&InterchainMessageType::LeftSwap).is_err());
+
+        let packet = InterchainSwapPacketData {
+            r#type: InterchainMessageType::TakePool,
+            data: Binary::default(),
+            state_change: Some(to_binary(&state_change).unwrap()),
+            memo: None,
+        };
+        assert_eq!(
+            packet.decode_valid_state_change().unwrap_err(),
+            invalid_state_change("Shares", "missing")
+        );
+    }
+
+    #[test]
+    fn test_failure_ack() {
+        let msg = MsgCancelMultiAssetDepositRequest {
+            sender: "maker".to_string(),
+            pool_id: "pool1".to_string(),
+            order_id: "order1".to_string(),
+            timeout_height: 0,
+            timeout_timestamp: 0,
+            memo: None,
+        };
+        let packet = InterchainSwapPacketData {
+            r#type: InterchainMessageType::CancelMultiDeposit,
+            data: to_binary(&msg).unwrap(),
+            state_change: None,
+            memo: None,
+        };
+        let failure = FailureAck::new(&packet, "failed".to_string());
+        assert_eq!(failure.pool_id.as_deref(), Some("pool1"));
+        assert_eq!(failure.order_id.as_deref(), Some("order1"));
+        assert_eq!(FailureAck::decode(&failure.encode().unwrap()), Some(failure));
+        assert_eq!(FailureAck::decode("failed"), None);
+    }
+}